@@ -0,0 +1,94 @@
+#!/usr/bin/env python3
+"""Writes tests/fixtures/svg-test-font.ttf, the font test_svg_output registers.
+
+A minimal TrueType font, 1000 units per em, with a rectangle for "1", a
+square for "x" and a triangle for U+2603 (a character without KaTeX
+metrics), so that the expected SVG paths and advance widths can be read
+off the glyph table below.
+
+Usage: scripts/make-svg-test-font.py [output.ttf]
+"""
+
+import struct
+import sys
+
+# (character, advance width, contour points on the curve, y pointing up)
+GLYPHS = [
+    (None, 500, []),
+    ("1", 500, [(100, 0), (100, 700), (400, 700), (400, 0)]),
+    ("x", 600, [(50, 0), (50, 450), (550, 450), (550, 0)]),
+    ("☃", 800, [(0, 0), (400, 700), (800, 0)]),
+]
+
+
+def glyph_data(points):
+    if not points:
+        return b""
+    xs = [x for x, _ in points]
+    ys = [y for _, y in points]
+    data = struct.pack(">hhhhh", 1, min(xs), min(ys), max(xs), max(ys))
+    data += struct.pack(">HH", len(points) - 1, 0)
+    data += bytes([0x01] * len(points))
+    last = 0
+    for x in xs:
+        data += struct.pack(">h", x - last)
+        last = x
+    last = 0
+    for y in ys:
+        data += struct.pack(">h", y - last)
+        last = y
+    return data + b"\0" * (len(data) % 2)
+
+
+def checksum(data):
+    data += b"\0" * (-len(data) % 4)
+    return sum(struct.unpack(">%dI" % (len(data) // 4), data)) & 0xFFFFFFFF
+
+
+def font():
+    glyf = b""
+    loca = []
+    for _, _, points in GLYPHS:
+        loca.append(len(glyf) // 2)
+        glyf += glyph_data(points)
+    loca.append(len(glyf) // 2)
+    count = len(GLYPHS)
+    max_points = max(len(points) for _, _, points in GLYPHS)
+
+    groups = sorted((ord(ch), index) for index, (ch, _, _) in enumerate(GLYPHS) if ch)
+    cmap_table = struct.pack(">HHIII", 12, 0, 16 + 12 * len(groups), 0, len(groups))
+    for code, index in groups:
+        cmap_table += struct.pack(">III", code, code, index)
+    tables = {
+        b"cmap": struct.pack(">HHHHI", 0, 1, 3, 10, 12) + cmap_table,
+        b"glyf": glyf,
+        b"head": struct.pack(
+            ">IIIIHHqqhhhhHHhhh",
+            0x00010000, 0x00010000, 0, 0x5F0F3CF5, 0, 1000, 0, 0,
+            0, 0, 800, 700, 0, 8, 2, 0, 0,
+        ),
+        b"hhea": struct.pack(
+            ">IhhhHhhhhhhhhhhhH",
+            0x00010000, 750, -250, 0, 800, 0, 0, 800, 1, 0, 0, 0, 0, 0, 0, 0, count,
+        ),
+        b"hmtx": b"".join(
+            struct.pack(">Hh", advance, min((x for x, _ in points), default=0))
+            for _, advance, points in GLYPHS
+        ),
+        b"loca": struct.pack(">%dH" % len(loca), *loca),
+        b"maxp": struct.pack(">IHHHHHHHHHHHHHH", 0x00010000, count, max_points, 1, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0),
+    }
+
+    offset = 12 + 16 * len(tables)
+    directory = struct.pack(">IHHHH", 0x00010000, len(tables), 64, 2, len(tables) * 16 - 64)
+    body = b""
+    for tag, data in sorted(tables.items()):
+        directory += struct.pack(">4sIII", tag, checksum(data), offset + len(body), len(data))
+        body += data + b"\0" * (-len(data) % 4)
+    return directory + body
+
+
+if __name__ == "__main__":
+    path = sys.argv[1] if len(sys.argv) > 1 else "tests/fixtures/svg-test-font.ttf"
+    with open(path, "wb") as file:
+        file.write(font())
@@ -17,6 +17,7 @@ use regex::Regex;
  */
 use std::collections::HashMap;
 use std::sync::Mutex;
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 /* The following tokenRegex
  * - matches typical whitespace (but not NBSP etc.) using its first group
@@ -68,8 +69,16 @@ static ref TOKEN_REGEX: Regex = {
         };
 }
 
+/**
+ * A lexer interface over `input` that has not lexed anything yet, for
+ * attaching `SourceLocation`s to nodes that did not come from the parser.
+ */
+pub(crate) fn source_interface(input: String) -> LexerInterface {
+    LexerInterface::new(input, &TOKEN_REGEX)
+}
+
 /** Main Lexer class */
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 pub struct Lexer {
     lexer_i: LexerInterface,
     settings: Settings,
@@ -136,9 +145,9 @@ impl Lexer {
     }
 }
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 impl Lexer {
-    #[wasm_bindgen(constructor)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
     pub fn new(input: String, settings: &Settings) -> Lexer {
         // Separate accents from characters
         Lexer {
@@ -155,7 +164,7 @@ impl Lexer {
         }
     }
 
-    #[wasm_bindgen(js_name = setCatcode)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = setCatcode))]
     pub fn set_catcode(&mut self, char: String, code: i32) {
         self.catcodes.insert(char, code);
     }
@@ -164,7 +173,6 @@ impl Lexer {
      * This function lexes a single token.
      */
     pub fn lex(&mut self) -> Token {
-        use web_sys::console;
         // let input = self.lexer_i.get_input();
         let pos = { self.lexer_i.get_last_index().clone() };
         // println!("pos = {}; input = {};", pos, input);
@@ -218,11 +226,13 @@ impl Lexer {
             let nl_index = self.lexer_i.get_input()[self.lexer_i.get_last_index()..].find('\n');
             if nl_index.is_none() {
                 self.lexer_i.set_last_index(self.lexer_i.get_input().len()); // EOF
-                self.settings.report_nonstrict(
+                if let Err(error) = self.settings.report_nonstrict(
                     "commentAtEnd" ,
                     "% comment has no terminating newline; LaTeX would fail because of commenting the end of math mode (e.g. $)",
                     None,
-                );
+                ) {
+                    self.report_parse_error(error.msg, error.loc);
+                }
             } else {
                 self.lexer_i.set_last_index(nl_index.unwrap() + 1);
             }
@@ -1,8 +1,8 @@
-use std::cell::RefCell;
 use crate::parse_error::ParseError;
 use crate::utils::{console_log, log};
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 use crate::parse_node::types::vcenter;
 
@@ -15,12 +15,19 @@ use crate::parse_node::types::vcenter;
  */
 
 pub type Mapping<T> = std::collections::HashMap<String, T>;
-pub type MapRef<T> = Arc<RefCell<Mapping<T>>>;
+/**
+ * A mapping shared between threads, such as `Settings.macros`. Global `set`s
+ * made while parsing are written back to it only by namespaces created to
+ * keep them, see `Namespace::new`.
+ */
+pub type MapRef<T> = Arc<RwLock<Mapping<T>>>;
 #[derive(Debug)]
 pub struct Namespace<Value> {
-    current: MapRef<Value>,
-    builtins: MapRef<Value>,
-    undef_stack: Vec<MapRef<Option<Value>>>,
+    current: Mapping<Value>,
+    builtins: Arc<Mapping<Value>>,
+    /// Where global and top-level `set`s are written back, if anywhere.
+    global_macros: Option<MapRef<Value>>,
+    undef_stack: Vec<Mapping<Option<Value>>>,
 }
 
 impl<Value: Clone> Namespace<Value> {
@@ -29,14 +36,23 @@ impl<Value: Clone> Namespace<Value> {
      * built-in mappings which never change.  The second argument is an object
      * of initial (global-level) mappings, which will constantly change
      * according to any global/top-level `set`s done.
+     *
+     * The global mappings are copied up front, so definitions local to this
+     * namespace are never visible to other threads sharing `global_macros`.
+     * Global and top-level `set`s are written back to `global_macros` only
+     * if `keep_globals` is set, as for a `RenderSession` owning its copy;
+     * otherwise they end with the namespace.
      */
-    pub fn new<'a>(
-        builtins: MapRef<Value>,
+    pub fn new(
+        builtins: Arc<Mapping<Value>>,
         global_macros: MapRef<Value>,
+        keep_globals: bool,
     ) -> Namespace<Value> {
+        let current = global_macros.read().unwrap().clone();
         Namespace {
-            current: global_macros,
+            current,
             builtins,
+            global_macros: if keep_globals { Some(global_macros) } else { None },
             undef_stack: Vec::new(),
         }
     }
@@ -45,7 +61,7 @@ impl<Value: Clone> Namespace<Value> {
      * Start a new nested group, affecting future local `set`s.
      */
     pub fn begin_group(&mut self) {
-        self.undef_stack.push(RefCell::new(HashMap::new()).into());
+        self.undef_stack.push(HashMap::new());
     }
 
     /**
@@ -56,16 +72,15 @@ impl<Value: Clone> Namespace<Value> {
             console_log!("Unbalanced namespace destruction: attempt to pop global namespace; please report this as a bug");
             return;
         }
-        let stack = self
-            .undef_stack
-            .pop() /*.expect("").lock()*/
-            .unwrap();
-        for (k, v) in stack.borrow().iter() {
-            //let mut cur = Arc::get_mut(&mut self.current).unwrap(); //.lock().unwrap();
-            if v.is_none() {
-                self.current.borrow_mut().remove(k);
-            } else {
-                self.current.borrow_mut().insert(k.to_string(), v.clone().unwrap());
+        let stack = self.undef_stack.pop().unwrap();
+        for (k, v) in stack.into_iter() {
+            match v {
+                None => {
+                    self.current.remove(&k);
+                }
+                Some(v) => {
+                    self.current.insert(k, v);
+                }
             }
         }
     }
@@ -85,11 +100,7 @@ impl<Value: Clone> Namespace<Value> {
      * `get(name) != null`.
      */
     pub fn has(&self, name: &str) -> bool {
-        // let cur = self.current.lock().unwrap();
-        // let buil = self.builtins.lock().unwrap();
-        // return cur.contains_key(name) ||
-        //     buil.contains_key(name);
-        return self.current.borrow().contains_key(name) || self.builtins.borrow().contains_key(name);
+        return self.current.contains_key(name) || self.builtins.contains_key(name);
     }
 
     /**
@@ -101,20 +112,10 @@ impl<Value: Clone> Namespace<Value> {
      * `if (namespace.has(...))`.
      */
     pub fn get(&self, name: &str) -> Option<Value> {
-        let cur = self.current.borrow();
-        match cur.get(name) {
-            None => {
-                match self.builtins.borrow().get(name) {
-                    Some(vv)=>{
-                        Some(vv.clone())
-                    },
-                    None =>{
-                        None
-                    }
-                }
-            }
-            Some(v) => {Some(v.clone())}
-        }
+        self.current
+            .get(name)
+            .or_else(|| self.builtins.get(name))
+            .cloned()
     }
 
     /**
@@ -131,35 +132,33 @@ impl<Value: Clone> Namespace<Value> {
             // and adding an undo with the *new* value (in case it later gets
             // locally reset within this environment).
             for stack in self.undef_stack.iter_mut() {
-                stack.borrow_mut().remove(name);
+                stack.remove(name);
             }
-            if self.undef_stack.len() > 0 {
-                self.undef_stack
-                    .last_mut()
-                    .unwrap()
-                    .borrow_mut()
-                    .insert(name.clone(), value.clone());
+            if let Some(top) = self.undef_stack.last_mut() {
+                top.insert(name.clone(), value.clone());
             }
         } else {
             // Undo this set at end of this group (possibly to `undefined`),
             // unless an undo is already in place, in which case that older
             // value is the correct one.
             if let Some(top) = self.undef_stack.last_mut() {
-                let mut top_mut = top.borrow_mut();
-                if top_mut.contains_key(name) {
-                    top_mut.insert(
-                        name.clone(),
-                        Some((*self.current.borrow().get(name).unwrap()).clone()),
-                    );
+                if !top.contains_key(name) {
+                    top.insert(name.clone(), self.current.get(name).cloned());
                 }
             }
         }
 
-        let  mut c = self.current.borrow_mut();
-        if value.is_none() {
-            c.remove(name);
-        } else {
-            c.insert(name.clone(), value.unwrap());
+        // Global and top-level definitions outlive this namespace if kept.
+        if let (true, Some(global_macros)) = (global || self.undef_stack.is_empty(), &self.global_macros) {
+            let mut globals = global_macros.write().unwrap();
+            match &value {
+                None => globals.remove(name),
+                Some(v) => globals.insert(name.clone(), v.clone()),
+            };
         }
+        match value {
+            None => self.current.remove(name),
+            Some(v) => self.current.insert(name.clone(), v),
+        };
     }
 }
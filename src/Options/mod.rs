@@ -1,4 +1,4 @@
-mod consts;
+pub(crate) mod consts;
 /**
  * This file contains information about the options that the Parser carries
  * around with it while parsing. Data is held in an `Options` object, and when
@@ -13,6 +13,8 @@ use crate::settings::Settings;
 use crate::utils::{console_log, log};
 use crate::Style::StyleInterface;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use types::{FontShape, FontWeight};
 use crate::Options::consts::{SIZE_MULTIPLIERS, SIZE_STYLE_MAP};
 
@@ -24,6 +26,23 @@ pub fn size_at_style(size: f64, style: &StyleInterface) -> f64 {
     }
 }
 
+/**
+ * The end of the `timeBudget` of a build, shared by every `Options` derived
+ * from the one made from the settings, along with whether a builder has
+ * found it passed.
+ */
+#[derive(Debug, Clone)]
+struct Deadline {
+    at: f64,
+    passed: Arc<AtomicBool>,
+}
+
+impl PartialEq for Deadline {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.passed, &other.passed)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Options {
     style: StyleInterface,
@@ -41,6 +60,8 @@ pub struct Options {
     pub sizeMultiplier: f64,
     pub maxSize: f64,
     pub minRuleThickness: f64,
+    /// When the `timeBudget` runs out, see `past_deadline`.
+    deadline: Option<Deadline>,
     _fontMetrics: Option<&'static FontMetrics>,
 }
 
@@ -57,15 +78,19 @@ impl Options {
     pub fn from_settings(settings: &Settings) -> Options {
         let mut res = Options::new();
         res.style = if settings.get_display_mode() {
-            let r = crate::Style::DISPLAY.read().unwrap();
+            let r = &*crate::Style::DISPLAY;
             r.clone()
         } else {
-            let r = crate::Style::TEXT.read().unwrap();
+            let r = &*crate::Style::TEXT;
             r.clone()
         };
         // console.log(`maxSiz === ${settings.maxSize}`)
         res.maxSize = settings.get_max_size().unwrap_or(100000.0);
         res.minRuleThickness = settings.get_min_rule_thickness();
+        res.deadline = settings.get_time_budget().map(|budget| Deadline {
+            at: crate::utils::now_ms() + budget,
+            passed: Arc::new(AtomicBool::new(false)),
+        });
         return res;
     }
 
@@ -139,10 +164,27 @@ impl Options {
             sizeMultiplier: SIZE_MULTIPLIERS[BASESIZE as i32 as usize - 1],
             maxSize: 0.0,
             minRuleThickness: 0.0,
+            deadline: None,
             _fontMetrics: None,
         }
     }
 
+    /**
+     * Whether the `timeBudget` has run out. Once it has, `build_group` builds
+     * nothing more and `build_tree` returns an error, the way the parser and
+     * the macro expander stop.
+     */
+    pub fn past_deadline(&self) -> bool {
+        match &self.deadline {
+            Some(deadline) if deadline.passed.load(Ordering::Relaxed) => true,
+            Some(deadline) if crate::utils::now_ms() > deadline.at => {
+                deadline.passed.store(true, Ordering::Relaxed);
+                true
+            }
+            _ => false,
+        }
+    }
+
     /**
      * Return an options object with the given style. If `this.style === style`,
      * returns `this`.
@@ -1,3 +1,4 @@
+use indexmap::IndexMap;
 use std::cell::RefCell;
 use std::{any::Any, str::FromStr};
 
@@ -41,10 +42,10 @@ use crate::{
         self,
         types::{ordgroup, AnyParseNode, Atom},
     },
-    settings::Settings,
+    settings::{CrossReferences, Settings},
     sourceLocation::SourceLocation,
     symbols::public::{Group, Mode},
-    token::Token,
+    token::{tokens_to_source, Token},
     types::{ArgType, BreakToken},
     unicodeSupOrSub::U_SUBS_AND_SUPS,
 };
@@ -55,7 +56,13 @@ pub struct Parser<'a> {
     pub settings: &'a Settings,
     pub left_right_depth: i32,
     pub next_token: Option<Token>,
+    // The token consume()d last, which ends the command being parsed.
+    last_token: Option<Token>,
     pub error: Option<ParseError>,
+    // Groups currently being parsed, and groups parsed so far, for the
+    // maxNestingDepth and maxParseNodes settings.
+    depth: usize,
+    node_count: usize,
 }
 
 const END_OF_EXPRESSION: [&'static str; 5] = ["}", "\\endgroup", "\\end", "\\right", "&"];
@@ -72,7 +79,10 @@ impl Parser<'_> {
             // Count leftright depth (for \middle errors)
             left_right_depth: 0,
             next_token: None,
+            last_token: None,
             error: None,
+            depth: 0,
+            node_count: 0,
         }
     }
 
@@ -90,6 +100,13 @@ impl Parser<'_> {
         self.error.take()
     }
 
+    /** See `Settings::report_nonstrict`; in "error" mode this fails the parse. */
+    pub(crate) fn report_nonstrict(&mut self, error_code: &str, error_msg: &str, token: Option<Token>) {
+        if let Err(error) = self.settings.report_nonstrict(error_code, error_msg, token) {
+            self.report_parse_error(error.msg, error.loc);
+        }
+    }
+
     /**
      * Checks a result to make sure it has the right type, and throws an
      * appropriate error otherwise.
@@ -109,7 +126,9 @@ impl Parser<'_> {
      * Discards the current lookahead token, considering it consumed.
      */
     pub fn consume(&mut self) {
-        self.next_token = None;
+        if let Some(token) = self.next_token.take() {
+            self.last_token = Some(token);
+        }
     }
 
     /**
@@ -209,6 +228,64 @@ impl Parser<'_> {
         return parse;
     }
 
+    /**
+     * Takes the tag of the equation (or row) just parsed, as set by `\tag`,
+     * or numbers it when `auto_number` is set and cross-references are
+     * enabled, registering the pending `\label` if any. Returns `None` when
+     * the equation keeps its default tag.
+     */
+    pub(crate) fn take_equation_tag(&mut self, auto_number: bool) -> Option<Vec<Box<dyn AnyParseNode>>> {
+        let cross_references = self.settings.get_cross_references();
+        let label = self.take_global_macro("\\df@label");
+        let reference = if self.gullet.macros.get(&"\\df@tag".to_string()).is_some() {
+            self.take_global_macro("\\df@tagref").unwrap_or_default()
+        } else if let (true, Some(cross_references)) = (auto_number, &cross_references) {
+            let number = cross_references.lock().unwrap().next_number().to_string();
+            self.gullet.macros.set(
+                &"\\df@tag".to_string(),
+                Some(MacroDefinition::Str(format!("\\text{{({})}}", number))),
+                true,
+            );
+            number
+        } else {
+            return None;
+        };
+        let tag = self.subparse(vec![Token::new("\\df@tag".to_string(), None)]);
+        self.gullet.macros.set(&"\\df@tag".to_string(), None, true);
+
+        match (cross_references, label) {
+            (Some(cross_references), Some(label)) => {
+                cross_references.lock().unwrap().define(&label, &reference);
+                let mut attributes = IndexMap::new();
+                attributes.insert("id".to_string(), CrossReferences::anchor(&label));
+                Some(vec![Box::new(parse_node::types::html {
+                    mode: self.mode,
+                    loc: None,
+                    attributes,
+                    body: tag,
+                }) as Box<dyn AnyParseNode>])
+            }
+            _ => Some(tag),
+        }
+    }
+
+    /**
+     * Undefines a macro set by `\gdef`, returning its expansion as LaTeX
+     * source.
+     */
+    fn take_global_macro(&mut self, name: &str) -> Option<String> {
+        let name = name.to_string();
+        let source = match self.gullet.macros.get(&name)? {
+            MacroDefinition::Str(source) => source.clone(),
+            MacroDefinition::MacroExpansion(expansion) => {
+                tokens_to_source(expansion.tokens.iter().rev().map(|token| token.text.as_str()))
+            }
+            _ => String::new(),
+        };
+        self.gullet.macros.set(&name, None, true);
+        Some(source)
+    }
+
     /**
      * Parses an "expression", which is a list of atoms.
      *
@@ -246,7 +323,7 @@ impl Parser<'_> {
                 }
             }
             if break_on_infix {
-                let funcs = crate::define::functions::public::_functions.read().unwrap();
+                let funcs = &*crate::define::functions::public::_functions;
                 if let Some((f1, f2)) = funcs.get(&lex.text) {
                     if f1.get_infix() {
                         break;
@@ -406,11 +483,14 @@ impl Parser<'_> {
         return group;
     }
 
-    // /**
-    //  * Converts the textual input of an unsupported command into a text node
-    //  * contained within a color node whose color is determined by errorColor
-    //  */
-    pub fn format_unsupported_cmd(&self, text: &str) -> parse_node::types::color {
+    /**
+     * Converts the textual input of an unsupported command into a text node
+     * contained within a color node whose color is determined by errorColor.
+     * `start` is the command token; the node's loc runs from it to the last
+     * token consumed, so that the command and its arguments can be printed
+     * back as written (see `parse_node::unparse`).
+     */
+    pub fn format_unsupported_cmd(&self, text: &str, start: Option<&Token>) -> parse_node::types::color {
         let textord_array = text
             .chars()
             .map(|c| {
@@ -429,11 +509,21 @@ impl Parser<'_> {
             font: None,
         }) as Box<dyn AnyParseNode>;
 
+        // Tokens from a macro expansion point into the macro's text.
+        let loc = match (start.and_then(|t| t.loc.as_ref()), self.last_token.as_ref().and_then(|t| t.loc.as_ref())) {
+            (Some(first), Some(last))
+                if first.start <= last.end && first.lexer.get_input() == last.lexer.get_input() =>
+            {
+                Some(SourceLocation::range(first, last))
+            }
+            _ => None,
+        };
+
         return parse_node::types::color {
             mode: self.mode,
             color: self.settings.get_error_color(),
             body: vec![text_node],
-            loc: None,
+            loc,
         };
     }
 
@@ -587,7 +677,7 @@ impl Parser<'_> {
                     semisimple: false,
                 }));
             } else {
-                let u_subs_and_sups = U_SUBS_AND_SUPS.lock().unwrap();
+                let u_subs_and_sups = &*U_SUBS_AND_SUPS;
                 if let Some(mut _s) = u_subs_and_sups.get(&lex.text.as_str()) {
                     let mut _str = _s.to_string();
                     // A Unicode subscript or superscript character.
@@ -664,7 +754,7 @@ impl Parser<'_> {
     ) -> Option<Box<dyn AnyParseNode>> {
         let token = self.fetch();
         let func = &token.text.clone();
-        let functions = _functions.read().unwrap();
+        let functions = &*_functions;
         if let Some(mut funcData) = functions.get(func) {
             self.consume(); // consume command token
 
@@ -720,7 +810,7 @@ impl Parser<'_> {
             token,
             break_on_token_text,
         });
-        let functions = _functions.read().unwrap();
+        let functions = &*_functions;
         let func = functions.get(name).unwrap();
         func.1(context, args, optArgs)
     }
@@ -840,7 +930,8 @@ impl Parser<'_> {
                 }
                 ArgType::primitive => {
                     if (optional) {
-                        panic!("A primitive argument cannot be optional");
+                        self.report_parse_error("A primitive argument cannot be optional".to_string(), None);
+                        return None;
                     }
                     let group = self.parse_group(name.clone(), None);
                     if group.is_none() {
@@ -1130,7 +1221,61 @@ impl Parser<'_> {
         breakOnTokenText: Option<BreakToken>,
     ) -> Option<Box<dyn AnyParseNode>> {
         let first_token = self.fetch();
-        let text = first_token.text;
+        if let Some(msg) = self.exceeded_limit() {
+            self.report_token_error(msg, &first_token);
+            return None;
+        }
+        self.depth += 1;
+        self.node_count += 1;
+        let result = self.parse_group_at(name, breakOnTokenText, first_token);
+        self.depth -= 1;
+        return result;
+    }
+
+    /**
+     * Counts one more level of nesting for a body that is parsed without
+     * going through `parse_group` first, like that of `\left` or of an
+     * environment. Returns `false`, with the error reported, if a limit is
+     * reached; otherwise `leave_nesting` must follow.
+     */
+    pub(crate) fn enter_nesting(&mut self) -> bool {
+        if let Some(msg) = self.exceeded_limit() {
+            let token = self.fetch();
+            self.report_token_error(msg, &token);
+            return false;
+        }
+        self.depth += 1;
+        true
+    }
+
+    pub(crate) fn leave_nesting(&mut self) {
+        self.depth -= 1;
+    }
+
+    /**
+     * Checks the resource limits in the settings before parsing one more
+     * group, returning the error message if one is reached.
+     */
+    fn exceeded_limit(&self) -> Option<String> {
+        if self.settings.get_max_nesting_depth().map_or(false, |max| self.depth >= max) {
+            return Some("Too deeply nested: need to increase maxNestingDepth setting".to_string());
+        }
+        if self.settings.get_max_parse_nodes().map_or(false, |max| self.node_count >= max) {
+            return Some("Too many parse nodes: need to increase maxParseNodes setting".to_string());
+        }
+        if self.gullet.past_deadline() {
+            return Some("Time budget exceeded: need to increase timeBudget setting".to_string());
+        }
+        None
+    }
+
+    fn parse_group_at(
+        &mut self,
+        name: String,
+        breakOnTokenText: Option<BreakToken>,
+        first_token: Token,
+    ) -> Option<Box<dyn AnyParseNode>> {
+        let text = first_token.text.clone();
 
         let mut result;
         // Try to parse an open brace or \begingroup
@@ -1158,10 +1303,10 @@ impl Parser<'_> {
             self.gullet.end_group();
             result = Some(Box::new(parse_node::types::ordgroup {
                 mode: self.mode,
-                loc: Some(SourceLocation::range(
-                    &first_token.loc.unwrap(),
-                    &lastToken.loc.unwrap(),
-                )),
+                loc: match (&first_token.loc, &lastToken.loc) {
+                    (Some(first), Some(last)) => Some(SourceLocation::range(first, last)),
+                    _ => None,
+                },
                 body: expression,
                 // A group formed by \begingroup...\endgroup is a semi-simple group
                 // which doesn't affect spacing in math mode, i.e., is transparent.
@@ -1182,13 +1327,15 @@ impl Parser<'_> {
                     .contains(&text.as_str()))
             {
                 if (self.settings.get_throw_on_error()) {
-                    panic!("Undefined control sequence: ");
-                    // throw new ParseError(
-                    //     "Undefined control sequence: " + text, firstToken);
+                    self.report_token_error(
+                        format!("Undefined control sequence: {}", text),
+                        &first_token,
+                    );
+                    return None;
                 }
-                result =
-                    Some(Box::new(self.format_unsupported_cmd(&text)) as Box<dyn AnyParseNode>);
                 self.consume();
+                result = Some(Box::new(self.format_unsupported_cmd(&text, Some(&first_token)))
+                    as Box<dyn AnyParseNode>);
             }
         }
         return result;
@@ -1208,20 +1355,20 @@ impl Parser<'_> {
     ) -> Vec<Box<dyn AnyParseNode>> {
         let mut i = 0usize;
         while i + 1 < group.len() {
-            if !parse_node::check_symbol_node_type(&group[i])
-                || !parse_node::check_symbol_node_type(&group[i + 1])
+            if !parse_node::check_symbol_node_type(group[i].as_ref())
+                || !parse_node::check_symbol_node_type(group[i + 1].as_ref())
             {
                 i += 1;
                 continue;
             }
 
-            let value = parse_node::check_symbol_node_type_text(&group[i]);
-            let next_value = parse_node::check_symbol_node_type_text(&group[i + 1]);
+            let value = parse_node::check_symbol_node_type_text(group[i].as_ref());
+            let next_value = parse_node::check_symbol_node_type_text(group[i + 1].as_ref());
 
             if value == "-" && next_value == "-" {
                 let is_triple_dash = i + 2 < group.len()
-                    && parse_node::check_symbol_node_type(&group[i + 2])
-                    && parse_node::check_symbol_node_type_text(&group[i + 2]) == "-";
+                    && parse_node::check_symbol_node_type(group[i + 2].as_ref())
+                    && parse_node::check_symbol_node_type_text(group[i + 2].as_ref()) == "-";
 
                 let end = if is_triple_dash { i + 3 } else { i + 2 };
                 let text = if is_triple_dash { "---" } else { "--" };
@@ -1303,7 +1450,7 @@ impl Parser<'_> {
             if crate::symbols::get_symbol(self.mode, &first_text).is_none() {
                 // This behavior is not strict (XeTeX-compatible) in math mode.
                 if (/*self.settings.get_strict() && */self.mode == Mode::math) {
-                    self.settings.report_nonstrict(
+                    self.report_nonstrict(
                         "unicodeTextInMathMode",
                         &format!(
                             "Accented Unicode text character \"{}\" used in math mode",
@@ -1396,13 +1543,13 @@ impl Parser<'_> {
             if (!crate::unicodeScripts::supportedCodepoint(
                 (text.chars().nth(0).unwrap() as u32).into(),
             )) {
-                self.settings.report_nonstrict(
+                self.report_nonstrict(
                     "unknownSymbol",
                     &format!("Unrecognized Unicode character \"{}\"  ({})", text, text),
                     Some(nucleus.clone()),
                 );
             } else if (self.mode == Mode::math) {
-                self.settings.report_nonstrict(
+                self.report_nonstrict(
                     "unicodeTextInMathMode",
                     &format!("Unicode text character \"{}\" used in math mode", text2),
                     Some(nucleus.clone()),
@@ -1447,7 +1594,8 @@ impl Parser<'_> {
                         base: Some(symbol),
                     }) as Box<dyn AnyParseNode>;
                 } else {
-                    panic!("Unknown accent {}", accent);
+                    self.report_token_error(format!("Unknown accent ' {}'", accent), &nucleus);
+                    break;
                 }
             }
         }
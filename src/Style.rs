@@ -1,8 +1,9 @@
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 
 #[derive(Debug,Clone)]
 #[derive(PartialEq)]
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 pub struct StyleInterface {
     pub id: usize,
     pub size: i32,
@@ -10,11 +11,11 @@ pub struct StyleInterface {
 }
 
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 impl StyleInterface {
 
 
-    #[wasm_bindgen(constructor)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
     pub fn new(id: usize, size: i32, cramped: bool) -> StyleInterface {
         StyleInterface {
             id: id as usize,
@@ -138,10 +139,9 @@ const cramp: [usize; 8] = [Dc, Dc, Tc, Tc, Sc, Sc, SSc, SSc];
 const text: [usize; 8] = [D, Dc, T, Tc, T, Tc, T, Tc];
 
 
-use std::sync::RwLock;
 lazy_static!{
-    pub static ref DISPLAY:RwLock<StyleInterface> = RwLock::new(StyleInterface::new(0, 0, false));
-    pub static ref TEXT:RwLock<StyleInterface> = RwLock::new(StyleInterface::new(2, 1, false));
-    pub static ref SCRIPT:RwLock<StyleInterface> = RwLock::new(StyleInterface::new(4, 2, false));
-    pub static ref SCRIPTSCRIPT:RwLock<StyleInterface> = RwLock::new(StyleInterface::new(6, 3, false));
+    pub static ref DISPLAY: StyleInterface = StyleInterface::new(0, 0, false);
+    pub static ref TEXT: StyleInterface = StyleInterface::new(2, 1, false);
+    pub static ref SCRIPT: StyleInterface = StyleInterface::new(4, 2, false);
+    pub static ref SCRIPTSCRIPT: StyleInterface = StyleInterface::new(6, 3, false);
 }
@@ -4,8 +4,9 @@ use std::process;
 use std::thread;
 use std::time::Instant;
 
-use clap::{ArgAction, Parser, ValueHint};
+use clap::{ArgAction, Parser, Subcommand, ValueEnum, ValueHint};
 use katex_wasm::settings::Settings;
+use katex_wasm::PngOptions;
 
 fn parse_line_number(value: &str) -> Result<usize, String> {
     let parsed = value
@@ -25,10 +26,44 @@ struct RenderTask {
     formula: String,
 }
 
+#[derive(Clone, Debug)]
+enum OutputKind {
+    Html,
+    Ast,
+    /// One `line-N.png` per formula, written to the directory.
+    Png(PngOptions, PathBuf),
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Format {
+    Html,
+    Png,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Print the katex.css matching the markup of this version
+    Css {
+        /// URL prefix of the KaTeX font files
+        #[arg(long, value_name = "URL", default_value = "fonts/")]
+        font_url: String,
+    },
+}
+
 #[derive(Debug)]
 enum RenderOutcome {
-    Ok { html: String, elapsed_ms: f64 },
-    Error { message: String, elapsed_ms: f64 },
+    Ok {
+        html: String,
+        diagnostics: Vec<String>,
+        trace: Vec<String>,
+        elapsed_ms: f64,
+    },
+    Error {
+        message: String,
+        diagnostics: Vec<String>,
+        trace: Vec<String>,
+        elapsed_ms: f64,
+    },
 }
 
 #[derive(Debug)]
@@ -38,7 +73,74 @@ struct RenderResult {
     outcome: RenderOutcome,
 }
 
-fn render_formula(task: RenderTask) -> RenderResult {
+/**
+ * The CLI defaults, then the `--config` file, then the individual flags,
+ * each overriding the previous ones.
+ */
+fn cli_settings(args: &Cli) -> Result<Settings, String> {
+    let mut settings = default_cli_settings();
+    if let Some(path) = &args.config {
+        let content = fs::read_to_string(path)
+            .map_err(|error| format!("Error reading config '{}': {}", path.display(), error))?;
+        let result = if path.extension().map_or(false, |ext| ext == "json") {
+            serde_json::from_str(&content)
+                .map_err(|error| error.to_string())
+                .and_then(|json| settings.update_from_json(&json))
+        } else {
+            settings.update_from_toml(&content)
+        };
+        result.map_err(|error| format!("Invalid config '{}': {}", path.display(), error))?;
+    }
+
+    let mut flags = serde_json::Map::new();
+    if args.inline {
+        flags.insert("displayMode".to_string(), false.into());
+    }
+    if let Some(output) = &args.output {
+        flags.insert("output".to_string(), output.clone().into());
+    }
+    if let Some(strict) = &args.strict {
+        flags.insert("strict".to_string(), strict.clone().into());
+    }
+    if args.no_trust {
+        flags.insert("trust".to_string(), false.into());
+    }
+    if args.leqno {
+        flags.insert("leqno".to_string(), true.into());
+    }
+    if args.fleqn {
+        flags.insert("fleqn".to_string(), true.into());
+    }
+    if let Some(max_size) = args.max_size {
+        flags.insert("maxSize".to_string(), max_size.into());
+    }
+    if let Some(max_expand) = args.max_expand {
+        flags.insert("maxExpand".to_string(), max_expand.into());
+    }
+    if let Some(path) = &args.preamble {
+        let content = fs::read_to_string(path)
+            .map_err(|error| format!("Error reading preamble '{}': {}", path.display(), error))?;
+        flags.insert("preamble".to_string(), content.into());
+    }
+    if args.trace_macros {
+        flags.insert("traceMacros".to_string(), true.into());
+    }
+    if args.inline_styles {
+        flags.insert("inlineStyles".to_string(), true.into());
+    }
+    let mut macros = serde_json::Map::new();
+    for definition in &args.macros {
+        let (name, expansion) = definition
+            .split_once('=')
+            .ok_or_else(|| format!("Invalid --macro '{}': expected NAME=EXPANSION", definition))?;
+        macros.insert(name.to_string(), expansion.into());
+    }
+    flags.insert("macros".to_string(), macros.into());
+    settings.update_from_json(&flags.into())?;
+    Ok(settings)
+}
+
+fn default_cli_settings() -> Settings {
     let mut settings = Settings::new();
     settings.set_display_mode(true);
     settings.set_output("html".to_string());
@@ -47,25 +149,51 @@ fn render_formula(task: RenderTask) -> RenderResult {
     settings.set_trust(true);
     settings.set_max_size(Some(200000.0));
     settings.set_max_expand(Some(1000));
+    settings
+}
 
+fn render_formula(task: RenderTask, settings: &Settings, kind: &OutputKind) -> RenderResult {
     let render_start = Instant::now();
-    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
-        katex_wasm::render_to_string(task.formula.clone(), settings)
-    }));
+    let (result, diagnostics, trace) = match kind {
+        OutputKind::Html => {
+            let output = katex_wasm::try_render_to_string_with_warnings(task.formula.clone(), settings.clone());
+            let diagnostics = output.diagnostics.iter().map(|diagnostic| diagnostic.to_string()).collect();
+            let trace = output.trace.iter().map(|step| step.to_string()).collect();
+            (output.markup, diagnostics, trace)
+        }
+        OutputKind::Ast => (
+            katex_wasm::try_parse_to_json(task.formula.clone(), settings.clone())
+                .map(|json| serde_json::to_string_pretty(&json).unwrap()),
+            vec![],
+            vec![],
+        ),
+        OutputKind::Png(options, dir) => (
+            katex_wasm::try_render_to_png(task.formula.clone(), settings.clone(), options).and_then(|png| {
+                let path = dir.join(format!("line-{}.png", task.line_num));
+                fs::write(&path, &png).map_err(|error| {
+                    katex_wasm::ParseError::new(format!("Error writing '{}': {}", path.display(), error), None)
+                })?;
+                Ok(format!("{} ({} bytes)", path.display(), png.len()))
+            }),
+            vec![],
+            vec![],
+        ),
+    };
     let elapsed_ms = render_start.elapsed().as_secs_f64() * 1000.0;
 
     let outcome = match result {
-        Ok(html) => RenderOutcome::Ok { html, elapsed_ms },
-        Err(panic_info) => {
-            let message = if let Some(msg) = panic_info.downcast_ref::<String>() {
-                msg.clone()
-            } else if let Some(msg) = panic_info.downcast_ref::<&str>() {
-                msg.to_string()
-            } else {
-                "unknown panic".to_string()
-            };
-            RenderOutcome::Error { message, elapsed_ms }
-        }
+        Ok(html) => RenderOutcome::Ok {
+            html,
+            diagnostics,
+            trace,
+            elapsed_ms,
+        },
+        Err(error) => RenderOutcome::Error {
+            message: error.to_string(),
+            diagnostics,
+            trace,
+            elapsed_ms,
+        },
     };
 
     RenderResult {
@@ -75,13 +203,18 @@ fn render_formula(task: RenderTask) -> RenderResult {
     }
 }
 
-fn render_tasks(tasks: Vec<RenderTask>, multi_threaded: bool) -> Vec<RenderResult> {
+fn render_tasks(
+    tasks: Vec<RenderTask>,
+    settings: &Settings,
+    multi_threaded: bool,
+    kind: &OutputKind,
+) -> Vec<RenderResult> {
     if tasks.is_empty() {
         return Vec::new();
     }
 
     if !multi_threaded {
-        return tasks.into_iter().map(render_formula).collect();
+        return tasks.into_iter().map(|task| render_formula(task, settings, kind)).collect();
     }
 
     let worker_count = thread::available_parallelism()
@@ -90,7 +223,7 @@ fn render_tasks(tasks: Vec<RenderTask>, multi_threaded: bool) -> Vec<RenderResul
         .min(tasks.len());
 
     if worker_count <= 1 {
-        return tasks.into_iter().map(render_formula).collect();
+        return tasks.into_iter().map(|task| render_formula(task, settings, kind)).collect();
     }
 
     let chunk_size = tasks.len().div_ceil(worker_count);
@@ -104,7 +237,7 @@ fn render_tasks(tasks: Vec<RenderTask>, multi_threaded: bool) -> Vec<RenderResul
             handles.push(scope.spawn(move || {
                 chunk_tasks
                     .into_iter()
-                    .map(render_formula)
+                    .map(|task| render_formula(task, settings, kind))
                     .collect::<Vec<RenderResult>>()
             }));
         }
@@ -129,12 +262,17 @@ fn render_tasks(tasks: Vec<RenderTask>, multi_threaded: bool) -> Vec<RenderResul
     name = "katex-rs-cli",
     bin_name = "katex-rs-cli",
     version,
-    about = "Render LaTeX formulas from a file with the Rust KaTeX implementation"
+    about = "Render LaTeX formulas from a file with the Rust KaTeX implementation",
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
 )]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Path to a file with one LaTeX formula per line
-    #[arg(value_name = "FORMULAS_TXT", value_hint = ValueHint::FilePath)]
-    formula_file_path: PathBuf,
+    #[arg(value_name = "FORMULAS_TXT", value_hint = ValueHint::FilePath, required = true)]
+    formula_file_path: Option<PathBuf>,
 
     /// Start line number (1-based, inclusive)
     #[arg(
@@ -155,11 +293,119 @@ struct Cli {
     /// Whether to render formulas in parallel (enabled by default)
     #[arg(long, default_value_t = true, action = ArgAction::Set)]
     multi_threaded: bool,
+
+    /// Print the parse tree as KaTeX-compatible JSON instead of HTML
+    #[arg(long, conflicts_with = "format")]
+    ast: bool,
+
+    /// Print HTML, or write a PNG image per formula to --out-dir; png needs --font-dir
+    #[arg(long, value_enum, default_value_t = Format::Html)]
+    format: Format,
+
+    /// Resolution of --format png
+    #[arg(long, value_name = "DPI", default_value_t = 96.0)]
+    dpi: f64,
+
+    /// Text color of --format png; the background is transparent
+    #[arg(long, value_name = "COLOR", default_value = "black")]
+    color: String,
+
+    /// Directory --format png writes line-N.png files to
+    #[arg(long, value_name = "DIR", value_hint = ValueHint::DirPath, default_value = ".")]
+    out_dir: PathBuf,
+
+    /// Settings file in TOML (or JSON, by extension) with KaTeX's option names
+    #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    config: Option<PathBuf>,
+
+    /// Render in inline mode instead of display mode
+    #[arg(long)]
+    inline: bool,
+
+    /// Output type: html, mathml, htmlAndMathml, svg, or text for a terminal preview
+    #[arg(long, value_name = "TYPE")]
+    output: Option<String>,
+
+    /// Strict mode: ignore, warn or error
+    #[arg(long, value_name = "MODE")]
+    strict: Option<String>,
+
+    /// Do not trust \href, \url, \includegraphics and \html* commands
+    #[arg(long)]
+    no_trust: bool,
+
+    /// Render \tag on the left
+    #[arg(long)]
+    leqno: bool,
+
+    /// Make display math flush left
+    #[arg(long)]
+    fleqn: bool,
+
+    /// Maximum size of user-specified sizes, in ems
+    #[arg(long, value_name = "EMS")]
+    max_size: Option<f64>,
+
+    /// Maximum number of macro expansions
+    #[arg(long, value_name = "N")]
+    max_expand: Option<usize>,
+
+    /// LaTeX file of \newcommand, \def and similar definitions used by every formula
+    #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    preamble: Option<PathBuf>,
+
+    /// Print every macro expansion, like TeX's \tracingmacros
+    #[arg(long)]
+    trace_macros: bool,
+
+    /// Write the katex.css rules into style attributes, for pages without the stylesheet
+    #[arg(long)]
+    inline_styles: bool,
+
+    /// Define a macro, e.g. --macro '\RR=\mathbb{R}'; may be repeated
+    #[arg(long = "macro", value_name = "NAME=EXPANSION")]
+    macros: Vec<String>,
+
+    /// Directory with the KaTeX_*.ttf fonts whose outlines --format png and --output svg draw
+    /// (required by --format png; without it --output svg falls back to <text>)
+    #[arg(long, value_name = "DIR", value_hint = ValueHint::DirPath)]
+    font_dir: Option<PathBuf>,
 }
 
 fn main() {
     let args = Cli::parse();
-    let formula_file_path = args.formula_file_path;
+    if let Some(Command::Css { font_url }) = &args.command {
+        print!("{}", katex_wasm::katex_css(font_url));
+        return;
+    }
+    let settings = match cli_settings(&args) {
+        Ok(settings) => settings,
+        Err(error) => {
+            eprintln!("{error}");
+            process::exit(1);
+        }
+    };
+    if let Some(dir) = &args.font_dir {
+        match katex_wasm::load_svg_font_dir(dir) {
+            Ok(0) => eprintln!("Warning: no KaTeX_*.ttf fonts in '{}'", dir.display()),
+            Ok(_) => {}
+            Err(error) => {
+                eprintln!("Error reading fonts: {error}");
+                process::exit(1);
+            }
+        }
+    }
+    if katex_wasm::registered_svg_fonts().is_empty() && !args.ast {
+        // Without fonts every PNG glyph would be a hollow box.
+        if let Format::Png = args.format {
+            eprintln!("Error: --format png draws glyphs from the KaTeX fonts; pass --font-dir with the KaTeX_*.ttf files");
+            process::exit(1);
+        }
+        if settings.get_output() == "svg" {
+            eprintln!("Warning: without --font-dir, --output svg writes glyphs as <text> in the KaTeX fonts");
+        }
+    }
+    let formula_file_path = args.formula_file_path.clone().unwrap_or_default();
 
     let content = match fs::read_to_string(&formula_file_path) {
         Ok(content) => content,
@@ -180,6 +426,18 @@ fn main() {
     let end_line = args.end_line.unwrap_or(total_lines).min(total_lines);
     let summary_only = args.summary_only;
     let multi_threaded = args.multi_threaded;
+    let kind = match (args.ast, args.format) {
+        (true, _) => OutputKind::Ast,
+        (false, Format::Html) => OutputKind::Html,
+        (false, Format::Png) => {
+            let options = PngOptions {
+                dpi: args.dpi,
+                color: args.color.clone(),
+                ..PngOptions::default()
+            };
+            OutputKind::Png(options, args.out_dir.clone())
+        }
+    };
 
     if start_line > end_line || start_line > total_lines {
         eprintln!(
@@ -195,6 +453,19 @@ fn main() {
     const BOLD: &str = "\x1b[1m";
     const DIM: &str = "\x1b[2m";
 
+    // What \message and friends printed, and the macro trace, if any.
+    fn print_diagnostics(diagnostics: &[String], trace: &[String]) {
+        for (title, lines) in [("Diagnostics", diagnostics), ("Macro trace", trace)] {
+            if !lines.is_empty() {
+                println!("{DIM}--- {title} ---{RESET}");
+                for line in lines {
+                    println!("{line}");
+                }
+                println!();
+            }
+        }
+    }
+
     if !summary_only {
         eprintln!(
             "Processing lines {}-{} from '{}'",
@@ -221,7 +492,7 @@ fn main() {
         });
     }
 
-    let results = render_tasks(tasks, multi_threaded);
+    let results = render_tasks(tasks, &settings, multi_threaded, &kind);
 
     for result in results {
         if !summary_only {
@@ -231,17 +502,40 @@ fn main() {
         }
 
         match result.outcome {
-            RenderOutcome::Ok { html, elapsed_ms } => {
+            RenderOutcome::Ok {
+                html,
+                diagnostics,
+                trace,
+                elapsed_ms,
+            } => {
                 if !summary_only {
-                    println!("{DIM}--- Rust HTML ({elapsed_ms:.2}ms) ---{RESET}");
+                    let label = match kind {
+                        OutputKind::Html => match settings.get_output().as_str() {
+                            "mathml" => "MathML",
+                            "htmlAndMathml" => "HTML and MathML",
+                            "svg" => "SVG",
+                            "text" => "text",
+                            _ => "HTML",
+                        },
+                        OutputKind::Ast => "AST",
+                        OutputKind::Png(..) => "PNG",
+                    };
+                    println!("{DIM}--- Rust {label} ({elapsed_ms:.2}ms) ---{RESET}");
                     println!("{html}");
                     println!();
+                    print_diagnostics(&diagnostics, &trace);
                     println!("{GREEN}✓ OK{RESET}");
                 }
                 pass_count += 1;
             }
-            RenderOutcome::Error { message, elapsed_ms } => {
+            RenderOutcome::Error {
+                message,
+                diagnostics,
+                trace,
+                elapsed_ms,
+            } => {
                 if !summary_only {
+                    print_diagnostics(&diagnostics, &trace);
                     println!("{RED}✗ ERROR ({elapsed_ms:.2}ms): {message}{RESET}");
                 }
                 error_count += 1;
@@ -515,10 +515,13 @@ pub fn build_group(
     options: Options,
     base_options: Option<Options>,
 ) -> Box<dyn HtmlDomNode> {
+    if options.past_deadline() {
+        return Box::new(make_span(vec![], vec![], None, CssStyle::default()));
+    }
     if let Some(g) = group {
         let t = g.get_type();
         let mut group_node = {
-            let _builders = _HTML_GROUP_BUILDERS.read().unwrap();
+            let _builders = &*_HTML_GROUP_BUILDERS;
             if let Some(f) = _builders.get(t) {
                 f(g, options.clone())
             } else {
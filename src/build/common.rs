@@ -17,9 +17,10 @@ use crate::{get_character_metrics, get_symbol, parse_node};
 use std::any::Any;
 use std::collections::HashMap;
 use std::str::FromStr;
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 
-#[wasm_bindgen(getter_with_clone)]
+#[cfg_attr(feature = "wasm", wasm_bindgen(getter_with_clone))]
 pub struct TmpSymbol {
     pub value: String,
     pub metrics: Option<CharacterMetrics>,
@@ -265,7 +266,7 @@ fn make_ord_node(
             font_name = font_data[0].to_string();
             font_classes = vec![font_data[1].to_string()];
         } else if is_font {
-            let font_map = FONT_MAP.lock().unwrap();
+            let font_map = &*FONT_MAP;
             font_name = font_map
                 .get(&font_or_family.as_str())
                 .unwrap()
@@ -821,7 +822,7 @@ pub struct FontInfo {
 }
 // A map between tex font commands an MathML mathvariant attribute values
 lazy_static! {
-    pub static ref FONT_MAP: std::sync::Mutex< HashMap<&'static str, FontInfo> >= std::sync::Mutex::new({
+    pub static ref FONT_MAP: HashMap<&'static str, FontInfo> = {
         HashMap::from([
         ("mathbf",FontInfo{
             variant: FontVariant::bold,
@@ -878,10 +879,10 @@ lazy_static! {
             fontName: "Typewriter-Regular",
         })
     ])
-    });
+    };
 
 
-    pub static ref SVG_DATA : std::sync::Mutex<HashMap<&'static str,(&'static str,f64,f64)> > = std::sync::Mutex::new({
+    pub static ref SVG_DATA: HashMap<&'static str,(&'static str,f64,f64)> = {
         HashMap::from([
             //   path, width, height
             ("vec", ("vec", 0.471, 0.714)),               // values from the font glyph
@@ -890,13 +891,13 @@ lazy_static! {
             ("oiiintSize1", ("oiiintSize1", 1.304, 0.499)),
             ("oiiintSize2", ("oiiintSize2", 1.98, 0.659))
         ])
-    });
+    };
 
 }
 
 pub fn static_svg(value: String, options: Options) -> Span {
     // Create a span with inline SVG for the element.
-    let svg_data = SVG_DATA.lock().unwrap();
+    let svg_data = &*SVG_DATA;
     let (pathName, width, height) = svg_data.get(value.as_str()).unwrap();
     let path = PathNode::new(pathName.to_string(), None);
     let svg_node_attr = HashMap::from([
@@ -0,0 +1,352 @@
+use std::sync::OnceLock;
+
+use crate::dom_tree::css_style::CssStyle;
+use crate::dom_tree::img::Img;
+use crate::dom_tree::span::Span;
+use crate::dom_tree::svg_node::SvgNode;
+use crate::dom_tree::symbol_node::SymbolNode;
+use crate::stylesheet::style_rules;
+use crate::tree::HtmlDomNode;
+
+fn has(classes: &[String], name: &str) -> bool {
+    classes.iter().any(|class| class == name)
+}
+
+/** One compound of a selector, such as `.op-symbol.small-op` or `span`. */
+#[derive(Debug)]
+struct Compound {
+    tag: Option<String>,
+    classes: Vec<String>,
+    // The classes of `:not(...)`.
+    not: Vec<String>,
+    // Whether the compound is joined to the previous one by `>`.
+    child: bool,
+}
+
+impl Compound {
+    /** `None` for what has no inline equivalent, such as `::before`. */
+    fn parse(compound: &str, child: bool) -> Option<Compound> {
+        if compound.contains("::") {
+            return None;
+        }
+        let (compound, not) = match compound.split_once(":not(") {
+            Some((compound, not)) => (compound, not.trim_end_matches(')')),
+            None => (compound, ""),
+        };
+        let classes = |list: &str| list.split('.').skip(1).map(str::to_string).collect::<Vec<_>>();
+        let tag = compound.split('.').next().filter(|tag| !tag.is_empty());
+        Some(Compound {
+            tag: tag.map(str::to_string),
+            classes: classes(compound),
+            not: classes(not),
+            child,
+        })
+    }
+
+    /**
+     * `span` stands for any element; `img` only matches the images, and the
+     * rules of `*`, `svg`, `path` and `body` are left to `SVG_STYLE` or to
+     * nothing.
+     */
+    fn matches(&self, classes: &[String], img: bool) -> bool {
+        let tag = match self.tag.as_deref() {
+            None | Some("span") => true,
+            Some("img") => img,
+            Some(_) => false,
+        };
+        tag && self.classes.iter().all(|class| has(classes, class))
+            && !self.not.iter().any(|class| has(classes, class))
+    }
+}
+
+/** A rule of katex.css, with its declarations as `CssStyle` longhands. */
+struct Rule {
+    selectors: Vec<Vec<Compound>>,
+    declarations: Vec<(String, String)>,
+}
+
+/**
+ * The longhands of a katex.css declaration, as `CssStyle` holds them:
+ * `border: 0.04em solid` becomes `border-top-width: 0.04em` and so on.
+ */
+fn longhands(name: &str, value: &str) -> Vec<(String, String)> {
+    let sides = ["top", "right", "bottom", "left"];
+    let values: Vec<&str> = value.split_whitespace().collect();
+    let border = |side: &str| {
+        values
+            .iter()
+            .map(|value| {
+                let kind = if value.starts_with(|c: char| c.is_ascii_digit() || c == '.') { "width" } else { "style" };
+                (format!("border-{}-{}", side, kind), value.to_string())
+            })
+            .collect::<Vec<_>>()
+    };
+    match name {
+        "font" => {
+            let parts: Vec<&str> = value.splitn(3, ' ').collect();
+            vec![
+                ("font-style".to_string(), parts[0].to_string()),
+                ("font-weight".to_string(), parts[0].to_string()),
+                ("font-size".to_string(), parts[1].to_string()),
+                ("font-family".to_string(), parts[2].to_string()),
+            ]
+        }
+        "margin" | "padding" => {
+            let index = [[0, 0, 0, 0], [0, 1, 0, 1], [0, 1, 2, 1], [0, 1, 2, 3]][values.len() - 1];
+            sides
+                .iter()
+                .zip(index)
+                .map(|(side, i)| (format!("{}-{}", name, side), values[i].to_string()))
+                .collect()
+        }
+        "border" => sides.iter().flat_map(|side| border(side)).collect(),
+        _ => match name.strip_prefix("border-").filter(|side| sides.contains(side)) {
+            Some(side) => border(side),
+            None => vec![(name.to_string(), value.to_string())],
+        },
+    }
+}
+
+/** The rules of `stylesheet::style_rules` that can be written inline. */
+fn rules() -> &'static [Rule] {
+    static RULES: OnceLock<Vec<Rule>> = OnceLock::new();
+    RULES.get_or_init(|| {
+        style_rules()
+            .into_iter()
+            .filter_map(|(selectors, declarations)| {
+                let selectors: Vec<Vec<Compound>> = selectors
+                    .split(", ")
+                    .filter_map(|selector| {
+                        let mut child = false;
+                        let mut compounds = vec![];
+                        for part in selector.split_whitespace() {
+                            if part == ">" {
+                                child = true;
+                                continue;
+                            }
+                            compounds.push(Compound::parse(part, child)?);
+                            child = false;
+                        }
+                        Some(compounds)
+                    })
+                    .collect();
+                let declarations: Vec<(String, String)> = declarations
+                    .iter()
+                    .flat_map(|(name, value)| longhands(name, value))
+                    .filter(|(name, value)| CssStyle::default().set_css_property(name, value.clone()))
+                    .collect();
+                (!selectors.is_empty() && !declarations.is_empty()).then_some(Rule { selectors, declarations })
+            })
+            .collect()
+    })
+}
+
+/**
+ * Whether `compounds` matches the last element of `path`, whose ancestors
+ * are the rest of `path` from the outside in.
+ */
+fn selector_matches(compounds: &[Compound], path: &[Vec<String>], img: bool) -> bool {
+    let (Some((last, compounds)), Some((element, ancestors))) = (compounds.split_last(), path.split_last()) else {
+        return false;
+    };
+    if !last.matches(element, img) {
+        return false;
+    }
+    if compounds.is_empty() {
+        return true;
+    }
+    if last.child {
+        return selector_matches(compounds, ancestors, false);
+    }
+    (1..=ancestors.len()).rev().any(|end| selector_matches(compounds, &ancestors[..end], false))
+}
+
+/**
+ * The style katex.css gives the last element of `path`, whose ancestors
+ * are the rest of `path`. Rules are applied in the order of katex.css, so
+ * later ones win.
+ */
+fn class_style(path: &[Vec<String>], img: bool) -> CssStyle {
+    let mut style = CssStyle::default();
+    for rule in rules() {
+        if rule.selectors.iter().any(|compounds| selector_matches(compounds, path, img)) {
+            for (name, value) in &rule.declarations {
+                style.set_css_property(name, value.clone());
+            }
+        }
+    }
+    style
+}
+
+/**
+ * `.katex svg`, whose properties `CssStyle` cannot all hold. A stroke width
+ * of 0 stands in for `.katex svg path { stroke: none }`; the `<line>`s of
+ * `\cancel` set their own.
+ */
+fn svg_style() -> &'static str {
+    static SVG_STYLE: OnceLock<String> = OnceLock::new();
+    SVG_STYLE.get_or_init(|| {
+        let rules = style_rules();
+        let declarations = rules.iter().find(|(selector, _)| selector == ".katex svg").map_or(&[][..], |(_, d)| d);
+        declarations
+            .iter()
+            .map(|(name, value)| match name.as_str() {
+                "stroke-width" => "stroke-width:0;".to_string(),
+                _ => format!("{}:{};", name, value),
+            })
+            .collect()
+    })
+}
+
+/**
+ * `style` with the properties of `defaults` it does not set itself, as a
+ * `style` attribute overrides the rules of a stylesheet. A shorthand of
+ * `style` also hides the longhands of `defaults` it covers, which would
+ * otherwise be written after it.
+ */
+fn merge(style: &CssStyle, defaults: &CssStyle) -> CssStyle {
+    let mut defaults = defaults.clone();
+    if style.border_width.is_some() {
+        defaults.border_top_width = None;
+        defaults.border_right_width = None;
+        defaults.border_bottom_width = None;
+        defaults.border_left_width = None;
+    }
+    if style.border_style.is_some() {
+        defaults.border_top_style = None;
+        defaults.border_right_style = None;
+        defaults.border_bottom_style = None;
+        defaults.border_left_style = None;
+    }
+    if style.margin.is_some() {
+        defaults.margin_top = None;
+        defaults.margin_right = None;
+        defaults.margin_bottom = None;
+        defaults.margin_left = None;
+    }
+    let css = defaults.to_css_str() + &style.to_css_str();
+    CssStyle::from_css_str(&css).unwrap_or_else(|_| style.clone())
+}
+
+struct InlineStyles {
+    /// The classes of the element being styled and of its ancestors.
+    path: Vec<Vec<String>>,
+    /// The `katexEqnNo` counter of katex.css, for the formula alone.
+    equation_number: usize,
+}
+
+impl InlineStyles {
+    fn node(&mut self, node: &mut dyn HtmlDomNode) {
+        if let Some(svg) = node.as_mut_any().downcast_mut::<SvgNode>() {
+            let style = svg_style().to_string() + svg.get_attribute("style").map_or("", String::as_str);
+            svg.set_attributes("style".to_string(), style);
+            return;
+        }
+
+        let classes = node.get_classes().clone();
+        self.path.push(classes.clone());
+        let defaults = class_style(&self.path, node.as_any().is::<Img>());
+        let style = merge(node.get_style(), &defaults);
+        match node.as_mut_any().downcast_mut::<Span>() {
+            // The error box and \htmlStyle put their style in an attribute,
+            // which would come after the one written for `CssStyle`.
+            Some(span) if span.get_attribute("style").is_some() => {
+                let attribute = style.to_css_str() + span.get_attribute("style").unwrap();
+                span.set_attribute("style".to_string(), attribute);
+                span.set_style(CssStyle::default());
+            }
+            _ => node.set_style(style),
+        }
+
+        if has(&classes, "eqn-num") {
+            // Written by `.eqn-num::before`, which has no inline equivalent.
+            self.equation_number += 1;
+            if let Some(children) = node.get_mut_children() {
+                let number = SymbolNode::new(format!("({})", self.equation_number));
+                children.insert(0, Box::new(number) as Box<dyn HtmlDomNode>);
+            }
+        }
+        // The MathML is hidden as a whole.
+        if !has(&classes, "katex-mathml") {
+            if let Some(children) = node.get_mut_children() {
+                for child in children.iter_mut() {
+                    self.node(child.as_mut());
+                }
+            }
+        }
+        self.path.pop();
+    }
+}
+
+/**
+ * Writes what katex.css says about the classes of `tree` into the style of
+ * each element, so that it renders where the stylesheet cannot be loaded,
+ * such as in email clients and EPUB readers. Only the `@font-face` rules of
+ * the KaTeX fonts are still needed.
+ */
+pub(crate) fn inline_styles(tree: &mut Span) {
+    let mut walker = InlineStyles {
+        path: vec![],
+        equation_number: 0,
+    };
+    walker.node(tree);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{class_style, longhands};
+    use crate::dom_tree::css_style::CssStyle;
+    use crate::stylesheet::katex_css;
+
+    /** The classes of a compound selector such as `.op-symbol.small-op` or `span`. */
+    fn classes(compound: &str) -> Vec<String> {
+        let compound = compound.split(":not(").next().unwrap();
+        compound.split('.').skip(1).map(str::to_string).collect()
+    }
+
+    #[test]
+    fn test_class_style_follows_katex_css() {
+        let css = katex_css("");
+        let mut missing = vec![];
+        for block in css.split("}\n").filter(|block| !block.trim().is_empty()) {
+            let (selectors, body) = block.split_once(" {\n").unwrap();
+            if selectors == "@font-face" {
+                continue;
+            }
+            let declarations: Vec<(&str, &str)> = body
+                .lines()
+                .map(|line| line.trim().trim_end_matches(';').split_once(": ").unwrap())
+                .collect();
+            for selector in selectors.split(", ") {
+                let compounds: Vec<&str> = selector.split(' ').filter(|compound| *compound != ">").collect();
+                let element = *compounds.last().unwrap();
+                // `.katex svg` is `svg_style`, and the rest have no inline equivalent.
+                if selector.contains("::") || ["*", "svg", "path", "body"].contains(&element) {
+                    continue;
+                }
+                let path: Vec<Vec<String>> = compounds.iter().map(|compound| classes(compound)).collect();
+                let actual = class_style(&path, element == "img").to_css_str();
+                let actual: Vec<&str> = actual.split(';').collect();
+                for (name, value) in declarations.iter().flat_map(|(name, value)| longhands(name, value)) {
+                    if !CssStyle::default().set_css_property(&name, value.clone()) {
+                        continue;
+                    }
+                    let declaration = format!("{}:{}", name, value);
+                    if !actual.contains(&declaration.as_str()) {
+                        missing.push(format!("{} {{ {} }}", selector, declaration));
+                    }
+                }
+            }
+        }
+        assert!(missing.is_empty(), "class_style lacks {:#?}", missing);
+
+        // `:not(...)` and the child combinator are honored.
+        let path = |classes: &[&[&str]]| -> Vec<Vec<String>> {
+            classes.iter().map(|list| list.iter().map(|class| class.to_string()).collect()).collect()
+        };
+        let style = class_style(&path(&[&["katex"], &["accent-body", "accent-full"]]), false);
+        assert_eq!(style.width, None);
+        let style = class_style(&path(&[&["katex"], &["katex-html"], &["base"], &["newline"]]), false);
+        assert_eq!(style.display, None);
+    }
+}
@@ -1,4 +1,5 @@
 use crate::dom_tree::css_style::CssStyle;
+use crate::dom_tree::math_wrapper::MathWrapper;
 use crate::dom_tree::span::Span;
 use crate::mathML_tree::math_node::MathNode;
 use crate::mathML_tree::public::{MathDomNode, MathNodeType};
@@ -6,7 +7,7 @@ use crate::mathML_tree::text_node::TextNode;
 use crate::parse_node::types::{AnyParseNode, ParseNodeToAny};
 use crate::types::{FontVariant, Mode};
 use crate::Options::Options;
-use crate::{get_character_metrics, get_symbol, parse_node, LIGATURES};
+use crate::{get_character_metrics, get_symbol, parse_node, HtmlDomNode, LIGATURES};
 use crate::define::functions::public::_MATHML_GROUP_BUILDERS;
 
 /**
@@ -105,6 +106,8 @@ pub fn get_variant(_group: &Box<dyn AnyParseNode>, options: &Options) -> Option<
             (group.mode, &group.text)
         } else if let Some(group) = _group.as_any().downcast_ref::<parse_node::types::mathord>() {
             (group.mode, &group.text)
+        } else if let Some(group) = _group.as_any().downcast_ref::<parse_node::types::textord>() {
+            (group.mode, &group.text)
         } else if let Some(group) = _group
             .as_any()
             .downcast_ref::<parse_node::types::op_token>()
@@ -144,7 +147,7 @@ pub fn get_variant(_group: &Box<dyn AnyParseNode>, options: &Options) -> Option<
         return None;
     }
 
-    let font_map = crate::build::common::FONT_MAP.lock().unwrap();
+    let font_map = &*crate::build::common::FONT_MAP;
     let font_info = font_map.get(&font.as_str()).unwrap();
     if let Some(sym) = get_symbol(mode, text) {
         if let Some(s) = sym.replace {
@@ -170,61 +173,84 @@ pub fn build_expression(
     expression: Vec<Box<dyn AnyParseNode>>,
     options: Options,
     isOrdgroup: bool,
-) -> Vec<MathNode> {
-    return vec![];
-    // if (expression.len() == 1) {
-    //     let group = buildGroup(expression[0], options);
-    //     if (isOrdgroup && group instanceof MathNode && group.type == "mo") {
-    //         // When TeX writers want to suppress spacing on an operator,
-    //         // they often put the operator by itself inside braces.
-    //         group.set_attribute("lspace", "0em");
-    //         group.set_attribute("rspace", "0em");
-    //     }
-    //     return [group];
-    // }
+) -> Vec<Box<dyn MathDomNode>> {
+    if expression.len() == 1 {
+        let mut group = build_group(Some(expression[0].clone()), options);
+        if let Some(g) = group.as_mut_any().downcast_mut::<MathNode>() {
+            if isOrdgroup && g.get_node_type().as_str() == "mo" {
+                // When TeX writers want to suppress spacing on an operator,
+                // they often put the operator by itself inside braces.
+                g.set_attribute("lspace".to_string(), "0em".to_string());
+                g.set_attribute("rspace".to_string(), "0em".to_string());
+            }
+        }
+        return vec![group];
+    }
 
-    // let groups = [];
-    // let lastGroup;
-    // for (let i = 0; i < expression.length; i++) {
-    //     let group = buildGroup(expression[i], options);
-    //     if (group instanceof MathNode && lastGroup instanceof MathNode) {
-    //         // Concatenate adjacent <mtext>s
-    //         if (group.type == 'mtext' && lastGroup.type == 'mtext'
-    //             && group.getAttribute('mathvariant') ===
-    //                lastGroup.getAttribute('mathvariant')) {
-    //             lastGroup.children.push(...group.children);
-    //             continue;
-    //         // Concatenate adjacent <mn>s
-    //         } else if (group.type == 'mn' && lastGroup.type == 'mn') {
-    //             lastGroup.children.push(...group.children);
-    //             continue;
-    //         // Concatenate <mn>...</mn> followed by <mi>.</mi>
-    //         } else if (group.type == 'mi' && group.children.length == 1 &&
-    //                    lastGroup.type == 'mn') {
-    //             let child = group.children[0];
-    //             if (child instanceof TextNode && child.text == '.') {
-    //                 lastGroup.children.push(...group.children);
-    //                 continue;
-    //             }
-    //         } else if (lastGroup.type == 'mi' && lastGroup.children.length == 1) {
-    //             let lastChild = lastGroup.children[0];
-    //             if (lastChild instanceof TextNode && lastChild.text == '\u0338' &&
-    //                 (group.type == 'mo' || group.type == 'mi' ||
-    //                     group.type == 'mn')) {
-    //                 let child = group.children[0];
-    //                 if (child instanceof TextNode && child.text.length > 0) {
-    //                     // Overlay with combining character long solidus
-    //                     child.text = child.text.slice(0, 1) + "\u0338" +
-    //                         child.text.slice(1);
-    //                     groups.pop();
-    //                 }
-    //             }
-    //         }
-    //     }
-    //     groups.push(group);
-    //     lastGroup = group;
-    // }
-    // return groups;
+    let mut groups: Vec<Box<dyn MathDomNode>> = vec![];
+    for expr in expression.into_iter() {
+        let mut group = build_group(Some(expr), options.clone());
+        let mut merged = false;
+        let mut pop_last = false;
+        if let (Some(g), Some(last_group)) = (
+            group.as_mut_any().downcast_mut::<MathNode>(),
+            groups
+                .last_mut()
+                .and_then(|l| l.as_mut_any().downcast_mut::<MathNode>()),
+        ) {
+            let g_type = g.get_node_type().as_str();
+            let last_type = last_group.get_node_type().as_str();
+            if g_type == "mtext"
+                && last_type == "mtext"
+                && g.get_attribute(&"mathvariant".to_string())
+                    == last_group.get_attribute(&"mathvariant".to_string())
+            {
+                // Concatenate adjacent <mtext>s
+                last_group.get_mut_children().extend(g.get_children().clone());
+                merged = true;
+            } else if g_type == "mn" && last_type == "mn" {
+                // Concatenate adjacent <mn>s
+                last_group.get_mut_children().extend(g.get_children().clone());
+                merged = true;
+            } else if g_type == "mi" && g.get_children().len() == 1 && last_type == "mn" {
+                // Concatenate <mn>...</mn> followed by <mi>.</mi>
+                if let Some(child) = g.get_children()[0].as_any().downcast_ref::<TextNode>() {
+                    if child.get_text() == "." {
+                        last_group.get_mut_children().extend(g.get_children().clone());
+                        merged = true;
+                    }
+                }
+            } else if last_type == "mi" && last_group.get_children().len() == 1 {
+                let is_not = last_group.get_children()[0]
+                    .as_any()
+                    .downcast_ref::<TextNode>()
+                    .map_or(false, |c| c.get_text() == "\u{0338}");
+                if is_not && (g_type == "mo" || g_type == "mi" || g_type == "mn") {
+                    if let Some(child) = g
+                        .get_mut_children()
+                        .first_mut()
+                        .and_then(|c| c.as_mut_any().downcast_mut::<TextNode>())
+                    {
+                        if child.get_text().len() > 0 {
+                            // Overlay with combining character long solidus
+                            let mut chars = child.get_text().chars();
+                            let first = chars.next().unwrap();
+                            let text = format!("{}\u{0338}{}", first, chars.as_str());
+                            child.set_text(text);
+                            pop_last = true;
+                        }
+                    }
+                }
+            }
+        }
+        if pop_last {
+            groups.pop();
+        }
+        if !merged {
+            groups.push(group);
+        }
+    }
+    return groups;
 }
 
 /**
@@ -236,11 +262,7 @@ pub fn build_expression_row(
     options: Options,
     is_ordgroup: bool,
 ) ->Box< dyn MathDomNode> {
-    return make_row(build_expression(expression, options, is_ordgroup)
-        .into_iter()
-        .map(|x|{Box::new(x) as Box<dyn MathDomNode>})
-        .collect()
-    );
+    return make_row(build_expression(expression, options, is_ordgroup));
 }
 
 /**
@@ -251,9 +273,12 @@ pub fn build_group(
     _group: Option<Box<dyn AnyParseNode>>,
     options: Options,
 )->Box<dyn MathDomNode> {
+    if options.past_deadline() {
+        return Box::new(MathNode::new(MathNodeType::Mrow, vec![], vec![])) as Box<dyn MathDomNode>;
+    }
     if let Some(group) = _group{
         let t = group.get_type();
-        let _builders = _MATHML_GROUP_BUILDERS.read().unwrap();
+        let _builders = &*_MATHML_GROUP_BUILDERS;
         if let Some(f) = _builders.get(t) {
             return f(group, options.clone());
         } else {
@@ -287,19 +312,16 @@ pub fn build_math_ml(
     // MathML takes care of the other spacing issues.
     // Wrap up the expression in an mrow so it is presented in the semantics
     // tag correctly, unless it's a single <mrow> or <mtable>.
-    let wrapper;
-    if expression.len() == 1 && ["mrow", "mtable"].contains(&expression[0].get_node_type().as_str())
+    let wrapper: Box<dyn MathDomNode>;
+    if expression.len() == 1
+        && expression[0]
+            .as_any()
+            .downcast_ref::<MathNode>()
+            .map_or(false, |e| ["mrow", "mtable"].contains(&e.get_node_type().as_str()))
     {
         wrapper = expression[0].clone();
     } else {
-        wrapper = MathNode::new(
-            MathNodeType::Mrow,
-            expression
-                .iter()
-                .map(|x| Box::new(x.clone()) as Box<dyn MathDomNode>)
-                .collect(),
-            vec![],
-        );
+        wrapper = Box::new(MathNode::new(MathNodeType::Mrow, expression, vec![]));
     }
 
     // Build a TeX annotation of the source
@@ -313,7 +335,7 @@ pub fn build_math_ml(
 
     let semantics = MathNode::new(
         MathNodeType::Semantics,
-        vec![Box::new(wrapper), Box::new(annotation)],
+        vec![wrapper, Box::new(annotation)],
         vec![],
     );
 
@@ -337,7 +359,7 @@ pub fn build_math_ml(
     };
     return Span::new(
         vec![wrapper_class.to_string()],
-        vec![/*Box::new(math) as Box<dyn HtmlDomNode>*/],
+        vec![Box::new(MathWrapper::new(Box::new(math))) as Box<dyn HtmlDomNode>],
         None,
         CssStyle::default(),
     );
@@ -1,10 +1,12 @@
 pub(crate) mod HTML;
 pub(crate) mod common;
+mod inline_styles;
 pub(crate) mod mathML;
 
 
 use crate::{
     dom_tree::{css_style::CssStyle, span::Span},
+    parse_error::ParseError,
     parse_node::types::AnyParseNode,
     settings::Settings,
     tree::HtmlDomNode,
@@ -29,24 +31,49 @@ pub fn display_wrap(node: Span, settings: Settings) -> Span {
     }
     return node;
 }
+
+/** Applies `inlineStyles` to a finished tree. */
+fn finish_tree(node: Span, settings: &Settings) -> Span {
+    let mut node = display_wrap(node, settings.clone());
+    if settings.get_inline_styles() && settings.get_output() != "svg" {
+        inline_styles::inline_styles(&mut node);
+    }
+    node
+}
+
+/**
+ * The error for a build that ran past its `timeBudget`, in which case the
+ * builders have left out what they had not built yet.
+ */
+fn check_deadline(node: Span, options: &Options) -> Result<Span, ParseError> {
+    if options.past_deadline() {
+        return Err(ParseError::new(
+            "Time budget exceeded: need to increase timeBudget setting".to_string(),
+            None,
+        ));
+    }
+    Ok(node)
+}
+
 pub fn build_tree(
     tree: Vec<Box<dyn AnyParseNode>>,
     expression: String,
     settings: Settings,
-) -> Span {
+) -> Result<Span, ParseError> {
     let options = Options::from_settings(&settings);
     //println!("build::build_tree options = {:#?}", options);
     let katex_node;
     if settings.get_output() == "mathml" {
-        return mathML::build_math_ml(
+        let math_node = mathML::build_math_ml(
             tree,
             expression,
-            options,
+            options.clone(),
             settings.get_display_mode(),
             true,
         );
-    } else if settings.get_output() == "html" {
-        let html_node = HTML::build_html(tree, options);
+        return check_deadline(math_node, &options);
+    } else if settings.get_output() == "html" || settings.get_output() == "svg" {
+        let html_node = HTML::build_html(tree, options.clone());
         katex_node = common::make_span(
             vec!["katex".to_string()],
             vec![Box::new(html_node) as Box<dyn HtmlDomNode>],
@@ -61,7 +88,7 @@ pub fn build_tree(
             settings.get_display_mode(),
             false,
         );
-        let html_node = HTML::build_html(tree, options);
+        let html_node = HTML::build_html(tree, options.clone());
         katex_node = common::make_span(
             vec!["katex".to_string()],
             vec![
@@ -73,21 +100,21 @@ pub fn build_tree(
         );
     }
 
-    return display_wrap(katex_node, settings);
+    check_deadline(finish_tree(katex_node, &settings), &options)
 }
 
 pub fn build_html_tree(
     tree: Vec<Box<dyn AnyParseNode>>,
     expression: String,
     settings: Settings,
-) -> Span {
+) -> Result<Span, ParseError> {
     let options = Options::from_settings(&settings);
-    let html_node = HTML::build_html(tree, options);
+    let html_node = HTML::build_html(tree, options.clone());
     let katex_node =  common::make_span(
         vec!["katex".to_string()],
         vec![Box::new(html_node) as Box<dyn HtmlDomNode>],
         None,
         CssStyle::new(),
     );
-    return display_wrap(katex_node, settings);
+    check_deadline(finish_tree(katex_node, &settings), &options)
 }
@@ -28,14 +28,15 @@ use std::{any::Any, sync::Arc};
 
 #[derive(struct_format::parse_node_type, Clone, Debug)]
 pub struct Align {
-    align: String,
-    pregap: Option<String>,
-    postgap: Option<String>,
+    pub(crate) align: String,
+    pub(crate) pregap: Option<String>,
+    pub(crate) postgap: Option<String>,
 }
 
 #[derive(struct_format::parse_node_type, Clone, Debug)]
+#[json(check = "check_separator")]
 pub struct Separator {
-    separator: String,
+    pub(crate) separator: String,
 }
 
 fn symbol_node_loc(node: &Box<dyn AnyParseNode>) -> Option<crate::sourceLocation::SourceLocation> {
@@ -236,12 +237,12 @@ fn parse_cd(parser: &mut crate::Parser::Parser) -> parse_node::types::array {
 
             row.push(Box::new(cell) as Box<dyn AnyParseNode>);
             j += 1;
-            if j >= row_nodes.len() || !check_symbol_node_type(&row_nodes[j]) {
+            if j >= row_nodes.len() || !check_symbol_node_type(row_nodes[j].as_ref()) {
                 parser.report_parse_error("Expected one of \"<>AV=|.\" after @".to_string(), None);
                 return empty_cd_array(parser.mode);
             }
 
-            let arrow_char = check_symbol_node_type_text(&row_nodes[j]);
+            let arrow_char = check_symbol_node_type_text(row_nodes[j].as_ref());
             let mut labels = [
                 parse_node::types::ordgroup {
                     mode: parser.mode,
@@ -390,16 +391,12 @@ fn validate_ams_environment_context(context: &mut FunctionContext2) {
 // // * true: Automatic equation numbering, overridable by \tag
 // // * false: Tags allowed on each row, but no automatic numbering
 // // This function *doesn't* work with the "split" environment name.
-fn get_auto_tag(name: &str) -> bool {
+fn get_auto_tag(name: &str) -> Option<bool> {
     if name.contains("ed") {
-        return false;
+        return None;
     }
 
-    if name.contains("*") {
-        return false;
-    }
-
-    return true;
+    Some(!name.contains("*"))
 }
 
 struct ParseArrayArgs {
@@ -408,7 +405,7 @@ struct ParseArrayArgs {
     cols: Vec<AlignSpec>,
     array_stretch: Option<f64>,
     col_separation_type: Option<parse_node::types::ColSeparationType>,
-    auto_tag: bool,
+    auto_tag: Option<bool>,
     single_row: bool,
     empty_single_row: bool,
     max_num_cols: Option<usize>,
@@ -474,13 +471,13 @@ fn parse_array(
     let mut row_gaps = vec![];
     let mut h_lines_before_row = vec![];
 
-    let mut tags = if auto_tag { Some(vec![]) } else { None };
+    let mut tags = auto_tag.map(|_| vec![]);
 
     // amsmath uses \global\@eqnswtrue and \global\@eqnswfalse to represent
     // whether this row should have an equation number.  Simulate this with
     // a \@eqnsw macro set to 1 or 0.
-    let mut begin_row = |auto_tag: bool, parser: &mut Parser| {
-        if auto_tag {
+    let mut begin_row = |auto_tag: Option<bool>, parser: &mut Parser| {
+        if auto_tag == Some(true) {
             parser.gullet.macros.set(
                 &"\\@eqnsw".to_string(),
                 Some(MacroDefinition::Str("1".to_string())),
@@ -488,26 +485,16 @@ fn parse_array(
             );
         }
     };
-    let mut end_row = |auto_tag: bool, parser: &mut Parser, tags: &mut Option<Vec<ArrayTag>>| {
+    let mut end_row = |auto_tag: Option<bool>, parser: &mut Parser, tags: &mut Option<Vec<ArrayTag>>| {
         if let Some(t) = tags {
-            if parser.gullet.macros.get(&"\\df@tag".to_string()).is_some() {
-                t.push(crate::parse_node::types::ArrayTag::B(
-                    parser.subparse(vec![Token::new("\\df@tag".to_string(), None)]),
-                ));
-                parser
-                    .gullet
-                    .macros
-                    .set(&"\\df@tag".to_string(), None, true);
-            } else if auto_tag {
-                let tmp = parser.gullet.macros.get(&"\\@eqnsw".to_string()).unwrap();
-                let ttt = if let MacroDefinition::Str(s) = tmp {
-                    s == "1"
-                } else {
-                    false
-                };
-                t.push(crate::parse_node::types::ArrayTag::A(ttt));
-            } else {
-                t.push(ArrayTag::A(false));
+            let numbered = auto_tag == Some(true)
+                && matches!(
+                    parser.gullet.macros.get(&"\\@eqnsw".to_string()),
+                    Some(MacroDefinition::Str(s)) if s == "1"
+                );
+            match parser.take_equation_tag(numbered) {
+                Some(tag) => t.push(ArrayTag::B(tag)),
+                None => t.push(ArrayTag::A(numbered)),
             }
         }
     };
@@ -551,7 +538,7 @@ fn parse_array(
                         panic!("Too many tab characters: &{:#?}", parser.next_token);
                     } else {
                         // {array} environment
-                        parser.settings.report_nonstrict(
+                        parser.report_nonstrict(
                             "textEnv",
                             "Too few columns specified in the {array} column argument.",
                             None,
@@ -683,7 +670,7 @@ fn array_html_builder(mut _group: Box<dyn AnyParseNode>, options: Options) -> Bo
         // But that needs adjustment because LaTeX applies \scriptstyle to the
         // entire array, including the colspace, but this function applies
         // \scriptstyle only inside each element.
-        let _script = crate::Style::SCRIPT.read().unwrap();
+        let _script = &*crate::Style::SCRIPT;
         let local_multiplier = options.having_style(&_script).sizeMultiplier;
         arraycolsep = 0.2778 * (local_multiplier / options.sizeMultiplier);
     }
@@ -1053,156 +1040,186 @@ fn array_html_builder(mut _group: Box<dyn AnyParseNode>, options: Options) -> Bo
     }
 }
 
-//
-// let alignMap = {
-// c: "center ".to_string(),
-// l: "left ".to_string(),
-// r: "right ".to_string(),
-// };
-//
+fn align_map(align: &str) -> &'static str {
+    match align {
+        "c" => "center ",
+        "l" => "left ",
+        "r" => "right ",
+        _ => "",
+    }
+}
+
 fn array_mathml_builder(_group: Box<dyn AnyParseNode>, options: Options) -> Box<dyn MathDomNode> {
     let group = _group
         .as_any()
         .downcast_ref::<parse_node::types::array>()
         .unwrap();
-    panic!("undefined")
-    // let tbl = [];
-    // let glue = MathNode::new(MathNodeType::Mtd, vec![], vec!["mtr-glue".to_string()]);
-    // let tag = MathNode::new(MathNodeType::Mtd, vec![], vec!["mml-eqn-num".to_string()]);
-    //     for rw in group.body{
-    //         let row = [];
-    //         for rw_item in rw{
-    //             row.push(MathNode::new(MathNodeType::Mtd, vec![mathML::build_group(Some(rw_item), options.clone())], vec![]));
-    //
-    //         }
-    //         if (group.tags & & group.tags[i]) {
-    //             row.unshift(glue);
-    //             row.push(glue);
-    //             if (group.leqno) {
-    //                 row.unshift(tag);
-    //             } else {
-    //                 row.push(tag);
-    //             }
-    //         }
-    //         tbl.push(MathNode::new("mtr".to_string(), row));
-    //     }
-    // let table = MathNode::new("mtable".to_string(), tbl);
-    //
-    // // Set column alignment, row spacing, column spacing, and
-    // // array lines by setting attributes on the table element.
-    //
-    // // Set the row spacing. In MathML, we specify a gap distance.
-    // // We do not use rowGap[] because MathML automatically increases
-    // // cell height with the height/depth of the element content.
-    //
-    // // LaTeX \arraystretch multiplies the row baseline-to-baseline distance.
-    // // We simulate this by adding (arraystretch - 1)em to the gap. This
-    // // does a reasonable job of adjusting arrays containing 1 em tall content.
-    //
-    // // The 0.16 and 0.09 values are found emprically. They produce an array
-    // // similar to LaTeX and in which content does not interfere with \hines.
-    // let gap = (group.arraystretch == 0.5)
-    // ? 0.1  // {smallmatrix}, {subarray}
-    // : 0.16 + group.arraystretch - 1 + (group.add_jot ? 0.09: 0);
-    // table.set_attribute("rowspacing".to_string(), crate::units::make_em(gap));
-    //
-    // // MathML table lines go only between cells.
-    // // To place a line on an edge we'll use <menclose>, if necessary.
-    // let menclose = "";
-    // let align = "";
-    //
-    // if (group.cols & & group.cols.length > 0) {
-    // // Find column alignment, column spacing, and  vertical lines.
-    // let cols = group.cols;
-    // let columnLines = "";
-    // let prevTypeWasAlign = false;
-    // let iStart = 0;
-    // let iEnd = cols.length;
-    //
-    // if (cols[0].type == "separator".to_string()) {
-    // menclose += "top ";
-    // iStart = 1;
-    // }
-    // if (cols[cols.length - 1].type == "separator".to_string()) {
-    // menclose += "bottom ";
-    // iEnd -= 1;
-    // }
-    //
-    // for ( let i = iStart; i < iEnd; i + + ) {
-    // if (cols[i].type == "align".to_string()) {
-    // align += alignMap[cols[i].align];
-    //
-    // if (prevTypeWasAlign) {
-    // columnLines += "none ";
-    // }
-    // prevTypeWasAlign = true;
-    // } else if (cols[i].type == "separator".to_string()) {
-    // // MathML accepts only single lines between cells.
-    // // So we read only the first of consecutive separators.
-    // if (prevTypeWasAlign) {
-    // columnLines += cols[i].separator == "|"
-    // ? "solid "
-    // : "dashed ";
-    // prevTypeWasAlign = false;
-    // }
-    // }
-    // }
-    //
-    // table.set_attribute("columnalign".to_string(), align.trim());
-    //
-    // if ( /[sd] /.test(columnLines)) {
-    // table.set_attribute("columnlines".to_string(), columnLines.trim());
-    // }
-    // }
-    //
-    // // Set column spacing.
-    // if (group.col_separation_type == "align".to_string()) {
-    // let cols = group.cols | | [];
-    // let spacing = "";
-    // for ( let i = 1; i < cols.length; i+ + ) {
-    // spacing += i % 2 ? "0em ": "1em ";
-    // }
-    // table.set_attribute("columnspacing".to_string(), spacing.trim());
-    // } else if (group.col_separation_type == "alignat" | |
-    // group.col_separation_type == "gather".to_string()) {
-    // table.set_attribute("columnspacing".to_string(), "0em".to_string());
-    // } else if (group.col_separation_type == "small".to_string()) {
-    // table.set_attribute("columnspacing".to_string(), "0.2778em".to_string());
-    // } else if (group.col_separation_type == "CD".to_string()) {
-    // table.set_attribute("columnspacing".to_string(), "0.5em".to_string());
-    // } else {
-    // table.set_attribute("columnspacing".to_string(), "1em".to_string());
-    // }
-    //
-    // // Address \hline and \hdashline
-    // let rowLines = "";
-    // let hlines = group.hLinesBeforeRow;
-    //
-    // menclose += hlines[0].length > 0 ? "left ": "";
-    // menclose += hlines[hlines.length - 1].length > 0 ? "right ": "";
-    //
-    // for ( let i = 1; i < hlines.length - 1; i + + ) {
-    // rowLines += (hlines[i].length == 0)
-    // ? "none "
-    // // MathML accepts only a single line between rows. Read one element.
-    // : hlines[i][0] ? "dashed ": "solid ";
-    // }
-    // if ( / [sd] /.test(rowLines)) {
-    // table.set_attribute("rowlines".to_string(), rowLines.trim());
-    // }
-    //
-    // if (menclose != "".to_string()) {
-    // table = MathNode::new("menclose".to_string(), [table]);
-    // table.set_attribute("notation".to_string(), menclose.trim());
-    // }
-    //
-    // if (group.arraystretch & & group.arraystretch < 1) {
-    // // A small array. Wrap in scriptstyle so row gap is not too large.
-    // table = MathNode::new("mstyle".to_string(), [table]);
-    // table.set_attribute("scriptlevel".to_string(), "1".to_string());
-    // }
-    //
-    // return table;
+    let mut tbl: Vec<Box<dyn MathDomNode>> = vec![];
+    let glue = MathNode::new(MathNodeType::Mtd, vec![], vec!["mtr-glue".to_string()]);
+    let tag = MathNode::new(MathNodeType::Mtd, vec![], vec!["mml-eqn-num".to_string()]);
+    for (i, rw) in group.body.iter().enumerate() {
+        let mut row: Vec<Box<dyn MathDomNode>> = vec![];
+        for rw_item in rw.iter() {
+            row.push(Box::new(MathNode::new(
+                MathNodeType::Mtd,
+                vec![mathML::build_group(Some(rw_item.clone()), options.clone())],
+                vec![],
+            )));
+        }
+        let has_tag = match group.tags.as_ref().and_then(|tags| tags.get(i)) {
+            Some(ArrayTag::A(a)) => *a,
+            Some(ArrayTag::B(_)) => true,
+            None => false,
+        };
+        if has_tag {
+            row.insert(0, Box::new(glue.clone()));
+            row.push(Box::new(glue.clone()));
+            if group.leqno {
+                row.insert(0, Box::new(tag.clone()));
+            } else {
+                row.push(Box::new(tag.clone()));
+            }
+        }
+        tbl.push(Box::new(MathNode::new(MathNodeType::Mtr, row, vec![])));
+    }
+    let mut table = MathNode::new(MathNodeType::Mtable, tbl, vec![]);
+
+    // Set column alignment, row spacing, column spacing, and
+    // array lines by setting attributes on the table element.
+
+    // Set the row spacing. In MathML, we specify a gap distance.
+    // We do not use rowGap[] because MathML automatically increases
+    // cell height with the height/depth of the element content.
+
+    // LaTeX \arraystretch multiplies the row baseline-to-baseline distance.
+    // We simulate this by adding (arraystretch - 1)em to the gap. This
+    // does a reasonable job of adjusting arrays containing 1 em tall content.
+
+    // The 0.16 and 0.09 values are found emprically. They produce an array
+    // similar to LaTeX and in which content does not interfere with \hines.
+    let gap = if group.array_stretch == 0.5 {
+        0.1 // {smallmatrix}, {subarray}
+    } else {
+        0.16 + group.array_stretch - 1.0 + if group.add_jot { 0.09 } else { 0.0 }
+    };
+    table.set_attribute("rowspacing".to_string(), crate::units::make_em(gap));
+
+    // MathML table lines go only between cells.
+    // To place a line on an edge we'll use <menclose>, if necessary.
+    let mut menclose = String::new();
+    let mut align = String::new();
+
+    if group.cols.len() > 0 {
+        // Find column alignment, column spacing, and  vertical lines.
+        let cols = &group.cols;
+        let mut column_lines = String::new();
+        let mut prev_type_was_align = false;
+        let mut i_start = 0;
+        let mut i_end = cols.len();
+
+        if let AlignSpec::Separator(_) = cols[0] {
+            menclose.push_str("top ");
+            i_start = 1;
+        }
+        if let AlignSpec::Separator(_) = cols[cols.len() - 1] {
+            menclose.push_str("bottom ");
+            i_end -= 1;
+        }
+
+        for i in i_start..i_end {
+            match &cols[i] {
+                AlignSpec::Align(a) => {
+                    align.push_str(align_map(&a.align));
+
+                    if prev_type_was_align {
+                        column_lines.push_str("none ");
+                    }
+                    prev_type_was_align = true;
+                }
+                AlignSpec::Separator(sep) => {
+                    // MathML accepts only single lines between cells.
+                    // So we read only the first of consecutive separators.
+                    if prev_type_was_align {
+                        column_lines.push_str(if sep.separator == "|" {
+                            "solid "
+                        } else {
+                            "dashed "
+                        });
+                        prev_type_was_align = false;
+                    }
+                }
+            }
+        }
+
+        table.set_attribute("columnalign".to_string(), align.trim().to_string());
+
+        if column_lines.contains('s') || column_lines.contains('d') {
+            table.set_attribute("columnlines".to_string(), column_lines.trim().to_string());
+        }
+    }
+
+    // Set column spacing.
+    match group.col_separation_type {
+        Some(ColSeparationType::Align) => {
+            let mut spacing = String::new();
+            for i in 1..group.cols.len() {
+                spacing.push_str(if i % 2 == 1 { "0em " } else { "1em " });
+            }
+            table.set_attribute("columnspacing".to_string(), spacing.trim().to_string());
+        }
+        Some(ColSeparationType::AlignAt) | Some(ColSeparationType::Gather) => {
+            table.set_attribute("columnspacing".to_string(), "0em".to_string());
+        }
+        Some(ColSeparationType::Small) => {
+            table.set_attribute("columnspacing".to_string(), "0.2778em".to_string());
+        }
+        Some(ColSeparationType::CD) => {
+            table.set_attribute("columnspacing".to_string(), "0.5em".to_string());
+        }
+        None => {
+            table.set_attribute("columnspacing".to_string(), "1em".to_string());
+        }
+    }
+
+    // Address \hline and \hdashline
+    let mut row_lines = String::new();
+    let hlines = &group.h_lines_before_row;
+
+    if hlines.len() > 0 {
+        if hlines[0].len() > 0 {
+            menclose.push_str("left ");
+        }
+        if hlines[hlines.len() - 1].len() > 0 {
+            menclose.push_str("right ");
+        }
+    }
+
+    for i in 1..hlines.len().saturating_sub(1) {
+        row_lines.push_str(if hlines[i].len() == 0 {
+            "none "
+        } else if hlines[i][0] {
+            // MathML accepts only a single line between rows. Read one element.
+            "dashed "
+        } else {
+            "solid "
+        });
+    }
+    if row_lines.contains('s') || row_lines.contains('d') {
+        table.set_attribute("rowlines".to_string(), row_lines.trim().to_string());
+    }
+
+    if menclose != "" {
+        table = MathNode::new(MathNodeType::Menclose, vec![Box::new(table)], vec![]);
+        table.set_attribute("notation".to_string(), menclose.trim().to_string());
+    }
+
+    if group.array_stretch < 1.0 {
+        // A small array. Wrap in scriptstyle so row gap is not too large.
+        table = MathNode::new(MathNodeType::Mstyle, vec![Box::new(table)], vec![]);
+        table.set_attribute("scriptlevel".to_string(), "1".to_string());
+    }
+
+    return Box::new(table) as Box<dyn MathDomNode>;
 }
 
 pub fn aligned_handler(
@@ -1229,7 +1246,7 @@ pub fn aligned_handler(
         array_stretch: None,
         col_separation_type: Some(separation_type),
         auto_tag: if is_split {
-            false
+            None
         } else {
             get_auto_tag(&context.func_name)
         },
@@ -1446,7 +1463,7 @@ pub fn array_handler_fn(
     // - The argument is wrapped in {} or [], in which case Parser's
     //   parseGroup() returns an "ordgroup" wrapping some symbol node.
     // - The argument is a bare symbol node.
-    let sym_node = check_symbol_node_type(&args[0]);
+    let sym_node = check_symbol_node_type(args[0].as_ref());
     let colalign = if sym_node {
         vec![args[0].clone()]
     } else {
@@ -1460,7 +1477,7 @@ pub fn array_handler_fn(
     };
     let mut cols = Vec::with_capacity(colalign.len());
     for nde in colalign {
-        let ca = crate::parse_node::check_symbol_node_type_text(&nde);
+        let ca = crate::parse_node::check_symbol_node_type_text(nde.as_ref());
         if "lcr".contains(&ca) {
             cols.push(AlignSpec::Align(Align {
                 align: ca,
@@ -1495,7 +1512,7 @@ pub fn array_handler_fn(
         cols,
         array_stretch: None,
         col_separation_type: None,
-        auto_tag: false,
+        auto_tag: None,
         single_row: false,
         empty_single_row: false,
         leqno: false,
@@ -1749,7 +1766,7 @@ pub fn matrix_handler_fn(
         max_num_cols: None,
         array_stretch: None,
         col_separation_type: None,
-        auto_tag: false,
+        auto_tag: None,
         single_row: false,
         empty_single_row: false,
         leqno: false,
@@ -1805,7 +1822,7 @@ pub fn cases_handler_fn(
         ],
         array_stretch: Some(1.2),
         col_separation_type: None,
-        auto_tag: false,
+        auto_tag: None,
         single_row: false,
         empty_single_row: false,
         max_num_cols: None,
@@ -1845,7 +1862,7 @@ pub fn smallmatrix_handler_fn(
         cols: vec![],
         array_stretch: Some(0.5),
         col_separation_type: None,
-        auto_tag: false,
+        auto_tag: None,
         single_row: false,
         empty_single_row: false,
         max_num_cols: None,
@@ -1862,7 +1879,7 @@ pub fn subarray_handler_fn(
     _opt_args: Vec<Option<Box<dyn AnyParseNode>>>,
 ) -> Box<dyn AnyParseNode> {
     let mut context = ctx.borrow_mut();
-    let colalign = if check_symbol_node_type(&args[0]) {
+    let colalign = if check_symbol_node_type(args[0].as_ref()) {
         vec![args[0].clone()]
     } else {
         args[0]
@@ -1876,7 +1893,7 @@ pub fn subarray_handler_fn(
 
     let mut cols = Vec::with_capacity(colalign.len());
     for nde in colalign {
-        let ca = crate::parse_node::check_symbol_node_type_text(&nde);
+        let ca = crate::parse_node::check_symbol_node_type_text(nde.as_ref());
         if "lc".contains(&ca) {
             cols.push(AlignSpec::Align(Align {
                 align: ca,
@@ -1916,7 +1933,7 @@ pub fn subarray_handler_fn(
         cols,
         array_stretch: Some(0.5),
         col_separation_type: None,
-        auto_tag: false,
+        auto_tag: None,
         single_row: false,
         empty_single_row: false,
         max_num_cols: None,
@@ -30,8 +30,7 @@ lazy_static! {
             j.clone()
         ]
     });
-    pub static ref _environments: std::sync::RwLock<HashMap<String, FunctionSpec>> =
-        std::sync::RwLock::new({
+    pub static ref _environments: HashMap<String, FunctionSpec> = {
             let mut res = HashMap::new();
             for data in ENVS.lock().unwrap().iter() {
                 for name in data.names.iter() {
@@ -39,5 +38,5 @@ lazy_static! {
                 }
             }
             res
-        });
+        };
 }
@@ -3,7 +3,9 @@ use crate::define::functions::public::{ord_argument, FunctionContext, FunctionDe
 use crate::dom_tree::css_style::CssStyle;
 use crate::dom_tree::span::Span;
 use crate::dom_tree::symbol_node::SymbolNode;
-use crate::mathML_tree::public::MathDomNode;
+use crate::build::mathML;
+use crate::mathML_tree::math_node::MathNode;
+use crate::mathML_tree::public::{MathDomNode, MathNodeType};
 use crate::types::{ArgType, Mode};
 use crate::utils::is_character_box;
 use crate::Options::Options;
@@ -121,7 +123,7 @@ pub fn html_builder(_group: Box<dyn AnyParseNode>, options: Options) -> Box<dyn
                 options.clone(),
             )) as Box<dyn HtmlDomNode>;
             width = {
-                let svg_data = crate::build::common::SVG_DATA.lock().unwrap();
+                let svg_data = &*crate::build::common::SVG_DATA;
                 svg_data.get("vec").unwrap().1
             }
         } else {
@@ -272,19 +274,32 @@ pub fn html_builder(_group: Box<dyn AnyParseNode>, options: Options) -> Box<dyn
 }
 
 fn mathml_builder(_group: Box<dyn AnyParseNode>, options: Options) -> Box<dyn MathDomNode> {
-    panic!("todo mathml builder");
-    // let accentNode =
-    //     group.isStretchy ?
-    //         stretchy.mathMLnode(group.label) :
-    //         new mathMLTree.MathNode("mo", [mml.makeText(group.label, group.mode)]);
-    //
-    // let node = new mathMLTree.MathNode(
-    //     "mover",
-    //     [mml.buildGroup(group.base, options), accentNode]);
-    //
-    // node.setAttribute("accent", "true");
-    //
-    // return node;
+    let group = _group
+        .as_any()
+        .downcast_ref::<parse_node::types::accent>()
+        .unwrap();
+    let accent_node = if group.isStretchy {
+        stretchy::math_ml_node(&group.label)
+    } else {
+        MathNode::new(
+            MathNodeType::Mo,
+            vec![Box::new(mathML::make_text(group.label.clone(), group.mode, None))],
+            vec![],
+        )
+    };
+
+    let mut node = MathNode::new(
+        MathNodeType::Mover,
+        vec![
+            mathML::build_group(group.base.clone(), options),
+            Box::new(accent_node) as Box<dyn MathDomNode>,
+        ],
+        vec![],
+    );
+
+    node.set_attribute("accent".to_string(), "true".to_string());
+
+    return Box::new(node) as Box<dyn MathDomNode>;
 }
 
 lazy_static! {
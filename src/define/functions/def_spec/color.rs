@@ -44,10 +44,7 @@ fn mathml_builder(_group: Box<dyn AnyParseNode>, options: Options) -> Box<dyn Ma
         false,
     );
 
-    let mut node = MathNode::new(MathNodeType::Mstyle, inner
-        .into_iter()
-        .map(|x| Box::new(x) as Box<dyn MathDomNode>)
-        .collect(), vec![]);
+    let mut node = MathNode::new(MathNodeType::Mstyle, inner, vec![]);
 
     node.set_attribute("mathcolor".to_string(), group.color.clone());
 
@@ -31,7 +31,7 @@ fn handler_fn(
         None
     };
     let  newLine =  !context.parser.settings.get_display_mode() ||
-        !context.parser.settings.use_strict_behavior("newLineInDisplayMode".to_string(), "In LaTeX, \\\\ or \\newline does nothing in display mode".to_string() );
+        !context.parser.settings.use_strict_behavior("newLineInDisplayMode", "In LaTeX, \\\\ or \\newline does nothing in display mode", None);
     return Box::new(parse_node::types::cr{
         mode: context.parser.mode,
         loc: None,
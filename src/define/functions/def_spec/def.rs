@@ -19,8 +19,7 @@ use std::sync::Mutex;
 //@flow
 
 lazy_static! {
-    static ref GlobalMap: std::sync::RwLock<HashMap<&'static str, &'static str>> =
-        std::sync::RwLock::new({
+    static ref GlobalMap: HashMap<&'static str, &'static str> = {
             let res = HashMap::from([
                 ("\\global", "\\global"),
                 ("\\long", "\\\\globallong"),
@@ -33,7 +32,7 @@ lazy_static! {
                 ("\\futurelet", "\\\\globalfuture"),
             ]);
             res
-        });
+        };
     static ref CTRL_SEQ: regex::Regex = regex::Regex::new(r"^(?:[\\{}$&#^_]|EOF)$").unwrap();
 }
 
@@ -65,6 +64,7 @@ fn let_command(parser: &mut Parser, name: &String, tok: &mut Token, global: bool
             // reproduce the same behavior in expansion
             delimiters: None,
             unexpandable: !parser.gullet.is_expandable(&tok.text),
+            default_arg: None,
         },
     );
     let mut _macro = parser.gullet.macros.get(&tok.text).unwrap_or({
@@ -86,14 +86,16 @@ pub fn handler_fn(
     args: Vec<Box<dyn AnyParseNode>>,
     opt_args: Vec<Option<Box<dyn AnyParseNode>>>,
 ) -> Box<dyn AnyParseNode> {
-    let y = GlobalMap.read().unwrap();
+    let y = &*GlobalMap;
     let mut ctx = context.borrow_mut();
     ctx.parser.consume_spaces();
-    let mut token = ctx.parser.fetch();
+    let token = ctx.parser.fetch();
     if let Some(x) = y.get(token.text.as_str()) {
         // KaTeX doesn't have \par, so ignore \long
         if ctx.func_name == "\\global" || ctx.func_name == "\\\\globallong" {
-            token.text = x.to_string();
+            if let Some(next) = ctx.parser.next_token.as_mut() {
+                next.text = x.to_string();
+            }
         }
         let _res = ctx.parser.parse_function(None, "".to_string()).unwrap();
         let res = _res
@@ -191,7 +193,7 @@ pub fn handler_fn_2(
     }
 
     let func_name = ctx.func_name.clone();
-    let y = GlobalMap.read().unwrap();
+    let y = &*GlobalMap;
 
     // Final arg is the expansion of the macro
     ctx.parser.gullet.macros.set(
@@ -203,6 +205,7 @@ pub fn handler_fn_2(
                     num_args: num_args as i32,
                     delimiters: Some(delimiters),
                     unexpandable: false,
+                    default_arg: None,
                 },
             ),
         ),
@@ -244,14 +244,15 @@ impl VirtualNode for IsMiddleSpan {
         self
     }
 
+    // A \middle the `leftright` builder did not size, e.g. one in a group
+    // inside \left...\right, is drawn as it is, as in KaTeX.
+    #[cfg(feature = "wasm")]
     fn to_node(&self) -> web_sys::Node {
-        panic!("undefined")
-        // this_to_node!(self.span, "span")
+        self.span.to_node()
     }
 
     fn to_markup(&self) -> String {
-        panic!("undefined")
-        // this_to_markup!(self, "span")
+        self.span.to_markup()
     }
 }
 
@@ -293,7 +294,7 @@ impl HtmlDomNode for IsMiddleSpan{
         return self.span.get_style();
     }
     fn get_mut_style(&mut self) -> &mut CssStyle {
-        return self.get_mut_style();
+        return self.span.get_mut_style();
     }
 
     fn set_style(&mut self, _style: CssStyle) {
@@ -342,12 +343,17 @@ fn invalid_delimiter_node(mode: crate::types::Mode) -> Box<dyn AnyParseNode> {
     }) as Box<dyn AnyParseNode>
 }
 
+/** Whether `text` is a delimiter `\big`, `\left` and the like accept. */
+pub(crate) fn is_delimiter(text: &str) -> bool {
+    DELIMITERS.iter().any(|delim| delim == text)
+}
+
 fn check_delimiter(
     delim: &Box<dyn AnyParseNode>,
     parser: &mut crate::Parser::Parser<'_>,
     func_name: &str,
 ) -> Option<String> {
-    if !check_symbol_node_type(delim) {
+    if !check_symbol_node_type(delim.as_ref()) {
         parser.report_parse_error(
             format!("Invalid delimiter type '{}'", delim.get_type()),
             delimiter_error_loc(delim),
@@ -355,7 +361,7 @@ fn check_delimiter(
         return None;
     }
 
-    let text = check_symbol_node_type_text(delim);
+    let text = check_symbol_node_type_text(delim.as_ref());
     if DELIMITERS.contains(&text) {
         Some(text)
     } else {
@@ -412,36 +418,34 @@ pub fn big_html_builder(_group: Box<dyn AnyParseNode>, options: Options) -> Box<
 }
 
 pub fn big_mathml_builder(_group: Box<dyn AnyParseNode>, options: Options) -> Box<dyn MathDomNode> {
-    panic!("undefined");
     let group = _group
         .as_any()
         .downcast_ref::<parse_node::types::delimsizing>()
         .unwrap();
-    // let children = [];
-    //
-    // if (group.delim != ".".to_string()) {
-    // children.push(mml.makeText(group.delim, group.mode));
-    // }
-    //
-    // let mut node = MathNode::new("mo".to_string(), children);
-    //
-    // if (group.mclass == "mopen" ||
-    // group.mclass == "mclose".to_string()) {
-    // // Only some of the delimsizing functions act as fences, and they
-    // // return "mopen" or "mclose" mclass.
-    // node.set_attribute("fence".to_string(), "true".to_string());
-    // } else {
-    // // Explicitly disable fencing if it's not a fence, to override the
-    // // defaults.
-    // node.set_attribute("fence".to_string(), "false".to_string());
-    // }
-    //
-    // node.set_attribute("stretchy".to_string(), "true".to_string());
-    // let size = crate::units::make_em(delimiter.sizeToMaxHeight[group.size]);
-    // node.set_attribute("minsize".to_string(), size);
-    // node.set_attribute("maxsize".to_string(), size);
-    //
-    // return node;
+    let mut children: Vec<Box<dyn MathDomNode>> = vec![];
+
+    if group.delim != "." {
+        children.push(Box::new(mathML::make_text(group.delim.clone(), group.mode, None)));
+    }
+
+    let mut node = MathNode::new(MathNodeType::Mo, children, vec![]);
+
+    if group.mclass == "mopen" || group.mclass == "mclose" {
+        // Only some of the delimsizing functions act as fences, and they
+        // return "mopen" or "mclose" mclass.
+        node.set_attribute("fence".to_string(), "true".to_string());
+    } else {
+        // Explicitly disable fencing if it's not a fence, to override the
+        // defaults.
+        node.set_attribute("fence".to_string(), "false".to_string());
+    }
+
+    node.set_attribute("stretchy".to_string(), "true".to_string());
+    let size = crate::units::make_em(crate::delimiter::SIZE_TO_MAX_HEIGHT[group.size]);
+    node.set_attribute("minsize".to_string(), size.clone());
+    node.set_attribute("maxsize".to_string(), size);
+
+    return Box::new(node) as Box<dyn MathDomNode>;
 }
 
 lazy_static! {
@@ -536,8 +540,9 @@ lazy_static! {
             names: vec!["\\right".to_string()],
             props,
             handler: lrr_handler_fn,
-            html_builder: Some(big_html_builder),
-            mathml_builder: Some(big_mathml_builder),
+            // Consumed by \left, never built.
+            html_builder: None,
+            mathml_builder: None,
         }
     });
 }
@@ -553,10 +558,14 @@ pub fn lr_handler_fn(
         return invalid_delimiter_node(context.parser.mode);
     };
     // Parse out the implicit body
+    if !context.parser.enter_nesting() {
+        return invalid_delimiter_node(context.parser.mode);
+    }
     context.parser.left_right_depth += 1;
     // parseExpression stops before '\\right'
     let body = context.parser.parse_expression(false, None);
     context.parser.left_right_depth -= 1;
+    context.parser.leave_nesting();
     if context.parser.error.is_some() {
         return Box::new(parse_node::types::leftright {
             mode: context.parser.mode,
@@ -719,35 +728,41 @@ pub fn lr_html_builder(_group: Box<dyn AnyParseNode>, options: Options) -> Box<d
 }
 
 pub fn lr_mathml_builder(_group: Box<dyn AnyParseNode>, options: Options) -> Box<dyn MathDomNode> {
-    panic!("undefined");
     let group = _group
         .as_any()
         .downcast_ref::<parse_node::types::leftright>()
         .unwrap();
-    // assert_parsed(group);
-    // let inner = mml::build_expression(group.body, options);
-    //
-    // if group.left != ".".to_string() {
-    //     let leftNode = MathNode::new("mo".to_string(), [mml.makeText(group.left, group.mode)]);
-    //
-    //     leftNode.set_attribute("fence".to_string(), "true".to_string());
-    //
-    //     inner.unshift(leftNode);
-    // }
-    //
-    // if (group.right != ".".to_string()) {
-    //     let rightNode = MathNode::new("mo".to_string(), [mml.makeText(group.right, group.mode)]);
-    //
-    //     rightNode.set_attribute("fence".to_string(), "true".to_string());
-    //
-    //     if (group.rightColor) {
-    //         rightNode.set_attribute("mathcolor".to_string(), group.rightColor);
-    //     }
-    //
-    //     inner.push(rightNode);
-    // }
-    //
-    // return mml.makeRow(inner);
+    let mut inner = mathML::build_expression(group.body.clone(), options, false);
+
+    if group.left != "." {
+        let mut left_node = MathNode::new(
+            MathNodeType::Mo,
+            vec![Box::new(mathML::make_text(group.left.clone(), group.mode, None))],
+            vec![],
+        );
+
+        left_node.set_attribute("fence".to_string(), "true".to_string());
+
+        inner.insert(0, Box::new(left_node));
+    }
+
+    if group.right != "." {
+        let mut right_node = MathNode::new(
+            MathNodeType::Mo,
+            vec![Box::new(mathML::make_text(group.right.clone(), group.mode, None))],
+            vec![],
+        );
+
+        right_node.set_attribute("fence".to_string(), "true".to_string());
+
+        if let Some(right_color) = &group.right_color {
+            right_node.set_attribute("mathcolor".to_string(), right_color.clone());
+        }
+
+        inner.push(Box::new(right_node));
+    }
+
+    return mathML::make_row(inner);
 }
 
 lazy_static! {
@@ -820,7 +835,6 @@ pub fn middle_mathml_builder(
     _group: Box<dyn AnyParseNode>,
     options: Options,
 ) -> Box<dyn MathDomNode> {
-    panic!("undefined");
     let group = _group
         .as_any()
         .downcast_ref::<parse_node::types::middle>()
@@ -830,18 +844,18 @@ pub fn middle_mathml_builder(
     // is in the fence part of the operator dictionary at:
     // https://www.w3.org/TR/MathML3/appendixc.html.
     // So we need to avoid U+2223 and use plain "|" instead.
-    // let textNode = if (group.delim == "\\vert" || group.delim == "|".to_string()) {
-    //     mml.makeText("|".to_string(), "text".to_string())
-    // } else {
-    //     mml.makeText(group.delim, group.mode)
-    // };
-    // let middleNode = MathNode::new(MathNodeType::Mo, vec![textNode]);
-    // middleNode.set_attribute("fence".to_string(), "true".to_string());
-    // // MathML gives 5/18em spacing to each <mo> element.
-    // // \middle should get delimiter spacing instead.
-    // middleNode.set_attribute("lspace".to_string(), "0.05em".to_string());
-    // middleNode.set_attribute("rspace".to_string(), "0.05em".to_string());
-    // return middleNode;
+    let text_node = if group.delim == "\\vert" || group.delim == "|" {
+        mathML::make_text("|".to_string(), crate::types::Mode::text, None)
+    } else {
+        mathML::make_text(group.delim.clone(), group.mode, None)
+    };
+    let mut middle_node = MathNode::new(MathNodeType::Mo, vec![Box::new(text_node)], vec![]);
+    middle_node.set_attribute("fence".to_string(), "true".to_string());
+    // MathML gives 5/18em spacing to each <mo> element.
+    // \middle should get delimiter spacing instead.
+    middle_node.set_attribute("lspace".to_string(), "0.05em".to_string());
+    middle_node.set_attribute("rspace".to_string(), "0.05em".to_string());
+    return Box::new(middle_node) as Box<dyn MathDomNode>;
 }
 lazy_static! {
     pub static ref MIDDLE: Mutex<FunctionDefSpec> = Mutex::new({
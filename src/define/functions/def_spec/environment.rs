@@ -51,7 +51,7 @@ fn env_handler_fn(
         // begin...end is similar to left...right
         // Build the environment object. Arguments and other information will
         // be made available to the begin and end methods using properties.
-        let envs = _environments.read().unwrap();
+        let envs = &*_environments;
         let Some(env) = envs.get(&env_name) else {
             ctx.parser.report_parse_error(
                 format!("No such environment: {}", env_name),
@@ -75,6 +75,14 @@ fn env_handler_fn(
                 semisimple: false,
             }) as Box<dyn AnyParseNode>;
         }
+        if !ctx.parser.enter_nesting() {
+            return Box::new(parse_node::types::ordgroup {
+                mode: ctx.parser.mode,
+                loc: None,
+                body: vec![],
+                semisimple: false,
+            }) as Box<dyn AnyParseNode>;
+        }
         let context = RefCell::new(FunctionContext2 {
             func_name: env_name.clone(),
             token: None,
@@ -82,6 +90,7 @@ fn env_handler_fn(
             break_on_token_text: None,
         });
         let result = env.1(context, args, opt_args);
+        ctx.parser.leave_nesting();
         if ctx.parser.error.is_some() {
             return result;
         }
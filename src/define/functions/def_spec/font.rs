@@ -27,10 +27,12 @@ fn html_builder(_group: Box<dyn AnyParseNode>, options: Options) -> Box<dyn Html
 }
 
 fn mathml_builder(_group: Box<dyn AnyParseNode>, options: Options) -> Box<dyn MathDomNode> {
-    panic!("udnefined")
-    // let font = group.font;
-    // let newOptions = options.withFont(font);
-    // return mathML::build_group(group.body, newOptions);
+    let group = _group
+        .as_any()
+        .downcast_ref::<parse_node::types::font>()
+        .unwrap();
+    let new_options = options.with_font(group.font.clone());
+    return mathML::build_group(Some(group.body.clone()), new_options);
 }
 
 lazy_static! {
@@ -8,6 +8,7 @@ use crate::dom_tree::css_style::CssStyle;
 use crate::dom_tree::span::Span;
 use crate::mathML_tree::math_node::MathNode;
 use crate::mathML_tree::public::{MathDomNode, MathNodeType};
+use crate::mathML_tree::text_node::TextNode;
 use crate::parse_node::types::Atom;
 use crate::parse_node::types::ParseNodeToAny;
 use crate::utils::is_character_box;
@@ -20,10 +21,10 @@ use std::sync::Mutex;
 
 pub fn adjust_style(size: &String, original_style: StyleInterface) -> StyleInterface {
     use crate::Style::{DISPLAY, SCRIPT, SCRIPTSCRIPT, TEXT};
-    let _script = SCRIPT.read().unwrap();
-    let _display = DISPLAY.read().unwrap();
-    let _script_script = SCRIPTSCRIPT.read().unwrap();
-    let _text = TEXT.read().unwrap();
+    let _script = &*SCRIPT;
+    let _display = &*DISPLAY;
+    let _script_script = &*SCRIPTSCRIPT;
+    let _text = &*TEXT;
     // Figure out what style this fraction should be in based on the
     // function used
     let mut style = original_style;
@@ -107,7 +108,7 @@ pub fn html_builder(_group: Box<dyn AnyParseNode>, options: Options) -> Box<dyn
     let mut num_shift;
     let clearance;
     let mut denom_shift;
-    let _display = crate::Style::DISPLAY.read().unwrap();
+    let _display = &*crate::Style::DISPLAY;
     if style.size == _display.size || group.size == "display" {
         num_shift = options.get_font_metrics().num1;
         if rule_width > 0.0 {
@@ -216,8 +217,8 @@ pub fn html_builder(_group: Box<dyn AnyParseNode>, options: Options) -> Box<dyn
     frac.set_depth(frac.get_depth() * new_options.sizeMultiplier / options.sizeMultiplier);
 
     // Rule 15e
-    let _scriptscript = crate::Style::SCRIPTSCRIPT.read().unwrap();
-    let _script = crate::Style::SCRIPT.read().unwrap();
+    let _scriptscript = &*crate::Style::SCRIPTSCRIPT;
+    let _script = &*crate::Style::SCRIPT;
     let delim_size = if style.size == _display.size {
         options.get_font_metrics().delim1
     } else if style.size == _scriptscript.size {
@@ -283,62 +284,71 @@ pub fn html_builder(_group: Box<dyn AnyParseNode>, options: Options) -> Box<dyn
 }
 
 pub fn mathml_builder(_group: Box<dyn AnyParseNode>, options: Options) -> Box<dyn MathDomNode> {
-    panic!("undefined mathml builder");
-    // let group = _group.as_any().downcast_ref::<parse_node::types::genfrac>().unwrap();
-    // let node = MathNode::new(
-    //     "mfrac".to_string(),
-    //     vec![
-    //         mathML::build_group(group.numer, options),
-    //         mathML::build_group(group.denom, options),
-    //     ]);
-    //
-    // if !group.hasBarLine {
-    //     node.set_attribute("linethickness".to_string(), "0px".to_string());
-    // } else if (group.barSize) {
-    //     let ruleWidth = calculateSize(group.barSize, options);
-    //     node.set_attribute("linethickness".to_string(), crate::units::make_em(ruleWidth));
-    // }
-    //
-    // let style = adjustStyle(group.size, options.style);
-    // if (style.size != options.style.size) {
-    //     node = MathNode::new("mstyle".to_string(), [node]);
-    //     let isDisplay = (style.size == Style.DISPLAY.size)?
-    //     "true": "false";
-    //     node.set_attribute("displaystyle".to_string(), isDisplay);
-    //     node.set_attribute("scriptlevel".to_string(), "0".to_string());
-    // }
-    //
-    // if (group.leftDelim != null | | group.rightDelim != null) {
-    //     let withDelims = [];
-    //
-    //     if (group.leftDelim != null) {
-    //         let leftOp = MathNode::new(
-    //             "mo".to_string(),
-    //             [new mathMLTree.TextNode(group.leftDelim.replace("\\".to_string(), "".to_string()))]
-    //         );
-    //
-    //         leftOp.set_attribute("fence".to_string(), "true".to_string());
-    //
-    //         withDelims.push(leftOp);
-    //     }
-    //
-    //     withDelims.push(node);
-    //
-    //     if (group.rightDelim != null) {
-    //         let rightOp = MathNode::new(
-    //             "mo".to_string(),
-    //             [new mathMLTree.TextNode(group.rightDelim.replace("\\".to_string(), "".to_string()))]
-    //         );
-    //
-    //         rightOp.set_attribute("fence".to_string(), "true".to_string());
-    //
-    //         withDelims.push(rightOp);
-    //     }
-    //
-    //     return mml.makeRow(withDelims);
-    // }
-    //
-    // return node;
+    let group = _group
+        .as_any()
+        .downcast_ref::<parse_node::types::genfrac>()
+        .unwrap();
+    let mut node = MathNode::new(
+        MathNodeType::Mfrac,
+        vec![
+            mathML::build_group(Some(group.numer.clone()), options.clone()),
+            mathML::build_group(Some(group.denom.clone()), options.clone()),
+        ],
+        vec![],
+    );
+
+    if !group.hasBarLine {
+        node.set_attribute("linethickness".to_string(), "0px".to_string());
+    } else if let Some(bar) = &group.barSize {
+        let rule_width = calculate_size(bar, &options);
+        node.set_attribute("linethickness".to_string(), crate::units::make_em(rule_width));
+    }
+
+    let style = adjust_style(&group.size, options.get_style());
+    if style.size != options.get_style().size {
+        node = MathNode::new(MathNodeType::Mstyle, vec![Box::new(node)], vec![]);
+        let is_display = if style.size == crate::Style::DISPLAY.size {
+            "true"
+        } else {
+            "false"
+        };
+        node.set_attribute("displaystyle".to_string(), is_display.to_string());
+        node.set_attribute("scriptlevel".to_string(), "0".to_string());
+    }
+
+    if group.leftDelim.is_some() || group.rightDelim.is_some() {
+        let mut with_delims: Vec<Box<dyn MathDomNode>> = vec![];
+
+        if let Some(left_delim) = &group.leftDelim {
+            let mut left_op = MathNode::new(
+                MathNodeType::Mo,
+                vec![Box::new(TextNode::new(left_delim.replace("\\", "")))],
+                vec![],
+            );
+
+            left_op.set_attribute("fence".to_string(), "true".to_string());
+
+            with_delims.push(Box::new(left_op));
+        }
+
+        with_delims.push(Box::new(node));
+
+        if let Some(right_delim) = &group.rightDelim {
+            let mut right_op = MathNode::new(
+                MathNodeType::Mo,
+                vec![Box::new(TextNode::new(right_delim.replace("\\", "")))],
+                vec![],
+            );
+
+            right_op.set_attribute("fence".to_string(), "true".to_string());
+
+            with_delims.push(Box::new(right_op));
+        }
+
+        return mathML::make_row(with_delims);
+    }
+
+    return Box::new(node) as Box<dyn MathDomNode>;
 }
 
 pub fn frac_handler_fn(
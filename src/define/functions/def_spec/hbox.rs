@@ -39,10 +39,15 @@ pub fn html_builder(_group: Box<dyn AnyParseNode>, options: Options) -> Box<dyn
     return Box::new(res) as Box<dyn HtmlDomNode>;
 }
 pub fn mathml_builder(_group: Box<dyn AnyParseNode>, options: Options) -> Box<dyn MathDomNode> {
-    panic!("undefined mathml builder");
-    // return MathNode::new(
-    // "mrow".to_string(), mml.buildExpression(group.body, options)
-    // );
+    let group = _group
+        .as_any()
+        .downcast_ref::<parse_node::types::hbox>()
+        .unwrap();
+    return Box::new(MathNode::new(
+        MathNodeType::Mrow,
+        mathML::build_expression(group.body.clone(), options, false),
+        vec![],
+    )) as Box<dyn MathDomNode>;
 }
 
 // \hbox is provided for compatibility with LaTeX \vcenter.
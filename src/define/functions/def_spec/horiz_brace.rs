@@ -56,7 +56,7 @@ pub(crate) fn html_builder(_group: Box<dyn AnyParseNode>, options: Options) -> B
                 .downcast_ref::<parse_node::types::horizBrace>()
                 .unwrap()
         };
-    let _display = crate::Style::DISPLAY.read().unwrap();
+    let _display = &*crate::Style::DISPLAY;
     // Build the base group
     let body = HTML::build_group(Some(group.base.clone()), options.having_base_style(&_display), None);
 
@@ -228,12 +228,23 @@ pub(crate) fn html_builder(_group: Box<dyn AnyParseNode>, options: Options) -> B
 }
 
 pub fn mathml_builder(_group: Box<dyn AnyParseNode>, options: Options) -> Box<dyn MathDomNode> {
-    panic!("undefined mathml builder");
-    // let accentNode = stretchy.mathMLnode(group.label);
-    // return MathNode::new(
-    //     (group.isOver ? "mover" : "munder".to_string()),
-    //         [mathML::build_group(group.base, options), accentNode]
-    // );
+    let group = _group
+        .as_any()
+        .downcast_ref::<parse_node::types::horizBrace>()
+        .unwrap();
+    let accent_node = stretchy::math_ml_node(&group.label);
+    return Box::new(MathNode::new(
+        if group.isOver {
+            MathNodeType::Mover
+        } else {
+            MathNodeType::Munder
+        },
+        vec![
+            mathML::build_group(Some(group.base.clone()), options),
+            Box::new(accent_node) as Box<dyn MathDomNode>,
+        ],
+        vec![],
+    )) as Box<dyn MathDomNode>;
 }
 
 fn handler_fn(
@@ -8,6 +8,7 @@ use crate::dom_tree::span::Span;
 use crate::mathML_tree::math_node::MathNode;
 use crate::mathML_tree::public::{MathDomNode, MathNodeType};
 use crate::parse_node::types::ParseNodeToAny;
+use crate::settings::TrustContext;
 use crate::utils::is_character_box;
 use crate::Options::Options;
 use crate::{parse_node, AnyParseNode, HtmlDomNode, types::ArgType};
@@ -23,13 +24,17 @@ fn html_builder(_group: Box<dyn AnyParseNode>, options: Options) -> Box<dyn Html
 }
 
 pub fn mathml_builder(_group: Box<dyn AnyParseNode>, options: Options) -> Box<dyn MathDomNode> {
-    panic!("undefined mathml builder");
-    // let math = mml.buildExpressionRow(group.body, options);
-    // if (!(math instanceof MathNode)) {
-    // math = new MathNode("mrow".to_string(), [math]);
-    // }
-    // math.set_attribute("href".to_string(), group.href);
-    // return math;
+    let group = _group
+        .as_any()
+        .downcast_ref::<parse_node::types::href>()
+        .unwrap();
+    let math = mathML::build_expression_row(group.body.clone(), options, false);
+    let mut math = match math.as_any().downcast_ref::<MathNode>() {
+        Some(m) => m.clone(),
+        None => MathNode::new(MathNodeType::Mrow, vec![math], vec![]),
+    };
+    math.set_attribute("href".to_string(), group.href.clone());
+    return Box::new(math) as Box<dyn MathDomNode>;
 }
 
 pub fn href_handler_fn(
@@ -40,13 +45,13 @@ pub fn href_handler_fn(
     let context = ctx.borrow();
     let body = &args[1];
     let href = args[0].as_any().downcast_ref::<parse_node::types::url>().unwrap();
-    //TODO
-    // if (!context.parser.settings.isTrusted({
-    //     command: "\\href".to_string(),
-    //     url: href,
-    // })) {
-    //     return parser.formatUnsupportedCmd("\\href".to_string());
-    // }
+    if !context.parser.settings.is_trusted(&TrustContext {
+        command: "\\href".to_string(),
+        url: Some(href.url.clone()),
+        ..Default::default()
+    }) {
+        return Box::new(context.parser.format_unsupported_cmd("\\href", context.token.as_ref())) as Box<dyn AnyParseNode>;
+    }
 
     let res = parse_node::types::href{
         mode: context.parser.mode,
@@ -86,13 +91,13 @@ pub fn url_handler_fn(
     let context = ctx.borrow();
     let href = args[0].as_any().downcast_ref::<parse_node::types::url>().unwrap();
 
-    //TODO
-    // if (!parser.settings.isTrusted({
-    //     command: "\\url".to_string(),
-    //     url: href,
-    // })) {
-    //     return parser.formatUnsupportedCmd("\\url".to_string());
-    // }
+    if !context.parser.settings.is_trusted(&TrustContext {
+        command: "\\url".to_string(),
+        url: Some(href.url.clone()),
+        ..Default::default()
+    }) {
+        return Box::new(context.parser.format_unsupported_cmd("\\url", context.token.as_ref())) as Box<dyn AnyParseNode>;
+    }
 
     let chars = href.url.chars().map(|c|{
         let res = parse_node::types::textord{
@@ -20,7 +20,7 @@ fn handler_fn(
     args: Vec<Box<dyn AnyParseNode>>,
     opt_args: Vec<Option<Box<dyn AnyParseNode>>>,
 ) -> Box<dyn AnyParseNode> {
-    let context = ctx.borrow();
+    let mut context = ctx.borrow_mut();
     let args0 = args[0]
         .as_any()
         .downcast_ref::<parse_node::types::raw>()
@@ -29,7 +29,7 @@ fn handler_fn(
     let body = &args[1];
 
     if context.parser.settings.get_strict() != "ignore" {
-        context.parser.settings.report_nonstrict(
+        context.parser.report_nonstrict(
             "htmlExtension",
             "HTML extension is disabled on strict mode",
             None,
@@ -61,15 +61,30 @@ fn handler_fn(
             panic!("Unrecognized html command");
         }
     }
-    let trust_context = TrustContext {
+    let mut trust_context = TrustContext {
         command: context.func_name.clone(),
-        context: attributes.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+        ..Default::default()
     };
+    match context.func_name.as_str() {
+        "\\htmlClass" => trust_context.class = Some(value.clone()),
+        "\\htmlId" => trust_context.id = Some(value.clone()),
+        "\\htmlStyle" => trust_context.style = Some(value.clone()),
+        _ => trust_context.attributes = Some(attributes.clone()),
+    }
 
     if !context.parser.settings.is_trusted(&trust_context) {
-        let res = context.parser.format_unsupported_cmd(&context.func_name);
+        let res = context.parser.format_unsupported_cmd(&context.func_name, context.token.as_ref());
         return Box::new(res) as Box<dyn AnyParseNode>;
     }
+    if let Some(policy) = context.parser.settings.get_html_policy() {
+        match policy.sanitize(attributes) {
+            Some(allowed) => attributes = allowed,
+            None => {
+                let res = context.parser.format_unsupported_cmd(&context.func_name, context.token.as_ref());
+                return Box::new(res) as Box<dyn AnyParseNode>;
+            }
+        }
+    }
     let res = parse_node::types::html {
         mode: context.parser.mode,
         attributes,
@@ -101,8 +116,11 @@ fn html_builder(_group: Box<dyn AnyParseNode>, options: Options) -> Box<dyn Html
 }
 
 pub fn mathml_builder(_group: Box<dyn AnyParseNode>, options: Options) -> Box<dyn MathDomNode> {
-    panic!("undefined mathml builder");
-    // return mml.buildExpressionRow(group.body, options);
+    let group = _group
+        .as_any()
+        .downcast_ref::<parse_node::types::html>()
+        .unwrap();
+    return mathML::build_expression_row(group.body.clone(), options, false);
 }
 
 lazy_static! {
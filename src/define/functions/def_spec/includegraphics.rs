@@ -13,7 +13,6 @@ use crate::utils::is_character_box;
 use crate::Options::Options;
 use crate::{parse_node, types::ArgType, AnyParseNode, HtmlDomNode, Measurement};
 use regex::Regex;
-use std::collections::HashMap;
 use std::sync::Mutex;
 
 fn size_data(s: &str) -> crate::Measurement {
@@ -117,10 +116,11 @@ fn includegraphics_handler_fn(
 
     let trust_context = TrustContext {
         command: "\\includegraphics".to_string(),
-        context: HashMap::from([("url".to_string(), "src".to_string())]),
+        url: Some(src.clone()),
+        ..Default::default()
     };
     if !context.parser.settings.is_trusted(&trust_context) {
-        let res = context.parser.format_unsupported_cmd("\\includegraphics");
+        let res = context.parser.format_unsupported_cmd("\\includegraphics", context.token.as_ref());
         return Box::new(res) as Box<dyn AnyParseNode>;
     }
 
@@ -7,6 +7,7 @@ use crate::dom_tree::css_style::CssStyle;
 use crate::dom_tree::span::Span;
 use crate::mathML_tree::math_node::MathNode;
 use crate::mathML_tree::public::{MathDomNode, MathNodeType};
+use crate::mathML_tree::space_node::SpaceNode;
 use crate::parse_node::types::ParseNodeToAny;
 use crate::utils::is_character_box;
 use crate::Options::Options;
@@ -24,21 +25,22 @@ fn handler_fn(
     let mut context = ctx.borrow_mut();
     let size = args[0].as_any().downcast_ref::<parse_node::types::size>().unwrap();
     if /*parser.settings.strict*/ true {
-        let math_function = (context.func_name.chars().nth(1) == Some('m'));  // \mkern, \mskip
+        let func_name = context.func_name.clone();
+        let math_function = (func_name.chars().nth(1) == Some('m'));  // \mkern, \mskip
         let mu_unit = (size.value.unit == "mu");
         if math_function {
             if !mu_unit {
-                context.parser.settings.report_nonstrict("mathVsTextUnits",
-                                                         format!("LaTeX's {} supports only mu units,  not {} units", context.func_name, size.value.unit).as_str(), None);
+                context.parser.report_nonstrict("mathVsTextUnits",
+                                                &format!("LaTeX's {} supports only mu units, not {} units", func_name, size.value.unit), None);
             }
             if context.parser.mode != Mode::math {
-                context.parser.settings.report_nonstrict("mathVsTextUnits",
-                                                        &format!("LaTeX's {} works only in math mode",context.func_name),None);
+                context.parser.report_nonstrict("mathVsTextUnits",
+                                                &format!("LaTeX's {} works only in math mode", func_name), None);
             }
         } else {  // !math_function
             if mu_unit {
-                context.parser.settings.report_nonstrict("mathVsTextUnits",
-                                                        &format!("LaTeX's {} doesn't support mu units",context.func_name),None);
+                context.parser.report_nonstrict("mathVsTextUnits",
+                                                &format!("LaTeX's {} doesn't support mu units", func_name), None);
             }
         }
     }
@@ -62,9 +64,12 @@ fn html_builder(_group: Box<dyn AnyParseNode>, options: Options) -> Box<dyn Html
 
 
 fn mathml_builder(_group: Box<dyn AnyParseNode>, options: Options) -> Box<dyn MathDomNode> {
-    panic!("undefined");
-// let dimension = crate::units::calculate_size(group.dimension, options);
-// return new mathMLTree.SpaceNode(dimension);
+    let group = _group
+        .as_any()
+        .downcast_ref::<parse_node::types::kern>()
+        .unwrap();
+    let dimension = crate::units::calculate_size(&group.dimension, &options);
+    return Box::new(SpaceNode::new(dimension)) as Box<dyn MathDomNode>;
 }
 
 
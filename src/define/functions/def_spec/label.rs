@@ -0,0 +1,154 @@
+use crate::define::functions::public::{FunctionContext, FunctionDefSpec, FunctionPropSpec};
+use crate::define::macros::public::MacroDefinition;
+use crate::settings::CrossReferences;
+use crate::token::Token;
+use crate::{parse_node, types::ArgType, AnyParseNode};
+use std::sync::Mutex;
+
+// \label, \ref and \eqref need cross-references to be enabled on the
+// RenderSession; otherwise they are undefined control sequences, as in KaTeX.
+
+/**
+ * `\label`, `\ref` or `\eqref` without cross-references: an error when
+ * `throwOnError` is set, else the command and its argument in the error color.
+ */
+fn without_cross_references(ctx: &FunctionContext, label: &str) -> Box<dyn AnyParseNode> {
+    let mut context = ctx.borrow_mut();
+    let func_name = context.func_name.clone();
+    if context.parser.settings.get_throw_on_error() {
+        let msg = format!("Undefined control sequence: {}", func_name);
+        match context.token.clone() {
+            Some(token) => context.parser.report_token_error(msg, &token),
+            None => context.parser.report_parse_error(msg, None),
+        }
+    }
+    let text = format!("{}{{{}}}", func_name, label);
+    Box::new(context.parser.format_unsupported_cmd(&text, context.token.as_ref())) as Box<dyn AnyParseNode>
+}
+
+fn label_handler_fn(
+    ctx: FunctionContext,
+    args: Vec<Box<dyn AnyParseNode>>,
+    _opt_args: Vec<Option<Box<dyn AnyParseNode>>>,
+) -> Box<dyn AnyParseNode> {
+    let label = args[0]
+        .as_any()
+        .downcast_ref::<parse_node::types::raw>()
+        .unwrap()
+        .string
+        .clone();
+    if ctx.borrow().parser.settings.get_cross_references().is_none() {
+        return without_cross_references(&ctx, &label);
+    }
+    let mut context = ctx.borrow_mut();
+
+    // Like amsmath, keep the label in \df@label until the tag of the
+    // equation is known, see Parser::take_equation_tag.
+    if context.parser.gullet.macros.get("\\df@label").is_some() {
+        context
+            .parser
+            .report_parse_error(format!("Multiple \\label's: label '{}' will be lost", label), None);
+    } else {
+        context.parser.gullet.macros.set(
+            &"\\df@label".to_string(),
+            Some(MacroDefinition::Str(label)),
+            true,
+        );
+    }
+
+    let res = parse_node::types::internal {
+        mode: context.parser.mode,
+        loc: None,
+    };
+    return Box::new(res) as Box<dyn AnyParseNode>;
+}
+
+lazy_static! {
+    pub static ref LABEL: Mutex<FunctionDefSpec> = Mutex::new({
+        let mut props = FunctionPropSpec::new();
+        props.set_num_args(1);
+        props.set_arg_types(vec![ArgType::raw]);
+        props.set_allowed_in_text(true);
+
+        FunctionDefSpec {
+            def_type: "internal".to_string(),
+            names: vec!["\\label".to_string()],
+            props,
+            handler: label_handler_fn,
+            html_builder: None,
+            mathml_builder: None,
+        }
+    });
+}
+
+fn ref_handler_fn(
+    ctx: FunctionContext,
+    args: Vec<Box<dyn AnyParseNode>>,
+    _opt_args: Vec<Option<Box<dyn AnyParseNode>>>,
+) -> Box<dyn AnyParseNode> {
+    let label = &args[0]
+        .as_any()
+        .downcast_ref::<parse_node::types::raw>()
+        .unwrap()
+        .string;
+    let cross_references = ctx.borrow().parser.settings.get_cross_references();
+    let cross_references = match cross_references {
+        Some(cross_references) => cross_references,
+        None => return without_cross_references(&ctx, label),
+    };
+    let mut context = ctx.borrow_mut();
+    let func_name = context.func_name.clone();
+
+    // Labels defined later in the document are not known yet, as in the
+    // first LaTeX run.
+    let reference = cross_references.lock().unwrap().get(label).map(|tag| tag.to_string());
+    let text = reference.clone().unwrap_or_else(|| "??".to_string());
+    let text = if func_name == "\\eqref" {
+        format!("({})", text)
+    } else {
+        text
+    };
+    context.parser.gullet.macros.set(
+        &"\\df@ref".to_string(),
+        Some(MacroDefinition::Str(format!("\\text{{{}}}", text))),
+        false,
+    );
+    let body = context
+        .parser
+        .subparse(vec![Token::new("\\df@ref".to_string(), None)]);
+
+    if reference.is_none() {
+        let res = parse_node::types::ordgroup {
+            mode: context.parser.mode,
+            loc: None,
+            body,
+            semisimple: false,
+        };
+        return Box::new(res) as Box<dyn AnyParseNode>;
+    }
+    let res = parse_node::types::href {
+        mode: context.parser.mode,
+        loc: None,
+        href: format!("#{}", CrossReferences::anchor(label)),
+        body,
+    };
+    return Box::new(res) as Box<dyn AnyParseNode>;
+}
+
+lazy_static! {
+    pub static ref REF: Mutex<FunctionDefSpec> = Mutex::new({
+        let mut props = FunctionPropSpec::new();
+        props.set_num_args(1);
+        props.set_arg_types(vec![ArgType::raw]);
+        props.set_allowed_in_text(true);
+
+        FunctionDefSpec {
+            def_type: "href".to_string(),
+            names: vec!["\\ref".to_string(), "\\eqref".to_string()],
+            props,
+            handler: ref_handler_fn,
+            html_builder: None,
+            mathml_builder: None,
+        }
+    });
+}
@@ -16,10 +16,10 @@ use std::sync::Mutex;
 
 
 fn chooseMathStyle<'a>(group: &'a parse_node::types::mathchoice, options: &Options) -> &'a Vec<Box<dyn AnyParseNode>> {
-    let _display = crate::Style::DISPLAY.read().unwrap().size;
-    let _text = crate::Style::TEXT.read().unwrap().size;
-    let _script = crate::Style::SCRIPT.read().unwrap().size;
-    let _script_script = crate::Style::SCRIPTSCRIPT.read().unwrap().size;
+    let _display = crate::Style::DISPLAY.size;
+    let _text = crate::Style::TEXT.size;
+    let _script = crate::Style::SCRIPT.size;
+    let _script_script = crate::Style::SCRIPTSCRIPT.size;
 
     let style_size = options.get_style().size;
     if style_size == _display {
@@ -38,10 +38,7 @@ pub fn mathml_builder(_group: Box<dyn AnyParseNode>, options: Options) -> Box<dy
         .as_any()
         .downcast_ref::<parse_node::types::mclass>()
         .unwrap();
-    let inner = mathML::build_expression(group.body.clone(), options, false)
-        .into_iter()
-        .map(|x| Box::new(x) as Box<dyn MathDomNode>)
-        .collect();
+    let inner = mathML::build_expression(group.body.clone(), options, false);
     let mut node;
     if (group.mclass == "minner") {
         node = MathNode::new(MathNodeType::Mpadded, inner, vec![]);
@@ -45,7 +45,9 @@ mod color;
 mod cd;
 mod assembleSupSub;
 mod def;
+mod label;
 
+pub(crate) use delimsizing::is_delimiter;
 use super::public::FunctionDefSpec;
 use std::sync::Mutex;
 use crate::define::functions::def_spec::vcenter::VCENTER;
@@ -144,6 +146,9 @@ lazy_static! {
         let nd = def::INTERNAL4.lock().unwrap();
         let ne = def::INTERNAL5.lock().unwrap();
 
+        let oa = label::LABEL.lock().unwrap();
+        let ob = label::REF.lock().unwrap();
+
         let res = vec![
             x.clone(),
             x2.clone(),
@@ -224,7 +229,9 @@ lazy_static! {
             nb.clone(),
             nc.clone(),
             nd.clone(),
-            ne.clone()
+            ne.clone(),
+            oa.clone(),
+            ob.clone()
         ];
         res
     });
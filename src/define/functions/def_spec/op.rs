@@ -56,7 +56,7 @@ pub fn html_builder(_group: Box<dyn AnyParseNode>, options: Options) -> Box<dyn
     let style = options.get_style();
 
     let mut large = false;
-    let _display = crate::Style::DISPLAY.read().unwrap();
+    let _display = &*crate::Style::DISPLAY;
     if style.size == _display.size
         && group.symbol
         && !NO_SUCCESSOR.contains(&&**group.name.as_ref().unwrap())
@@ -259,7 +259,7 @@ fn mathml_builder(_group: Box<dyn AnyParseNode>, options: Options) -> Box<dyn Ma
         let children = mathML::build_expression(body.clone(), options.clone(), false);
         node = MathNode::new(
             MathNodeType::Mo,
-            children.into_iter().map(|n| Box::new(n) as Box<dyn MathDomNode>).collect(),
+            children,
             vec![]
         );
     } else {
@@ -8,6 +8,8 @@ use crate::dom_tree::span::Span;
 use crate::dom_tree::symbol_node::SymbolNode;
 use crate::mathML_tree::math_node::MathNode;
 use crate::mathML_tree::public::{MathDomNode, MathNodeType};
+use crate::mathML_tree::space_node::SpaceNode;
+use crate::mathML_tree::text_node::TextNode;
 use crate::parse_node::types::ParseNodeToAny;
 use crate::utils::is_character_box;
 use crate::Options::Options;
@@ -115,77 +117,88 @@ pub(crate) fn html_builder(_group: Box<dyn AnyParseNode>, options: Options) -> B
 }
 
 fn mathml_builder(_group: Box<dyn AnyParseNode>, options: Options) -> Box<dyn MathDomNode> {
-    panic!("undefined")
+    let group = _group
+        .as_any()
+        .downcast_ref::<parse_node::types::operatorname>()
+        .unwrap();
     // The steps taken here are similar to the html version.
-    // let expression = mathML::build_expression(
-    //     group.body, options.withFont("mathrm".to_string()));
-    //
-    // // Is expression a string or has it something like a fraction?
-    // let isAllString = true;  // default
-    // for (let i = 0; i < expression.length; i+ +) {
-    //     let node = expression[i];
-    //     if (node
-    //     instanceof
-    //     mathMLTree.SpaceNode) {
-    //         // Do nothing
-    //     } else if (node
-    //     instanceof
-    //     mathMLTree.MathNode) {
-    //         switch(node. type ) {
-    //             case
-    //             "mi":
-    //                 case
-    //             "mn":
-    //                 case
-    //             "ms":
-    //                 case
-    //             "mspace":
-    //                 case
-    //             "mtext":
-    //             break;  // Do nothing yet.
-    //             case
-    //             "mo": {
-    //                 let child = node.children[0];
-    //                 if (node.children.length == 1 &&
-    //                     child
-    //                 instanceof
-    //                 mathMLTree.TextNode) {
-    //                     child.text =
-    //                         child.text.replace(/ \u2212 /, "-".to_string())
-    //                             .replace(/ \u2217 /, "*".to_string());
-    //                 } else {
-    //                     isAllString = false;
-    //                 }
-    //                 break;
-    //             }
-    //             default:
-    //                 isAllString = false;
-    //         }
-    //     } else {
-    //         isAllString = false;
-    //     }
-    // }
-    //
-    // if (isAllString) {
-    //     // Write a single TextNode instead of multiple nested tags.
-    //     let word = expression.map(node => node.toText()).join("".to_string());
-    //     expression = [new
-    //     mathMLTree.TextNode(word)];
-    // }
-    //
-    // let identifier = MathNode::new("mi".to_string(), expression);
-    // identifier.set_attribute("mathvariant".to_string(), "normal".to_string());
-    //
-    // // \u2061 is the same as &ApplyFunction;
-    // // ref: https://www.w3schools.com/charsets/ref_html_entities_a.asp
-    // let operator = MathNode::new("mo".to_string(),
-    //                              [mml.makeText("\u2061".to_string(), "text".to_string())]);
-    //
-    // if (group.parentIsSupSub) {
-    //     return MathNode::new("mrow".to_string(), [identifier, operator]);
-    // } else {
-    //     return mathMLTree.newDocumentFragment([identifier, operator]);
-    // }
+    let mut expression = mathML::build_expression(
+        group.body.clone(),
+        options.with_font("mathrm".to_string()),
+        false,
+    );
+
+    // Is expression a string or has it something like a fraction?
+    let mut is_all_string = true; // default
+    for node in expression.iter_mut() {
+        if node.as_any().is::<SpaceNode>() {
+            // Do nothing
+        } else if let Some(node) = node.as_mut_any().downcast_mut::<MathNode>() {
+            match node.get_node_type() {
+                MathNodeType::Mi
+                | MathNodeType::Mn
+                | MathNodeType::Ms
+                | MathNodeType::Mspace
+                | MathNodeType::Mtext => {} // Do nothing yet.
+                MathNodeType::Mo => {
+                    let is_single = node.get_children().len() == 1;
+                    match node.get_mut_children()[0]
+                        .as_mut_any()
+                        .downcast_mut::<TextNode>()
+                    {
+                        Some(child) if is_single => {
+                            let text = child
+                                .get_text()
+                                .replacen('\u{2212}', "-", 1)
+                                .replacen('\u{2217}', "*", 1);
+                            child.set_text(text);
+                        }
+                        _ => {
+                            is_all_string = false;
+                        }
+                    }
+                }
+                _ => {
+                    is_all_string = false;
+                }
+            }
+        } else {
+            is_all_string = false;
+        }
+    }
+
+    if is_all_string {
+        // Write a single TextNode instead of multiple nested tags.
+        let word = expression
+            .iter()
+            .map(|node| node.to_text())
+            .collect::<Vec<String>>()
+            .join("");
+        expression = vec![Box::new(TextNode::new(word))];
+    }
+
+    let mut identifier = MathNode::new(MathNodeType::Mi, expression, vec![]);
+    identifier.set_attribute("mathvariant".to_string(), "normal".to_string());
+
+    // \u2061 is the same as &ApplyFunction;
+    // ref: https://www.w3schools.com/charsets/ref_html_entities_a.asp
+    let operator = MathNode::new(
+        MathNodeType::Mo,
+        vec![Box::new(mathML::make_text(
+            "\u{2061}".to_string(),
+            crate::types::Mode::text,
+            None,
+        ))],
+        vec![],
+    );
+
+    // MathML has no document fragment, so the pair is always kept in an
+    // <mrow>, as `op` does for text operators.
+    return Box::new(MathNode::new(
+        MathNodeType::Mrow,
+        vec![Box::new(identifier), Box::new(operator)],
+        vec![],
+    )) as Box<dyn MathDomNode>;
 }
 
 // \operatorname
@@ -8,6 +8,7 @@ use crate::dom_tree::css_style::CssStyle;
 use crate::dom_tree::span::Span;
 use crate::mathML_tree::math_node::MathNode;
 use crate::mathML_tree::public::{MathDomNode, MathNodeType};
+use crate::mathML_tree::text_node::TextNode;
 use crate::parse_node::types::ParseNodeToAny;
 use crate::utils::is_character_box;
 use crate::Options::Options;
@@ -89,17 +90,28 @@ pub fn html_builder(_group: Box<dyn AnyParseNode>, options: Options) -> Box<dyn
 }
 
 pub fn mathml_builder(_group: Box<dyn AnyParseNode>, options: Options) -> Box<dyn MathDomNode> {
-    panic!("undefined mathml_builder");
-    // let operator = MathNode::new(
-    // "mo".to_string(), [new mathMLTree.TextNode("\u203e".to_string())]);
-    // operator.set_attribute("stretchy".to_string(), "true".to_string());
-    //
-    // let node = MathNode::new(
-    // "mover".to_string(),
-    // [mathML::build_group(group.body, options), operator]);
-    // node.set_attribute("accent".to_string(), "true".to_string());
-    //
-    // return node;
+    let group = _group
+        .as_any()
+        .downcast_ref::<parse_node::types::overline>()
+        .unwrap();
+    let mut operator = MathNode::new(
+        MathNodeType::Mo,
+        vec![Box::new(TextNode::new("\u{203e}".to_string())) as Box<dyn MathDomNode>],
+        vec![],
+    );
+    operator.set_attribute("stretchy".to_string(), "true".to_string());
+
+    let mut node = MathNode::new(
+        MathNodeType::Mover,
+        vec![
+            mathML::build_group(Some(group.body.clone()), options),
+            Box::new(operator) as Box<dyn MathDomNode>,
+        ],
+        vec![],
+    );
+    node.set_attribute("accent".to_string(), "true".to_string());
+
+    return Box::new(node) as Box<dyn MathDomNode>;
 }
 
 lazy_static! {
@@ -33,9 +33,12 @@ fn phantom_html_builder(_group: Box<dyn AnyParseNode>, options: Options) -> Box<
 }
 
 fn phantom_mathml_builder(_group: Box<dyn AnyParseNode>, options: Options) -> Box<dyn MathDomNode> {
-    panic!("undefined")
-    // let inner = mml.buildExpression(group.body, options);
-    // return MathNode::new("mphantom".to_string(), inner);
+    let group = _group
+        .as_any()
+        .downcast_ref::<parse_node::types::phantom>()
+        .unwrap();
+    let inner = mathML::build_expression(group.body.clone(), options, false);
+    return Box::new(MathNode::new(MathNodeType::Mphantom, inner, vec![])) as Box<dyn MathDomNode>;
 }
 
 fn phantom_handler_fn(
@@ -136,13 +139,16 @@ fn hphantom_mathml_builder(
     _group: Box<dyn AnyParseNode>,
     options: Options,
 ) -> Box<dyn MathDomNode> {
-    panic!("undefined");
-    // let inner = mml.buildExpression(ordargument(group.body), options);
-    // let phantom = MathNode::new( MathNodeType::Mphantom, inner);
-    // let node = MathNode::new("mpadded".to_string(), [phantom]);
-    // node.set_attribute("height".to_string(), "0px".to_string());
-    // node.set_attribute("depth".to_string(), "0px".to_string());
-    // return node;
+    let group = _group
+        .as_any()
+        .downcast_ref::<parse_node::types::hphantom>()
+        .unwrap();
+    let inner = mathML::build_expression(ord_argument(&group.body), options, false);
+    let phantom = MathNode::new(MathNodeType::Mphantom, inner, vec![]);
+    let mut node = MathNode::new(MathNodeType::Mpadded, vec![Box::new(phantom)], vec![]);
+    node.set_attribute("height".to_string(), "0px".to_string());
+    node.set_attribute("depth".to_string(), "0px".to_string());
+    return Box::new(node) as Box<dyn MathDomNode>;
 }
 
 lazy_static! {
@@ -204,12 +210,15 @@ fn vphantom_mathml_builder(
     _group: Box<dyn AnyParseNode>,
     options: Options,
 ) -> Box<dyn MathDomNode> {
-    panic!("undefined");
-    // let inner = mml.buildExpression(ordargument(group.body), options);
-    // let phantom = MathNode::new("mphantom".to_string(), inner);
-    // let node = MathNode::new("mpadded".to_string(), [phantom]);
-    // node.set_attribute("width".to_string(), "0px".to_string());
-    // return node;
+    let group = _group
+        .as_any()
+        .downcast_ref::<parse_node::types::vphantom>()
+        .unwrap();
+    let inner = mathML::build_expression(ord_argument(&group.body), options, false);
+    let phantom = MathNode::new(MathNodeType::Mphantom, inner, vec![]);
+    let mut node = MathNode::new(MathNodeType::Mpadded, vec![Box::new(phantom)], vec![]);
+    node.set_attribute("width".to_string(), "0px".to_string());
+    return Box::new(node) as Box<dyn MathDomNode>;
 }
 
 lazy_static! {
@@ -48,12 +48,18 @@ fn html_builder(_group: Box<dyn AnyParseNode>, options: Options) -> Box<dyn Html
     return Box::new(node) as Box<dyn HtmlDomNode>;
 }
 fn mathml_builder(_group: Box<dyn AnyParseNode>, options: Options) -> Box<dyn MathDomNode> {
-    panic!("undeinfed")
-    // let inner = mml.buildExpression(group.body, style);
-    // // Wrap with an <mstyle> element.
-    // let node = MathNode::new("mstyle".to_string(), inner);
-    // node.set_attribute("style".to_string(), "text-shadow: 0.02em 0.01em 0.04px".to_string());
-    // return node;
+    let group = _group
+        .as_any()
+        .downcast_ref::<parse_node::types::pmb>()
+        .unwrap();
+    let inner = mathML::build_expression(group.body.clone(), options, false);
+    // Wrap with an <mstyle> element.
+    let mut node = MathNode::new(MathNodeType::Mstyle, inner, vec![]);
+    node.set_attribute(
+        "style".to_string(),
+        "text-shadow: 0.02em 0.01em 0.04px".to_string(),
+    );
+    return Box::new(node) as Box<dyn MathDomNode>;
 }
 
 lazy_static! {
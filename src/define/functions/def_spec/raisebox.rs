@@ -53,12 +53,18 @@ fn html_builder(_group: Box<dyn AnyParseNode>, options: Options) -> Box<dyn Html
 }
 
 fn mathml_builder(_group: Box<dyn AnyParseNode>, options: Options) -> Box<dyn MathDomNode> {
-    panic!("undefined")
-    // let node = MathNode::new(
-    // "mpadded".to_string(), [mathML::build_group(group.body, options)]);
-    // let dy = group.dy.number + group.dy.unit;
-    // node.set_attribute("voffset".to_string(), dy);
-    // return node;
+    let group = _group
+        .as_any()
+        .downcast_ref::<parse_node::types::raisebox>()
+        .unwrap();
+    let mut node = MathNode::new(
+        MathNodeType::Mpadded,
+        vec![mathML::build_group(Some(group.body.clone()), options)],
+        vec![],
+    );
+    let dy = format!("{}{}", group.dy.number, group.dy.unit);
+    node.set_attribute("voffset".to_string(), dy);
+    return Box::new(node) as Box<dyn MathDomNode>;
 }
 
 // Box manipulation
@@ -65,27 +65,33 @@ fn html_builder(_group: Box<dyn AnyParseNode>, options: Options) -> Box<dyn Html
 }
 
 fn mathml_builder(_group: Box<dyn AnyParseNode>, options: Options) -> Box<dyn MathDomNode> {
-    panic!("undefined")
-    // let width = crate::units::calculate_size(group.width, options);
-    // let height = crate::units::calculate_size(group.get_height(), options);
-    // let shift = (group.shift) ? crate::units::calculate_size(group.shift, options) : 0;
-    // let color = options.color && options.getColor() || "black";
-    //
-    // let rule = MathNode::new("mspace".to_string());
-    // rule.set_attribute("mathbackground".to_string(), color);
-    // rule.set_attribute("width".to_string(), crate::units::make_em(width));
-    // rule.set_attribute("height".to_string(), crate::units::make_em(height));
-    //
-    // let wrapper = MathNode::new("mpadded".to_string(), [rule]);
-    // if (shift >= 0) {
-    //     wrapper.set_attribute("height".to_string(), crate::units::make_em(shift));
-    // } else {
-    //     wrapper.set_attribute("height".to_string(), crate::units::make_em(shift));
-    //     wrapper.set_attribute("depth".to_string(), crate::units::make_em(-shift));
-    // }
-    // wrapper.set_attribute("voffset".to_string(), crate::units::make_em(shift));
-    //
-    // return wrapper;
+    let group = _group
+        .as_any()
+        .downcast_ref::<parse_node::types::rule>()
+        .unwrap();
+    let width = crate::units::calculate_size(&group.width, &options);
+    let height = crate::units::calculate_size(&group.height, &options);
+    let shift = match &group.shift {
+        Some(shift) => crate::units::calculate_size(shift, &options),
+        None => 0.0,
+    };
+    let color = options.get_color().unwrap_or("black".to_string());
+
+    let mut rule = MathNode::new(MathNodeType::Mspace, vec![], vec![]);
+    rule.set_attribute("mathbackground".to_string(), color);
+    rule.set_attribute("width".to_string(), crate::units::make_em(width));
+    rule.set_attribute("height".to_string(), crate::units::make_em(height));
+
+    let mut wrapper = MathNode::new(MathNodeType::Mpadded, vec![Box::new(rule)], vec![]);
+    if shift >= 0.0 {
+        wrapper.set_attribute("height".to_string(), crate::units::make_em(shift));
+    } else {
+        wrapper.set_attribute("height".to_string(), crate::units::make_em(shift));
+        wrapper.set_attribute("depth".to_string(), crate::units::make_em(-shift));
+    }
+    wrapper.set_attribute("voffset".to_string(), crate::units::make_em(shift));
+
+    return Box::new(wrapper) as Box<dyn MathDomNode>;
 }
 
 
@@ -99,20 +99,26 @@ fn handler_fn(
 }
 
 fn mathml_builder(_group: Box<dyn AnyParseNode>, options: Options) -> Box<dyn MathDomNode> {
-    panic!("undefined")
-    // let newOptions = options.havingSize(group.size);
-    // let inner = mml.buildExpression(group.body, newOptions);
-    //
-    // let mut node = MathNode::new("mstyle".to_string(), inner);
-    //
-    // // TODO(emily): This doesn't produce the correct size for nested size
-    // // changes, because we don't keep state of what style we're currently
-    // // in, so we can't reset the size to normal before changing it.  Now
-    // // that we're passing an options parameter we should be able to fix
-    // // this.
-    // node.set_attribute("mathsize".to_string(), crate::units::make_em(newOptions.sizeMultiplier));
-    //
-    // return node;
+    let group = _group
+        .as_any()
+        .downcast_ref::<parse_node::types::sizing>()
+        .unwrap();
+    let new_options = options.having_size(group.size as f64);
+    let inner = mathML::build_expression(group.body.clone(), new_options.clone(), false);
+
+    let mut node = MathNode::new(MathNodeType::Mstyle, inner, vec![]);
+
+    // TODO(emily): This doesn't produce the correct size for nested size
+    // changes, because we don't keep state of what style we're currently
+    // in, so we can't reset the size to normal before changing it.  Now
+    // that we're passing an options parameter we should be able to fix
+    // this.
+    node.set_attribute(
+        "mathsize".to_string(),
+        crate::units::make_em(new_options.sizeMultiplier),
+    );
+
+    return Box::new(node) as Box<dyn MathDomNode>;
 }
 
 lazy_static! {
@@ -140,19 +140,25 @@ fn html_builder(_group: Box<dyn AnyParseNode>, options: Options) -> Box<dyn Html
 }
 
 fn mathml_builder(_group: Box<dyn AnyParseNode>, options: Options) -> Box<dyn MathDomNode> {
-    panic!("undefined")
-    // let node = MathNode::new(
-    // "mpadded".to_string(), [mathML::build_group(group.body, options)]);
-    //
-    // if (group.smashHeight) {
-    // node.set_attribute("height".to_string(), "0px".to_string());
-    // }
-    //
-    // if (group.smashDepth) {
-    // node.set_attribute("depth".to_string(), "0px".to_string());
-    // }
-    //
-    // return node;
+    let group = _group
+        .as_any()
+        .downcast_ref::<parse_node::types::smash>()
+        .unwrap();
+    let mut node = MathNode::new(
+        MathNodeType::Mpadded,
+        vec![mathML::build_group(Some(group.body.clone()), options)],
+        vec![],
+    );
+
+    if group.smash_height {
+        node.set_attribute("height".to_string(), "0px".to_string());
+    }
+
+    if group.smash_depth {
+        node.set_attribute("depth".to_string(), "0px".to_string());
+    }
+
+    return Box::new(node) as Box<dyn MathDomNode>;
 }
 
 // smash, with optional [tb], as in AMS
@@ -61,7 +61,7 @@ fn html_builder(_group: Box<dyn AnyParseNode>, options: Options) -> Box<dyn Html
     let theta = metrics.defaultRuleThickness;
 
     let mut phi = theta;
-    let _text = crate::Style::TEXT.read().unwrap();
+    let _text = &*crate::Style::TEXT;
     if options.get_style().id < _text.id {
         phi = options.get_font_metrics().xHeight;
     }
@@ -121,7 +121,7 @@ fn html_builder(_group: Box<dyn AnyParseNode>, options: Options) -> Box<dyn Html
         // Handle the optional root index
 
         // The index is always in scriptscript style
-        let _scriptscript = crate::Style::SCRIPTSCRIPT.read().unwrap();
+        let _scriptscript = &*crate::Style::SCRIPTSCRIPT;
         let new_options = options.having_style(&_scriptscript);
         let rootm = HTML::build_group(Some(group_index.clone()), new_options, Some(options.clone()));
 
@@ -70,10 +70,7 @@ fn mathml_builder(_group: Box<dyn AnyParseNode>, options: Options) -> Box<dyn Ma
 
     let mut node = MathNode::new(
         MathNodeType::Mstyle,
-        inner
-            .into_iter()
-            .map(|x| Box::new(x) as Box<dyn MathDomNode>)
-            .collect(),
+        inner,
         vec![],
     );
 
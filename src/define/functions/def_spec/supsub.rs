@@ -2,7 +2,9 @@ use crate::build::common::{make_span, make_vlist, PositionType, VListChild, VLis
 use crate::build::HTML::{DomType, Side};
 use crate::define::functions::{FunctionDefSpec, FunctionPropSpec};
 use crate::dom_tree::symbol_node::SymbolNode;
-use crate::mathML_tree::public::MathDomNode;
+use crate::build::mathML;
+use crate::mathML_tree::math_node::MathNode;
+use crate::mathML_tree::public::{MathDomNode, MathNodeType};
 use crate::utils::is_character_box;
 use crate::Options::Options;
 use crate::{build, make_em, parse_node, AnyParseNode, HtmlDomNode};
@@ -21,7 +23,7 @@ fn html_builder_delegate(
     group: &parse_node::types::supsub,
     options: &Options,
 ) -> Option<fn(_group: Box<dyn AnyParseNode>, options: Options) -> Box<dyn HtmlDomNode>> {
-    let style = crate::Style::DISPLAY.read().unwrap();
+    let style = &*crate::Style::DISPLAY;
     if let Some(base) = &group.base {
         if let Some(op) = base.as_any().downcast_ref::<parse_node::types::op>() {
             // Operators handle supsubs differently when they have limits
@@ -140,7 +142,7 @@ fn supsub_html_builder(_group: Box<dyn AnyParseNode>, options: Options) -> Box<d
         }
     }
 
-    let display_style = crate::Style::DISPLAY.read().unwrap();
+    let display_style = &*crate::Style::DISPLAY;
     // Rule 18c
     let min_sup_shift;
     if options.get_style() == display_style.clone() {
@@ -296,77 +298,93 @@ fn supsub_mathml_builder(_group: Box<dyn AnyParseNode>, options: Options) -> Box
         .as_any()
         .downcast_ref::<parse_node::types::supsub>()
         .unwrap();
-    panic!("mathml_Builder");
     // Is the inner group a relevant horizonal brace?
-    // let isBrace = false;
-    // let isOver;
-    // let isSup;
-    //
-    // if (group.base && group.base.type === "horizBrace") {
-    //     isSup = !!group.sup;
-    //     if (isSup === group.base.isOver) {
-    //         isBrace = true;
-    //         isOver = group.base.isOver;
-    //     }
-    // }
-    //
-    // if (group.base &&
-    //     (group.base.type === "op" || group.base.type === "operatorname")) {
-    // group.base.parent_is_sup_sub = true;
-    // }
-    //
-    // let children = [mml.buildGroup(group.base, options)];
-    //
-    // if (group.sub) {
-    //     children.push(mml.buildGroup(group.sub, options));
-    // }
-    //
-    // if (group.sup) {
-    //     children.push(mml.buildGroup(group.sup, options));
-    // }
-    //
-    // let nodeType: MathNodeType;
-    // if (isBrace) {
-    //     nodeType = (isOver ? "mover" : "munder");
-    // } else if (!group.sub) {
-    //     let base = group.base;
-    //     if (base && base.type === "op" && base.limits &&
-    //         (options.style === Style.DISPLAY || base.always_handle_sup_sub)) {
-    //         nodeType = "mover";
-    //     } else if (base && base.type === "operatorname" &&
-    //         base.always_handle_sup_sub &&
-    //         (base.limits || options.style === Style.DISPLAY)) {
-    //         nodeType = "mover";
-    //     } else {
-    //         nodeType = "msup";
-    //     }
-    // } else if (!group.sup) {
-    //     let base = group.base;
-    //     if (base && base.type === "op" && base.limits &&
-    //         (options.style === Style.DISPLAY || base.always_handle_sup_sub)) {
-    //         nodeType = "munder";
-    //     } else if (base && base.type === "operatorname" &&
-    //         base.always_handle_sup_sub &&
-    //         (base.limits || options.style === Style.DISPLAY)) {
-    //         nodeType = "munder";
-    //     } else {
-    //         nodeType = "msub";
-    //     }
-    // } else {
-    //     let base = group.base;
-    //     if (base && base.type === "op" && base.limits &&
-    //         options.style === Style.DISPLAY) {
-    //         nodeType = "munderover";
-    //     } else if (base && base.type === "operatorname" &&
-    //         base.always_handle_sup_sub &&
-    //         (options.style === Style.DISPLAY || base.limits)) {
-    //         nodeType = "munderover";
-    //     } else {
-    //         nodeType = "msubsup";
-    //     }
-    // }
-    //
-    // return new mathMLTree.MathNode(nodeType, children);
+    let mut is_brace = false;
+    let mut is_over = false;
+
+    if let Some(hb) = group
+        .base
+        .as_ref()
+        .and_then(|b| b.as_any().downcast_ref::<parse_node::types::horizBrace>())
+    {
+        let is_sup = group.sup.is_some();
+        if is_sup == hb.isOver {
+            is_brace = true;
+            is_over = hb.isOver;
+        }
+    }
+
+    let mut base = group.base.clone();
+    if let Some(b) = base.as_mut() {
+        if let Some(op) = b.as_mut_any().downcast_mut::<parse_node::types::op>() {
+            op.parentIsSupSub = true;
+        } else if let Some(op_name) = b
+            .as_mut_any()
+            .downcast_mut::<parse_node::types::operatorname>()
+        {
+            op_name.parent_is_sup_sub = true;
+        }
+    }
+
+    let mut children = vec![mathML::build_group(base, options.clone())];
+
+    if group.sub.is_some() {
+        children.push(mathML::build_group(group.sub.clone(), options.clone()));
+    }
+
+    if group.sup.is_some() {
+        children.push(mathML::build_group(group.sup.clone(), options.clone()));
+    }
+
+    let is_display = options.get_style() == crate::Style::DISPLAY.clone();
+    let base_op = group
+        .base
+        .as_ref()
+        .and_then(|b| b.as_any().downcast_ref::<parse_node::types::op>());
+    let base_op_name = group
+        .base
+        .as_ref()
+        .and_then(|b| b.as_any().downcast_ref::<parse_node::types::operatorname>());
+    let node_type;
+    if is_brace {
+        node_type = if is_over {
+            MathNodeType::Mover
+        } else {
+            MathNodeType::Munder
+        };
+    } else if group.sub.is_none() {
+        if base_op.map_or(false, |op| op.limits && (is_display || op.alwaysHandleSupSub)) {
+            node_type = MathNodeType::Mover;
+        } else if base_op_name
+            .map_or(false, |op| op.always_handle_sup_sub && (op.limits || is_display))
+        {
+            node_type = MathNodeType::Mover;
+        } else {
+            node_type = MathNodeType::Msup;
+        }
+    } else if group.sup.is_none() {
+        if base_op.map_or(false, |op| op.limits && (is_display || op.alwaysHandleSupSub)) {
+            node_type = MathNodeType::Munder;
+        } else if base_op_name
+            .map_or(false, |op| op.always_handle_sup_sub && (op.limits || is_display))
+        {
+            node_type = MathNodeType::Munder;
+        } else {
+            node_type = MathNodeType::Msub;
+        }
+    } else {
+        if base_op.map_or(false, |op| op.limits && is_display) {
+            node_type = MathNodeType::Munderover;
+        } else if base_op_name
+            .map_or(false, |op| op.always_handle_sup_sub && (is_display || op.limits))
+        {
+            node_type = MathNodeType::Munderover;
+        } else {
+            node_type = MathNodeType::Msubsup;
+        }
+    }
+
+    return Box::new(MathNode::new(node_type, children, vec![])) as Box<dyn MathDomNode>;
 }
 // Super scripts and subscripts, whose precise placement can depend on other
 // functions that precede them.
@@ -381,7 +399,7 @@ lazy_static! {
             props,
             handler: |a, b, c| panic!("error"),
             html_builder: Some(supsub_html_builder),
-            mathml_builder: None,
+            mathml_builder: Some(supsub_mathml_builder),
         }
     });
 }
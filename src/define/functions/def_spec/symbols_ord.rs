@@ -42,8 +42,8 @@ pub fn mathord_mathml_builder(
     );
 
     let variant = get_variant(&_group, &options).unwrap_or(FontVariant::italic);
-    let default_variant = DEFAULT_VARIANT.lock().unwrap();
-    if &variant.as_str() != default_variant.get(node.get_node_type().as_str()).unwrap() {
+    let default_variant = &*DEFAULT_VARIANT;
+    if default_variant.get(node.get_node_type().as_str()) != Some(&variant.as_str()) {
         node.set_attribute("mathvariant".to_string(), variant.as_str().to_string());
     }
     return Box::new(node) as Box<dyn MathDomNode>;
@@ -73,16 +73,16 @@ fn textord_mathml_builder(_group: Box<dyn AnyParseNode>, options: Options) -> Bo
         vec![],
     );
 
-    let default_variant = DEFAULT_VARIANT.lock().unwrap();
-    if &variant.as_str() != default_variant.get(node.get_node_type().as_str()).unwrap() {
+    let default_variant = &*DEFAULT_VARIANT;
+    if default_variant.get(node.get_node_type().as_str()) != Some(&variant.as_str()) {
         node.set_attribute("mathvariant".to_string(), variant.as_str().to_string());
     }
 
     return Box::new(node) as Box<dyn MathDomNode>;
 }
 lazy_static! {
-    static ref DEFAULT_VARIANT: Mutex<HashMap<&'static str, &'static str>> =
-        Mutex::new({ HashMap::from([("mi", "italic"), ("mn", "normal"), ("mtext", "normal"),]) });
+    static ref DEFAULT_VARIANT: HashMap<&'static str, &'static str> =
+        HashMap::from([("mi", "italic"), ("mn", "normal"), ("mtext", "normal"),]);
     pub static ref MATHORD: Mutex<FunctionDefSpec> = Mutex::new({
         let mut props = FunctionPropSpec::new();
 
@@ -110,7 +110,7 @@ lazy_static! {
                     "textord".to_string(),
                 );
             }),
-            mathml_builder: Some(mathord_mathml_builder),
+            mathml_builder: Some(textord_mathml_builder),
         }
     });
 }
@@ -7,6 +7,7 @@ use crate::dom_tree::css_style::CssStyle;
 use crate::dom_tree::span::Span;
 use crate::mathML_tree::math_node::MathNode;
 use crate::mathML_tree::public::{MathDomNode, MathNodeType};
+use crate::mathML_tree::text_node::TextNode;
 use crate::parse_node::types::ParseNodeToAny;
 use crate::utils::is_character_box;
 use crate::Options::Options;
@@ -130,11 +131,14 @@ fn mathml_builder(_group: Box<dyn AnyParseNode>, options: Options) -> Box<dyn Ma
         .as_any()
         .downcast_ref::<parse_node::types::verb>()
         .unwrap();
-    panic!("undeifne");
-// let text = new mathMLTree.TextNode(makeVerb(group));
-// let node = MathNode::new("mtext".to_string(), [text]);
-// node.set_attribute("mathvariant".to_string(), "monospace".to_string());
-// return node;
+    let text = TextNode::new(make_verb(group));
+    let mut node = MathNode::new(
+        MathNodeType::Mtext,
+        vec![Box::new(text) as Box<dyn MathDomNode>],
+        vec![],
+    );
+    node.set_attribute("mathvariant".to_string(), "monospace".to_string());
+    return Box::new(node) as Box<dyn MathDomNode>;
 }
 
 lazy_static! {
@@ -1,4 +1,5 @@
 // mod sqrt;
 mod def_spec;
 pub mod public;
+pub(crate) use def_spec::is_delimiter;
 use public::{FunctionDefSpec, FunctionPropSpec};
@@ -187,7 +187,7 @@ lazy_static! {
      * `functions.js` just exports this same dictionary again and makes it public.
      * `Parser.js` requires this dictionary.
      */
-    pub static ref _functions: std::sync::RwLock<HashMap<String,FunctionSpec>> =  std::sync::RwLock::new({
+    pub static ref _functions: HashMap<String,FunctionSpec> = {
         let mut res = HashMap::new();
         for data in super::def_spec::FUNCS.lock().unwrap().iter(){
              for name in data.names.iter() {
@@ -195,13 +195,13 @@ lazy_static! {
             }
         }
         res
-    });
+    };
 
     ///
     /// All HTML builders. Should be only used in the `define*` and the `build*ML`
     /// functions.
     ///
-    pub static ref _HTML_GROUP_BUILDERS: std::sync::RwLock<HashMap<String, HtmlBuilder>> =  std::sync::RwLock::new({
+    pub static ref _HTML_GROUP_BUILDERS: HashMap<String, HtmlBuilder> = {
         let mut res = HashMap::new();
         for data in super::def_spec::FUNCS.lock().unwrap().iter(){
             if let Some(h) = data.html_builder{
@@ -215,12 +215,12 @@ lazy_static! {
             }
         }
         res
-    });
+    };
     /**
      * All MathML builders. Should be only used in the `define*` and the `build*ML`
      * functions.
      */
-    pub static ref _MATHML_GROUP_BUILDERS: std::sync::RwLock<HashMap<String,MathMLBuilder>> =  std::sync::RwLock::new({
+    pub static ref _MATHML_GROUP_BUILDERS: HashMap<String,MathMLBuilder> = {
         let mut res = HashMap::new();
         for data in super::def_spec::FUNCS.lock().unwrap().iter(){
             if let Some(h) = data.mathml_builder{
@@ -233,7 +233,7 @@ lazy_static! {
             }
         }
         res
-    });
+    };
 }
 
 // pub fn get_function(name:&String)->Option<&(FunctionPropSpec, FunctionHandler)>{
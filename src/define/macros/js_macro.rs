@@ -0,0 +1,191 @@
+/**
+ * Macros given as JS values in the `macros` option: strings are expanded
+ * like `\def` bodies, and functions are called with a `MacroContext` each
+ * time the macro is expanded, as in KaTeX.js.
+ */
+use std::cell::Cell;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use js_sys::{Array, Function, JsString, Object, Reflect};
+use wasm_bindgen::prelude::*;
+
+use super::macro_expander::MacroExpander;
+use super::public::{MacroDefinition, MacroExpansion};
+use crate::settings::JsFunction;
+use crate::token::Token;
+use crate::Namespace::Mapping;
+
+pub(crate) fn macros_from_js(value: &JsValue) -> Mapping<MacroDefinition> {
+    let mut res = Mapping::new();
+    for entry in Object::entries(&Object::from(value.clone())).iter() {
+        let entry = Array::from(&entry);
+        let name = match entry.get(0).as_string() {
+            Some(name) => name,
+            None => continue,
+        };
+        let definition = entry.get(1);
+        if let Some(expansion) = definition.as_string() {
+            res.insert(name, MacroDefinition::Str(expansion));
+        } else if definition.is_function() {
+            let function = JsFunction::new(Function::from(definition));
+            let macro_name = name.clone();
+            res.insert(
+                name,
+                MacroDefinition::Callback(Arc::new(move |expander: &mut MacroExpander| {
+                    match function.get() {
+                        Some(function) => call_js_macro(&function, &macro_name, expander),
+                        None => {
+                            expander.report_parse_error(
+                                format!("Macro {} is a JS function of another thread", macro_name),
+                                None,
+                            );
+                            MacroDefinition::Str(String::new())
+                        }
+                    }
+                })),
+            );
+        }
+    }
+    res
+}
+
+fn call_js_macro(function: &Function, name: &str, expander: &mut MacroExpander) -> MacroDefinition {
+    let handle = Rc::new(Cell::new(
+        (expander as *mut MacroExpander).cast::<MacroExpander<'static>>(),
+    ));
+    let context = JsMacroContext {
+        expander: handle.clone(),
+    };
+    let result = function.call1(&JsValue::NULL, &JsValue::from(context));
+    // The context must not reach the expander once the macro has returned.
+    handle.set(std::ptr::null_mut());
+    match result.map(|value| definition_from_js(&value)) {
+        Ok(Some(definition)) => definition,
+        Ok(None) => {
+            expander.report_parse_error(
+                format!("Macro {} must return a string or {{tokens, numArgs}}", name),
+                None,
+            );
+            MacroDefinition::Str(String::new())
+        }
+        Err(error) => {
+            let message = Reflect::get(&error, &JsString::from("message"))
+                .ok()
+                .and_then(|m| m.as_string())
+                .or_else(|| error.as_string())
+                .unwrap_or_default();
+            expander.report_parse_error(format!("Macro {} threw: {}", name, message), None);
+            MacroDefinition::Str(String::new())
+        }
+    }
+}
+
+/** A string, or a `{tokens, numArgs}` object with tokens in reverse order. */
+fn definition_from_js(value: &JsValue) -> Option<MacroDefinition> {
+    if let Some(expansion) = value.as_string() {
+        return Some(MacroDefinition::Str(expansion));
+    }
+    let tokens = Reflect::get(value, &JsString::from("tokens")).ok()?;
+    if !Array::is_array(&tokens) {
+        return None;
+    }
+    let tokens = Array::from(&tokens)
+        .iter()
+        .map(|token| {
+            token
+                .as_string()
+                .or_else(|| {
+                    Reflect::get(&token, &JsString::from("text"))
+                        .ok()
+                        .and_then(|t| t.as_string())
+                })
+                .map(|text| Token::new(text, None))
+        })
+        .collect::<Option<Vec<Token>>>()?;
+    let num_args = Reflect::get(value, &JsString::from("numArgs"))
+        .ok()
+        .and_then(|n| n.as_f64())
+        .unwrap_or(0.0) as i32;
+    Some(MacroDefinition::MacroExpansion(MacroExpansion {
+        tokens,
+        num_args,
+        delimiters: None,
+        unexpandable: false,
+        default_arg: None,
+    }))
+}
+
+fn token_to_js(token: &Token) -> JsValue {
+    let obj = Object::new();
+    Reflect::set(&obj, &JsString::from("text"), &JsValue::from_str(&token.text)).unwrap();
+    obj.into()
+}
+
+/**
+ * The argument passed to function macros, a subset of KaTeX's
+ * `MacroContextInterface`. It is only valid while the macro runs.
+ */
+#[wasm_bindgen(js_name = MacroContext)]
+pub struct JsMacroContext {
+    expander: Rc<Cell<*mut MacroExpander<'static>>>,
+}
+
+impl JsMacroContext {
+    fn with<R>(&self, f: impl FnOnce(&mut MacroExpander) -> R) -> Result<R, JsValue> {
+        let expander = self.expander.get();
+        if expander.is_null() {
+            return Err(JsValue::from_str(
+                "MacroContext used after the macro has returned",
+            ));
+        }
+        // SAFETY: the pointer is only set while the expander is borrowed by
+        // `call_js_macro`, which clears it before returning.
+        Ok(f(unsafe { &mut *expander }))
+    }
+}
+
+#[wasm_bindgen(js_class = MacroContext)]
+impl JsMacroContext {
+    /** Returns the next token without expanding it. */
+    pub fn future(&self) -> Result<JsValue, JsValue> {
+        self.with(|e| token_to_js(&e.future()))
+    }
+
+    #[wasm_bindgen(js_name = popToken)]
+    pub fn pop_token(&self) -> Result<JsValue, JsValue> {
+        self.with(|e| token_to_js(&e.pop_token()))
+    }
+
+    #[wasm_bindgen(js_name = consumeSpaces)]
+    pub fn consume_spaces(&self) -> Result<(), JsValue> {
+        self.with(|e| e.consume_spaces())
+    }
+
+    /** The arguments as arrays of tokens, each in reverse order. */
+    #[wasm_bindgen(js_name = consumeArgs)]
+    pub fn consume_args(&self, num_args: usize) -> Result<Array, JsValue> {
+        let args = self
+            .with(|e| e.consume_args(num_args, None, None))?
+            .map_err(|error| error.to_js_error())?;
+        Ok(args
+            .iter()
+            .map(|arg| arg.iter().map(token_to_js).collect::<Array>())
+            .collect())
+    }
+
+    #[wasm_bindgen(js_name = expandMacroAsText)]
+    pub fn expand_macro_as_text(&self, name: String) -> Result<Option<String>, JsValue> {
+        self.with(|e| e.expand_macro_as_text(&name))
+    }
+
+    #[wasm_bindgen(js_name = isDefined)]
+    pub fn is_defined(&self, name: String) -> Result<bool, JsValue> {
+        self.with(|e| e.is_defined(&name))
+    }
+
+    #[wasm_bindgen(js_name = isExpandable)]
+    pub fn is_expandable(&self, name: String) -> Result<bool, JsValue> {
+        self.with(|e| e.is_expandable(&name))
+    }
+}
@@ -9,9 +9,10 @@ use regex::Regex;
 
 use crate::{
     parse_error::ParseError,
-    settings::Settings,
+    settings::{DiagnosticKind, ExpansionStep, Settings},
+    sourceLocation::SourceLocation,
     symbols::{get_symbol, public::Mode},
-    token::Token,
+    token::{tokens_to_source, Token},
     Lexer::Lexer,
     Namespace::Namespace,
 };
@@ -25,9 +26,15 @@ pub const IMPLICIT_COMMANDS: [&str; 4] = ["^", "_", "\\limits", "\\nolimits"];
 pub struct MacroExpander<'a> {
     settings: &'a Settings,
     expansion_count: i32,
+    /// When the `timeBudget` setting runs out, see `utils::now_ms`.
+    deadline: Option<f64>,
     lexer: Lexer,
     pub macros: Namespace<MacroDefinition>,
     stack: Vec<Token>,
+    /// The environments opened by `\begin`, innermost last.
+    environments: Vec<String>,
+    /// The `\newenvironment` environments whose end code is running.
+    ending: Vec<String>,
     pub mode: Mode,
 }
 
@@ -41,14 +48,18 @@ impl MacroExpander<'_> {
         MacroExpander {
             settings: settings,
             expansion_count: 0,
+            deadline: settings.get_time_budget().map(|budget| crate::utils::now_ms() + budget),
             lexer: Lexer::new(input, settings),
             // Make new global namespace
             macros: Namespace::<MacroDefinition>::new(
-                std::sync::Arc::new(crate::define::macros::macro_map::create_macro_map().into()),
+                settings.get_builtin_macros(),
                 settings.get_ref_macros(),
+                settings.keeps_definitions(),
             ),
             mode,
             stack: vec![], // contains tokens in REVERSE order
+            environments: vec![],
+            ending: vec![],
         }
     }
     pub fn set_lexer_catcode(&mut self, char: String, code: i32) {
@@ -74,6 +85,11 @@ impl MacroExpander<'_> {
         self.lexer.take_error()
     }
 
+    /** Whether the `timeBudget` of this parse has run out. */
+    pub(crate) fn past_deadline(&self) -> bool {
+        self.deadline.map_or(false, |deadline| crate::utils::now_ms() > deadline)
+    }
+
     pub fn report_parse_error(
         &mut self,
         msg: String,
@@ -276,14 +292,80 @@ impl MacroExpander<'_> {
         });
     }
 
+    /**
+     * Consume an optional argument in brackets, if the next non-space token
+     * is `[`. As in LaTeX, the argument ends at the first `]` outside of
+     * braces, so `\sqrt[3]{x}` has to be braced inside it.
+     */
+    pub fn consume_optional_arg(&mut self) -> Result<Option<Vec<Token>>, ParseError> {
+        self.consume_spaces();
+        if self.future().text != "[" {
+            return Ok(None);
+        }
+        self.pop_token();
+        let mut tokens: Vec<Token> = vec![];
+        let mut depth = 0;
+        loop {
+            let tok = self.pop_token();
+            match tok.text.as_str() {
+                "{" => depth += 1,
+                "}" if depth == 0 => {
+                    return Err(ParseError {
+                        msg: String::from("Extra }"),
+                        loc: tok.loc,
+                    });
+                }
+                "}" => depth -= 1,
+                "]" if depth == 0 => break,
+                "EOF" => {
+                    return Err(ParseError {
+                        msg: "Unexpected end of input in a macro argument, expected ']'".to_string(),
+                        loc: tok.loc,
+                    });
+                }
+                _ => {}
+            }
+            tokens.push(tok);
+        }
+        // As for other arguments, [{...}] loses its braces.
+        if tokens.len() >= 2
+            && tokens[0].text == "{"
+            && tokens[tokens.len() - 1].text == "}"
+            && Self::is_one_group(&tokens)
+        {
+            tokens.pop();
+            tokens.remove(0);
+        }
+        tokens.reverse(); // to fit in with stack order
+        Ok(Some(tokens))
+    }
+
+    /** Whether `tokens` (in order) are a single `{...}` group. */
+    fn is_one_group(tokens: &[Token]) -> bool {
+        let mut depth = 0;
+        for (i, tok) in tokens.iter().enumerate() {
+            match tok.text.as_str() {
+                "{" => depth += 1,
+                "}" => depth -= 1,
+                _ => {}
+            }
+            if depth == 0 && i + 1 < tokens.len() {
+                return false;
+            }
+        }
+        true
+    }
+
     /**
      * Consume the specified f64 of (delimited) arguments from the token
-     * stream and return the resulting array of arguments.
+     * stream and return the resulting array of arguments. With a
+     * `default_arg`, the first argument is optional, in brackets.
      */
     pub fn consume_args(
         &mut self,
         num_args: usize,
         _delimiters: Option<Vec<Vec<String>>>,
+        default_arg: Option<Vec<Token>>,
     ) -> Result<Vec<Vec<Token>>, ParseError> {
         if let Some(delimiters) = &_delimiters {
             if delimiters.len() != num_args + 1 {
@@ -304,7 +386,10 @@ impl MacroExpander<'_> {
         }
 
         let mut res: Vec<Vec<Token>> = vec![];
-        for i in 0..num_args {
+        if let (Some(default_arg), true) = (default_arg, num_args > 0) {
+            res.push(self.consume_optional_arg()?.unwrap_or(default_arg));
+        }
+        for i in res.len()..num_args {
             match self.consume_arg(if let Some(d) = &_delimiters {
                 Some(&d[i + 1])
             } else {
@@ -366,7 +451,7 @@ impl MacroExpander<'_> {
             return Ok(ExpandOneRes::token(topToken));
         }
         self.expansion_count += 1;
-        if self.expansion_count > self.settings.get_max_expand().unwrap_or(0) {
+        if self.settings.get_max_expand().map_or(false, |max| self.expansion_count > max) {
             return Err(ParseError {
                 msg: "Too many expansions: infinite loop or \
                 need to increase maxExpand setting"
@@ -374,10 +459,18 @@ impl MacroExpander<'_> {
                 loc: None,
             });
         }
+        if self.past_deadline() {
+            return Err(ParseError {
+                msg: "Time budget exceeded: need to increase timeBudget setting".to_string(),
+                loc: topToken.loc.clone(),
+            });
+        }
         let mut tokens = expansion.tokens;
-        let args = self
-            .consume_args(expansion.num_args as usize, expansion.delimiters)
-            .unwrap();
+        let args = self.consume_args(
+            expansion.num_args as usize,
+            expansion.delimiters,
+            expansion.default_arg,
+        )?;
         if expansion.num_args > 0 {
             // paste arguments in place of the placeholders
             // tokens = tokens.slice(); // make a shallow copy
@@ -416,6 +509,17 @@ impl MacroExpander<'_> {
                 i -= 1;
             }
         }
+        if self.settings.is_tracing_macros() {
+            self.settings.record_expansion(ExpansionStep {
+                name: name.clone(),
+                args: args
+                    .iter()
+                    .map(|arg| tokens_to_source(arg.iter().rev().map(|tok| tok.text.as_str())))
+                    .collect(),
+                tokens: tokens.iter().rev().map(|tok| tok.text.clone()).collect(),
+                loc: topToken.loc.clone(),
+            });
+        }
         // Concatenate expansion onto top of stack.
         self.push_tokens(tokens.clone());
         return Ok(ExpandOneRes::tokens(tokens));
@@ -437,7 +541,14 @@ impl MacroExpander<'_> {
      */
     pub fn expand_next_token(&mut self) -> Token {
         loop {
-            let mut _expanded = self.expand_once(false).expect("");
+            let mut _expanded = match self.expand_once(false) {
+                Ok(expanded) => expanded,
+                Err(error) => {
+                    // Stop the parser at a fake end of input.
+                    self.report_parse_error(error.msg, error.loc);
+                    return Token::new("EOF".to_string(), None);
+                }
+            };
             // expandOnce returns Token if and only if it's fully expanded.
             if let ExpandOneRes::token(mut expanded) = _expanded {
                 // the token after \noexpand is interpreted as if its meaning
@@ -476,7 +587,15 @@ impl MacroExpander<'_> {
         let oldStackLength = self.stack.len();
         self.push_tokens(tokens);
         while (self.stack.len() > oldStackLength) {
-            let _expanded = self.expand_once(true).unwrap(); // expand only expandable tokens
+            // expand only expandable tokens
+            let _expanded = match self.expand_once(true) {
+                Ok(expanded) => expanded,
+                Err(error) => {
+                    self.report_parse_error(error.msg, error.loc);
+                    self.stack.truncate(oldStackLength);
+                    break;
+                }
+            };
             // expandOnce returns Token if and only if it's fully expanded.
             if let ExpandOneRes::token(mut expanded) = _expanded {
                 if expanded.treatAsRelax {
@@ -512,6 +631,9 @@ impl MacroExpander<'_> {
      * argument count.  Or returns `null` if no such macro.
      */
     fn _getExpansion(&mut self, name: &String) -> Option<MacroExpansion> {
+        if name == "\\begin" || name == "\\end" {
+            return self.expand_user_environment(name);
+        }
         let _definition = self.macros.get(name);
         if _definition.is_none() {
             // mainly checking for undefined here
@@ -529,6 +651,7 @@ impl MacroExpander<'_> {
         let definition = _definition.unwrap();
         let d = match definition {
             MacroDefinition::MacroContext(ref f) => f(self),
+            MacroDefinition::Callback(ref f) => f(self),
 
             MacroDefinition::Str(s) => MacroDefinition::Str(s.clone()),
             MacroDefinition::MacroExpansion(m) => MacroDefinition::MacroExpansion(m.clone()),
@@ -560,10 +683,12 @@ impl MacroExpander<'_> {
                     num_args: numArgs,
                     delimiters: None,
                     unexpandable: false, // used in \let
+                    default_arg: None,
                 });
             }
-            MacroDefinition::MacroContext(m) => {
-                panic!("impossible")
+            MacroDefinition::MacroContext(_) | MacroDefinition::Callback(_) => {
+                self.report_parse_error(format!("Macro {} expanded to another function", name), None);
+                None
             }
             MacroDefinition::MacroExpansion(exp) => {
                 return Some(exp);
@@ -571,6 +696,90 @@ impl MacroExpander<'_> {
         }
     }
 
+    /**
+     * Expands `\begin{name}` to `\begingroup\name` and `\end{name}` to
+     * `\endname\endgroup` when `name` was defined by `\newenvironment`,
+     * which also defines `\endname`. Other environments are left to the
+     * parser, with their name put back on the stack, but are tracked too: as
+     * in LaTeX, each `\end{name}` must close the innermost open environment,
+     * which for `\newenvironment` ones is checked after `\endname` ran.
+     */
+    fn expand_user_environment(&mut self, command: &str) -> Option<MacroExpansion> {
+        if self.future().text != "{" {
+            return None;
+        }
+        let arg = match self.consume_arg(None) {
+            Ok(arg) => arg,
+            Err(error) => {
+                self.report_parse_error(error.msg, error.loc);
+                return None;
+            }
+        };
+        let name: String = arg.tokens.iter().rev().map(|token| token.text.as_str()).collect();
+        let end = format!("\\end{}", name);
+        let is_user = self.macros.has(&end);
+        if command == "\\begin" {
+            self.environments.push(name.clone());
+        } else if is_user && self.ending.last() != Some(&name) {
+            // Run the end code, then come back to check what it closed.
+            self.ending.push(name);
+            let mut tokens = vec![arg.end];
+            tokens.extend(arg.tokens);
+            tokens.extend([arg.start, Token::new("\\end".to_string(), None), Token::new(end, None)]);
+            return Some(MacroExpansion {
+                tokens,
+                num_args: 0,
+                delimiters: None,
+                unexpandable: false,
+                default_arg: None,
+            });
+        } else if self.environments.last() == Some(&name) {
+            self.environments.pop();
+            if is_user {
+                self.ending.pop();
+            }
+        } else if is_user || self.environments.iter().any(|open| self.macros.has(&format!("\\end{}", open))) {
+            let msg = match self.environments.last() {
+                Some(open) => format!("Mismatch: \\begin{{{}}} matched by \\end{{{}}}", open, name),
+                None => format!("Mismatch: \\end{{{}}} without \\begin{{{}}}", name, name),
+            };
+            let loc = arg.start.range(&arg.end, String::new()).loc;
+            self.report_parse_error(msg, loc);
+            return None;
+        }
+        if !is_user {
+            self.push_token(arg.end);
+            self.push_tokens(arg.tokens);
+            self.push_token(arg.start);
+            return None;
+        }
+        let mut tokens = if command == "\\begin" {
+            vec![
+                Token::new("\\begingroup".to_string(), arg.start.loc),
+                Token::new(format!("\\{}", name), None),
+            ]
+        } else {
+            vec![Token::new("\\endgroup".to_string(), arg.end.loc)]
+        };
+        tokens.reverse();
+        Some(MacroExpansion {
+            tokens,
+            num_args: 0,
+            delimiters: None,
+            unexpandable: false,
+            default_arg: None,
+        })
+    }
+
+    /**
+     * Whether `name` is a built-in environment or one defined by
+     * `\newenvironment`.
+     */
+    pub fn is_environment(&self, name: &str) -> bool {
+        crate::define::environments::_environments.contains_key(name)
+            || self.macros.has(&format!("\\end{}", name))
+    }
+
     /**
      * Determine whether a command is currently "defined" (has some
      * functionality), meaning that it's a macro (in the current group),
@@ -579,17 +788,84 @@ impl MacroExpander<'_> {
      */
     pub fn is_defined(&self, name: &String) -> bool {
         return self.macros.has(name) ||
-            // functions.hasOwnProperty(name) ||  //!todo
+            crate::define::functions::public::_functions.contains_key(name) ||
             get_symbol(Mode::math, &name).is_some() ||
             get_symbol(Mode::text, &name).is_some() ||
             IMPLICIT_COMMANDS.contains(&name.as_str());
     }
 
+    /**
+     * What `\show` prints for `token`, as in TeX: `\foo=macro:#1->body` for
+     * macros, `\frac=\frac` for other commands and `the letter x` for
+     * characters.
+     */
+    pub fn meaning(&self, token: &Token) -> String {
+        let name = &token.text;
+        if !name.starts_with('\\') && !self.macros.has(name) {
+            let kind = if name.chars().all(|c| c.is_alphabetic()) {
+                "the letter"
+            } else {
+                "the character"
+            };
+            return format!("{} {}", kind, name);
+        }
+        let meaning = match self.macros.get(name) {
+            Some(MacroDefinition::Str(body)) => {
+                let stripped = body.replace("##", "");
+                let mut params = String::new();
+                let mut num_args = 0;
+                while stripped.contains(format!("#{}", num_args + 1).as_str()) {
+                    num_args += 1;
+                    params.push_str(&format!("#{}", num_args));
+                }
+                format!("macro:{}->{}", params, body)
+            }
+            Some(MacroDefinition::MacroExpansion(expansion)) => {
+                let source = |tokens: &[Token]| tokens_to_source(tokens.iter().rev().map(|tok| tok.text.as_str()));
+                if expansion.unexpandable {
+                    // \let to a token
+                    source(&expansion.tokens)
+                } else {
+                    let mut params = String::new();
+                    if let Some(delimiters) = &expansion.delimiters {
+                        params.push_str(&delimiters[0].concat());
+                    }
+                    for i in 1..=expansion.num_args {
+                        if i == 1 && expansion.default_arg.is_some() {
+                            params.push_str("[#1]");
+                        } else {
+                            params.push_str(&format!("#{}", i));
+                        }
+                        if let Some(delimiters) = &expansion.delimiters {
+                            params.push_str(&delimiters[i as usize].concat());
+                        }
+                    }
+                    format!("macro:{}->{}", params, source(&expansion.tokens))
+                }
+            }
+            Some(_) => name.clone(),
+            None => {
+                let _f = &*crate::define::functions::public::_functions;
+                if self.is_defined(name) || _f.get(name).is_some() {
+                    name.clone()
+                } else {
+                    "undefined".to_string()
+                }
+            }
+        };
+        format!("{}={}", name, meaning)
+    }
+
+    /** Reports what `\message`, `\errmessage` or `\show` would print. */
+    pub(crate) fn report_diagnostic(&self, kind: DiagnosticKind, text: String, loc: Option<SourceLocation>) {
+        self.settings.report_diagnostic(kind, text, loc);
+    }
+
     /**
      * Determine whether a command is expandable.
      */
     pub fn is_expandable(&self, name: &String) -> bool {
-        let _f = crate::define::functions::public::_functions.read().unwrap();
+        let _f = &*crate::define::functions::public::_functions;
         let _macro = self.macros.get(name);
         if let Some(m) = _macro {
             match m {
@@ -607,7 +883,7 @@ impl MacroExpander<'_> {
                         }
                     };
                 }
-                MacroDefinition::MacroContext(context) => {
+                MacroDefinition::MacroContext(_) | MacroDefinition::Callback(_) => {
                     return true;
                 }
             }
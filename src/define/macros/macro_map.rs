@@ -1,15 +1,21 @@
 use super::public::MacroDefinition;
 use crate::symbols::{get_symbol, public::Group};
-use crate::token::Token;
+use crate::settings::DiagnosticKind;
+use crate::token::{tokens_to_source, Token};
 use crate::types::Mode;
 use super::macro_expander::MacroExpander;
 
 fn new_me(tokens: Vec<Token>, num_args: i32) -> MacroDefinition {
+    new_me_with_default(tokens, num_args, None)
+}
+
+fn new_me_with_default(tokens: Vec<Token>, num_args: i32, default_arg: Option<Vec<Token>>) -> MacroDefinition {
     MacroDefinition::MacroExpansion(super::public::MacroExpansion {
         tokens,
         num_args,
         delimiters: None,
         unexpandable: false,
+        default_arg,
     })
 }
 
@@ -135,13 +141,21 @@ fn tag_literal_macro(context: &mut MacroExpander) -> MacroDefinition {
         return report_macro_error(context, "Multiple \\tag".to_string(), None);
     }
 
-    match context.consume_arg(None) {
-        Ok(arg) => {
+    // #1 is the tag as shown, #2 what \ref shows for it.
+    let args = context
+        .consume_arg(None)
+        .and_then(|tag| Ok((tag, context.consume_arg(None)?)));
+    match args {
+        Ok((tag, reference)) => {
             let mut expanded = tokens_from_texts(&["\\gdef", "\\df@tag", "{", "\\text", "{"]);
-            let mut body = arg.tokens;
+            let mut body = tag.tokens;
             body.reverse();
             expanded.extend(body);
-            expanded.extend(tokens_from_texts(&["}", "}"]));
+            expanded.extend(tokens_from_texts(&["}", "}", "\\gdef", "\\df@tagref", "{"]));
+            let mut body = reference.tokens;
+            body.reverse();
+            expanded.extend(body);
+            expanded.push(Token::new("}".to_string(), None));
             expanded.reverse();
             new_me(expanded, 0)
         }
@@ -328,21 +342,21 @@ fn char_macro(context: &mut MacroExpander) -> MacroDefinition {
 }
 
 fn firstoftwo_macro(context: &mut MacroExpander) -> MacroDefinition {
-    match context.consume_args(2, None) {
+    match context.consume_args(2, None, None) {
         Ok(args) => new_me(args[0].clone(), 0),
         Err(err) => report_macro_error(context, err.msg, err.loc),
     }
 }
 
 fn secondoftwo_macro(context: &mut MacroExpander) -> MacroDefinition {
-    match context.consume_args(2, None) {
+    match context.consume_args(2, None, None) {
         Ok(args) => new_me(args[1].clone(), 0),
         Err(err) => report_macro_error(context, err.msg, err.loc),
     }
 }
 
 fn ifnextchar_macro(context: &mut MacroExpander) -> MacroDefinition {
-    match context.consume_args(3, None) {
+    match context.consume_args(3, None, None) {
         Ok(args) => {
             context.consume_spaces();
             let next_token = context.future();
@@ -375,6 +389,53 @@ fn read_macro_definition_arg(context: &mut MacroExpander) -> Option<super::publi
     }
 }
 
+/**
+ * Reads the `[n][default]` that may follow the name in `\newcommand` and
+ * `\newenvironment`: the number of arguments and the default of an
+ * optional first argument.
+ */
+fn read_arg_spec(context: &mut MacroExpander) -> Option<(i32, Option<Vec<Token>>)> {
+    context.consume_spaces();
+    if context.future().text != "[" {
+        return Some((0, None));
+    }
+    context.pop_token();
+    let mut arg_text = String::new();
+    loop {
+        let token = context.expand_next_token();
+        if token.text == "]" {
+            break;
+        }
+        if token.text == "EOF" {
+            report_macro_error(
+                context,
+                "Unexpected end of input in a macro argument, expected ']'".to_string(),
+                token.loc.clone(),
+            );
+            return None;
+        }
+        arg_text.push_str(token.text.as_str());
+    }
+    if !arg_text.chars().all(|ch| ch.is_ascii_whitespace() || ch.is_ascii_digit())
+        || arg_text.trim().is_empty()
+    {
+        report_macro_error(context, format!("Invalid number of arguments: {}", arg_text), None);
+        return None;
+    }
+    let num_args = arg_text.trim().parse::<i32>().unwrap_or(0);
+    if num_args == 0 {
+        return Some((num_args, None));
+    }
+    match context.consume_optional_arg() {
+        Ok(default_arg) => Some((num_args, default_arg)),
+        Err(err) => {
+            report_macro_error(context, err.msg, err.loc);
+            None
+        }
+    }
+}
+
+
 fn newcommand_impl(
     context: &mut MacroExpander,
     exists_ok: bool,
@@ -384,11 +445,12 @@ fn newcommand_impl(
     let Some(name_arg) = read_macro_definition_arg(context) else {
         return MacroDefinition::Str(String::new());
     };
+    let name_loc = name_arg.start.range(&name_arg.end, String::new()).loc;
     if name_arg.tokens.len() != 1 {
         return report_macro_error(
             context,
             "\\newcommand's first argument must be a macro name".to_string(),
-            None,
+            name_loc,
         );
     }
     let name = name_arg.tokens[0].text.clone();
@@ -400,7 +462,7 @@ fn newcommand_impl(
                 "\\newcommand{{{}}} attempting to redefine {}; use \\renewcommand",
                 name, name
             ),
-            None,
+            name_loc,
         );
     }
     if !exists && !nonexists_ok {
@@ -410,54 +472,109 @@ fn newcommand_impl(
                 "\\renewcommand{{{}}} when command {} does not yet exist; use \\newcommand",
                 name, name
             ),
-            None,
+            name_loc,
         );
     }
 
-    let mut num_args = 0;
-    let Some(mut body_arg) = read_macro_definition_arg(context) else {
+    let Some((num_args, default_arg)) = read_arg_spec(context) else {
         return MacroDefinition::Str(String::new());
     };
-    if body_arg.tokens.len() == 1 && body_arg.tokens[0].text == "[" {
-        let mut arg_text = String::new();
-        loop {
-            let token = context.expand_next_token();
-            if token.text == "]" {
-                break;
-            }
-            if token.text == "EOF" {
-                return report_macro_error(
-                    context,
-                    "Unexpected end of input in a macro argument, expected ']'".to_string(),
-                    token.loc.clone(),
-                );
+    let Some(body_arg) = read_macro_definition_arg(context) else {
+        return MacroDefinition::Str(String::new());
+    };
+
+    if !(exists && skip_if_exists) {
+        context.macros.set(
+            &name,
+            Some(new_me_with_default(body_arg.tokens, num_args, default_arg)),
+            false,
+        );
+    }
+    MacroDefinition::Str(String::new())
+}
+
+/**
+ * `\newenvironment{name}[n][default]{begin}{end}` defines `\name` as the
+ * begin code, taking the arguments, and `\endname` as the end code, as in
+ * LaTeX. `\begin{name}` then expands to `\begingroup\name` and
+ * `\end{name}` to `\endname\endgroup`, see
+ * `MacroExpander::expand_user_environment`.
+ */
+fn newenvironment_impl(context: &mut MacroExpander, exists_ok: bool, nonexists_ok: bool) -> MacroDefinition {
+    let Some(name_arg) = read_macro_definition_arg(context) else {
+        return MacroDefinition::Str(String::new());
+    };
+    let name_loc = name_arg.start.range(&name_arg.end, String::new()).loc;
+    let name: String = name_arg.tokens.iter().rev().map(|token| token.text.as_str()).collect();
+    let name = name.trim().to_string();
+    if name.is_empty() || name.starts_with('\\') {
+        return report_macro_error(
+            context,
+            "\\newenvironment's first argument must be an environment name".to_string(),
+            name_loc,
+        );
+    }
+    let exists = context.is_environment(&name);
+    if exists && !exists_ok {
+        return report_macro_error(
+            context,
+            format!(
+                "\\newenvironment{{{}}} attempting to redefine {}; use \\renewenvironment",
+                name, name
+            ),
+            name_loc,
+        );
+    }
+    if !exists && !nonexists_ok {
+        return report_macro_error(
+            context,
+            format!(
+                "\\renewenvironment{{{}}} when environment {} does not yet exist; use \\newenvironment",
+                name, name
+            ),
+            name_loc,
+        );
+    }
+
+    // Like LaTeX, a new environment may not take over existing commands
+    // such as `\frac` for `{frac}`.
+    if !exists {
+        for command in [format!("\\{}", name), format!("\\end{}", name)] {
+            if context.is_defined(&command) {
+                return report_macro_error(context, format!("Command {} already defined", command), name_loc);
             }
-            arg_text.push_str(token.text.as_str());
-        }
-        if !arg_text.chars().all(|ch| ch.is_ascii_whitespace() || ch.is_ascii_digit())
-            || arg_text.trim().is_empty()
-        {
-            return report_macro_error(
-                context,
-                format!("Invalid number of arguments: {}", arg_text),
-                None,
-            );
         }
-        num_args = arg_text.trim().parse::<i32>().unwrap_or(0);
-        let Some(next_body_arg) = read_macro_definition_arg(context) else {
-            return MacroDefinition::Str(String::new());
-        };
-        body_arg = next_body_arg;
     }
 
-    if !(exists && skip_if_exists) {
-        context
-            .macros
-            .set(&name, Some(new_me(body_arg.tokens, num_args)), false);
-    }
+    let Some((num_args, default_arg)) = read_arg_spec(context) else {
+        return MacroDefinition::Str(String::new());
+    };
+    let Some(begin_arg) = read_macro_definition_arg(context) else {
+        return MacroDefinition::Str(String::new());
+    };
+    let Some(end_arg) = read_macro_definition_arg(context) else {
+        return MacroDefinition::Str(String::new());
+    };
+
+    context.macros.set(
+        &format!("\\{}", name),
+        Some(new_me_with_default(begin_arg.tokens, num_args, default_arg)),
+        false,
+    );
+    context
+        .macros
+        .set(&format!("\\end{}", name), Some(new_me(end_arg.tokens, 0)), false);
     MacroDefinition::Str(String::new())
 }
 
+fn newenvironment_macro(context: &mut MacroExpander) -> MacroDefinition {
+    newenvironment_impl(context, false, true)
+}
+
+fn renewenvironment_macro(context: &mut MacroExpander) -> MacroDefinition {
+    newenvironment_impl(context, true, false)
+}
+
 fn newcommand_macro(context: &mut MacroExpander) -> MacroDefinition {
     newcommand_impl(context, false, true, false)
 }
@@ -470,6 +587,46 @@ fn providecommand_macro(context: &mut MacroExpander) -> MacroDefinition {
     newcommand_impl(context, true, true, true)
 }
 
+/**
+ * `\message` and `\errmessage` report their argument, unexpanded as in
+ * KaTeX.js, to the diagnostics of the render.
+ */
+fn message_impl(context: &mut MacroExpander, kind: DiagnosticKind) -> MacroDefinition {
+    if let Some(arg) = read_macro_definition_arg(context) {
+        let text = tokens_to_source(arg.tokens.iter().rev().map(|tok| tok.text.as_str()));
+        let loc = arg.start.range(&arg.end, String::new()).loc;
+        context.report_diagnostic(kind, text, loc);
+    }
+    MacroDefinition::Str(String::new())
+}
+
+fn message_macro(context: &mut MacroExpander) -> MacroDefinition {
+    message_impl(context, DiagnosticKind::Message)
+}
+
+fn errmessage_macro(context: &mut MacroExpander) -> MacroDefinition {
+    message_impl(context, DiagnosticKind::ErrMessage)
+}
+
+fn show_macro(context: &mut MacroExpander) -> MacroDefinition {
+    let token = context.pop_token();
+    if token.text == "EOF" {
+        return report_macro_error(context, "\\show needs a token to show".to_string(), token.loc);
+    }
+    let meaning = context.meaning(&token);
+    context.report_diagnostic(DiagnosticKind::Show, meaning, token.loc);
+    MacroDefinition::Str(String::new())
+}
+
+lazy_static! {
+    /**
+     * The built-in macros, shared by every `MacroExpander`. They are never
+     * modified, so no lock is needed to read them from several threads.
+     */
+    pub static ref BUILTIN_MACROS: std::sync::Arc<crate::Namespace::Mapping<MacroDefinition>> =
+        std::sync::Arc::new(create_macro_map());
+}
+
 pub fn create_macro_map() -> crate::Namespace::Mapping<MacroDefinition> {
     let mut res = std::collections::HashMap::from([
         //////////////////////////////////////////////////////////////////////
@@ -530,7 +687,7 @@ pub fn create_macro_map() -> crate::Namespace::Mapping<MacroDefinition> {
         (
             "\\TextOrMath".to_string(),
             MacroDefinition::MacroContext(|context| {
-                let args = context.consume_args(2, None).unwrap();
+                let args = context.consume_args(2, None, None).unwrap();
                 return new_me(
                     args[if context.mode == Mode::text { 0 } else { 1 }].clone(),
                     0,
@@ -641,7 +798,6 @@ pub fn create_macro_map() -> crate::Namespace::Mapping<MacroDefinition> {
         //
         //     // \newcommand{\macro}[args]{definition}
         // // \renewcommand{\macro}[args]{definition}
-        // // TODO: Optional arguments: \newcommand{\macro}[args][default]{definition}
         //     const newcommand = (context, existsOK: boolean, nonexistsOK: boolean) => {
         //         let arg = context.consumeArg().tokens;
         //         if (arg.length !== 1) {
@@ -696,28 +852,18 @@ pub fn create_macro_map() -> crate::Namespace::Mapping<MacroDefinition> {
             "\\providecommand".to_string(),
             MacroDefinition::MacroContext(providecommand_macro),
         ),
-        //
-        // // terminal (console) tools
-        //     defineMacro("\\message", (context) => {
-        //         const arg = context.consumeArgs(1)[0];
-        //         // eslint-disable-next-line no-console
-        //         console.log(arg.reverse().map(token => token.text).join(""));
-        //         return '';
-        //     });
-        //     defineMacro("\\errmessage", (context) => {
-        //         const arg = context.consumeArgs(1)[0];
-        //         // eslint-disable-next-line no-console
-        //         console.error(arg.reverse().map(token => token.text).join(""));
-        //         return '';
-        //     });
-        //     defineMacro("\\show", (context) => {
-        //         const tok = context.popToken();
-        //         const name = tok.text;
-        //         // eslint-disable-next-line no-console
-        //         console.log(tok, context.macros.get(name), functions[name],
-        //                     symbols.math[name], symbols.text[name]);
-        //         return '';
-        //     });
+        (
+            "\\newenvironment".to_string(),
+            MacroDefinition::MacroContext(newenvironment_macro),
+        ),
+        (
+            "\\renewenvironment".to_string(),
+            MacroDefinition::MacroContext(renewenvironment_macro),
+        ),
+        // terminal (console) tools
+        ("\\message".to_string(), MacroDefinition::MacroContext(message_macro)),
+        ("\\errmessage".to_string(), MacroDefinition::MacroContext(errmessage_macro)),
+        ("\\show".to_string(), MacroDefinition::MacroContext(show_macro)),
         //
         // //////////////////////////////////////////////////////////////////////
         // // Grouping
@@ -834,7 +980,7 @@ pub fn create_macro_map() -> crate::Namespace::Mapping<MacroDefinition> {
         //     defineMacro("\\not", '\\html@mathml{\\mathrel{\\mathrlap\\@not}}{\\char"338}');
         (
             "\\not".to_string(),
-            MacroDefinition::Str("\\mathrel{\\mathrlap\\@not}".to_string()),
+            MacroDefinition::Str("\\html@mathml{\\mathrel{\\mathrlap\\@not}}{\\char\"338}".to_string()),
         ),
         //
         // // Negated symbols from base/fontmath.ltx:
@@ -847,7 +993,7 @@ pub fn create_macro_map() -> crate::Namespace::Mapping<MacroDefinition> {
         // defineMacro("\u2260", "\\neq");
         (
             "\\neq".to_string(),
-            MacroDefinition::Str("\\mathrel{\\not=}".to_string()),
+            MacroDefinition::Str("\\html@mathml{\\mathrel{\\not=}}{\\mathrel{\\char`≠}}".to_string()),
         ),
         (
             "\\ne".to_string(),
@@ -859,7 +1005,7 @@ pub fn create_macro_map() -> crate::Namespace::Mapping<MacroDefinition> {
         ),
         (
             "\\notin".to_string(),
-            MacroDefinition::Str("\\mathrel{{\\in}\\mathllap{/\\mskip1mu}}".to_string()),
+            MacroDefinition::Str("\\html@mathml{\\mathrel{{\\in}\\mathllap{/\\mskip1mu}}}{\\mathrel{\\char`∉}}".to_string()),
         ),
         ("∉".to_string(), MacroDefinition::Str("\\notin".to_string())),
         //
@@ -1271,8 +1417,9 @@ pub fn create_macro_map() -> crate::Namespace::Mapping<MacroDefinition> {
             "\\qquad".to_string(),
             MacroDefinition::Str("\\hskip2em\\relax".to_string()),
         ),
-        ("\\tag".to_string(), MacroDefinition::Str("\\@ifstar\\tag@literal\\tag@paren".to_string())),
-        ("\\tag@paren".to_string(), MacroDefinition::Str("\\tag@literal{({#1})}".to_string())),
+        ("\\tag".to_string(), MacroDefinition::Str("\\@ifstar\\tag@star\\tag@paren".to_string())),
+        ("\\tag@star".to_string(), MacroDefinition::Str("\\tag@literal{#1}{#1}".to_string())),
+        ("\\tag@paren".to_string(), MacroDefinition::Str("\\tag@literal{({#1})}{#1}".to_string())),
         ("\\tag@literal".to_string(), MacroDefinition::MacroContext(tag_literal_macro)),
         //
         // // \renewcommand{\bmod}{\nonscript\mskip-\medmuskip\mkern5mu\mathbin
@@ -1394,6 +1541,19 @@ pub fn create_macro_map() -> crate::Namespace::Mapping<MacroDefinition> {
             "\\operatorname".to_string(),
             MacroDefinition::Str("\\@ifstar\\operatornamewithlimits\\operatorname@".to_string()),
         ),
+        // amsopn.sty, with \def in place of \DeclareRobustCommand
+        (
+            "\\DeclareMathOperator".to_string(),
+            MacroDefinition::Str("\\@ifstar\\DeclareMathOperator@star\\DeclareMathOperator@".to_string()),
+        ),
+        (
+            "\\DeclareMathOperator@".to_string(),
+            MacroDefinition::Str("\\def#1{\\operatorname{#2}}".to_string()),
+        ),
+        (
+            "\\DeclareMathOperator@star".to_string(),
+            MacroDefinition::Str("\\def#1{\\operatorname*{#2}}".to_string()),
+        ),
         (
             "\\@hspace".to_string(),
             MacroDefinition::Str("\\hskip #1\\relax".to_string()),
@@ -1,3 +1,5 @@
 pub mod macro_expander;
 pub mod public;
-mod macro_map;
\ No newline at end of file
+pub(crate) mod macro_map;
+#[cfg(feature = "wasm")]
+pub(crate) mod js_macro;
\ No newline at end of file
@@ -114,14 +114,24 @@ pub struct MacroExpansion {
     pub num_args: i32,
     pub delimiters: Option<Vec<Vec<String>>>,
     pub unexpandable: bool, // used in \let
+    /// Default of an optional first argument (in reverse order), as in
+    /// `\newcommand{\norm}[2][2]{...}`.
+    pub default_arg: Option<Vec<Token>>,
 }
 
+/**
+ * A macro implemented by a closure rather than a plain function, such as a
+ * function value of the `macros` option in JS.
+ */
+pub type MacroCallback = std::sync::Arc<dyn Fn(&mut MacroExpander) -> MacroDefinition + Send + Sync>;
+
 #[derive(Clone)]
 pub enum MacroDefinition{
     // #[default]
     Str(String),
     MacroExpansion(MacroExpansion),
-    MacroContext(fn(&mut MacroExpander) -> MacroDefinition)
+    MacroContext(fn(&mut MacroExpander) -> MacroDefinition),
+    Callback(MacroCallback),
 }
 
 impl std::fmt::Debug for MacroDefinition {
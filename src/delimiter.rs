@@ -159,7 +159,7 @@ pub fn make_large_delim(
         Some(options),
         Default::default(),
     );
-    let _text = crate::Style::TEXT.read().unwrap();
+    let _text = &*crate::Style::TEXT;
     let span = style_wrap(
         Box::new(tmp_span) as Box<dyn HtmlDomNode>,
         &_text,
@@ -538,7 +538,7 @@ fn make_stacked_delim(
     }
 
     // Finally, build the vlist
-    let _text = crate::Style::TEXT.read().unwrap();
+    let _text = &*crate::Style::TEXT;
     let new_options = options.having_base_style(&_text);
     let inner = crate::build::common::make_vlist(crate::build::common::VListParam {
         position_type: PositionType::Bottom,
@@ -760,7 +760,7 @@ const STACK_NEVER_DELIMITERS: [&str; 8] = [
 // Metrics of the different sizes. Found by looking at TeX's output of
 // $\bigl| // \Bigl| \biggl| \Biggl| \showlists$
 // Used to create stacked delimiters of appropriate sizes in makeSizedDelim.
-const SIZE_TO_MAX_HEIGHT: [f64; 5] = [0.0, 1.2, 1.8, 2.4, 3.0];
+pub(crate) const SIZE_TO_MAX_HEIGHT: [f64; 5] = [0.0, 1.2, 1.8, 2.4, 3.0];
 
 /**
  * Used to create a delimiter of a specific size, where `size` is 1, 2, 3, or 4.
@@ -821,9 +821,9 @@ enum Delimiter {
 lazy_static! {
     // Delimiters that never stack try small delimiters and large delimiters only
     static ref  STACK_NEVER_DELIMITER_SEQUENCE: Vec<Delimiter> = {
-        let _script_script = crate::Style::SCRIPTSCRIPT.read().unwrap();
-        let _script = crate::Style::SCRIPT.read().unwrap();
-        let _text = crate::Style::TEXT.read().unwrap();
+        let _script_script = &*crate::Style::SCRIPTSCRIPT;
+        let _script = &*crate::Style::SCRIPT;
+        let _text = &*crate::Style::TEXT;
         vec![
             Delimiter::Small(_script_script.clone()),
             Delimiter::Small(_script.clone()),
@@ -837,9 +837,9 @@ lazy_static! {
 
 // Delimiters that always stack try the small delimiters first, then stack
     static ref  STACK_ALWAYS_DELIMITER_SEQUENCE: Vec<Delimiter> = {
-        let _script_script = crate::Style::SCRIPTSCRIPT.read().unwrap();
-        let _script = crate::Style::SCRIPT.read().unwrap();
-        let _text = crate::Style::TEXT.read().unwrap();
+        let _script_script = &*crate::Style::SCRIPTSCRIPT;
+        let _script = &*crate::Style::SCRIPT;
+        let _text = &*crate::Style::TEXT;
         vec![
             Delimiter::Small(_script_script.clone()),
             Delimiter::Small(_script.clone()),
@@ -853,9 +853,9 @@ lazy_static! {
 // stack afterwards
 // stack afterwards
     static ref STACK_LARGE_DELIMITER_SEQUEUE: Vec<Delimiter> = {
-        let _scriptscript = crate::Style::SCRIPTSCRIPT.read().unwrap();
-        let _script = crate::Style::SCRIPT.read().unwrap();
-        let _text = crate::Style::TEXT.read().unwrap();
+        let _scriptscript = &*crate::Style::SCRIPTSCRIPT;
+        let _script = &*crate::Style::SCRIPT;
+        let _text = &*crate::Style::TEXT;
         let res = vec![
             Delimiter::Small(_scriptscript.clone()),
             Delimiter::Small(_script.clone()),
@@ -61,6 +61,7 @@ impl VirtualNode for Anchor {
         self
     }
 
+    #[cfg(feature = "wasm")]
     fn to_node(&self) -> web_sys::Node {
         this_to_node!(self, "a")
     }
@@ -1,12 +1,13 @@
 use std::fmt::Formatter;
 use struct_format::format;
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 
 /**
  * This node represents an image embed (<img>) element.
  */
 #[derive(Clone, format, Default, PartialEq)]
-#[wasm_bindgen(getter_with_clone)]
+#[cfg_attr(feature = "wasm", wasm_bindgen(getter_with_clone))]
 pub struct CssStyle {
     pub background_color: Option<String>,
     pub border_bottom_width: Option<String>,
@@ -31,16 +32,66 @@ pub struct CssStyle {
     pub top: Option<String>,
     pub width: Option<String>,
     pub vertical_align: Option<String>,
+    // Used by `inlineStyles` for what katex.css would set. Longhands only, so
+    // that the node's own properties, written before them, are not overridden.
+    pub align_items: Option<String>,
+    pub border_bottom_style: Option<String>,
+    pub border_collapse: Option<String>,
+    pub border_left_style: Option<String>,
+    pub border_left_width: Option<String>,
+    pub border_top_style: Option<String>,
+    pub box_sizing: Option<String>,
+    pub clip: Option<String>,
+    pub display: Option<String>,
+    pub flex_direction: Option<String>,
+    pub font_family: Option<String>,
+    pub font_size: Option<String>,
+    pub font_style: Option<String>,
+    pub font_weight: Option<String>,
+    pub line_height: Option<String>,
+    pub margin_bottom: Option<String>,
+    pub max_height: Option<String>,
+    pub max_width: Option<String>,
+    pub min_height: Option<String>,
+    pub overflow: Option<String>,
+    pub padding_right: Option<String>,
+    pub right: Option<String>,
+    pub table_layout: Option<String>,
+    pub text_align: Option<String>,
+    pub text_indent: Option<String>,
+    pub white_space: Option<String>,
 }
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 impl CssStyle {
-    #[wasm_bindgen(constructor)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
     pub fn new() -> CssStyle {
         CssStyle::default()
     }
 }
 
+impl CssStyle {
+    /**
+     * Parses a declaration list such as `color: red; margin-left: 1em`.
+     * Property names are case-insensitive; a property CssStyle cannot hold
+     * is returned as the error.
+     */
+    pub fn from_css_str(style: &str) -> Result<CssStyle, String> {
+        let mut res = CssStyle::default();
+        for declaration in style.split(';') {
+            if declaration.trim().is_empty() {
+                continue;
+            }
+            let (name, value) = declaration.split_once(':').ok_or_else(|| declaration.trim().to_string())?;
+            let name = name.trim().to_ascii_lowercase();
+            if !res.set_css_property(&name, value.trim().to_string()) {
+                return Err(name);
+            }
+        }
+        Ok(res)
+    }
+}
+
 impl std::fmt::Debug for CssStyle{
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "CssStyle {}",self.to_css_str())
@@ -62,4 +113,11 @@ mod tests {
         test_css_style.min_width = Some("aaa".to_string());
         println!("test_css_style = {}", test_css_style.to_css_str());
     }
+
+    #[test]
+    fn test_css_style_parse() {
+        let style = CssStyle::from_css_str("Color: red; margin-left:1em;").unwrap();
+        assert_eq!(style.to_css_str(), "color:red;margin-left:1em;");
+        assert_eq!(CssStyle::from_css_str("color: red; cursor: pointer"), Err("cursor".to_string()));
+    }
 }
@@ -2,6 +2,7 @@ use std::any::Any;
 use crate::dom_tree::css_style::CssStyle;
 use crate::mathML_tree::public::MathDomNode;
 use crate::{HtmlDomNode, VirtualNode};
+#[cfg(feature = "wasm")]
 use web_sys::Node;
 use struct_format::html_dom_node;
 
@@ -44,6 +45,7 @@ impl VirtualNode for DocumentFragment  {
     }
 
     /** Convert the fragment into a node. */
+    #[cfg(feature = "wasm")]
     fn to_node(&self) -> web_sys::Node {
         let document = web_sys::window().expect("").document().expect("");
         let frag = document.create_document_fragment();
@@ -2,11 +2,13 @@ use std::any::Any;
 use crate::dom_tree::css_style::CssStyle;
 use crate::utils::escape_to;
 use crate::{HtmlDomNode, VirtualNode};
+#[cfg(feature = "wasm")]
 use js_sys::Array;
 use struct_format::html_dom_node;
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 #[derive(html_dom_node, Clone, Debug)]
 pub struct Img {
     src: String,
@@ -18,9 +20,9 @@ pub struct Img {
     style: CssStyle,
 }
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 impl Img {
-    #[wasm_bindgen(constructor)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
     pub fn new(src: String, alt: String, style: CssStyle) -> Img {
         Img {
             src: src,
@@ -33,6 +35,7 @@ impl Img {
         }
     }
 
+    #[cfg(feature = "wasm")]
     #[wasm_bindgen(getter)]
     pub fn classes(&self) -> Array {
         let arr = Array::new_with_length(self.classes.len() as u32);
@@ -43,6 +46,12 @@ impl Img {
     }
 }
 
+impl Img {
+    pub fn get_src(&self) -> &String {
+        &self.src
+    }
+}
+
 impl VirtualNode for Img {
     fn as_any(&self) -> &dyn Any {
         self
@@ -53,6 +62,7 @@ impl VirtualNode for Img {
     }
 
 
+    #[cfg(feature = "wasm")]
     fn to_node(&self) -> web_sys::Node {
         let document = web_sys::window().expect("").document().expect("");
         let node = document.create_element("img").expect("");
@@ -3,9 +3,11 @@ use crate::dom_tree::css_style::CssStyle;
 use crate::utils::escape_to;
 use crate::units::make_em;
 use crate::{path_get, scriptFromCodepoint, VirtualNode};
+#[cfg(feature = "wasm")]
 use js_sys::Array;
 use std::collections::HashMap;
 use std::sync::Mutex;
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 
 #[derive(Clone, Debug)]
@@ -21,6 +23,7 @@ impl VirtualNode for LineNode {
         self
     }
 
+    #[cfg(feature = "wasm")]
     fn to_node(&self) -> web_sys::Node {
         let svgNS = "http://www.w3.org/2000/svg";
 
@@ -66,6 +69,11 @@ impl VirtualNode for LineNode {
 }
 
 impl LineNode {
+    pub fn get_attribute(&self, name: &str) -> Option<&String> {
+        self.attributes.get(name)
+    }
+
+    #[cfg(feature = "wasm")]
     pub fn new_from_js(attributes: js_sys::Object) -> LineNode {
         let mut res = HashMap::new();
         for (k, v) in js_sys::Object::keys(&attributes)
@@ -82,6 +90,7 @@ impl LineNode {
         LineNode { attributes }
     }
 
+    #[cfg(feature = "wasm")]
     pub fn toNode(self) -> web_sys::Node {
         self.to_node()
     }
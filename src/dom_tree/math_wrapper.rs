@@ -0,0 +1,53 @@
+use std::any::Any;
+use crate::dom_tree::css_style::CssStyle;
+use crate::mathML_tree::public::MathDomNode;
+use crate::{HtmlDomNode, VirtualNode};
+use struct_format::html_dom_node;
+
+/**
+ * This node lets a MathML tree (usually a `<math>` element) sit among the
+ * children of a span. It has no box metrics of its own; `to_node` and
+ * `to_markup` simply defer to the wrapped MathML node.
+ */
+#[derive(Clone, html_dom_node, Debug)]
+pub struct MathWrapper {
+    math: Box<dyn MathDomNode>,
+    // HtmlDomNode
+    classes: Vec<String>,
+    height: f64,
+    depth: f64,
+    max_font_size: f64,
+    style: CssStyle, // Never used; needed for satisfying interface.
+}
+
+impl MathWrapper {
+    pub fn new(math: Box<dyn MathDomNode>) -> MathWrapper {
+        MathWrapper {
+            math,
+            classes: Vec::new(),
+            height: 0.0,
+            depth: 0.0,
+            max_font_size: 0.0,
+            style: CssStyle::default(),
+        }
+    }
+}
+
+impl VirtualNode for MathWrapper {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_mut_any(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    #[cfg(feature = "wasm")]
+    fn to_node(&self) -> web_sys::Node {
+        self.math.to_node()
+    }
+
+    fn to_markup(&self) -> String {
+        self.math.to_markup()
+    }
+}
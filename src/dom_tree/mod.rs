@@ -3,6 +3,7 @@ pub mod css_style;
 pub(crate) mod document_fragment;
 pub mod img;
 pub mod line_node;
+pub(crate) mod math_wrapper;
 pub(crate) mod path_node;
 pub(crate) mod span;
 pub(crate) mod svg_node;
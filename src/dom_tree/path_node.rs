@@ -3,12 +3,14 @@ use crate::dom_tree::css_style::CssStyle;
 use crate::utils::escape_to;
 use crate::units::make_em;
 use crate::{path_get, scriptFromCodepoint, VirtualNode};
+#[cfg(feature = "wasm")]
 use js_sys::Array;
 use std::collections::HashMap;
 use std::sync::Mutex;
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 
-#[wasm_bindgen(getter_with_clone)]
+#[cfg_attr(feature = "wasm", wasm_bindgen(getter_with_clone))]
 #[derive(Clone, Debug)]
 pub struct PathNode /* implements VirtualNode*/ {
     pub pathName: String,
@@ -24,6 +26,7 @@ impl VirtualNode for PathNode {
         self
     }
 
+    #[cfg(feature = "wasm")]
     fn to_node(&self) -> web_sys::Node {
         let svgNS = "http://www.w3.org/2000/svg";
 
@@ -39,9 +42,9 @@ impl VirtualNode for PathNode {
         return format!("<path d=\"{}\"/>", self.path_data());
     }
 }
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 impl PathNode {
-    fn path_data(&self) -> String {
+    pub(crate) fn path_data(&self) -> String {
         let data = if let Some(alt) = &self.alternate {
             alt.clone()
         } else {
@@ -55,7 +58,7 @@ impl PathNode {
             .to_string()
     }
 
-    #[wasm_bindgen(constructor)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
     pub fn new(pathName: String, alternate: Option<String>) -> PathNode {
         PathNode {
             pathName,
@@ -63,6 +66,7 @@ impl PathNode {
         }
     }
 
+    #[cfg(feature = "wasm")]
     pub fn toNode(&self) -> web_sys::Node {
         return self.to_node();
     }
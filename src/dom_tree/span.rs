@@ -47,6 +47,10 @@ impl Span {
         self.attributes.insert(attribute, value);
     }
 
+    pub fn get_attribute(&self, attribute: &str) -> Option<&String> {
+        self.attributes.get(attribute)
+    }
+
     // pub fn get_mut_children(&mut self) -> &Vec<Box<dyn HtmlDomNode>> {
     //     &self.children
     // }
@@ -61,6 +65,7 @@ impl VirtualNode for Span {
         self
     }
 
+    #[cfg(feature = "wasm")]
     fn to_node(&self) -> web_sys::Node {
         this_to_node!(self, "span")
     }
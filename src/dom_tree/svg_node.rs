@@ -3,10 +3,12 @@ use crate::dom_tree::line_node::LineNode;
 use crate::dom_tree::path_node::PathNode;
 use crate::units::make_em;
 use crate::{HtmlDomNode, path_get, scriptFromCodepoint, VirtualNode};
+#[cfg(feature = "wasm")]
 use js_sys::Array;
 use std::any::{Any, TypeId};
 use std::collections::HashMap;
 use std::sync::Mutex;
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 
 /**
@@ -29,6 +31,13 @@ impl SvgNode{
         self.attributes.insert(k,v);
         self
     }
+    pub fn get_attribute(&self, name: &str) -> Option<&String> {
+        self.attributes.get(name)
+    }
+    /** The `<path>` and `<line>` elements, which `HtmlDomNode::get_children` does not cover. */
+    pub fn svg_children(&self) -> &Vec<Box<dyn VirtualNode>> {
+        &self.children
+    }
 }
 impl VirtualNode for SvgNode {
     fn as_any(&self) -> &dyn Any {
@@ -39,6 +48,7 @@ impl VirtualNode for SvgNode {
         self
     }
 
+    #[cfg(feature = "wasm")]
     fn to_node(&self) -> web_sys::Node {
         let svgNS = "http://www.w3.org/2000/svg";
 
@@ -2,15 +2,16 @@ use crate::dom_tree::css_style::CssStyle;
 use crate::units::make_em;
 use crate::utils::escape_to;
 use crate::{scriptFromCodepoint, HtmlDomNode, VirtualNode};
+#[cfg(feature = "wasm")]
 use js_sys::Array;
 use std::fmt::Write;
 use std::any::Any;
 use std::collections::HashMap;
-use std::sync::Mutex;
 use struct_format::html_dom_node;
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 lazy_static! {
-    static ref iCombinations:Mutex<HashMap<&'static str,&'static str> >  = Mutex::new({
+    static ref iCombinations: HashMap<&'static str,&'static str>  = {
     HashMap::from([
             ("î", "\u{0131}\u{0302}"),
             ("ï", "\u{0131}\u{0308}"),
@@ -18,7 +19,7 @@ lazy_static! {
             // 'ī': '\u0131\u0304', // enable when we add Extended Latin
             ("ì", "\u{0131}\u{0300}")
         ])
-    });
+    };
 }
 
 ///A symbol node contains information about a single symbol. It either renders
@@ -65,7 +66,7 @@ impl SymbolNode {
             res.classes.push(script + "_fallback");
         }
 
-        let i_comb = iCombinations.lock().unwrap();
+        let i_comb = &*iCombinations;
         if i_comb.contains_key(res.text.as_str()) {
             // add ī when we add Extended Latin
             res.text = i_comb.get(&*res.text).unwrap().to_string();
@@ -118,6 +119,7 @@ impl VirtualNode for SymbolNode {
     ///Creates a text node or span from a symbol node. Note that a span is only
     ///created if it is needed.
      */
+    #[cfg(feature = "wasm")]
     fn to_node(&self) -> web_sys::Node {
         let document = web_sys::window().expect("").document().expect("");
         let node = document.create_text_node(&self.text);
@@ -3,14 +3,20 @@ use crate::dom_tree::css_style::CssStyle;
 use crate::dom_tree::span::Span;
 use crate::dom_tree::symbol_node::SymbolNode;
 use crate::parse_node::types::AnyParseNode;
-use crate::parse::parse_tree_with_error;
+use crate::parse::{check_tree_limits, check_tree_trust, parse_tree_with_error, try_parse_tree};
+use crate::parse_node::json::{parse_tree_from_json, parse_tree_to_json};
+use crate::parse_node::unparse::parse_tree_to_latex;
+use crate::parse_node::visit::any_node;
 use crate::parse_error::ParseError;
-use crate::settings::Settings;
+use crate::settings::{Diagnostic, ExpansionStep, Settings, StrictWarning};
 use crate::tree::HtmlDomNode;
 use crate::utils::escape_to;
 use crate::VirtualNode;
 use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
+#[cfg(feature = "wasm")]
 use wasm_bindgen::JsValue;
 
 fn format_parse_error(error: &ParseError) -> String {
@@ -32,6 +38,13 @@ fn render_error_dom(error: &ParseError, expression: &str, settings: &Settings) -
     node
 }
 
+/** `render_error_dom` for the SVG and PNG output, which read the color from the style. */
+fn render_error_box(error: &ParseError, expression: &str, settings: &Settings) -> Span {
+    let mut node = render_error_dom(error, expression, settings);
+    node.get_mut_style().color = Some(settings.get_error_color()).filter(|color| !color.is_empty());
+    node
+}
+
 fn render_error_markup(error: &ParseError, expression: &str, settings: &Settings) -> String {
     let mut markup = String::new();
     markup.push_str("<span class=\"katex-error\" title=\"");
@@ -44,145 +57,45 @@ fn render_error_markup(error: &ParseError, expression: &str, settings: &Settings
     return markup;
 }
 
-fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
-    if let Some(message) = payload.downcast_ref::<String>() {
-        message.clone()
-    } else if let Some(message) = payload.downcast_ref::<&str>() {
-        message.to_string()
+/** The markup of a built tree, or the SVG document for `output: "svg"`. */
+fn tree_to_markup(tree: &Span, expression: &str, settings: &Settings) -> String {
+    if settings.get_output() == "svg" {
+        crate::svg::to_svg(tree, Some(expression), settings)
     } else {
-        "internal render error".to_string()
+        tree.to_markup()
     }
 }
 
-fn contains_infix_nodes(nodes: &[Box<dyn AnyParseNode>]) -> bool {
-    nodes.iter().any(contains_infix_node)
-}
-
-fn contains_infix_node(node: &Box<dyn AnyParseNode>) -> bool {
-    if node.get_type() == "infix" {
-        return true;
-    }
-
-    if let Some(group) = node.as_any().downcast_ref::<crate::parse_node::types::array>() {
-        return group.body.iter().any(|row| contains_infix_nodes(row));
-    }
-    if let Some(group) = node.as_any().downcast_ref::<crate::parse_node::types::color>() {
-        return contains_infix_nodes(&group.body);
-    }
-    if let Some(group) = node.as_any().downcast_ref::<crate::parse_node::types::op>() {
-        return group
-            .body
-            .as_ref()
-            .is_some_and(|body| contains_infix_nodes(body));
-    }
-    if let Some(group) = node.as_any().downcast_ref::<crate::parse_node::types::ordgroup>() {
-        return contains_infix_nodes(&group.body);
-    }
-    if let Some(group) = node.as_any().downcast_ref::<crate::parse_node::types::styling>() {
-        return contains_infix_nodes(&group.body);
-    }
-    if let Some(group) = node.as_any().downcast_ref::<crate::parse_node::types::supsub>() {
-        return group.base.as_ref().is_some_and(contains_infix_node)
-            || group.sup.as_ref().is_some_and(contains_infix_node)
-            || group.sub.as_ref().is_some_and(contains_infix_node);
-    }
-    if let Some(group) = node.as_any().downcast_ref::<crate::parse_node::types::tag>() {
-        return contains_infix_nodes(&group.body) || contains_infix_nodes(&group.tag);
-    }
-    if let Some(group) = node.as_any().downcast_ref::<crate::parse_node::types::text>() {
-        return contains_infix_nodes(&group.body);
-    }
-    if let Some(group) = node.as_any().downcast_ref::<crate::parse_node::types::accent>() {
-        return group.base.as_ref().is_some_and(contains_infix_node);
-    }
-    if let Some(group) = node.as_any().downcast_ref::<crate::parse_node::types::accentUnder>() {
-        return contains_infix_node(&group.base);
-    }
-    if let Some(group) = node.as_any().downcast_ref::<crate::parse_node::types::enclose>() {
-        return contains_infix_node(&group.body);
-    }
-    if let Some(group) = node.as_any().downcast_ref::<crate::parse_node::types::font>() {
-        return contains_infix_node(&group.body);
-    }
-    if let Some(group) = node.as_any().downcast_ref::<crate::parse_node::types::genfrac>() {
-        return contains_infix_node(&group.numer) || contains_infix_node(&group.denom);
-    }
-    if let Some(group) = node.as_any().downcast_ref::<crate::parse_node::types::hbox>() {
-        return contains_infix_nodes(&group.body);
-    }
-    if let Some(group) = node.as_any().downcast_ref::<crate::parse_node::types::horizBrace>() {
-        return contains_infix_node(&group.base);
-    }
-    if let Some(group) = node.as_any().downcast_ref::<crate::parse_node::types::href>() {
-        return contains_infix_nodes(&group.body);
-    }
-    if let Some(group) = node.as_any().downcast_ref::<crate::parse_node::types::html>() {
-        return contains_infix_nodes(&group.body);
-    }
-    if let Some(group) = node.as_any().downcast_ref::<crate::parse_node::types::htmlmathml>() {
-        return contains_infix_nodes(&group.html) || contains_infix_nodes(&group.mathml);
-    }
-    if let Some(group) = node.as_any().downcast_ref::<crate::parse_node::types::lap>() {
-        return contains_infix_node(&group.body);
-    }
-    if let Some(group) = node.as_any().downcast_ref::<crate::parse_node::types::leftright>() {
-        return contains_infix_nodes(&group.body);
-    }
-    if let Some(group) = node.as_any().downcast_ref::<crate::parse_node::types::mathchoice>() {
-        return contains_infix_nodes(&group.display)
-            || contains_infix_nodes(&group.text)
-            || contains_infix_nodes(&group.script)
-            || contains_infix_nodes(&group.scriptscript);
-    }
-    if let Some(group) = node.as_any().downcast_ref::<crate::parse_node::types::mclass>() {
-        return contains_infix_nodes(&group.body);
-    }
-    if let Some(group) = node.as_any().downcast_ref::<crate::parse_node::types::operatorname>() {
-        return contains_infix_nodes(&group.body);
-    }
-    if let Some(group) = node.as_any().downcast_ref::<crate::parse_node::types::overline>() {
-        return contains_infix_node(&group.body);
-    }
-    if let Some(group) = node.as_any().downcast_ref::<crate::parse_node::types::phantom>() {
-        return contains_infix_nodes(&group.body);
-    }
-    if let Some(group) = node.as_any().downcast_ref::<crate::parse_node::types::hphantom>() {
-        return contains_infix_node(&group.body);
-    }
-    if let Some(group) = node.as_any().downcast_ref::<crate::parse_node::types::vphantom>() {
-        return contains_infix_node(&group.body);
-    }
-    if let Some(group) = node.as_any().downcast_ref::<crate::parse_node::types::pmb>() {
-        return contains_infix_nodes(&group.body);
-    }
-    if let Some(group) = node.as_any().downcast_ref::<crate::parse_node::types::raisebox>() {
-        return contains_infix_node(&group.body);
-    }
-    if let Some(group) = node.as_any().downcast_ref::<crate::parse_node::types::sizing>() {
-        return contains_infix_nodes(&group.body);
-    }
-    if let Some(group) = node.as_any().downcast_ref::<crate::parse_node::types::smash>() {
-        return contains_infix_node(&group.body);
-    }
-    if let Some(group) = node.as_any().downcast_ref::<crate::parse_node::types::sqrt>() {
-        return contains_infix_node(&group.body)
-            || group.index.as_ref().is_some_and(contains_infix_node);
-    }
-    if let Some(group) = node.as_any().downcast_ref::<crate::parse_node::types::underline>() {
-        return contains_infix_node(&group.body);
-    }
-    if let Some(group) = node.as_any().downcast_ref::<crate::parse_node::types::vcenter>() {
-        return contains_infix_node(&group.body);
-    }
-    if let Some(group) = node.as_any().downcast_ref::<crate::parse_node::types::xArrow>() {
-        return contains_infix_node(&group.body)
-            || group.below.as_ref().is_some_and(contains_infix_node);
+/**
+ * Runs `render` with its own copy of the equation numbers and labels of a
+ * `RenderSession`, and keeps what it numbered and labelled only if it
+ * succeeds, so a formula that fails takes no number and defines no label
+ * for a tag that is never shown.
+ */
+pub(crate) fn with_cross_references<T>(
+    settings: &Settings,
+    render: impl FnOnce(&Settings) -> Result<T, ParseError>,
+) -> Result<T, ParseError> {
+    let shared = match settings.get_cross_references() {
+        Some(shared) => shared,
+        None => return render(settings),
+    };
+    let draft = Arc::new(Mutex::new(shared.lock().unwrap().clone()));
+    let mut settings = settings.clone();
+    settings.set_cross_references(Some(draft.clone()));
+    let result = render(&settings);
+    if result.is_ok() {
+        *shared.lock().unwrap() = draft.lock().unwrap().clone();
     }
+    result
+}
 
-    false
+fn contains_infix_nodes(nodes: &[Box<dyn AnyParseNode>]) -> bool {
+    any_node(nodes, |node| node.get_type() == "infix")
 }
 
 fn build_dom_tree(expression: &str, settings: &Settings) -> Result<Span, ParseError> {
+    let start = crate::utils::now_ms();
     match catch_unwind(AssertUnwindSafe(|| {
         let tree = parse_tree_with_error(expression.to_string(), settings.clone())?;
         // if contains_infix_nodes(&tree) {
@@ -191,61 +104,339 @@ fn build_dom_tree(expression: &str, settings: &Settings) -> Result<Span, ParseEr
         //         loc: None,
         //     });
         // }
-        Ok(crate::build::build_tree(
-            tree,
-            expression.to_string(),
-            settings.clone(),
-        ))
+        // The build gets what is left of the budget after parsing.
+        let mut settings = settings.clone();
+        if let Some(budget) = settings.get_time_budget() {
+            settings.set_time_budget(Some(budget - (crate::utils::now_ms() - start)));
+        }
+        crate::build::build_tree(tree, expression.to_string(), settings)
     })) {
         Ok(result) => result,
-        Err(payload) => Err(ParseError {
-            msg: panic_message(payload),
-            loc: None,
-        }),
+        Err(payload) => Err(ParseError::from_panic(payload)),
+    }
+}
+
+/** The expression laid out as text for `output: "text"`, see `crate::text`. */
+fn build_text(expression: &str, settings: &Settings) -> Result<String, ParseError> {
+    match catch_unwind(AssertUnwindSafe(|| {
+        let tree = parse_tree_with_error(expression.to_string(), settings.clone())?;
+        Ok(crate::text::to_text(&tree, settings))
+    })) {
+        Ok(result) => result,
+        Err(payload) => Err(ParseError::from_panic(payload)),
+    }
+}
+
+fn check_output_size(markup: String, settings: &Settings) -> Result<String, ParseError> {
+    match settings.get_max_output_bytes() {
+        Some(max) if markup.len() > max => Err(ParseError::new(
+            "Output too large: need to increase maxOutputBytes setting".to_string(),
+            None,
+        )),
+        _ => Ok(markup),
     }
 }
 
 /**
  * Generates and returns the katex build tree. This is used for advanced
  * use cases (like rendering to custom output).
+ *
+ * With `throwOnError` set, a parse error is returned as `Err`; otherwise the
+ * error is rendered in place as a `katex-error` span.
  */
-pub fn render_to_dom_tree(expression: String, settings: Settings) -> Span {
-    match build_dom_tree(&expression, &settings) {
-        Ok(tree) => tree,
+pub fn try_render_to_dom_tree(expression: String, settings: Settings) -> Result<Span, ParseError> {
+    match with_cross_references(&settings, |settings| build_dom_tree(&expression, settings)) {
+        Ok(tree) => Ok(tree),
         Err(error) => {
             if settings.get_throw_on_error() {
-                panic!("{}", format_parse_error(&error));
+                return Err(error);
             }
-            render_error_dom(&error, &expression, &settings)
+            Ok(render_error_dom(&error, &expression, &settings))
         }
     }
 }
 
+/**
+ * Generates and returns the katex build tree, panicking on a parse error
+ * when `throwOnError` is set. See `try_render_to_dom_tree`.
+ */
+pub fn render_to_dom_tree(expression: String, settings: Settings) -> Span {
+    try_render_to_dom_tree(expression, settings)
+        .unwrap_or_else(|error| panic!("{}", format_parse_error(&error)))
+}
+
+#[cfg(feature = "wasm")]
 #[wasm_bindgen]
-pub fn render(expression: String, base_node: &web_sys::Node, options: &JsValue) {
+pub fn render(expression: String, base_node: &web_sys::Node, options: &JsValue) -> Result<(), JsValue> {
+    render_into(expression, base_node, Settings::new_from_js(options)?)
+}
+
+/**
+ * Replaces the contents of `base_node` by `expression` rendered for the
+ * output type of `settings`: text for `"text"`, SVG markup for `"svg"` and
+ * DOM nodes otherwise. Shared by `render` and `RenderSession::render`.
+ */
+#[cfg(feature = "wasm")]
+pub(crate) fn render_into(expression: String, base_node: &web_sys::Node, settings: Settings) -> Result<(), JsValue> {
     base_node.set_text_content(Some(""));
-    let node = render_to_dom_tree(expression, Settings::new_from_js(options)).to_node();
-    base_node.append_child(&node);
+    if settings.get_output() == "text" {
+        let text = try_render_to_string(expression, settings).map_err(|error| error.to_js_error())?;
+        base_node.set_text_content(Some(&text));
+        return Ok(());
+    }
+    if settings.get_output() == "svg" {
+        use wasm_bindgen::JsCast;
+        let markup = try_render_to_string(expression, settings).map_err(|error| error.to_js_error())?;
+        let element = base_node
+            .dyn_ref::<web_sys::Element>()
+            .ok_or_else(|| JsValue::from_str("render: the base node must be an element for SVG output"))?;
+        element.set_inner_html(&markup);
+        return Ok(());
+    }
+    let node = try_render_to_dom_tree(expression, settings)
+        .map_err(|error| error.to_js_error())?
+        .to_node();
+    base_node.append_child(&node)?;
+    Ok(())
 }
 
 /**
  * Parse and build an expression, and return the markup for that.
+ *
+ * With `throwOnError` set, a parse error is returned as `Err`; otherwise the
+ * error is rendered in place as a `katex-error` span, or for `output: "text"`
+ * the expression is returned as it is.
  */
-pub fn render_to_string(expression: String, settings: Settings) -> String {
-    match build_dom_tree(&expression, &settings) {
-        Ok(tree) => tree.to_markup(),
+pub fn try_render_to_string(expression: String, settings: Settings) -> Result<String, ParseError> {
+    if settings.get_output() == "text" {
+        let text = with_cross_references(&settings, |settings| {
+            build_text(&expression, settings).and_then(|text| check_output_size(text, settings))
+        });
+        return match text {
+            Err(_) if !settings.get_throw_on_error() => Ok(expression),
+            result => result,
+        };
+    }
+    let markup = with_cross_references(&settings, |settings| {
+        build_dom_tree(&expression, settings)
+            .and_then(|tree| check_output_size(tree_to_markup(&tree, &expression, settings), settings))
+    });
+    match markup {
+        Ok(markup) => Ok(markup),
         Err(error) => {
             if settings.get_throw_on_error() {
-                panic!("{}", format_parse_error(&error));
+                return Err(error);
+            }
+            if settings.get_output() == "svg" {
+                let node = render_error_box(&error, &expression, &settings);
+                return Ok(crate::svg::to_svg(&node, Some(&format_parse_error(&error)), &settings));
             }
-            render_error_markup(&error, &expression, &settings)
+            Ok(render_error_markup(&error, &expression, &settings))
         }
     }
 }
 
+/**
+ * Parse and build an expression, and return the markup for that, panicking
+ * on a parse error when `throwOnError` is set. See `try_render_to_string`.
+ */
+pub fn render_to_string(expression: String, settings: Settings) -> String {
+    try_render_to_string(expression, settings)
+        .unwrap_or_else(|error| panic!("{}", format_parse_error(&error)))
+}
+
+#[cfg(feature = "wasm")]
 #[wasm_bindgen(js_name = renderToString)]
-pub fn render_to_string_for_js(expression: String, settings: &JsValue) -> String {
-    return render_to_string(expression, Settings::new_from_js(settings));
+pub fn render_to_string_for_js(expression: String, settings: &JsValue) -> Result<String, JsValue> {
+    try_render_to_string(expression, Settings::new_from_js(settings)?)
+        .map_err(|error| error.to_js_error())
+}
+
+/**
+ * Parse and build an expression and rasterize it to a PNG image with a
+ * transparent background, as described by `options`. Glyphs are drawn from
+ * the fonts given to `register_svg_font`; none are bundled, so register the
+ * KaTeX fonts first or every glyph comes out as a hollow box.
+ *
+ * With `throwOnError` set, a parse error is returned as `Err`; otherwise the
+ * expression is drawn in `errorColor`.
+ */
+#[cfg(feature = "png")]
+pub fn try_render_to_png(
+    expression: String,
+    mut settings: Settings,
+    options: &crate::svg::PngOptions,
+) -> Result<Vec<u8>, ParseError> {
+    // The layout reads the styles the builders set, not those of katex.css.
+    settings.set_inline_styles(false);
+    let tree = match with_cross_references(&settings, |settings| build_dom_tree(&expression, settings)) {
+        Ok(tree) => tree,
+        Err(error) => {
+            if settings.get_throw_on_error() {
+                return Err(error);
+            }
+            render_error_box(&error, &expression, &settings)
+        }
+    };
+    crate::svg::to_png(&tree, options).map_err(|msg| ParseError::new(msg, None))
+}
+
+/**
+ * Markup, or the error that stopped the render, together with the strict
+ * warnings raised while rendering, what `\message`, `\errmessage` and
+ * `\show` printed, and the macro expansions if the `traceMacros` setting is
+ * on. These are kept on error, when they help most.
+ */
+#[derive(Clone, Debug)]
+pub struct RenderOutput {
+    pub markup: Result<String, ParseError>,
+    pub warnings: Vec<StrictWarning>,
+    pub diagnostics: Vec<Diagnostic>,
+    pub trace: Vec<ExpansionStep>,
+}
+
+/**
+ * Like `try_render_to_string`, but also returns the LaTeX-incompatible input
+ * accepted because strict mode (or the strict function) said "warn", in the
+ * order it was found, and the diagnostics of the render.
+ */
+pub fn try_render_to_string_with_warnings(expression: String, settings: Settings) -> RenderOutput {
+    let mut settings = settings;
+    let warnings = settings.collect_warnings();
+    let diagnostics = settings.collect_diagnostics();
+    let markup = try_render_to_string(expression, settings);
+    let warnings = std::mem::take(&mut *warnings.lock().unwrap());
+    let diagnostics = std::mem::take(&mut *diagnostics.lock().unwrap());
+    RenderOutput {
+        markup,
+        warnings,
+        diagnostics: diagnostics.messages,
+        trace: diagnostics.trace,
+    }
+}
+
+/**
+ * Returns `{markup, warnings, diagnostics, trace}`, each warning being
+ * `{code, message, position, length}`, each diagnostic
+ * `{kind, text, position, length}` and each expansion step
+ * `{name, args, tokens, position, length}`, with UTF-16 positions. A
+ * `ParseError` thrown on failure carries the same `warnings`, `diagnostics`
+ * and `trace`.
+ */
+#[cfg(feature = "wasm")]
+#[wasm_bindgen(js_name = renderToStringWithWarnings)]
+pub fn render_to_string_with_warnings_for_js(expression: String, settings: &JsValue) -> Result<JsValue, JsValue> {
+    use js_sys::{Array, Object, Reflect};
+    let output = try_render_to_string_with_warnings(expression, Settings::new_from_js(settings)?);
+    let warnings = output
+        .warnings
+        .iter()
+        .map(|warning| warning.to_js_value())
+        .collect::<Array>();
+    let diagnostics = output
+        .diagnostics
+        .iter()
+        .map(|diagnostic| diagnostic.to_js_value())
+        .collect::<Array>();
+    let trace = output.trace.iter().map(|step| step.to_js_value()).collect::<Array>();
+    let obj: JsValue = match &output.markup {
+        Ok(markup) => {
+            let obj = Object::new();
+            Reflect::set(&obj, &"markup".into(), &JsValue::from_str(markup))?;
+            obj.into()
+        }
+        Err(error) => error.to_js_error(),
+    };
+    Reflect::set(&obj, &"warnings".into(), &warnings)?;
+    Reflect::set(&obj, &"diagnostics".into(), &diagnostics)?;
+    Reflect::set(&obj, &"trace".into(), &trace)?;
+    match output.markup {
+        Ok(_) => Ok(obj),
+        Err(_) => Err(obj),
+    }
+}
+
+/**
+ * Parse an expression and return its parse tree in the JSON shape of
+ * `katex.__parse` (see `parse_node::json`).
+ */
+pub fn try_parse_to_json(expression: String, settings: Settings) -> Result<serde_json::Value, ParseError> {
+    let tree = try_parse_tree(expression, settings)?;
+    Ok(parse_tree_to_json(&tree))
+}
+
+#[cfg(feature = "wasm")]
+#[wasm_bindgen(js_name = __parse)]
+pub fn parse_for_js(expression: String, settings: &JsValue) -> Result<JsValue, JsValue> {
+    let json = try_parse_to_json(expression, Settings::new_from_js(settings)?)
+        .map_err(|error| error.to_js_error())?;
+    js_sys::JSON::parse(&json.to_string())
+}
+
+/**
+ * Parse an expression and print it back as canonical LaTeX (see
+ * `parse_node::unparse`), e.g. `\\frac12` becomes `\\frac{1}{2}`.
+ */
+pub fn try_normalize_latex(expression: String, settings: Settings) -> Result<String, ParseError> {
+    let tree = try_parse_tree(expression, settings)?;
+    Ok(parse_tree_to_latex(&tree))
+}
+
+#[cfg(feature = "wasm")]
+#[wasm_bindgen(js_name = __normalize)]
+pub fn normalize_for_js(expression: String, settings: &JsValue) -> Result<String, JsValue> {
+    try_normalize_latex(expression, Settings::new_from_js(settings)?).map_err(|error| error.to_js_error())
+}
+
+/**
+ * Build a parse tree that did not come from the parser (e.g. one read back
+ * with `parse_tree_from_json`) and return the markup for it. `expression`
+ * is only used for the MathML annotation. The tree is first held to the
+ * trust setting, the `HtmlPolicy` and the limits, as parsed input is (see
+ * `check_tree_trust` and `check_tree_limits`).
+ */
+pub fn try_render_tree_to_string(
+    mut tree: Vec<Box<dyn AnyParseNode>>,
+    expression: String,
+    settings: Settings,
+) -> Result<String, ParseError> {
+    check_tree_limits(&tree, &settings)?;
+    check_tree_trust(&mut tree, &settings)?;
+    match catch_unwind(AssertUnwindSafe(|| {
+        if settings.get_output() == "text" {
+            return Ok(crate::text::to_text(&tree, &settings));
+        }
+        let dom_tree = crate::build::build_tree(tree, expression.clone(), settings.clone())?;
+        Ok(tree_to_markup(&dom_tree, &expression, &settings))
+    })) {
+        Ok(markup) => check_output_size(markup?, &settings),
+        Err(payload) => Err(ParseError::from_panic(payload)),
+    }
+}
+
+/**
+ * Render a parse tree given as JSON in the shape of `katex.__parse`,
+ * skipping the parser. An invalid tree is always returned as `Err`,
+ * whatever `throwOnError` says. The MathML annotation is the tree printed
+ * back as LaTeX.
+ */
+pub fn try_render_json_to_string(json: &str, settings: Settings) -> Result<String, ParseError> {
+    let value: serde_json::Value = serde_json::from_str(json)
+        .map_err(|e| ParseError::new(format!("Invalid parse tree: {}", e), None))?;
+    let tree = parse_tree_from_json(&value, String::new())?;
+    let expression = parse_tree_to_latex(&tree);
+    try_render_tree_to_string(tree, expression, settings)
+}
+
+#[cfg(feature = "wasm")]
+#[wasm_bindgen(js_name = renderTreeToString)]
+pub fn render_tree_to_string_for_js(tree: &JsValue, settings: &JsValue) -> Result<String, JsValue> {
+    let json = match tree.as_string() {
+        Some(json) => json,
+        None => String::from(js_sys::JSON::stringify(tree)?),
+    };
+    try_render_json_to_string(&json, Settings::new_from_js(settings)?)
+        .map_err(|error| error.to_js_error())
 }
 
 const TEST_CASE: [&str; 1] = [
@@ -301,7 +492,11 @@ const TEST_CASE: [&str; 1] = [
 mod tests {
     use std::io::Write;
     use std::process::{Command, Stdio};
-    use crate::katex::{render, render_to_string, TEST_CASE};
+    use crate::katex::{
+        render_to_string, try_normalize_latex, try_parse_to_json, try_render_json_to_string,
+        try_render_to_string, try_render_to_string_with_warnings, TEST_CASE,
+    };
+    use crate::parse_node::json::parse_tree_from_json;
     use crate::settings::Settings;
 
     fn render_with_js_katex(expression: &str) -> String {
@@ -389,6 +584,852 @@ process.stdout.write(html);
             );
         }
     }
+
+    #[test]
+    fn test_mathml_output() {
+        let mut settings = Settings::new();
+        settings.set_output("mathml".to_string());
+        let markup = render_to_string("\\frac{a}{b^2}".to_string(), settings.clone());
+        assert_eq!(
+            markup,
+            "<span class=\"katex\"><math xmlns=\"http://www.w3.org/1998/Math/MathML\">\
+             <semantics><mrow><mfrac><mi>a</mi><msup><mi>b</mi><mn>2</mn></msup></mfrac></mrow>\
+             <annotation encoding=\"application/x-tex\">\\frac{a}{b^2}</annotation>\
+             </semantics></math></span>"
+        );
+
+        settings.set_output("htmlAndMathml".to_string());
+        let markup = render_to_string("x".to_string(), settings);
+        assert!(markup.starts_with(
+            "<span class=\"katex\"><span class=\"katex-mathml\"><math xmlns=\"http://www.w3.org/1998/Math/MathML\">"
+        ));
+        assert!(markup.contains("<span class=\"katex-html\" aria-hidden=\"true\">"));
+    }
+
+    #[test]
+    fn test_try_render_to_string() {
+        let mut settings = Settings::new();
+        settings.set_throw_on_error(true);
+        assert!(try_render_to_string("x^2".to_string(), settings.clone()).is_ok());
+
+        let error = try_render_to_string("a + \\undefinedmacro".to_string(), settings.clone())
+            .unwrap_err();
+        assert_eq!(error.raw_message(), "Undefined control sequence: \\undefinedmacro");
+        assert_eq!(error.position(), Some(4));
+        assert_eq!(error.length(), Some(15));
+
+        // Errors the parser used to panic on come back as ParseErrors too.
+        let error = try_render_to_string("a\u{305}".to_string(), settings.clone()).unwrap_err();
+        assert_eq!(error.raw_message(), "Unknown accent ' \u{305}'");
+        assert_eq!(error.position(), Some(0));
+
+        settings.set_throw_on_error(false);
+        let markup = try_render_to_string("\\frac{a".to_string(), settings).unwrap();
+        assert!(markup.starts_with("<span class=\"katex-error\""));
+    }
+
+    #[test]
+    fn test_render_json_tree() {
+        let json = try_parse_to_json("x^2".to_string(), Settings::new())
+            .unwrap()
+            .to_string()
+            .replace("\"text\":\"x\"", "\"text\":\"y\"");
+        assert_eq!(
+            try_render_json_to_string(&json, Settings::new()).unwrap(),
+            try_render_to_string("y^2".to_string(), Settings::new()).unwrap()
+        );
+        assert!(try_render_json_to_string("[{\"type\":\"nope\"}]", Settings::new()).is_err());
+        // Values the builders cannot draw fail before building, on wasm too.
+        let json = try_parse_to_json("\\Huge x".to_string(), Settings::new()).unwrap().to_string();
+        let error = try_render_json_to_string(&json.replace("\"size\":11", "\"size\":99"), Settings::new()).unwrap_err();
+        assert_eq!(error.raw_message(), "Invalid parse tree: [0]: sizing.size: expected 1 to 11, got 99");
+        // A \middle outside of \left...\right is drawn at its normal size.
+        let middle = r#"[{"type":"middle","mode":"math","delim":"|"}]"#;
+        assert!(try_render_json_to_string(middle, Settings::new()).is_ok());
+        assert!(try_render_to_string("\\left( a {\\middle|} b \\right)".to_string(), Settings::new()).is_ok());
+
+        // A tree skips the parser, not its trust checks.
+        let href = r#"{"type":"href","mode":"math","href":"javascript:alert(1)","body":[]}"#;
+        let html = r#"{"type":"html","mode":"math","attributes":{"onmouseover":"alert(2)"},"body":[]}"#;
+        let payload = format!("[{},{}]", href, html);
+        let error = try_render_json_to_string(&payload, Settings::new()).unwrap_err();
+        assert_eq!(error.raw_message(), "Untrusted \\href in parse tree");
+        let mut trusted = Settings::new();
+        trusted.set_trust(true);
+        let error = try_render_json_to_string(&payload, trusted.clone()).unwrap_err();
+        assert_eq!(error.raw_message(), "Untrusted attribute 'onmouseover' in parse tree");
+        let class = r#"[{"type":"html","mode":"math","attributes":{"class":"a"},"body":[]}]"#;
+        assert!(try_render_json_to_string(class, Settings::new()).is_err());
+        assert!(try_render_json_to_string(class, trusted.clone()).unwrap().contains("enclosing a"));
+        use crate::settings::HtmlPolicy;
+        let mut policy = HtmlPolicy::new();
+        policy.set_id_prefix("user-");
+        trusted.set_html_policy(Some(policy));
+        assert!(try_render_json_to_string(class, trusted.clone()).is_err());
+        let id = class.replace("\"class\"", "\"id\"");
+        assert!(try_render_json_to_string(&id, trusted.clone()).unwrap().contains("id=\"user-a\""));
+
+        let mut settings = Settings::new();
+        settings.set_max_parse_nodes(Some(2));
+        let json = try_parse_to_json("xyz".to_string(), Settings::new()).unwrap().to_string();
+        let error = try_render_json_to_string(&json, settings).unwrap_err();
+        assert!(error.raw_message().contains("maxParseNodes"));
+    }
+
+    #[test]
+    fn test_parse_to_json() {
+        let json = try_parse_to_json("x^2".to_string(), Settings::new()).unwrap();
+        assert_eq!(
+            json.to_string(),
+            "[{\"type\":\"supsub\",\"mode\":\"math\",\
+             \"base\":{\"type\":\"mathord\",\"mode\":\"math\",\"loc\":{\"start\":0,\"end\":1},\"text\":\"x\"},\
+             \"sup\":{\"type\":\"textord\",\"mode\":\"math\",\"loc\":{\"start\":2,\"end\":3},\"text\":\"2\"}}]"
+        );
+        // KaTeX only sets `semisimple` on the group of `\begingroup`.
+        let json = try_parse_to_json("\\begin{matrix}a\\end{matrix}".to_string(), Settings::new()).unwrap();
+        assert!(!json.to_string().contains("semisimple"));
+        let json = try_parse_to_json("\\begingroup a\\endgroup".to_string(), Settings::new()).unwrap();
+        assert!(json.to_string().contains("\"semisimple\":true"));
+        // `loc`s count UTF-16 code units, like `katex.__parse`.
+        let json = try_parse_to_json("é+x".to_string(), Settings::new()).unwrap();
+        assert_eq!(json[0]["loc"], serde_json::json!({"start": 0, "end": 1}));
+        assert_eq!(json[1]["loc"], serde_json::json!({"start": 1, "end": 2}));
+        let tree = parse_tree_from_json(&json, "é+x".to_string()).unwrap();
+        let loc = tree[1].get_loc().unwrap();
+        assert_eq!((loc.start, loc.end), (2, 3));
+    }
+
+    #[test]
+    fn test_normalize_latex() {
+        let normalize = |expression: &str| try_normalize_latex(expression.to_string(), Settings::new()).unwrap();
+        let cases = [
+            ("\\frac12", "\\frac{1}{2}"),
+            ("x ^ {{2}}", "x^{{2}}"),
+            ("{a \\over b}+\\sqrt [3] x", "{\\frac{a}{b}}+\\sqrt[3]{x}"),
+            ("\\color{red} a b", "\\textcolor{red}{ab}"),
+            ("\\left( \\begin{matrix} a & b \\end{matrix} \\right)", "\\begin{pmatrix}a&b\\end{pmatrix}"),
+            ("\\alpha b", "\\alpha b"),
+        ];
+        for (expression, expected) in cases {
+            let normalized = normalize(expression);
+            assert_eq!(normalized, expected);
+            assert_eq!(normalize(&normalized), normalized);
+        }
+
+        // These come back as the same tree, so they render the same.
+        let cases = [
+            ("\\sum\\limits_{i=0}^n", "\\sum\\limits_{i=0}^n"),
+            ("x^\\alpha_\\&", "x_\\&^\\alpha"),
+            // The group \mathbf takes here is the one \bf takes.
+            ("\\mathbf{{y}}", "\\bf y"),
+            ("\\hat{{x}} \\mathbf{{}}", "\\hat{{x}}\\mathbf{}"),
+            // A text accent keeps its base bare unless it was braced.
+            ("\\text{\\\"a\\\"{b}\\c c}", "\\text{\\\"a\\\"{b}\\c c}"),
+            ("x^{\\prime\\prime}", "x''"),
+            ("a\\mskip3mu b\\kern1em c", "a\\,b\\quad c"),
+        ];
+        for (expression, expected) in cases {
+            let normalized = normalize(expression);
+            assert_eq!(normalized, expected);
+            assert_eq!(
+                try_render_to_string(normalized, Settings::new()).unwrap(),
+                try_render_to_string(expression.to_string(), Settings::new()).unwrap()
+            );
+        }
+
+        // Shorthands are written back as such, and unsupported or untrusted
+        // commands as they were written.
+        for expression in [
+            "\\bf y",
+            "T_{{\\cal G}}",
+            "\\mathit{{a}}\\atop b",
+            "x^{'}",
+            "x''^2",
+            "x_a'^{bc}",
+            "\\not=",
+            "a\\neq b\\notin C",
+            "a\\,b\\:c\\;d\\!e\\quad f\\qquad g",
+            "\\text{a\\,b\\quad c}",
+            "\\href{https://katex.org}{x}",
+            "\\htmlClass{a}{x}",
+            "\\includegraphics[height=1em]{a.png}y",
+            "\\undefined x",
+            "\\undefined\\ y",
+        ] {
+            assert_eq!(normalize(expression), expression);
+            assert_eq!(
+                try_render_to_string(normalize(expression), Settings::new()).unwrap(),
+                try_render_to_string(expression.to_string(), Settings::new()).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_render_across_threads() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Settings>();
+        assert_send_sync::<crate::ParseError>();
+        assert_send_sync::<Vec<Box<dyn crate::AnyParseNode>>>();
+        assert_send_sync::<crate::dom_tree::span::Span>();
+
+        let mut settings = Settings::new();
+        settings.set_display_mode(true);
+        let expected = try_render_to_string("\\frac{a}{b}".to_string(), settings.clone()).unwrap();
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..4)
+                .map(|_| {
+                    let settings = &settings;
+                    scope.spawn(move || {
+                        try_render_to_string("\\frac{a}{b}".to_string(), settings.clone()).unwrap()
+                    })
+                })
+                .collect();
+            for handle in handles {
+                assert_eq!(handle.join().unwrap(), expected);
+            }
+        });
+
+        // Shared settings keep no definitions from one render to the next.
+        settings.set_throw_on_error(true);
+        settings.set_global_group(true);
+        for definition in ["\\gdef\\x{1}", "\\global\\def\\x{1}", "\\def\\x{1}", "\\newcommand\\x{1}"] {
+            try_render_to_string(definition.to_string(), settings.clone()).unwrap();
+            let error = try_render_to_string("\\x".to_string(), settings.clone()).unwrap_err();
+            assert!(error.to_string().contains("Undefined control sequence: \\x"));
+        }
+        assert!(settings.get_ref_macros().read().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_render_session() {
+        use crate::session::RenderSession;
+
+        let render = |session: &RenderSession, expression: &str| {
+            session.try_render_to_string(expression.to_string()).unwrap()
+        };
+        let plain = |expression: &str| {
+            try_render_to_string(expression.to_string(), Settings::new()).unwrap()
+        };
+
+        let mut session = RenderSession::new(Settings::new());
+        render(&session, "\\gdef\\a{x} {\\global\\def\\b{y}} \\def\\c{z} {\\def\\d{w}}");
+        assert!(session.has_macro("\\a"));
+        assert!(session.has_macro("\\b"));
+        assert!(!session.has_macro("\\c"));
+        assert!(!session.has_macro("\\d"));
+        assert_eq!(render(&session, "\\a\\b"), plain("xy"));
+
+        let snapshot = session.snapshot();
+        render(&session, "\\gdef\\a{q}");
+        assert_eq!(render(&session, "\\a"), plain("q"));
+        session.restore(&snapshot);
+        assert_eq!(render(&session, "\\a"), plain("x"));
+        session.reset();
+        assert!(!session.has_macro("\\a"));
+
+        let mut settings = Settings::new();
+        settings.set_global_group(true);
+        let session = RenderSession::new(settings.clone());
+        render(&session, "\\newcommand\\RR{\\mathbb{R}} \\def\\c{z} {\\def\\d{w}}");
+        assert!(session.has_macro("\\RR"));
+        assert!(session.has_macro("\\c"));
+        assert!(!session.has_macro("\\d"));
+        // The session does not write back to the settings it was made from.
+        assert!(!settings.get_ref_macros().read().unwrap().contains_key("\\RR"));
+        settings.set_throw_on_error(true);
+        assert!(try_render_to_string("\\RR".to_string(), settings).is_err());
+        assert!(!RenderSession::new(Settings::new()).has_macro("\\RR"));
+    }
+
+    #[test]
+    fn test_tags_and_cross_references() {
+        use crate::session::RenderSession;
+
+        let mut settings = Settings::new();
+        settings.set_display_mode(true);
+        settings.set_throw_on_error(true);
+        let render = |session: &RenderSession, expression: &str| {
+            session.try_render_to_string(expression.to_string()).unwrap()
+        };
+
+        // A \tag left over by one render does not affect the next one.
+        let session = RenderSession::new(settings.clone());
+        assert!(render(&session, "x\\tag{1}").contains("class=\"tag\""));
+        assert!(render(&session, "y\\tag*{A}").contains("class=\"tag\""));
+        let error = session.try_render_to_string("x\\tag{1}\\tag{2}".to_string()).unwrap_err();
+        assert!(error.to_string().contains("Multiple \\tag"));
+        let mut inline = settings.clone();
+        inline.set_display_mode(false);
+        let error = try_render_to_string("x\\tag{1}".to_string(), inline).unwrap_err();
+        assert!(error.to_string().contains("\\tag works only in display equations"));
+        // Without cross-references, \label and \eqref are undefined.
+        for expression in ["x \\label{a}", "x \\eqref{a}"] {
+            let error = session.try_render_to_string(expression.to_string()).unwrap_err();
+            assert!(error.to_string().contains("Undefined control sequence: \\"));
+        }
+        let mut lenient = settings.clone();
+        lenient.set_throw_on_error(false);
+        let html = try_render_to_string("x \\label{a} \\eqref{a}".to_string(), lenient.clone()).unwrap();
+        assert!(!html.contains("href"));
+        assert!(html.contains("eqref{a}"));
+        let latex = try_normalize_latex("\\label{a} x".to_string(), lenient).unwrap();
+        assert_eq!(latex, "\\label{a}x");
+
+        let mut session = RenderSession::new(settings.clone());
+        session.enable_cross_references();
+        let equation = render(&session, "\\begin{equation} E=mc^2 \\label{eq:e} \\end{equation}");
+        assert!(equation.contains("id=\"katex-eq-eq:e\""));
+        assert!(equation.contains("(1)"));
+        render(&session, "\\begin{align} a \\label{a} \\\\ b \\notag \\\\ c \\end{align}");
+        render(&session, "\\begin{gather*} d \\tag{$\\ast$} \\label{d} \\end{gather*}");
+        assert_eq!(session.label_tag("eq:e").as_deref(), Some("1"));
+        assert_eq!(session.label_tag("a").as_deref(), Some("2"));
+        assert_eq!(session.label_tag("d").as_deref(), Some("$\\ast$"));
+        assert_eq!(session.cross_references().unwrap().equation_count(), 3);
+
+        let refs = render(&session, "\\eqref{eq:e} \\ref{a} \\ref{b}");
+        assert!(refs.contains("<a href=\"#katex-eq-eq:e\"><span class=\"mord text\"><span class=\"mord\">(1)</span>"));
+        assert!(refs.contains("<a href=\"#katex-eq-a\">"));
+        assert!(refs.contains("??"));
+        assert!(session
+            .try_render_to_string("\\begin{equation} x \\label{x} \\label{y} \\end{equation}".to_string())
+            .is_err());
+
+        // A formula that fails numbers nothing and defines no label, with or
+        // without throwOnError.
+        let failing = "\\begin{equation}a\\label{q}\\end{equation}\\frac";
+        assert!(session.try_render_to_string(failing.to_string()).is_err());
+        assert!(session.try_parse_tree(failing.to_string()).is_err());
+        let mut lenient_settings = settings.clone();
+        lenient_settings.set_throw_on_error(false);
+        let mut lenient_session = RenderSession::new(lenient_settings);
+        lenient_session.enable_cross_references();
+        assert!(render(&lenient_session, failing).contains("katex-error"));
+        for session in [&session, &lenient_session] {
+            assert_eq!(session.label_tag("q"), None);
+        }
+        assert_eq!(session.cross_references().unwrap().equation_count(), 3);
+        assert_eq!(lenient_session.cross_references().unwrap().equation_count(), 0);
+        assert!(render(&lenient_session, "\\begin{equation} x \\end{equation}").contains("(1)"));
+
+        session.reset();
+        assert_eq!(session.label_tag("a"), None);
+        assert!(render(&session, "\\begin{equation} x \\end{equation}").contains("(1)"));
+    }
+
+    #[test]
+    fn test_newcommand_optional_arg() {
+        let mut settings = Settings::new();
+        settings.set_throw_on_error(true);
+        let render = |expression: &str| try_render_to_string(expression.to_string(), settings.clone());
+        let norm = "\\newcommand{\\norm}[2][2]{\\|#2\\|_{#1}}";
+
+        assert_eq!(render(&format!("{} \\norm{{v}}", norm)).unwrap(), render("\\|v\\|_{2}").unwrap());
+        assert_eq!(
+            render(&format!("{} \\norm [\\infty]{{v}}", norm)).unwrap(),
+            render("\\|v\\|_{\\infty}").unwrap()
+        );
+        // Brackets after the mandatory argument are not an optional argument.
+        assert_eq!(
+            render(&format!("{} \\norm{{v}}[0,1]", norm)).unwrap(),
+            render("\\|v\\|_{2}[0,1]").unwrap()
+        );
+        // The argument ends at the first ] outside of braces.
+        assert_eq!(
+            render("\\newcommand{\\s}[1][x]{\\sqrt{#1}} \\s[{\\sqrt[3]{y}}] \\s[{]}] \\s[[]").unwrap(),
+            render("\\sqrt{\\sqrt[3]{y}} \\sqrt{]} \\sqrt{[}").unwrap()
+        );
+        assert!(render("\\newcommand{\\s}[1][x]{#1} \\s[y").is_err());
+        // \newenvironment takes the same default for its begin code.
+        let br = "\\newenvironment{br}[2][(]{\\left#1 #2}{\\right)}";
+        assert_eq!(
+            render(&format!("{} \\begin{{br}}{{x}} y \\end{{br}}", br)).unwrap(),
+            render("\\left(x y\\right)").unwrap()
+        );
+        assert_eq!(
+            render(&format!("{} \\begin{{br}}[[]{{x}} y \\end{{br}}", br)).unwrap(),
+            render("\\left[x y\\right)").unwrap()
+        );
+        assert_eq!(render("\\newcommand\\b{[} \\b").unwrap(), render("[").unwrap());
+    }
+
+    #[test]
+    fn test_newenvironment() {
+        let mut settings = Settings::new();
+        settings.set_display_mode(true);
+        settings.set_throw_on_error(true);
+        let render = |expression: &str| try_render_to_string(expression.to_string(), settings.clone());
+
+        assert_eq!(
+            render("\\newenvironment{mat}{\\begin{pmatrix}}{\\end{pmatrix}} \\begin{mat} a&b \\end{mat}").unwrap(),
+            render("\\begin{pmatrix} a&b \\end{pmatrix}").unwrap()
+        );
+        assert_eq!(
+            render("\\newenvironment{br}[2]{\\left#1 #2}{\\right)} \\begin{br}[{x} y \\end{br}").unwrap(),
+            render("\\left[x y\\right)").unwrap()
+        );
+        assert!(render("\\renewenvironment{matrix}{\\begin{bmatrix}}{\\end{bmatrix}} \\begin{matrix}1\\end{matrix}")
+            .unwrap()
+            .contains("["));
+
+        // Definitions are local to the group, like those of \newcommand.
+        assert!(render("{\\newenvironment{foo}{a}{b}} \\begin{foo}x\\end{foo}").is_err());
+        assert!(render("\\newenvironment{foo}{\\def\\x{1}}{} \\begin{foo}\\x\\end{foo}\\x").is_err());
+        let error = render("\\newenvironment{matrix}{a}{b}").unwrap_err();
+        assert!(error.to_string().contains("use \\renewenvironment"));
+        let error = render("\\renewenvironment{foo}{a}{b}").unwrap_err();
+        assert!(error.to_string().contains("use \\newenvironment"));
+        let error = render("\\newenvironment{foo}{a}{b} \\newenvironment{baz}{c}{d} \\begin{foo} \\begin{baz} x \\end{foo}")
+            .unwrap_err();
+        assert!(error.to_string().contains("Mismatch: \\begin{baz} matched by \\end{foo}"));
+        let error = render("\\newenvironment{foo}{a}{b} x \\end{foo}").unwrap_err();
+        assert!(error.to_string().contains("Mismatch: \\end{foo} without \\begin{foo}"));
+        // Built-in environments are closed in order too, except by the end code.
+        let error = render("\\newenvironment{foo}{(}{)} \\begin{foo} x \\end{bar}").unwrap_err();
+        assert!(error.to_string().contains("Mismatch: \\begin{foo} matched by \\end{bar}"));
+        let error = render("\\newenvironment{foo}{(}{)} \\begin{foo} \\begin{matrix} x \\end{foo}").unwrap_err();
+        assert!(error.to_string().contains("Mismatch: \\begin{matrix} matched by \\end{foo}"));
+        assert_eq!(
+            render("\\newenvironment{foo}{(}{)} \\begin{foo} \\begin{matrix} x \\\\ y \\end{matrix} \\end{foo}").unwrap(),
+            render("( \\begin{matrix} x \\\\ y \\end{matrix} )").unwrap()
+        );
+        for name in ["frac", "sqrt", "end"] {
+            let error = render(&format!("\\newenvironment{{{}}}{{a}}{{b}}", name)).unwrap_err();
+            assert!(error.to_string().contains(&format!("Command \\{} already defined", name)));
+        }
+    }
+
+    #[test]
+    fn test_settings_macros() {
+        let render = |expression: &str, settings: &Settings| {
+            try_render_to_string(expression.to_string(), settings.clone()).unwrap()
+        };
+        let plain = Settings::new();
+
+        let mut settings = Settings::new();
+        settings.set_macros(std::collections::HashMap::from([
+            ("\\RR".to_string(), "\\mathbb{R}".to_string()),
+            ("\\pair".to_string(), "(#1, #2)".to_string()),
+        ]));
+        settings.add_macro("\\half", "\\frac{#1}{2}");
+        assert_eq!(render("x \\in \\RR", &settings), render("x \\in \\mathbb{R}", &plain));
+        assert_eq!(render("\\pair{a}{b}", &settings), render("(a, b)", &plain));
+        assert_eq!(render("\\half x", &settings), render("\\frac{x}{2}", &plain));
+
+        settings.set_macros(std::collections::HashMap::new());
+        let mut strict = settings.clone();
+        strict.set_throw_on_error(true);
+        assert!(try_render_to_string("\\RR".to_string(), strict).is_err());
+    }
+
+    #[test]
+    fn test_strict_mode() {
+        use crate::settings::StrictLevel;
+        let mut settings = Settings::new();
+        settings.set_display_mode(true);
+        let output = try_render_to_string_with_warnings("a \\\\ b \\kern1mu".to_string(), settings.clone());
+        assert!(output.markup.is_ok());
+        let codes: Vec<&str> = output.warnings.iter().map(|w| w.code.as_str()).collect();
+        assert_eq!(codes, vec!["newLineInDisplayMode", "mathVsTextUnits"]);
+
+        let mut strict = settings.clone();
+        strict.set_strict("error".to_string());
+        strict.set_throw_on_error(true);
+        let error = try_render_to_string("x é".to_string(), strict.clone()).unwrap_err();
+        assert!(error.raw_message().contains("[unicodeTextInMathMode]"));
+        assert_eq!(error.position(), Some(2));
+        assert!(try_render_to_string("x \\kern1mu".to_string(), strict.clone()).is_err());
+        assert!(try_render_to_string("x % comment".to_string(), strict).is_err());
+
+        settings.set_strict_function(|code, _, _| match code {
+            "unicodeTextInMathMode" => StrictLevel::Ignore,
+            _ => StrictLevel::Warn,
+        });
+        let output = try_render_to_string_with_warnings("é \\kern1mu".to_string(), settings);
+        assert_eq!(output.warnings.len(), 1);
+        assert_eq!(output.warnings[0].code, "mathVsTextUnits");
+    }
+
+    #[test]
+    fn test_preamble() {
+        use crate::preamble::Preamble;
+        let mut settings = Settings::new();
+        settings.set_throw_on_error(true);
+        let source = "% notation\n\\newcommand{\\RR}{\\mathbb{R}}\n\\def\\pair#1#2{(#1,#2)}\n\\let\\eps=\\varepsilon\n\\DeclareMathOperator{\\tr}{tr}\\DeclareMathOperator*{\\argmax}{arg\\,max}\n\\newenvironment{pmat}{\\begin{pmatrix}}{\\end{pmatrix}}\n";
+        let preamble = Preamble::parse(source, &settings).unwrap();
+        assert!(preamble.has_macro("\\RR") && preamble.has_macro("\\endpmat") && preamble.has_macro("\\operatorname"));
+
+        let formula = "\\pair{\\RR}{\\eps} \\tr A \\argmax_x \\begin{pmat}1\\end{pmat}";
+        let expected = try_render_to_string(format!("{}{}", source, formula), settings.clone()).unwrap();
+        let mut with_preamble = settings.clone();
+        with_preamble.set_preamble(Some(preamble));
+        assert_eq!(try_render_to_string(formula.to_string(), with_preamble.clone()).unwrap(), expected);
+        // The definitions do not leak into the settings' own macros.
+        assert!(settings.get_ref_macros().read().unwrap().is_empty());
+        assert!(try_render_to_string("\\newcommand\\RR{R}".to_string(), with_preamble.clone()).is_err());
+        assert!(try_render_to_string("\\renewcommand\\RR{R}\\RR".to_string(), with_preamble).is_ok());
+    }
+
+    #[test]
+    fn test_diagnostics_and_macro_trace() {
+        use crate::settings::DiagnosticKind;
+        let mut settings = Settings::new();
+        settings.set_throw_on_error(true);
+        let input = "\\def\\f#1{[#1]}\\message{hi \\alpha x}\\show\\f\\errmessage{oops}\\f{ab}\\show x";
+        let output = try_render_to_string_with_warnings(input.to_string(), settings.clone());
+        let diagnostics: Vec<String> = output.diagnostics.iter().map(|d| d.to_string()).collect();
+        assert_eq!(
+            diagnostics,
+            vec!["hi \\alpha x", "> \\f=macro:#1->[#1].", "! oops", "> the letter x."]
+        );
+        assert_eq!(output.diagnostics[0].kind, DiagnosticKind::Message);
+        assert_eq!(output.diagnostics[1].loc.as_ref().map(|loc| loc.start), Some(40));
+        assert!(output.trace.is_empty());
+        assert_eq!(output.markup.unwrap(), try_render_to_string("[ab]".to_string(), settings.clone()).unwrap());
+
+        settings.set_trace_macros(true);
+        let output = try_render_to_string_with_warnings(input.to_string(), settings.clone());
+        let step = output.trace.iter().find(|step| step.name == "\\f").unwrap();
+        assert_eq!(step.args, vec!["ab"]);
+        assert_eq!(step.tokens, vec!["[", "a", "b", "]"]);
+        assert_eq!(step.to_string(), "\\f #1<ab> ->[ab]");
+        assert_eq!(step.loc.as_ref().map(|loc| loc.start), Some(59));
+
+        // What was printed and expanded before an error is still returned.
+        let input = "\\def\\f#1{[#1]}\\f{a}\\message{hi}\\undefined";
+        let output = try_render_to_string_with_warnings(input.to_string(), settings);
+        assert!(output.markup.unwrap_err().to_string().contains("Undefined control sequence"));
+        assert_eq!(output.diagnostics.len(), 1);
+        assert_eq!(output.diagnostics[0].to_string(), "hi");
+        assert!(!output.trace.is_empty());
+    }
+
+    #[test]
+    fn test_resource_limits() {
+        let mut settings = Settings::new();
+        settings.set_throw_on_error(true);
+        let nested = |n: usize| format!("{}x{}", "{".repeat(n), "}".repeat(n));
+
+        settings.set_max_nesting_depth(Some(50));
+        assert!(try_render_to_string(nested(40), settings.clone()).is_ok());
+        let error = try_render_to_string(nested(100_000), settings.clone()).unwrap_err();
+        assert!(error.raw_message().contains("maxNestingDepth"));
+        assert_eq!(error.position(), Some(50));
+        let left_right = "\\left(".repeat(100) + "x" + &"\\right)".repeat(100);
+        let error = try_render_to_string(left_right, settings.clone()).unwrap_err();
+        assert!(error.raw_message().contains("maxNestingDepth"));
+
+        settings.set_max_parse_nodes(Some(100));
+        assert!(try_render_to_string("x".repeat(50), settings.clone()).is_ok());
+        let error = try_render_to_string("x".repeat(150), settings.clone()).unwrap_err();
+        assert!(error.raw_message().contains("maxParseNodes"));
+
+        settings.set_max_output_bytes(Some(200));
+        let error = try_render_to_string("x".repeat(50), settings.clone()).unwrap_err();
+        assert!(error.raw_message().contains("maxOutputBytes"));
+
+        let mut settings = Settings::new();
+        settings.set_throw_on_error(true);
+        settings.set_max_expand(None);
+        settings.set_time_budget(Some(50.0));
+        let error = try_render_to_string("\\def\\a{\\a}\\a".to_string(), settings.clone()).unwrap_err();
+        assert!(error.raw_message().contains("timeBudget"));
+        settings.set_throw_on_error(false);
+        assert!(try_render_to_string("\\def\\a{\\a}\\a".to_string(), settings).unwrap().contains("katex-error"));
+        // Building stops at the deadline too, here without any parsing.
+        let matrix = format!("\\begin{{matrix}}{}\\end{{matrix}}", "\\sqrt{x}\\\\".repeat(400));
+        let json = try_parse_to_json(matrix, Settings::new()).unwrap().to_string();
+        let mut settings = Settings::new();
+        settings.set_time_budget(Some(1.0));
+        let error = try_render_json_to_string(&json, settings).unwrap_err();
+        assert!(error.raw_message().contains("timeBudget"));
+
+        // With the default limit, nesting fails before it overflows a small
+        // stack, 1 MB as in wasm; unoptimized code needs several times that.
+        let stack_size = if cfg!(debug_assertions) { 4 << 20 } else { 1 << 20 };
+        let nested_renders = std::thread::Builder::new().stack_size(stack_size).spawn(|| {
+            let mut settings = Settings::new();
+            settings.set_throw_on_error(true);
+            let cases = [
+                ("\\sqrt{", "}"),
+                ("\\begin{matrix}", "\\end{matrix}"),
+                ("\\left(", "\\right)"),
+                ("\\frac{", "}{}"),
+                ("x^{", "}"),
+                ("\\boxed{", "}"),
+            ];
+            for (open, close) in cases {
+                let nested = |n: usize| format!("{}x{}", open.repeat(n), close.repeat(n));
+                let deepest = (1..).find(|&n| try_render_to_string(nested(n), settings.clone()).is_err()).unwrap();
+                assert!(deepest > 10);
+                // The parse fails, at a position, before the builders are reached.
+                for n in [deepest, 3000] {
+                    let error = try_parse_to_json(nested(n), settings.clone()).unwrap_err();
+                    assert!(error.raw_message().contains("maxNestingDepth"));
+                    assert!(error.position().is_some());
+                }
+                // A tree read back from JSON is held to the same limit. (The
+                // JSON of nested matrices is refused by serde_json first.)
+                if open == "\\begin{matrix}" {
+                    continue;
+                }
+                let json = try_parse_to_json(nested(deepest - 1), settings.clone()).unwrap().to_string();
+                assert!(try_render_json_to_string(&json, settings.clone()).is_ok());
+                let mut lowered = settings.clone();
+                lowered.set_max_nesting_depth(Some(10));
+                let error = try_render_json_to_string(&json, lowered).unwrap_err();
+                assert!(error.raw_message().contains("maxNestingDepth"));
+            }
+        });
+        nested_renders.unwrap().join().unwrap();
+    }
+
+    #[test]
+    fn test_trust_function() {
+        let mut settings = Settings::new();
+        settings.set_trust_function(|context| match context.command.as_str() {
+            "\\href" | "\\url" => context.protocol.as_deref() == Some("https"),
+            "\\htmlClass" => context.class.as_deref() == Some("note"),
+            _ => false,
+        });
+        let render = |expression: &str| {
+            try_render_to_string(expression.to_string(), settings.clone()).unwrap()
+        };
+        assert!(render("\\href{https://katex.org}{x}").contains("href=\"https://katex.org\""));
+        assert!(!render("\\href{javascript:alert(1)}{x}").contains("<a "));
+        assert!(render("\\url{https://katex.org}").contains("<a "));
+        assert!(render("\\htmlClass{note}{x}").contains("note"));
+        assert!(!render("\\htmlClass{other}{x}").contains("other\""));
+        assert!(!render("\\includegraphics{https://katex.org/a.png}").contains("<img"));
+        assert!(!render("\\htmlData{foo=bar}{x}").contains("data-foo"));
+
+        // A URL without a readable protocol is untrusted even with `trust: true`.
+        for use_function in [false, true] {
+            let mut settings = Settings::new();
+            if use_function {
+                settings.set_trust_function(|_| true);
+            } else {
+                settings.set_trust(true);
+            }
+            let html = try_render_to_string("\\href{javascript&colon;alert(1)}{x}".to_string(), settings).unwrap();
+            assert!(!html.contains("<a "));
+        }
+    }
+
+    #[test]
+    fn test_html_policy() {
+        use crate::settings::HtmlPolicy;
+        let mut policy = HtmlPolicy::new();
+        policy.allow_class_pattern("note-[a-z]+").unwrap();
+        policy.set_id_prefix("eq-");
+        policy.allow_style_property("color");
+        policy.allow_data_key("foo");
+        let mut settings = Settings::new();
+        settings.set_trust(true);
+        settings.set_html_policy(Some(policy));
+        let render = |expression: &str| {
+            try_render_to_string(expression.to_string(), settings.clone()).unwrap()
+        };
+
+        // What the policy allows is checked in `html_policy`; a rejected
+        // command renders as unsupported.
+        assert!(render("\\htmlClass{note-a note-b}{x}").contains("enclosing note-a note-b"));
+        assert!(!render("\\htmlClass{note-a katex}{x}").contains("note-a"));
+        assert!(render("\\htmlId{one}{x}").contains("id=\"eq-one\""));
+        assert!(render("\\htmlStyle{Color: red}{x}").contains("color:red;"));
+        assert!(!render("\\htmlStyle{position: fixed}{x}").contains("fixed"));
+        assert!(render("\\htmlData{foo=1}{x}").contains("data-foo=\"1\""));
+        assert!(!render("\\htmlData{foo=1, bar=2}{x}").contains("data-"));
+    }
+
+    #[test]
+    fn test_visit_parse_tree() {
+        use crate::parse::try_parse_tree;
+        use crate::parse_node::types::{mathord, AnyParseNode};
+        use crate::parse_node::unparse::parse_tree_to_latex;
+        use crate::parse_node::visit::{any_node, fold_children, walk_node, Fold, Visit, VisitMut};
+
+        struct CountMathords(usize);
+        impl Visit for CountMathords {
+            fn visit_node(&mut self, node: &Box<dyn AnyParseNode>) {
+                if node.get_type() == "mathord" {
+                    self.0 += 1;
+                }
+                walk_node(self, node);
+            }
+        }
+
+        struct RenameX;
+        impl VisitMut for RenameX {
+            fn visit_node_mut(&mut self, node: &mut Box<dyn AnyParseNode>) {
+                if let Some(ord) = node.as_mut_any().downcast_mut::<mathord>() {
+                    if ord.text == "x" {
+                        ord.text = "y".to_string();
+                    }
+                }
+                crate::parse_node::visit::walk_node_mut(self, node);
+            }
+        }
+
+        struct UnwrapSqrt;
+        impl Fold for UnwrapSqrt {
+            fn fold_node(&mut self, node: Box<dyn AnyParseNode>) -> Box<dyn AnyParseNode> {
+                let node = fold_children(self, node);
+                match node.as_any().downcast_ref::<crate::parse_node::types::sqrt>() {
+                    Some(group) => group.body.clone(),
+                    None => node,
+                }
+            }
+        }
+
+        let expression = "a^{\\sqrt{\\frac{x}{b_x}}} \\begin{matrix} x & \\text{$x$} \\end{matrix}";
+        let mut tree = try_parse_tree(expression.to_string(), Settings::new()).unwrap();
+
+        let mut count = CountMathords(0);
+        count.visit_nodes(&tree);
+        assert_eq!(count.0, 6);
+        assert!(any_node(&tree, |node| node.get_type() == "genfrac"));
+        assert!(!any_node(&tree, |node| node.get_type() == "infix"));
+
+        RenameX.visit_nodes_mut(&mut tree);
+        let tree = UnwrapSqrt.fold_nodes(tree);
+        assert_eq!(
+            parse_tree_to_latex(&tree),
+            "a^{{\\frac{y}{b_y}}}\\begin{matrix}y&\\text{\\(y\\)}\\end{matrix}"
+        );
+    }
+
+    #[test]
+    fn test_svg_output() {
+        let mut settings = Settings::new();
+        settings.set_output("svg".to_string());
+        let svg = crate::try_render_to_string("\\frac{a}{b}".to_string(), settings.clone()).unwrap();
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+        assert!(svg.contains("<title>\\frac{a}{b}</title>"));
+        // The fraction line, and glyphs as text without registered fonts.
+        assert!(svg.contains("<rect x=\"120\" y=\"-270\" width=\"370\" height=\"40\"/>"));
+        assert!(svg.contains("font-family=\"KaTeX_Math, serif\""));
+        // Each glyph written as text is reported, once per character and font.
+        let output = try_render_to_string_with_warnings("aa+".to_string(), settings.clone());
+        let missing: Vec<String> = output.diagnostics.iter().map(|d| d.to_string()).collect();
+        assert_eq!(
+            missing,
+            [
+                "Missing character: There is no a in font Math-Italic!",
+                "Missing character: There is no + in font Main-Regular!",
+            ]
+        );
+        assert_eq!(output.diagnostics[0].kind, crate::settings::DiagnosticKind::MissingCharacter);
+
+        let svg = crate::try_render_to_string("\\sqrt{x}".to_string(), settings.clone()).unwrap();
+        assert!(svg.contains("viewBox=\"0 0 400000 1080\" preserveAspectRatio=\"xMinYMin slice\""));
+
+        settings.set_error_color("#cc0000".to_string());
+        let svg = crate::try_render_to_string("\\frac{".to_string(), settings.clone()).unwrap();
+        assert!(svg.contains("fill=\"#cc0000\""));
+        assert!(crate::register_svg_font("Main-Regular", vec![1, 2, 3]).is_err());
+
+        // With a registered font, glyphs are outlines defined once and placed
+        // with <use>. The fixture (scripts/make-svg-test-font.py) has "1", "x"
+        // and U+2603 only, so "x" of Math-Italic falls back to Main-Regular.
+        // Glyphs advance by their KaTeX metrics, or by the font's advance
+        // width for characters KaTeX has none for, like U+2603.
+        let font = include_bytes!("../tests/fixtures/svg-test-font.ttf");
+        crate::register_svg_font("KaTeX_Main-Regular.ttf", font.to_vec()).unwrap();
+        assert!(crate::registered_svg_fonts().contains(&"Main-Regular".to_string()));
+        let svg = crate::try_render_to_string("1x\u{2603}\u{2603}1".to_string(), settings.clone()).unwrap();
+        assert_eq!(svg.matches("<path id=").count(), 3);
+        for (ch, path) in [
+            ("31", "M100 0L100 -700L400 -700L400 0L100 0Z"),
+            ("78", "M50 0L50 -450L550 -450L550 0L50 0Z"),
+            ("2603", "M0 0L400 -700L800 0L0 0Z"),
+        ] {
+            assert!(svg.contains(&format!("<path id=\"KaTeX_Main-Regular-{}\" d=\"{}\"/>", ch, path)));
+        }
+        let uses: Vec<&str> = svg.split("<use xlink:href=\"#KaTeX_Main-Regular-").skip(1).collect();
+        let positions: Vec<&str> = uses
+            .iter()
+            .map(|element| element.split("\"/>").next().unwrap())
+            .collect();
+        assert_eq!(
+            positions,
+            [
+                "31\" transform=\"translate(0 0)",
+                "78\" transform=\"translate(500 0)",
+                "2603\" transform=\"translate(1071.5 0)",
+                "2603\" transform=\"translate(1871.5 0)",
+                "31\" transform=\"translate(2671.5 0)",
+            ]
+        );
+        assert!(svg.contains("width=\"3.1715em\""));
+        assert!(!svg.contains("<text"));
+        let output = try_render_to_string_with_warnings("xy".to_string(), settings.clone());
+        assert_eq!(output.diagnostics.len(), 1);
+        assert_eq!(output.diagnostics[0].text, "y in font Math-Italic");
+    }
+
+    #[cfg(feature = "png")]
+    #[test]
+    fn test_png_output() {
+        use crate::PngOptions;
+        let options = PngOptions {
+            dpi: 72.0,
+            font_size: 10.0,
+            ..PngOptions::default()
+        };
+        // 12.1 pixels per em.
+        let png = crate::try_render_to_png("\\rule{1em}{0.5em}".to_string(), Settings::new(), &options).unwrap();
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        assert_eq!(u32::from_be_bytes(png[16..20].try_into().unwrap()), 13);
+        assert_eq!(u32::from_be_bytes(png[20..24].try_into().unwrap()), 7);
+        assert!(png.windows(4).any(|chunk| chunk == b"pHYs"));
+
+        let mut settings = Settings::new();
+        settings.set_throw_on_error(true);
+        assert!(crate::try_render_to_png("\\frac{".to_string(), settings, &options).is_err());
+        let options = PngOptions {
+            color: "not a color".to_string(),
+            ..PngOptions::default()
+        };
+        assert!(crate::try_render_to_png("x".to_string(), Settings::new(), &options).is_err());
+    }
+
+    #[test]
+    fn test_inline_styles() {
+        let mut settings = Settings::new();
+        settings.set_inline_styles(true);
+        let html = crate::try_render_to_string("\\frac12^{\\fbox{a}}".to_string(), settings.clone()).unwrap();
+        assert!(html.contains("font-family:KaTeX_Main, Times New Roman, serif;font-size:1.21em;"));
+        assert!(html.contains("<span class=\"vlist-r\" style=\"display:table-row;text-align:center;\">"));
+        assert!(html.contains("style=\"display:inline-block;font-size:0.7em;\""));
+        // \fbox's own border is not overridden by that of the class.
+        assert!(html.contains("border-style:solid;border-width:0.0400em;"));
+        assert!(!html.contains("border-top-width:0.04em"));
+
+        settings.set_display_mode(true);
+        let html = crate::try_render_to_string("\\begin{equation}a\\end{equation}".to_string(), settings).unwrap();
+        assert!(html.starts_with("<span class=\"katex-display\" style=\"margin-left:0;margin-right:0;margin-top:1em;display:block;"));
+        assert!(html.contains("<span class=\"eqn-num\" style=\"display:inline-block;\">(1)</span>"));
+    }
+
+    #[test]
+    fn test_text_output() {
+        let mut settings = Settings::new();
+        settings.set_output("text".to_string());
+        let text = |expression: &str| crate::try_render_to_string(expression.to_string(), settings.clone()).unwrap();
+        assert_eq!(text("\\frac12"), " 1\n───\n 2");
+        assert_eq!(text("x^2+y_i=f'"), " 2\nx  + y  = f′\n      i");
+        assert_eq!(text("\\sqrt{x}"), " _\n√x");
+        assert_eq!(text("\\begin{pmatrix}1&2\\\\3&4\\end{pmatrix}"), "⎛1  2⎞\n⎝3  4⎠");
+        assert_eq!(text("\\left(\\frac ab\\right)"), "⎛ a ⎞\n⎜───⎟\n⎝ b ⎠");
+        // Without throwOnError a parse error gives back the expression.
+        assert_eq!(text("\\frac{1}{"), "\\frac{1}{");
+        // Huge lengths are cut to a screenful rather than allocated.
+        assert_eq!(text("\\rule{100000em}{1em}").chars().count(), 200);
+        assert_eq!(text("a\\kern100000em b"), format!("a{}b", " ".repeat(200)));
+        let rule = r#"[{"type":"rule","mode":"math","width":{"number":1e300,"unit":"em"},"height":{"number":1,"unit":"em"}}]"#;
+        assert_eq!(crate::try_render_json_to_string(rule, settings.clone()).unwrap().chars().count(), 200);
+    }
 }
 
 /*****
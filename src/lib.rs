@@ -9,6 +9,7 @@ mod define;
 mod dom_tree;
 mod metrics;
 mod parse_error;
+mod preamble;
 //mod ParseError;
 //mod Setting;
 // mod Lexer;
@@ -20,11 +21,15 @@ mod mathML_tree;
 pub mod parse;
 pub mod parse_node;
 pub mod settings;
+mod session;
 mod sourceLocation;
 mod spacingData;
+mod stylesheet;
 mod stretchy;
+mod svg;
 mod svgGeometry;
 mod symbols;
+mod text;
 mod token;
 mod tree;
 mod types;
@@ -37,9 +42,27 @@ mod utils;
 mod wide_character;
 mod delimiter;
 
-pub use crate::katex::render_to_string;
-pub use crate::parse::parseTree;
+pub use crate::katex::{
+    render_to_string, try_normalize_latex, try_parse_to_json, try_render_json_to_string,
+    try_render_to_dom_tree, try_render_to_string, try_render_to_string_with_warnings,
+    try_render_tree_to_string, RenderOutput,
+};
+pub use crate::parse::{parseTree, try_parse_tree};
+pub use crate::parse_error::ParseError;
+pub use crate::preamble::Preamble;
+pub use crate::session::{MacroSnapshot, RenderSession};
+pub use crate::stylesheet::katex_css;
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::svg::load_svg_font_dir;
+pub use crate::svg::{register_svg_font, registered_svg_fonts};
+#[cfg(feature = "png")]
+pub use crate::katex::try_render_to_png;
+#[cfg(feature = "png")]
+pub use crate::svg::PngOptions;
+pub use crate::parse_node::json::{parse_tree_from_json, parse_tree_to_json};
 pub use crate::parse_node::types::AnyParseNode;
+pub use crate::parse_node::unparse::parse_tree_to_latex;
+pub use crate::parse_node::visit::{Fold, Visit, VisitMut};
 
 // When the `wee_alloc` feature is enabled, use `wee_alloc` as the global
 // allocator.
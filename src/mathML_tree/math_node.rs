@@ -3,10 +3,12 @@ use crate::units::make_em;
 use crate::utils::escape_to;
 use crate::Options::Options;
 use crate::{path_get, scriptFromCodepoint, HtmlDomNode, VirtualNode};
+#[cfg(feature = "wasm")]
 use js_sys::Array;
 use std::any::{Any, TypeId};
-use std::collections::HashMap;
+use indexmap::IndexMap;
 use std::sync::Mutex;
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 /**
  * This node represents a general purpose MathML node of any type. The
@@ -16,7 +18,7 @@ use wasm_bindgen::prelude::*;
 #[derive(Clone, Debug)]
 pub struct MathNode {
     node_type: MathNodeType,
-    attributes: HashMap<String, String>,
+    attributes: IndexMap<String, String>,
     children: Vec<Box<dyn MathDomNode>>,
     classes: Vec<String>,
 }
@@ -29,7 +31,7 @@ impl MathNode {
     ) -> MathNode {
         MathNode {
             node_type: t,
-            attributes: HashMap::new(),
+            attributes: IndexMap::new(),
             children,
             classes,
         }
@@ -56,6 +58,13 @@ impl MathNode {
     pub fn set_node_type(&mut self, t: MathNodeType) {
         self.node_type = t;
     }
+
+    pub fn get_children(&self) -> &Vec<Box<dyn MathDomNode>> {
+        &self.children
+    }
+    pub fn get_mut_children(&mut self) -> &mut Vec<Box<dyn MathDomNode>> {
+        &mut self.children
+    }
 }
 impl VirtualNode for MathNode {
     fn as_any(&self) -> &dyn Any {
@@ -69,6 +78,7 @@ impl VirtualNode for MathNode {
     /**
      * Converts the math node into a MathML-namespaced DOM element.
      */
+    #[cfg(feature = "wasm")]
     fn to_node(&self) -> web_sys::Node {
         let document = web_sys::window().expect("").document().expect("");
         let mut node = document
@@ -84,7 +94,9 @@ impl VirtualNode for MathNode {
         }
 
         // Apply the class
-        web_sys::Element::set_attribute(&node, "className", self.classes.join(" ").as_str());
+        if self.classes.len() > 0 {
+            web_sys::Element::set_attribute(&node, "class", self.classes.join(" ").as_str());
+        }
 
         for child in self.children.iter() {
             node.append_child(&child.to_node());
@@ -102,8 +114,9 @@ impl VirtualNode for MathNode {
         for (k, v) in self.attributes.iter() {
             markup.push(' ');
             markup.push_str(k);
-            markup.push('=');
+            markup.push_str("=\"");
             escape_to(&mut markup, v);
+            markup.push('"');
         }
         // Add the class
         if self.classes.len() > 0 {
@@ -135,6 +148,6 @@ impl MathDomNode for MathNode {
             .iter()
             .map(|child| child.to_text())
             .collect::<Vec<String>>()
-            .join(" ");
+            .join("");
     }
 }
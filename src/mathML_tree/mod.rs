@@ -1,4 +1,4 @@
 pub(crate) mod math_node;
 pub(crate) mod public;
 pub mod text_node;
-mod space_node;
+pub(crate) mod space_node;
@@ -3,29 +3,31 @@ use crate::units::make_em;
 use crate::utils::escape_to;
 use crate::Options::Options;
 use crate::{path_get, scriptFromCodepoint, HtmlDomNode, VirtualNode};
+#[cfg(feature = "wasm")]
 use js_sys::Array;
 use std::any::{Any, TypeId};
 use std::collections::HashMap;
 use std::sync::Mutex;
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 
 /**
  * This node represents a space, but may render as <mspace.../> or as text,
  * depending on the width.
  */
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 #[derive(Clone,Debug)]
 pub struct SpaceNode {
     width: f64,
     character: Option<String>,
 }
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 impl SpaceNode {
     /**
      * Create a Space node with width given in CSS ems.
      */
-    #[wasm_bindgen(constructor)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
     pub fn new(width: f64) -> SpaceNode {
         return SpaceNode {
             width,
@@ -64,22 +66,24 @@ impl VirtualNode for SpaceNode {
         self
     }
 
+    #[cfg(feature = "wasm")]
     fn to_node(&self) -> web_sys::Node {
-        web_sys::console::log_1(&"asdfadfasdfasdf".into());
         let document = web_sys::window().expect("").document().expect("");
         return match self.character.clone() {
             Some(c) => web_sys::Node::from(document.create_text_node(c.as_str())),
-            None => web_sys::Node::from(
-                document
+            None => {
+                let node = document
                     .create_element_ns(Some("http://www.w3.org/1998/Math/MathML"), "mspace")
-                    .expect(""),
-            ),
+                    .expect("");
+                node.set_attribute("width", make_em(self.width).as_str());
+                web_sys::Node::from(node)
+            }
         };
     }
     fn to_markup(&self) -> String {
         return match self.character.clone() {
-            Some(c) => format!(" < mtext > {c} < /mtext >"),
-            None => format!(" <mspace width = \"{}\" / >", make_em(self.width)),
+            Some(c) => format!("<mtext>{c}</mtext>"),
+            None => format!("<mspace width=\"{}\"/>", make_em(self.width)),
         };
     }
 }
@@ -92,8 +96,9 @@ impl MathDomNode for SpaceNode {
     }
 }
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 impl SpaceNode {
+    #[cfg(feature = "wasm")]
     pub fn toNode(&self) -> web_sys::Node {
         return self.to_node();
     }
@@ -3,23 +3,26 @@ use crate::units::make_em;
 use crate::utils::escape_to;
 use crate::Options::Options;
 use crate::{path_get, scriptFromCodepoint, HtmlDomNode, VirtualNode};
+#[cfg(feature = "wasm")]
 use js_sys::Array;
 use std::any::{Any, TypeId};
 use std::collections::HashMap;
 use std::sync::Mutex;
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 #[derive(Clone, Debug)]
 pub struct TextNode {
     text: String,
 }
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 impl TextNode {
-    #[wasm_bindgen(constructor)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
     pub fn new(text: String) -> TextNode {
         TextNode { text }
     }
+    #[cfg(feature = "wasm")]
     pub fn toNode(&self) -> web_sys::Node {
         return self.to_node();
     }
@@ -31,6 +34,14 @@ impl TextNode {
         return self.text.clone();
     }
 }
+impl TextNode {
+    pub fn get_text(&self) -> &String {
+        &self.text
+    }
+    pub fn set_text(&mut self, text: String) {
+        self.text = text;
+    }
+}
 impl VirtualNode for TextNode {
     fn as_any(&self) -> &dyn Any {
         self
@@ -43,6 +54,7 @@ impl VirtualNode for TextNode {
     /**
      * Converts the math node into a MathML-namespaced DOM element.
      */
+    #[cfg(feature = "wasm")]
     fn to_node(&self) -> web_sys::Node {
         let document = web_sys::window().expect("").document().expect("");
         let node = document.create_text_node(self.text.as_str());
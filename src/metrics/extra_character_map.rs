@@ -18,8 +18,7 @@ macro_rules! define_all_extra_char{
     }
 }
 lazy_static! {
-    pub static ref extra_character_map:std::sync::Mutex<std::collections::HashMap<char,char> > =std::sync::Mutex::new(
-        define_all_extra_char!{
+    pub static ref extra_character_map: std::collections::HashMap<char,char> = define_all_extra_char!{
             // Latin-1
     'Å': 'A',
     'Ð': 'D',
@@ -93,6 +92,5 @@ lazy_static! {
     'э': 'e',
     'ю': 'm',
     'я': 'r'
-}
-    );
+};
 }
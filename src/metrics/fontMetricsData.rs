@@ -1,8 +1,8 @@
 // This file is GENERATED by dockers/fonts/buildMetrics.sh. DO NOT MODIFY.
 use crate::metrics::{make_metric_map,CharacterMetrics};
-use std::{collections::HashMap,sync::Mutex};
+use std::{collections::HashMap,sync::RwLock};
 lazy_static! {
-    static ref AMS_Regular_map: Mutex<HashMap<String, CharacterMetrics> >= Mutex::new(
+    static ref AMS_Regular_map: RwLock<HashMap<String, CharacterMetrics> >= RwLock::new(
          make_metric_map!{
             "32": [0, 0, 0, 0, 0.25],
             "65": [0, 0.68889, 0, 0, 0.72222],
@@ -263,7 +263,7 @@ lazy_static! {
             "57371": [0.13597, 0.63597, 0, 0, 0.77778]
         }
     );
-    static ref Caligraphic_Regular_map: Mutex<HashMap<String, CharacterMetrics> >= Mutex::new(
+    static ref Caligraphic_Regular_map: RwLock<HashMap<String, CharacterMetrics> >= RwLock::new(
          make_metric_map!{
             "32": [0, 0, 0, 0, 0.25],
             "65": [0, 0.68333, 0, 0.19445, 0.79847],
@@ -295,7 +295,7 @@ lazy_static! {
             "160": [0, 0, 0, 0, 0.25]
         }
     );
-    static ref Fraktur_Regular_map: Mutex<HashMap<String, CharacterMetrics> >= Mutex::new(
+    static ref Fraktur_Regular_map: RwLock<HashMap<String, CharacterMetrics> >= RwLock::new(
          make_metric_map!{
             "32": [0, 0, 0, 0, 0.25],
             "33": [0, 0.69141, 0, 0, 0.29574],
@@ -392,7 +392,7 @@ lazy_static! {
             "58119": [0, 0.47534, 0, 0, 0.50073]
         }
     );
-    static ref Main_Bold_map: Mutex<HashMap<String, CharacterMetrics> >= Mutex::new(
+    static ref Main_Bold_map: RwLock<HashMap<String, CharacterMetrics> >= RwLock::new(
          make_metric_map!{
             "32": [0, 0, 0, 0, 0.25],
             "33": [0, 0.69444, 0, 0, 0.35],
@@ -648,7 +648,7 @@ lazy_static! {
             "57376": [0.19444, 0.69444, 0, 0, 0]
         }
     );
-    static ref Main_BoldItalic_map: Mutex<HashMap<String, CharacterMetrics> >= Mutex::new(
+    static ref Main_BoldItalic_map: RwLock<HashMap<String, CharacterMetrics> >= RwLock::new(
          make_metric_map!{
             "32": [0, 0, 0, 0, 0.25],
             "33": [0, 0.69444, 0.11417, 0, 0.38611],
@@ -779,7 +779,7 @@ lazy_static! {
             "8221": [0, 0.69444, 0.07939, 0, 0.62055]
         }
     );
-    static ref Main_Italic_map: Mutex<HashMap<String, CharacterMetrics> >= Mutex::new(
+    static ref Main_Italic_map: RwLock<HashMap<String, CharacterMetrics> >= RwLock::new(
          make_metric_map!{
             "32": [0, 0, 0, 0, 0.25],
             "33": [0, 0.69444, 0.12417, 0, 0.30667],
@@ -909,7 +909,7 @@ lazy_static! {
             "8463": [0, 0.68889, 0, 0, 0.54028]
         }
     );
-    static ref Main_Regular_map: Mutex<HashMap<String, CharacterMetrics> >= Mutex::new(
+    static ref Main_Regular_map: RwLock<HashMap<String, CharacterMetrics> >= RwLock::new(
          make_metric_map!{
             "32": [0, 0, 0, 0, 0.25],
             "33": [0, 0.69444, 0, 0, 0.27778],
@@ -1189,7 +1189,7 @@ lazy_static! {
             "57376": [0.19444, 0.69444, 0, 0, 0]
         }
     );
-    static ref Math_BoldItalic_map: Mutex<HashMap<String, CharacterMetrics> >= Mutex::new(
+    static ref Math_BoldItalic_map: RwLock<HashMap<String, CharacterMetrics> >= RwLock::new(
          make_metric_map!{
             "32": [0, 0, 0, 0, 0.25],
             "48": [0, 0.44444, 0, 0, 0.575],
@@ -1300,7 +1300,7 @@ lazy_static! {
             "57911": [0.19444, 0.44444, 0, 0, 0.43889]
         }
     );
-    static ref Math_Italic_map: Mutex<HashMap<String, CharacterMetrics> >= Mutex::new(
+    static ref Math_Italic_map: RwLock<HashMap<String, CharacterMetrics> >= RwLock::new(
          make_metric_map!{
             "32": [0, 0, 0, 0, 0.25],
             "48": [0, 0.43056, 0, 0, 0.5],
@@ -1411,7 +1411,7 @@ lazy_static! {
             "57911": [0.19444, 0.43056, 0, 0.08334, 0.38403]
         }
     );
-    static ref SansSerif_Bold_map: Mutex<HashMap<String, CharacterMetrics> >= Mutex::new(
+    static ref SansSerif_Bold_map: RwLock<HashMap<String, CharacterMetrics> >= RwLock::new(
          make_metric_map!{
             "32": [0, 0, 0, 0, 0.25],
             "33": [0, 0.69444, 0, 0, 0.36667],
@@ -1535,7 +1535,7 @@ lazy_static! {
             "8221": [0, 0.69444, 0, 0, 0.55834]
         }
     );
-    static ref SansSerif_Italic_map: Mutex<HashMap<String, CharacterMetrics> >= Mutex::new(
+    static ref SansSerif_Italic_map: RwLock<HashMap<String, CharacterMetrics> >= RwLock::new(
          make_metric_map!{
             "32": [0, 0, 0, 0, 0.25],
             "33": [0, 0.69444, 0.05733, 0, 0.31945],
@@ -1660,7 +1660,7 @@ lazy_static! {
             "8221": [0, 0.69444, 0.00316, 0, 0.5]
         }
     );
-    static ref SansSerif_Regular_map: Mutex<HashMap<String, CharacterMetrics> >= Mutex::new(
+    static ref SansSerif_Regular_map: RwLock<HashMap<String, CharacterMetrics> >= RwLock::new(
          make_metric_map!{
             "32": [0, 0, 0, 0, 0.25],
             "33": [0, 0.69444, 0, 0, 0.31945],
@@ -1785,7 +1785,7 @@ lazy_static! {
             "8221": [0, 0.69444, 0, 0, 0.5]
         }
     );
-    static ref Script_Regular_map: Mutex<HashMap<String, CharacterMetrics> >= Mutex::new(
+    static ref Script_Regular_map: RwLock<HashMap<String, CharacterMetrics> >= RwLock::new(
          make_metric_map!{
             "32": [0, 0, 0, 0, 0.25],
             "65": [0, 0.7, 0.22925, 0, 0.80253],
@@ -1817,7 +1817,7 @@ lazy_static! {
             "160": [0, 0, 0, 0, 0.25]
         }
     );
-    static ref Size1_Regular_map: Mutex<HashMap<String, CharacterMetrics> >= Mutex::new(
+    static ref Size1_Regular_map: RwLock<HashMap<String, CharacterMetrics> >= RwLock::new(
          make_metric_map!{
             "32": [0, 0, 0, 0, 0.25],
             "40": [0.35001, 0.85, 0, 0, 0.45834],
@@ -1866,7 +1866,7 @@ lazy_static! {
             "10758": [0.25001, 0.75, 0, 0, 0.83334]
         }
     );
-    static ref Size2_Regular_map: Mutex<HashMap<String, CharacterMetrics> >= Mutex::new(
+    static ref Size2_Regular_map: RwLock<HashMap<String, CharacterMetrics> >= RwLock::new(
          make_metric_map!{
             "32": [0, 0, 0, 0, 0.25],
             "40": [0.65002, 1.15, 0, 0, 0.59722],
@@ -1907,7 +1907,7 @@ lazy_static! {
             "10758": [0.55001, 1.05, 0, 0, 1.11111]
         }
     );
-    static ref Size3_Regular_map: Mutex<HashMap<String, CharacterMetrics> >= Mutex::new(
+    static ref Size3_Regular_map: RwLock<HashMap<String, CharacterMetrics> >= RwLock::new(
          make_metric_map!{
             "32": [0, 0, 0, 0, 0.25],
             "40": [0.95003, 1.45, 0, 0, 0.73611],
@@ -1932,7 +1932,7 @@ lazy_static! {
             "10217": [0.95003, 1.45, 0, 0, 0.75]
         }
     );
-    static ref Size4_Regular_map: Mutex<HashMap<String, CharacterMetrics> >= Mutex::new(
+    static ref Size4_Regular_map: RwLock<HashMap<String, CharacterMetrics> >= RwLock::new(
          make_metric_map!{
             "32": [0, 0, 0, 0, 0.25],
             "40": [1.25003, 1.75, 0, 0, 0.79167],
@@ -1983,7 +1983,7 @@ lazy_static! {
             "57683": [0, 0.12, 0, 0, 0.45]
         }
     );
-    static ref Typewriter_Regular_map: Mutex<HashMap<String, CharacterMetrics> >= Mutex::new(
+    static ref Typewriter_Regular_map: RwLock<HashMap<String, CharacterMetrics> >= RwLock::new(
          make_metric_map!{
             "32": [0, 0, 0, 0, 0.525],
             "33": [0, 0.61111, 0, 0, 0.525],
@@ -2116,47 +2116,47 @@ lazy_static! {
 }
 pub fn get_char_metrics(font:&str,character:String)->Option<CharacterMetrics>{
     match font {
-        "AMS-Regular" => AMS_Regular_map.lock().unwrap().get(&character).copied(),
-        "Caligraphic-Regular" => Caligraphic_Regular_map.lock().unwrap().get(&character).copied(),
-        "Fraktur-Regular" => Fraktur_Regular_map.lock().unwrap().get(&character).copied(),
-        "Main-Bold" => Main_Bold_map.lock().unwrap().get(&character).copied(),
-        "Main-BoldItalic" => Main_BoldItalic_map.lock().unwrap().get(&character).copied(),
-        "Main-Italic" => Main_Italic_map.lock().unwrap().get(&character).copied(),
-        "Main-Regular" => Main_Regular_map.lock().unwrap().get(&character).copied(),
-        "Math-BoldItalic" => Math_BoldItalic_map.lock().unwrap().get(&character).copied(),
-        "Math-Italic" => Math_Italic_map.lock().unwrap().get(&character).copied(),
-        "SansSerif-Bold" => SansSerif_Bold_map.lock().unwrap().get(&character).copied(),
-        "SansSerif-Italic" => SansSerif_Italic_map.lock().unwrap().get(&character).copied(),
-        "SansSerif-Regular" => SansSerif_Regular_map.lock().unwrap().get(&character).copied(),
-        "Script-Regular" => Script_Regular_map.lock().unwrap().get(&character).copied(),
-        "Size1-Regular" => Size1_Regular_map.lock().unwrap().get(&character).copied(),
-        "Size2-Regular" => Size2_Regular_map.lock().unwrap().get(&character).copied(),
-        "Size3-Regular" => Size3_Regular_map.lock().unwrap().get(&character).copied(),
-        "Size4-Regular" => Size4_Regular_map.lock().unwrap().get(&character).copied(),
-        "Typewriter-Regular" => Typewriter_Regular_map.lock().unwrap().get(&character).copied(),
+        "AMS-Regular" => AMS_Regular_map.read().unwrap().get(&character).copied(),
+        "Caligraphic-Regular" => Caligraphic_Regular_map.read().unwrap().get(&character).copied(),
+        "Fraktur-Regular" => Fraktur_Regular_map.read().unwrap().get(&character).copied(),
+        "Main-Bold" => Main_Bold_map.read().unwrap().get(&character).copied(),
+        "Main-BoldItalic" => Main_BoldItalic_map.read().unwrap().get(&character).copied(),
+        "Main-Italic" => Main_Italic_map.read().unwrap().get(&character).copied(),
+        "Main-Regular" => Main_Regular_map.read().unwrap().get(&character).copied(),
+        "Math-BoldItalic" => Math_BoldItalic_map.read().unwrap().get(&character).copied(),
+        "Math-Italic" => Math_Italic_map.read().unwrap().get(&character).copied(),
+        "SansSerif-Bold" => SansSerif_Bold_map.read().unwrap().get(&character).copied(),
+        "SansSerif-Italic" => SansSerif_Italic_map.read().unwrap().get(&character).copied(),
+        "SansSerif-Regular" => SansSerif_Regular_map.read().unwrap().get(&character).copied(),
+        "Script-Regular" => Script_Regular_map.read().unwrap().get(&character).copied(),
+        "Size1-Regular" => Size1_Regular_map.read().unwrap().get(&character).copied(),
+        "Size2-Regular" => Size2_Regular_map.read().unwrap().get(&character).copied(),
+        "Size3-Regular" => Size3_Regular_map.read().unwrap().get(&character).copied(),
+        "Size4-Regular" => Size4_Regular_map.read().unwrap().get(&character).copied(),
+        "Typewriter-Regular" => Typewriter_Regular_map.read().unwrap().get(&character).copied(),
         _ => None
     }
 }
 pub fn set_char_metrics(font:&str,character:String, target:CharacterMetrics)->Option<CharacterMetrics>{
     match font{
-        "AMS-Regular" => AMS_Regular_map.lock().unwrap().insert(character,target),
-        "Caligraphic-Regular" => Caligraphic_Regular_map.lock().unwrap().insert(character,target),
-        "Fraktur-Regular" => Fraktur_Regular_map.lock().unwrap().insert(character,target),
-        "Main-Bold" => Main_Bold_map.lock().unwrap().insert(character,target),
-        "Main-BoldItalic" => Main_BoldItalic_map.lock().unwrap().insert(character,target),
-        "Main-Italic" => Main_Italic_map.lock().unwrap().insert(character,target),
-        "Main-Regular" => Main_Regular_map.lock().unwrap().insert(character,target),
-        "Math-BoldItalic" => Math_BoldItalic_map.lock().unwrap().insert(character,target),
-        "Math-Italic" => Math_Italic_map.lock().unwrap().insert(character,target),
-        "SansSerif-Bold" => SansSerif_Bold_map.lock().unwrap().insert(character,target),
-        "SansSerif-Italic" => SansSerif_Italic_map.lock().unwrap().insert(character,target),
-        "SansSerif-Regular" => SansSerif_Regular_map.lock().unwrap().insert(character,target),
-        "Script-Regular" => Script_Regular_map.lock().unwrap().insert(character,target),
-        "Size1-Regular" => Size1_Regular_map.lock().unwrap().insert(character,target),
-        "Size2-Regular" => Size2_Regular_map.lock().unwrap().insert(character,target),
-        "Size3-Regular" => Size3_Regular_map.lock().unwrap().insert(character,target),
-        "Size4-Regular" => Size4_Regular_map.lock().unwrap().insert(character,target),
-        "Typewriter-Regular" => Typewriter_Regular_map.lock().unwrap().insert(character,target),
+        "AMS-Regular" => AMS_Regular_map.write().unwrap().insert(character,target),
+        "Caligraphic-Regular" => Caligraphic_Regular_map.write().unwrap().insert(character,target),
+        "Fraktur-Regular" => Fraktur_Regular_map.write().unwrap().insert(character,target),
+        "Main-Bold" => Main_Bold_map.write().unwrap().insert(character,target),
+        "Main-BoldItalic" => Main_BoldItalic_map.write().unwrap().insert(character,target),
+        "Main-Italic" => Main_Italic_map.write().unwrap().insert(character,target),
+        "Main-Regular" => Main_Regular_map.write().unwrap().insert(character,target),
+        "Math-BoldItalic" => Math_BoldItalic_map.write().unwrap().insert(character,target),
+        "Math-Italic" => Math_Italic_map.write().unwrap().insert(character,target),
+        "SansSerif-Bold" => SansSerif_Bold_map.write().unwrap().insert(character,target),
+        "SansSerif-Italic" => SansSerif_Italic_map.write().unwrap().insert(character,target),
+        "SansSerif-Regular" => SansSerif_Regular_map.write().unwrap().insert(character,target),
+        "Script-Regular" => Script_Regular_map.write().unwrap().insert(character,target),
+        "Size1-Regular" => Size1_Regular_map.write().unwrap().insert(character,target),
+        "Size2-Regular" => Size2_Regular_map.write().unwrap().insert(character,target),
+        "Size3-Regular" => Size3_Regular_map.write().unwrap().insert(character,target),
+        "Size4-Regular" => Size4_Regular_map.write().unwrap().insert(character,target),
+        "Typewriter-Regular" => Typewriter_Regular_map.write().unwrap().insert(character,target),
         _ => None
     }
 }
\ No newline at end of file
@@ -28,7 +28,7 @@ pub fn get_character_metrics(
         None => return None,
     };
     let mut metrics = get_char_metrics(font, (ch as u32).to_string());
-    let _extra_char = extra_character_map.lock().unwrap();
+    let _extra_char = &*extra_character_map;
     let tmp_ch = _extra_char.get(&ch);
     if metrics.is_none() && tmp_ch.is_some() {
         metrics = get_char_metrics(font, (tmp_ch.unwrap().to_owned() as u32).to_string());
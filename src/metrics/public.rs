@@ -1,6 +1,7 @@
 use crate::types::Mode;
 use crate::unicodeScripts::supportedCodepoint;
 use std::collections::HashMap;
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 /**
  * This file contains metrics regarding fonts and individual symbols. The sigma
@@ -179,7 +180,7 @@ use wasm_bindgen::prelude::*;
 //     'ю': 'm',
 //     'я': 'r',
 // // };
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 #[derive(Debug,Default,Clone,Copy)]
 pub struct CharacterMetrics {
     pub depth: f64,
@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 
 
@@ -62,7 +63,7 @@ use wasm_bindgen::prelude::*;
 // };
 
 #[derive(Debug,Clone, PartialEq)]
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 pub struct FontMetrics {
     pub slant: f64,
     pub space: f64,
@@ -1,9 +1,18 @@
 use crate::{
     parse_error::ParseError,
-    parse_node::types::AnyParseNode,
-    settings::Settings,
+    parse_node::{
+        self,
+        types::AnyParseNode,
+        visit::{walk_node, walk_node_mut, Visit, VisitMut},
+    },
+    settings::{Settings, TrustContext},
+    sourceLocation::SourceLocation,
+    types::Mode,
     Parser::Parser,
 };
+use indexmap::IndexMap;
+use regex::Regex;
+use std::panic::{catch_unwind, AssertUnwindSafe};
 
 // @flow
 /**
@@ -20,36 +29,232 @@ pub fn parse_tree_with_error(
 ) -> Result<Vec<Box<dyn AnyParseNode>>, ParseError> {
     let mut parser = Parser::new(to_parse, &settings);
 
-    // Blank out any \df@tag to avoid spurious "Duplicate \tag" errors
-    // delete parser.gullet.macros.current["\\df@tag"];
+    // Blank out any \df@tag to avoid spurious "Duplicate \tag" errors, and
+    // any \label left over by a failed render.
+    for name in ["\\df@tag", "\\df@tagref", "\\df@label"] {
+        parser.gullet.macros.set(&name.to_string(), None, true);
+    }
 
-    let tree = parser.parse();
+    let mut tree = parser.parse();
     if let Some(error) = parser.take_error() {
         return Err(error);
     }
 
     // Prevent a color definition from persisting between calls to katex.render().
-    // delete parser.gullet.macros.current["\\current@color"];
-    // delete parser.gullet.macros.current["\\color"];
+    parser.gullet.macros.set(&"\\current@color".to_string(), None, true);
+    parser.gullet.macros.set(&"\\color".to_string(), None, true);
 
     // If the input used \tag, it will set the \df@tag macro to the tag.
     // In this case, we separately parse the tag and wrap the tree.
-    // if (parser.gullet.macros.get("\\df@tag")) {
-    //     if (!settings.displayMode) {
-    //         throw new ParseError("\\tag works only in display equations");
-    //     }
-    //     tree = [{
-    //         type: "tag",
-    //         mode: "text",
-    //         body: tree,
-    //         tag: parser.subparse([new Token("\\df@tag")]),
-    //     }];
-    // }
+    if parser.gullet.macros.get("\\df@tag").is_some() {
+        if !settings.get_display_mode() {
+            return Err(ParseError::new(
+                "\\tag works only in display equations".to_string(),
+                None,
+            ));
+        }
+        let tag = parser.take_equation_tag(false).unwrap_or_default();
+        if let Some(error) = parser.take_error() {
+            return Err(error);
+        }
+        tree = vec![Box::new(parse_node::types::tag {
+            mode: Mode::text,
+            loc: None,
+            body: tree,
+            tag,
+        }) as Box<dyn AnyParseNode>];
+    }
+    // A \label outside of a numbered equation refers to nothing.
+    parser.gullet.macros.set(&"\\df@label".to_string(), None, true);
 
+    check_tree_limits(&tree, &settings)?;
     Ok(tree)
 }
 
+/**
+ * Checks `maxNestingDepth` and `maxParseNodes` against the nodes of a parse
+ * tree, the root nodes being at depth 1. The builders recurse once or a few
+ * times per node, so a tree that passes builds without running out of
+ * stack. The parser checks its own recursion as it goes; this runs on what
+ * it returns, and on trees that skip it, such as those read from JSON.
+ */
+pub fn check_tree_limits(tree: &[Box<dyn AnyParseNode>], settings: &Settings) -> Result<(), ParseError> {
+    let mut limits = TreeLimits {
+        max_depth: settings.get_max_nesting_depth(),
+        max_nodes: settings.get_max_parse_nodes(),
+        depth: 0,
+        nodes: 0,
+        error: None,
+    };
+    limits.visit_nodes(tree);
+    match limits.error {
+        Some(error) => Err(error),
+        None => Ok(()),
+    }
+}
+
+struct TreeLimits {
+    max_depth: Option<usize>,
+    max_nodes: Option<usize>,
+    depth: usize,
+    nodes: usize,
+    error: Option<ParseError>,
+}
+
+impl Visit for TreeLimits {
+    fn visit_node(&mut self, node: &Box<dyn AnyParseNode>) {
+        if self.error.is_some() {
+            return;
+        }
+        self.depth += 1;
+        self.nodes += 1;
+        let msg = if self.max_depth.is_some_and(|max| self.depth > max) {
+            Some("Too deeply nested: need to increase maxNestingDepth setting")
+        } else if self.max_nodes.is_some_and(|max| self.nodes > max) {
+            Some("Too many parse nodes: need to increase maxParseNodes setting")
+        } else {
+            None
+        };
+        match msg {
+            Some(msg) => self.error = Some(ParseError::new(msg.to_string(), first_loc(node))),
+            None => walk_node(self, node),
+        }
+        self.depth -= 1;
+    }
+}
+
+lazy_static! {
+    // `data-` attribute names that are safe to write into markup unquoted.
+    static ref DATA_ATTRIBUTE: Regex = Regex::new(r"^data-[A-Za-z0-9_.:-]+$").unwrap();
+}
+
+/**
+ * Checks a parse tree that did not come from the parser (e.g. one read back
+ * from JSON) as the parser would have: `\href`/`\url` and
+ * `\includegraphics` URLs against `trust`, and the attributes of the
+ * `\html*` commands against `trust` and the `HtmlPolicy`, rewriting them as
+ * the policy says. Only `class`, `id`, `style` and `data-*` attributes are
+ * accepted. A node that fails is an error, since a tree cannot be rendered
+ * with a command in place of the node as parsed input is.
+ */
+pub fn check_tree_trust(tree: &mut [Box<dyn AnyParseNode>], settings: &Settings) -> Result<(), ParseError> {
+    let mut trust = TreeTrust { settings, error: None };
+    trust.visit_nodes_mut(tree);
+    match trust.error {
+        Some(error) => Err(error),
+        None => Ok(()),
+    }
+}
+
+struct TreeTrust<'a> {
+    settings: &'a Settings,
+    error: Option<ParseError>,
+}
+
+impl TreeTrust<'_> {
+    fn check(&self, node: &mut Box<dyn AnyParseNode>) -> Result<(), String> {
+        let url_context = |command: &str, url: &String| TrustContext {
+            command: command.to_string(),
+            url: Some(url.clone()),
+            ..Default::default()
+        };
+        let any = node.as_mut_any();
+        if let Some(href) = any.downcast_ref::<parse_node::types::href>() {
+            if !self.settings.is_trusted(&url_context("\\href", &href.href)) {
+                return Err("\\href".to_string());
+            }
+        } else if let Some(graphics) = any.downcast_ref::<parse_node::types::includegraphics>() {
+            if !self.settings.is_trusted(&url_context("\\includegraphics", &graphics.src)) {
+                return Err("\\includegraphics".to_string());
+            }
+        } else if let Some(html) = any.downcast_mut::<parse_node::types::html>() {
+            let mut data = IndexMap::new();
+            for (name, value) in html.attributes.iter() {
+                let mut context = TrustContext::default();
+                match name.as_str() {
+                    "class" => (context.command, context.class) = ("\\htmlClass".to_string(), Some(value.clone())),
+                    "id" => (context.command, context.id) = ("\\htmlId".to_string(), Some(value.clone())),
+                    "style" => (context.command, context.style) = ("\\htmlStyle".to_string(), Some(value.clone())),
+                    _ if DATA_ATTRIBUTE.is_match(name) => {
+                        data.insert(name.clone(), value.clone());
+                        continue;
+                    }
+                    _ => return Err(format!("attribute '{}'", name)),
+                }
+                if !self.settings.is_trusted(&context) {
+                    return Err(context.command);
+                }
+            }
+            if !data.is_empty() {
+                let context = TrustContext {
+                    command: "\\htmlData".to_string(),
+                    attributes: Some(data),
+                    ..Default::default()
+                };
+                if !self.settings.is_trusted(&context) {
+                    return Err(context.command);
+                }
+            }
+            if let Some(policy) = self.settings.get_html_policy() {
+                html.attributes = policy
+                    .sanitize(html.attributes.clone())
+                    .ok_or_else(|| "HTML attributes".to_string())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl VisitMut for TreeTrust<'_> {
+    fn visit_node_mut(&mut self, node: &mut Box<dyn AnyParseNode>) {
+        if self.error.is_some() {
+            return;
+        }
+        match self.check(node) {
+            Ok(()) => walk_node_mut(self, node),
+            Err(what) => {
+                self.error = Some(ParseError::new(
+                    format!("Untrusted {} in parse tree", what),
+                    node.get_loc().cloned(),
+                ))
+            }
+        }
+    }
+}
+
+/** The location of `node`, or else of the first node inside it that has one. */
+fn first_loc(node: &Box<dyn AnyParseNode>) -> Option<SourceLocation> {
+    struct FirstLoc(Option<SourceLocation>);
+    impl Visit for FirstLoc {
+        fn visit_node(&mut self, node: &Box<dyn AnyParseNode>) {
+            if self.0.is_none() {
+                self.0 = node.get_loc().cloned();
+                walk_node(self, node);
+            }
+        }
+    }
+    let mut visitor = FirstLoc(None);
+    visitor.visit_node(node);
+    visitor.0
+}
+
+/**
+ * Like `parse_tree_with_error`, but also turns panics raised while parsing
+ * into a ParseError, so callers never need `catch_unwind`. The parser reports
+ * its errors without panicking; this is only a fallback for native builds,
+ * as panics abort in wasm.
+ */
+pub fn try_parse_tree(
+    to_parse: String,
+    settings: Settings,
+) -> Result<Vec<Box<dyn AnyParseNode>>, ParseError> {
+    match catch_unwind(AssertUnwindSafe(|| parse_tree_with_error(to_parse, settings))) {
+        Ok(result) => result,
+        Err(payload) => Err(ParseError::from_panic(payload)),
+    }
+}
+
 pub fn  parseTree(toParse: String, settings: Settings)-> Vec<Box::<dyn AnyParseNode>> { 
-    parse_tree_with_error(toParse, settings)
+    try_parse_tree(toParse, settings)
         .unwrap_or_else(|error| panic!("ParseError: {}", error))
 }
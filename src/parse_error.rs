@@ -1,4 +1,6 @@
-use std::fmt::{self, Error};
+use std::fmt;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::JsValue;
 
 /**
  * This is the ParseError class, which is the main error thrown by KaTeX
@@ -8,7 +10,7 @@ use std::fmt::{self, Error};
  * If possible, a caller should provide a Token or ParseNode with information
  * about where in the source string the problem occurred.
  */
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ParseError {
     pub msg: String,
     pub loc: Option<crate::sourceLocation::SourceLocation>,
@@ -17,69 +19,137 @@ pub struct ParseError {
 impl fmt::Display for ParseError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut error: String = format!("KaTeX parse error: {}", self.msg);
-        let mut start: usize = 0;
-        if let Some(loc) = &self.loc {
-            if loc.start <= loc.end {
-                // If we have the input and a position, make the error a bit fancier
-
-                // Get the input
-                let input = loc.lexer.get_input();
-
-                // Prepend some information
-                start = loc.start as usize;
-                let end: usize = loc.end as usize;
-                if start == input.len() as usize {
-                    error += " at end of input: ";
-                } else {
-                    let display_pos = input[..start].chars().count() + 1;
-                    error.push_str(&format!(" at position {}: ", display_pos));
-                }
-
-                // Underline token in question using combining underscores
-                let underlined: String = input[start..end]
-                    .chars()
-                    .flat_map(|c| [c, '\u{0332}'])
-                    .collect();
-
-                // Extract some context from the input and add it to the error
-                let left;
-                if start > 15 {
-                    left = format!("…{}", &input[start - 15..start]);
-                } else {
-                    left = String::from(&input[0..start]);
-                }
-                let right = if end + 15 < input.len() {
-                    format!("{}…", &input[end..end + 15])
-                } else {
-                    String::from(&input[end..])
-                };
-                error.push_str(&format!("{}{}{}", left, underlined, right));
+        if let Some((input, start, end)) = self.range() {
+            // If we have the input and a position, make the error a bit fancier
+
+            // Prepend some information
+            if start == input.len() {
+                error += " at end of input: ";
+            } else {
+                let display_pos = input[..start].chars().count() + 1;
+                error.push_str(&format!(" at position {}: ", display_pos));
             }
+
+            // Underline token in question using combining underscores
+            let underlined: String = input[start..end]
+                .chars()
+                .flat_map(|c| [c, '\u{0332}'])
+                .collect();
+
+            // Extract some context from the input and add it to the error
+            let before = &input[..start];
+            let left = match before.char_indices().rev().nth(14) {
+                Some((i, _)) if i > 0 => format!("…{}", &before[i..]),
+                _ => before.to_string(),
+            };
+            let after = &input[end..];
+            let right = match after.char_indices().nth(15) {
+                Some((i, _)) => format!("{}…", &after[..i]),
+                None => after.to_string(),
+            };
+            error.push_str(&format!("{}{}{}", left, underlined, right));
         }
         return write!(f, "{}", error);
     }
 }
 
-// impl std::error::Error for ParseError {
-//     fn description(&self) -> &str {
-//     }
-
-//     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
-//         None
-//     }
-
-//     fn type_id(&self, _: private::Internal) -> std::any::TypeId
-//     where
-//         Self: 'static,
-//     {
-//         std::any::TypeId::of::<Self>()
-//     }
-
-//     fn backtrace(&self) -> Option<&std::backtrace::Backtrace> {
-//         None
-//     }
-
-//     fn cause(&self) -> Option<&dyn std::error::Error> {
-//         self.source()
-//     }
-// }
+impl std::error::Error for ParseError {}
+
+impl ParseError {
+    pub fn new(msg: String, loc: Option<crate::sourceLocation::SourceLocation>) -> ParseError {
+        ParseError { msg, loc }
+    }
+
+    /**
+     * Builds a ParseError out of a panic payload caught while parsing or
+     * building, so internal errors surface the same way as parse errors.
+     */
+    pub fn from_panic(payload: Box<dyn std::any::Any + Send>) -> ParseError {
+        let msg = if let Some(message) = payload.downcast_ref::<String>() {
+            message.clone()
+        } else if let Some(message) = payload.downcast_ref::<&str>() {
+            message.to_string()
+        } else {
+            "internal render error".to_string()
+        };
+        ParseError { msg, loc: None }
+    }
+
+    /** The error message without the "KaTeX parse error:" prefix and context. */
+    pub fn raw_message(&self) -> &str {
+        &self.msg
+    }
+
+    /**
+     * The input and the byte range of the offending token in it, if the
+     * `loc` is known and fits the input (one read from JSON may not).
+     */
+    fn range(&self) -> Option<(&str, usize, usize)> {
+        let loc = self.loc.as_ref()?;
+        let input = loc.lexer.get_input();
+        let offset = |i: i32| usize::try_from(i).ok().filter(|&i| input.is_char_boundary(i));
+        let (start, end) = (offset(loc.start)?, offset(loc.end)?);
+        (start <= end).then_some((input, start, end))
+    }
+
+    /** Byte offset of the offending token in the input, if known. */
+    pub fn position(&self) -> Option<usize> {
+        self.range().map(|(_, start, _)| start)
+    }
+
+    /** Length in bytes of the offending token in the input, if known. */
+    pub fn length(&self) -> Option<usize> {
+        self.range().map(|(_, start, end)| end - start)
+    }
+
+    /**
+     * Converts the error into a JS exception shaped like KaTeX.js's
+     * ParseError: an `Error` named "ParseError" carrying `position`,
+     * `length` and `rawMessage`. Positions are UTF-16 offsets, as in JS.
+     */
+    #[cfg(feature = "wasm")]
+    pub fn to_js_error(&self) -> JsValue {
+        let error = js_sys::Error::new(&self.to_string());
+        error.set_name("ParseError");
+        let (position, length) = match self.range() {
+            Some((input, start, end)) => {
+                (
+                    JsValue::from(input[..start].encode_utf16().count() as u32),
+                    JsValue::from(input[start..end].encode_utf16().count() as u32),
+                )
+            }
+            None => (JsValue::UNDEFINED, JsValue::UNDEFINED),
+        };
+        let _ = js_sys::Reflect::set(&error, &"position".into(), &position);
+        let _ = js_sys::Reflect::set(&error, &"length".into(), &length);
+        let _ = js_sys::Reflect::set(&error, &"rawMessage".into(), &JsValue::from_str(&self.msg));
+        error.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ParseError;
+    use crate::sourceLocation::SourceLocation;
+
+    fn error_at(input: &str, start: i32, end: i32) -> ParseError {
+        let lexer = crate::Lexer::source_interface(input.to_string());
+        ParseError::new("Oops".to_string(), Some(SourceLocation { lexer, start, end }))
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(error_at("a+b", 1, 2).to_string(), "KaTeX parse error: Oops at position 2: a+\u{332}b");
+        assert_eq!(error_at("ab", 2, 2).to_string(), "KaTeX parse error: Oops at end of input: ab");
+        assert_eq!(
+            error_at("ééééééééééééééééé+ééééééééééééééééé", 34, 35).to_string(),
+            "KaTeX parse error: Oops at position 18: …ééééééééééééééé+\u{332}ééééééééééééééé…"
+        );
+        // A loc that does not fit the input is left out rather than sliced.
+        for (start, end) in [(-1, 1), (0, 9), (2, 1), (1, 2)] {
+            let error = error_at("éa", start, end);
+            assert_eq!(error.to_string(), "KaTeX parse error: Oops");
+            assert_eq!((error.position(), error.length()), (None, None));
+        }
+    }
+}
@@ -0,0 +1,820 @@
+use crate::build::common::FONT_MAP;
+use crate::define::environments::array::{Align, AlignSpec, Separator};
+use crate::define::functions::is_delimiter;
+use crate::parse_error::ParseError;
+use crate::parse_node::types::{self, AnyParseNode, ArrayTag, Atom, ColSeparationType};
+use crate::sourceLocation::{LexerInterface, SourceLocation};
+use crate::stretchy::is_stretchy_label;
+use crate::symbols::get_symbol;
+use crate::token::Token;
+use crate::types::{Mode, StyleStr};
+use crate::units::Measurement;
+use indexmap::IndexMap;
+use serde_json::{Map, Value};
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::str::FromStr;
+use std::sync::{Arc, Weak};
+
+/**
+ * Conversion of parse node fields into the JSON shape `katex.__parse`
+ * produces. `None` means the field is left out, the same way
+ * `JSON.stringify` drops `undefined` properties.
+ *
+ * `loc` only keeps `start` and `end`, in UTF-16 code units as JS counts
+ * them; the lexer it points to is omitted.
+ */
+pub trait ToJson {
+    fn to_json(&self) -> Option<Value>;
+}
+
+impl ToJson for bool {
+    fn to_json(&self) -> Option<Value> {
+        Some(Value::Bool(*self))
+    }
+}
+
+impl ToJson for usize {
+    fn to_json(&self) -> Option<Value> {
+        Some(Value::from(*self))
+    }
+}
+
+impl ToJson for i32 {
+    fn to_json(&self) -> Option<Value> {
+        Some(Value::from(*self))
+    }
+}
+
+impl ToJson for f64 {
+    fn to_json(&self) -> Option<Value> {
+        // JS has a single number type; print `1` rather than `1.0`.
+        if self.fract() == 0.0 && self.abs() < 1e15 {
+            return Some(Value::from(*self as i64));
+        }
+        Some(Value::from(*self))
+    }
+}
+
+impl ToJson for String {
+    fn to_json(&self) -> Option<Value> {
+        Some(Value::String(self.clone()))
+    }
+}
+
+impl<T: ToJson> ToJson for Option<T> {
+    fn to_json(&self) -> Option<Value> {
+        self.as_ref().and_then(|v| v.to_json())
+    }
+}
+
+impl<T: ToJson> ToJson for Vec<T> {
+    fn to_json(&self) -> Option<Value> {
+        Some(Value::Array(
+            self.iter()
+                .map(|v| v.to_json().unwrap_or(Value::Null))
+                .collect(),
+        ))
+    }
+}
+
+impl ToJson for Box<dyn AnyParseNode> {
+    fn to_json(&self) -> Option<Value> {
+        Some(AnyParseNode::to_json(self.as_ref()))
+    }
+}
+
+impl ToJson for IndexMap<String, String> {
+    fn to_json(&self) -> Option<Value> {
+        let mut obj = Map::new();
+        for (k, v) in self.iter() {
+            obj.insert(k.clone(), Value::String(v.clone()));
+        }
+        Some(Value::Object(obj))
+    }
+}
+
+impl ToJson for Mode {
+    fn to_json(&self) -> Option<Value> {
+        Some(Value::from(match self {
+            Mode::math => "math",
+            Mode::text => "text",
+        }))
+    }
+}
+
+impl ToJson for StyleStr {
+    fn to_json(&self) -> Option<Value> {
+        Some(Value::from(match self {
+            StyleStr::text => "text",
+            StyleStr::display => "display",
+            StyleStr::script => "script",
+            StyleStr::scriptscript => "scriptscript",
+        }))
+    }
+}
+
+impl ToJson for Atom {
+    fn to_json(&self) -> Option<Value> {
+        Some(Value::from(self.as_str()))
+    }
+}
+
+impl ToJson for ColSeparationType {
+    fn to_json(&self) -> Option<Value> {
+        Some(Value::from(match self {
+            ColSeparationType::Align => "align",
+            ColSeparationType::AlignAt => "alignat",
+            ColSeparationType::Gather => "gather",
+            ColSeparationType::Small => "small",
+            ColSeparationType::CD => "CD",
+        }))
+    }
+}
+
+impl ToJson for ArrayTag {
+    fn to_json(&self) -> Option<Value> {
+        match self {
+            ArrayTag::A(b) => b.to_json(),
+            ArrayTag::B(nodes) => nodes.to_json(),
+        }
+    }
+}
+
+impl ToJson for AlignSpec {
+    fn to_json(&self) -> Option<Value> {
+        let mut value = match self {
+            AlignSpec::Align(a) => AnyParseNode::to_json(a),
+            AlignSpec::Separator(s) => AnyParseNode::to_json(s),
+        };
+        if let Value::Object(obj) = &mut value {
+            let t = obj["type"].as_str().unwrap_or_default().to_lowercase();
+            obj.insert("type".to_string(), Value::String(t));
+            // KaTeX stores the gaps as numbers.
+            for key in ["pregap", "postgap"] {
+                if let Some(n) = obj
+                    .get(key)
+                    .and_then(|v| v.as_str())
+                    .and_then(|s| s.parse::<f64>().ok())
+                {
+                    obj.insert(key.to_string(), n.to_json().unwrap());
+                }
+            }
+        }
+        Some(value)
+    }
+}
+
+impl ToJson for Measurement {
+    fn to_json(&self) -> Option<Value> {
+        let mut obj = Map::new();
+        obj.insert("number".to_string(), self.number.to_json().unwrap());
+        obj.insert("unit".to_string(), Value::String(self.unit.clone()));
+        Some(Value::Object(obj))
+    }
+}
+
+/**
+ * The UTF-16 offset, as JS counts it, of each byte offset of `input`, or
+ * -1 inside a character.
+ */
+fn utf16_offsets(input: &str) -> Vec<i32> {
+    let mut offsets = vec![-1; input.len() + 1];
+    let mut units = 0;
+    for (i, c) in input.char_indices() {
+        offsets[i] = units;
+        units += c.len_utf16() as i32;
+    }
+    offsets[input.len()] = units;
+    offsets
+}
+
+/**
+ * The byte offset of each UTF-16 offset of `input`, or -1 inside a
+ * surrogate pair.
+ */
+fn byte_offsets(input: &str) -> Vec<i32> {
+    let mut offsets = Vec::with_capacity(input.len() + 1);
+    for (i, c) in input.char_indices() {
+        offsets.push(i as i32);
+        if c.len_utf16() == 2 {
+            offsets.push(-1);
+        }
+    }
+    offsets.push(input.len() as i32);
+    offsets
+}
+
+/** `utf16_offsets` of an input, and which input that is. */
+type CachedOffsets = (Weak<str>, Rc<Vec<i32>>);
+
+thread_local! {
+    /**
+     * `utf16_offsets` of the input the last `loc` was written for, as every
+     * `loc` of a tree would otherwise rescan the same input.
+     */
+    static UTF16_OFFSETS: RefCell<Option<CachedOffsets>> = const { RefCell::new(None) };
+}
+
+/** The UTF-16 offset, as JS counts it, of byte offset `offset` in the input of `lexer`. */
+fn utf16_offset(lexer: &LexerInterface, offset: i32) -> i32 {
+    let input = lexer.shared_input();
+    let offsets = UTF16_OFFSETS.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        match &*cache {
+            Some((cached, offsets)) if Weak::ptr_eq(cached, &Arc::downgrade(input)) => offsets.clone(),
+            _ => {
+                let offsets = Rc::new(utf16_offsets(input));
+                *cache = Some((Arc::downgrade(input), offsets.clone()));
+                offsets
+            }
+        }
+    });
+    match usize::try_from(offset).ok().and_then(|i| offsets.get(i)) {
+        Some(&units) if units >= 0 => units,
+        _ => offset,
+    }
+}
+
+impl ToJson for SourceLocation {
+    fn to_json(&self) -> Option<Value> {
+        let mut obj = Map::new();
+        obj.insert("start".to_string(), Value::from(utf16_offset(&self.lexer, self.start)));
+        obj.insert("end".to_string(), Value::from(utf16_offset(&self.lexer, self.end)));
+        Some(Value::Object(obj))
+    }
+}
+
+impl ToJson for Token {
+    fn to_json(&self) -> Option<Value> {
+        let mut obj = Map::new();
+        obj.insert("text".to_string(), Value::String(self.text.clone()));
+        if let Some(loc) = self.loc.to_json() {
+            obj.insert("loc".to_string(), loc);
+        }
+        if self.noexpand {
+            obj.insert("noexpand".to_string(), Value::Bool(true));
+        }
+        if self.treatAsRelax {
+            obj.insert("treatAsRelax".to_string(), Value::Bool(true));
+        }
+        Some(Value::Object(obj))
+    }
+}
+
+/**
+ * State shared while reading a parse tree back from JSON. `loc`s are
+ * attached to `source`, the LaTeX the tree was parsed from (if known).
+ */
+pub struct JsonContext {
+    source: LexerInterface,
+    // `byte_offsets` of the source, for the UTF-16 offsets of `loc`s.
+    byte_offsets: Vec<i32>,
+}
+
+impl JsonContext {
+    pub fn new(source: String) -> JsonContext {
+        JsonContext {
+            byte_offsets: byte_offsets(&source),
+            source: crate::Lexer::source_interface(source),
+        }
+    }
+
+    /** The byte offset of UTF-16 offset `offset` in the source, if it is a character boundary. */
+    fn byte_offset(&self, offset: i32) -> Option<i32> {
+        let offset = *self.byte_offsets.get(usize::try_from(offset).ok()?)?;
+        (offset >= 0).then_some(offset)
+    }
+}
+
+/**
+ * The reverse of `ToJson`. `value` is `None` when the field is missing,
+ * which only `Option`s and booleans (falsy like `undefined`) accept.
+ */
+pub trait FromJson: Sized {
+    fn from_json(value: Option<&Value>, ctx: &JsonContext) -> Result<Self, String>;
+
+    /** Reads the value of an `Option` field that is set; `None` drops it. */
+    fn from_json_optional(value: &Value, ctx: &JsonContext) -> Result<Option<Self>, String> {
+        Self::from_json(Some(value), ctx).map(Some)
+    }
+}
+
+fn expect<'a>(value: Option<&'a Value>) -> Result<&'a Value, String> {
+    value.ok_or_else(|| "missing field".to_string())
+}
+
+fn expect_str(value: Option<&Value>) -> Result<&str, String> {
+    let v = expect(value)?;
+    v.as_str().ok_or_else(|| format!("expected a string, got {}", v))
+}
+
+impl FromJson for bool {
+    fn from_json(value: Option<&Value>, _ctx: &JsonContext) -> Result<Self, String> {
+        match value {
+            None | Some(Value::Null) => Ok(false),
+            Some(Value::Bool(b)) => Ok(*b),
+            Some(v) => Err(format!("expected a boolean, got {}", v)),
+        }
+    }
+}
+
+impl FromJson for usize {
+    fn from_json(value: Option<&Value>, _ctx: &JsonContext) -> Result<Self, String> {
+        let v = expect(value)?;
+        v.as_u64()
+            .map(|n| n as usize)
+            .ok_or_else(|| format!("expected a non-negative integer, got {}", v))
+    }
+}
+
+impl FromJson for i32 {
+    fn from_json(value: Option<&Value>, _ctx: &JsonContext) -> Result<Self, String> {
+        let v = expect(value)?;
+        v.as_i64()
+            .map(|n| n as i32)
+            .ok_or_else(|| format!("expected an integer, got {}", v))
+    }
+}
+
+impl FromJson for f64 {
+    fn from_json(value: Option<&Value>, _ctx: &JsonContext) -> Result<Self, String> {
+        let v = expect(value)?;
+        v.as_f64().ok_or_else(|| format!("expected a number, got {}", v))
+    }
+}
+
+impl FromJson for String {
+    fn from_json(value: Option<&Value>, _ctx: &JsonContext) -> Result<Self, String> {
+        expect_str(value).map(|s| s.to_string())
+    }
+}
+
+impl<T: FromJson> FromJson for Option<T> {
+    fn from_json(value: Option<&Value>, ctx: &JsonContext) -> Result<Self, String> {
+        match value {
+            None | Some(Value::Null) => Ok(None),
+            Some(v) => T::from_json_optional(v, ctx),
+        }
+    }
+}
+
+impl<T: FromJson> FromJson for Vec<T> {
+    fn from_json(value: Option<&Value>, ctx: &JsonContext) -> Result<Self, String> {
+        let v = expect(value)?;
+        let items = v
+            .as_array()
+            .ok_or_else(|| format!("expected an array, got {}", v))?;
+        items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| T::from_json(Some(item), ctx).map_err(|e| format!("[{}]: {}", i, e)))
+            .collect()
+    }
+}
+
+/**
+ * Builds the node struct named by the `type` key.
+ */
+macro_rules! node_from_json {
+    ($t:expr, $value:expr, $ctx:expr; $($name:ident),* $(,)?) => {
+        $(
+            if $t == stringify!($name).replace('_', "-") {
+                return Ok(Box::new(types::$name::from_json($value, $ctx)?) as Box<dyn AnyParseNode>);
+            }
+        )*
+    };
+}
+
+impl FromJson for Box<dyn AnyParseNode> {
+    fn from_json(value: Option<&Value>, ctx: &JsonContext) -> Result<Self, String> {
+        let v = expect(value)?;
+        let t = v
+            .get("type")
+            .and_then(|t| t.as_str())
+            .ok_or_else(|| format!("expected a parse node with a 'type', got {}", v))?;
+        // Nodes the parser only passes between functions (`size`, `url`,
+        // `internal` and the like) have no builder; a `tag` is a whole
+        // formula, see `parse_tree_from_json`.
+        node_from_json!(t, value, ctx;
+            array, cdlabel, cdlabelparent, color, op, ordgroup, styling, supsub, text,
+            verb, atom, mathord, spacing, textord, accent, accentUnder, cr, delimsizing,
+            enclose, font, genfrac, hbox, horizBrace, href, html, htmlmathml,
+            includegraphics, kern, lap, leftright, mathchoice, middle, mclass,
+            operatorname, overline, phantom, hphantom, vphantom, pmb, raisebox, rule,
+            sizing, smash, sqrt, underline, vcenter, xArrow,
+        );
+        Err(format!("unknown parse node type '{}'", t))
+    }
+}
+
+impl FromJson for IndexMap<String, String> {
+    fn from_json(value: Option<&Value>, _ctx: &JsonContext) -> Result<Self, String> {
+        let v = expect(value)?;
+        let obj = v
+            .as_object()
+            .ok_or_else(|| format!("expected an object, got {}", v))?;
+        obj.iter()
+            .map(|(k, v)| match v.as_str() {
+                Some(s) => Ok((k.clone(), s.to_string())),
+                None => Err(format!("{}: expected a string, got {}", k, v)),
+            })
+            .collect()
+    }
+}
+
+impl FromJson for Mode {
+    fn from_json(value: Option<&Value>, _ctx: &JsonContext) -> Result<Self, String> {
+        let s = expect_str(value)?;
+        Mode::from_str(s).map_err(|_| format!("unknown mode '{}'", s))
+    }
+}
+
+impl FromJson for StyleStr {
+    fn from_json(value: Option<&Value>, _ctx: &JsonContext) -> Result<Self, String> {
+        let s = expect_str(value)?;
+        StyleStr::from_str(s).map_err(|_| format!("unknown style '{}'", s))
+    }
+}
+
+impl FromJson for Atom {
+    fn from_json(value: Option<&Value>, _ctx: &JsonContext) -> Result<Self, String> {
+        match expect_str(value)? {
+            "bin" => Ok(Atom::bin),
+            "close" => Ok(Atom::close),
+            "inner" => Ok(Atom::inner),
+            "open" => Ok(Atom::open),
+            "punct" => Ok(Atom::punct),
+            "rel" => Ok(Atom::rel),
+            s => Err(format!("unknown atom family '{}'", s)),
+        }
+    }
+}
+
+impl FromJson for ColSeparationType {
+    fn from_json(value: Option<&Value>, _ctx: &JsonContext) -> Result<Self, String> {
+        match expect_str(value)? {
+            "align" => Ok(ColSeparationType::Align),
+            "alignat" => Ok(ColSeparationType::AlignAt),
+            "gather" => Ok(ColSeparationType::Gather),
+            "small" => Ok(ColSeparationType::Small),
+            "CD" => Ok(ColSeparationType::CD),
+            s => Err(format!("unknown colSeparationType '{}'", s)),
+        }
+    }
+}
+
+impl FromJson for ArrayTag {
+    fn from_json(value: Option<&Value>, ctx: &JsonContext) -> Result<Self, String> {
+        match expect(value)? {
+            Value::Bool(b) => Ok(ArrayTag::A(*b)),
+            _ => Vec::from_json(value, ctx).map(ArrayTag::B),
+        }
+    }
+}
+
+impl FromJson for AlignSpec {
+    fn from_json(value: Option<&Value>, ctx: &JsonContext) -> Result<Self, String> {
+        let v = expect(value)?;
+        let mut obj = v
+            .as_object()
+            .ok_or_else(|| format!("expected a column spec, got {}", v))?
+            .clone();
+        // KaTeX stores the gaps as numbers.
+        for key in ["pregap", "postgap"] {
+            if let Some(n) = obj.get(key).and_then(|v| v.as_f64()) {
+                obj.insert(key.to_string(), Value::String(n.to_string()));
+            }
+        }
+        let value = Value::Object(obj);
+        match v.get("type").and_then(|t| t.as_str()) {
+            Some("align") => Align::from_json(Some(&value), ctx).map(AlignSpec::Align),
+            Some("separator") => Separator::from_json(Some(&value), ctx).map(AlignSpec::Separator),
+            _ => Err(format!("unknown column spec {}", v)),
+        }
+    }
+}
+
+impl FromJson for Measurement {
+    fn from_json(value: Option<&Value>, ctx: &JsonContext) -> Result<Self, String> {
+        let v = expect(value)?;
+        let measurement = Measurement {
+            number: f64::from_json(v.get("number"), ctx).map_err(|e| format!("number: {}", e))?,
+            unit: String::from_json(v.get("unit"), ctx).map_err(|e| format!("unit: {}", e))?,
+        };
+        if !measurement.unit_is_valid() {
+            return Err(format!("unit: unknown unit '{}'", measurement.unit));
+        }
+        Ok(measurement)
+    }
+}
+
+impl FromJson for SourceLocation {
+    fn from_json(value: Option<&Value>, ctx: &JsonContext) -> Result<Self, String> {
+        let v = expect(value)?;
+        let start = i32::from_json(v.get("start"), ctx).map_err(|e| format!("start: {}", e))?;
+        let end = i32::from_json(v.get("end"), ctx).map_err(|e| format!("end: {}", e))?;
+        let input = ctx.source.get_input();
+        if input.is_empty() {
+            return Ok(SourceLocation { lexer: ctx.source.clone(), start, end });
+        }
+        match (ctx.byte_offset(start), ctx.byte_offset(end)) {
+            (Some(start), Some(end)) if start <= end => Ok(SourceLocation {
+                lexer: ctx.source.clone(),
+                start,
+                end,
+            }),
+            _ => Err(format!("{}..{} is not a range of the source", start, end)),
+        }
+    }
+
+    /** Without the source, a `loc` points at nothing and is left out. */
+    fn from_json_optional(value: &Value, ctx: &JsonContext) -> Result<Option<Self>, String> {
+        let loc = Self::from_json(Some(value), ctx)?;
+        Ok(Some(loc).filter(|_| !ctx.source.get_input().is_empty()))
+    }
+}
+
+impl FromJson for Token {
+    fn from_json(value: Option<&Value>, ctx: &JsonContext) -> Result<Self, String> {
+        let v = expect(value)?;
+        Ok(Token {
+            text: String::from_json(v.get("text"), ctx).map_err(|e| format!("text: {}", e))?,
+            loc: Option::from_json(v.get("loc"), ctx).map_err(|e| format!("loc: {}", e))?,
+            noexpand: bool::from_json(v.get("noexpand"), ctx)?,
+            treatAsRelax: bool::from_json(v.get("treatAsRelax"), ctx)?,
+        })
+    }
+}
+
+/*
+ * Checks of values the builders rely on but the JSON shape does not express,
+ * called by the derived `from_json` of the node (see `#[json(check)]`). The
+ * parser never makes such nodes, so a tree that fails them did not come
+ * from `katex.__parse`. Errors start with the name of the field.
+ */
+
+fn check_non_empty(field: &str, text: &str) -> Result<(), String> {
+    if text.is_empty() {
+        return Err(format!("{}: expected a non-empty string", field));
+    }
+    Ok(())
+}
+
+fn check_range(field: &str, value: usize, max: usize) -> Result<(), String> {
+    if !(1..=max).contains(&value) {
+        return Err(format!("{}: expected 1 to {}, got {}", field, max, value));
+    }
+    Ok(())
+}
+
+fn check_delimiter(field: &str, delim: &str) -> Result<(), String> {
+    if !is_delimiter(delim) {
+        return Err(format!("{}: unknown delimiter '{}'", field, delim));
+    }
+    Ok(())
+}
+
+fn check_stretchy_label(label: &str, wide_accents: bool) -> Result<(), String> {
+    if !is_stretchy_label(label, wide_accents) {
+        return Err(format!("label: unknown label '{}'", label));
+    }
+    Ok(())
+}
+
+pub(crate) fn check_array(node: &types::array) -> Result<(), String> {
+    if node.h_lines_before_row.len() <= node.body.len() {
+        return Err(format!("hLinesBeforeRow: expected {} rows of lines", node.body.len() + 1));
+    }
+    Ok(())
+}
+
+pub(crate) fn check_supsub(node: &types::supsub) -> Result<(), String> {
+    if node.sup.is_none() && node.sub.is_none() {
+        return Err("sup: missing field, and no sub".to_string());
+    }
+    Ok(())
+}
+
+pub(crate) fn check_sizing(node: &types::sizing) -> Result<(), String> {
+    check_range("size", node.size, 11)
+}
+
+pub(crate) fn check_delimsizing(node: &types::delimsizing) -> Result<(), String> {
+    check_range("size", node.size, 4)?;
+    check_delimiter("delim", &node.delim)
+}
+
+pub(crate) fn check_middle(node: &types::middle) -> Result<(), String> {
+    check_delimiter("delim", &node.delim)
+}
+
+pub(crate) fn check_leftright(node: &types::leftright) -> Result<(), String> {
+    check_delimiter("left", &node.left)?;
+    check_delimiter("right", &node.right)
+}
+
+pub(crate) fn check_genfrac(node: &types::genfrac) -> Result<(), String> {
+    if let Some(delim) = &node.leftDelim {
+        check_delimiter("leftDelim", delim)?;
+    }
+    if let Some(delim) = &node.rightDelim {
+        check_delimiter("rightDelim", delim)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn check_accent(node: &types::accent) -> Result<(), String> {
+    if node.base.is_none() {
+        return Err("base: missing field".to_string());
+    }
+    if node.isStretchy {
+        return check_stretchy_label(&node.label, true);
+    }
+    check_non_empty("label", &node.label)
+}
+
+pub(crate) fn check_accent_under(node: &types::accentUnder) -> Result<(), String> {
+    check_stretchy_label(&node.label, true)
+}
+
+pub(crate) fn check_horiz_brace(node: &types::horizBrace) -> Result<(), String> {
+    check_stretchy_label(&node.label, false)
+}
+
+pub(crate) fn check_x_arrow(node: &types::xArrow) -> Result<(), String> {
+    check_stretchy_label(&node.label, false)
+}
+
+pub(crate) fn check_enclose(node: &types::enclose) -> Result<(), String> {
+    match node.label.as_str() {
+        "\\cancel" | "\\bcancel" | "\\xcancel" | "\\sout" | "\\phase" | "\\fbox" | "\\angl" => Ok(()),
+        "\\colorbox" if node.backgroundColor.is_some() => Ok(()),
+        "\\fcolorbox" if node.backgroundColor.is_some() && node.borderColor.is_some() => Ok(()),
+        "\\colorbox" | "\\fcolorbox" => Err(format!("label: {} without its colors", node.label)),
+        label => Err(format!("label: unknown label '{}'", label)),
+    }
+}
+
+pub(crate) fn check_font(node: &types::font) -> Result<(), String> {
+    if node.font != "boldsymbol" && !FONT_MAP.contains_key(node.font.as_str()) {
+        return Err(format!("font: unknown font '{}'", node.font));
+    }
+    Ok(())
+}
+
+pub(crate) fn check_mclass(node: &types::mclass) -> Result<(), String> {
+    if node.is_character_box && node.body.len() != 1 {
+        return Err("body: expected a single symbol in a character box".to_string());
+    }
+    Ok(())
+}
+
+pub(crate) fn check_op(node: &types::op) -> Result<(), String> {
+    match (&node.name, &node.body) {
+        (Some(name), _) => check_non_empty("name", name),
+        (None, Some(_)) => Ok(()),
+        (None, None) => Err("name: missing field".to_string()),
+    }
+}
+
+pub(crate) fn check_atom(node: &types::atom) -> Result<(), String> {
+    if get_symbol(node.mode, &node.text).is_none() {
+        return Err(format!("text: unknown symbol '{}'", node.text));
+    }
+    Ok(())
+}
+
+pub(crate) fn check_mathord(node: &types::mathord) -> Result<(), String> {
+    check_non_empty("text", &node.text)
+}
+
+pub(crate) fn check_textord(node: &types::textord) -> Result<(), String> {
+    check_non_empty("text", &node.text)
+}
+
+pub(crate) fn check_spacing(node: &types::spacing) -> Result<(), String> {
+    match node.text.as_str() {
+        " " | "\\ " | "\\space" | "~" | "\\nobreakspace" | "\\nobreak" | "\\allowbreak" => Ok(()),
+        text => Err(format!("text: unknown space '{}'", text)),
+    }
+}
+
+pub(crate) fn check_separator(node: &Separator) -> Result<(), String> {
+    match node.separator.as_str() {
+        "|" | ":" => Ok(()),
+        separator => Err(format!("separator: unknown separator '{}'", separator)),
+    }
+}
+
+/**
+ * Serializes a parse tree (as returned by `parseTree`) into the same JSON
+ * shape as `katex.__parse`.
+ */
+pub fn parse_tree_to_json(tree: &[Box<dyn AnyParseNode>]) -> Value {
+    Value::Array(
+        tree.iter()
+            .map(|node| AnyParseNode::to_json(node.as_ref()))
+            .collect(),
+    )
+}
+
+/**
+ * Reads a parse tree in the JSON shape of `katex.__parse` back into parse
+ * nodes. `source` is the LaTeX the tree came from, used for `loc`s; pass an
+ * empty string if it is unknown.
+ */
+pub fn parse_tree_from_json(
+    json: &Value,
+    source: String,
+) -> Result<Vec<Box<dyn AnyParseNode>>, ParseError> {
+    let ctx = JsonContext::new(source);
+    let tree = match json.as_array().map(|nodes| nodes.as_slice()) {
+        // A formula with \tag is a single `tag` node.
+        Some([node]) if node.get("type").and_then(|t| t.as_str()) == Some("tag") => types::tag::from_json(Some(node), &ctx)
+            .map(|tag| vec![Box::new(tag) as Box<dyn AnyParseNode>])
+            .map_err(|e| format!("[0]: {}", e)),
+        _ => Vec::from_json(Some(json), &ctx),
+    };
+    tree.map_err(|e| ParseError::new(format!("Invalid parse tree: {}", e), None))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_tree_from_json;
+    use crate::settings::Settings;
+    use serde_json::Value;
+
+    /** The error reading back the tree of `expression` with `pointer` set to `value`. */
+    fn error_with(expression: &str, pointer: &str, value: Value) -> String {
+        let mut settings = Settings::new();
+        settings.set_display_mode(true);
+        let mut json = crate::try_parse_to_json(expression.to_string(), settings).unwrap();
+        assert!(parse_tree_from_json(&json, String::new()).is_ok());
+        *json.pointer_mut(pointer).unwrap() = value;
+        parse_tree_from_json(&json, String::new()).unwrap_err().raw_message().to_string()
+    }
+
+    #[test]
+    fn test_values_the_builders_reject() {
+        let cases = [
+            ("\\Huge x", "/0/size", Value::from(99), "[0]: sizing.size: expected 1 to 11, got 99"),
+            ("\\Huge x", "/0/size", Value::from(0), "[0]: sizing.size: expected 1 to 11, got 0"),
+            ("\\big(", "/0/size", Value::from(9), "[0]: delimsizing.size: expected 1 to 4, got 9"),
+            ("\\big(", "/0/delim", Value::from("x"), "[0]: delimsizing.delim: unknown delimiter 'x'"),
+            ("\\left(x\\right)", "/0/right", Value::from(""), "[0]: leftright.right: unknown delimiter ''"),
+            ("\\widehat{x}", "/0/label", Value::from("\\zzz"), "[0]: accent.label: unknown label '\\zzz'"),
+            ("\\hat{x}", "/0/label", Value::from(""), "[0]: accent.label: expected a non-empty string"),
+            ("\\xrightarrow{a}", "/0/label", Value::from("\\widehat"), "[0]: xArrow.label: unknown label '\\widehat'"),
+            ("\\cancel{x}", "/0/label", Value::from(""), "[0]: enclose.label: unknown label ''"),
+            ("\\mathbb{R}", "/0/font", Value::from("zzz"), "[0]: font.font: unknown font 'zzz'"),
+            ("x", "/0/text", Value::from(""), "[0]: mathord.text: expected a non-empty string"),
+            ("1", "/0/text", Value::from(""), "[0]: textord.text: expected a non-empty string"),
+            ("+", "/0/text", Value::from("zzz"), "[0]: atom.text: unknown symbol 'zzz'"),
+            ("\\kern1em", "/0/dimension/unit", Value::from("zz"), "[0]: kern.dimension: unit: unknown unit 'zz'"),
+            ("x^2", "/0/sup", Value::Null, "[0]: supsub.sup: missing field, and no sub"),
+            ("\\begin{array}{|c}a\\end{array}", "/0/cols/0/separator", Value::from("-"), "[0]: array.cols: [0]: Separator.separator: unknown separator '-'"),
+            ("\\begin{matrix}a\\end{matrix}", "/0/hLinesBeforeRow", Value::Array(vec![]), "[0]: array.hLinesBeforeRow: expected 2 rows of lines"),
+            ("x", "/0/type", Value::from("internal"), "[0]: unknown parse node type 'internal'"),
+        ];
+        for (expression, pointer, value, expected) in cases {
+            assert_eq!(error_with(expression, pointer, value), format!("Invalid parse tree: {}", expected));
+        }
+
+        // A tag is only the whole formula.
+        let mut settings = Settings::new();
+        settings.set_display_mode(true);
+        let tag = crate::try_parse_to_json("x\\tag{1}".to_string(), settings).unwrap();
+        let nested = serde_json::json!([{"type": "ordgroup", "mode": "math", "body": tag}]);
+        assert_eq!(
+            parse_tree_from_json(&nested, String::new()).unwrap_err().raw_message(),
+            "Invalid parse tree: [0]: ordgroup.body: [0]: unknown parse node type 'tag'"
+        );
+    }
+
+    #[test]
+    fn test_locs() {
+        let json = serde_json::json!([{"type": "mathord", "mode": "math", "text": "x", "loc": {"start": -1, "end": 5}}]);
+        // Without the source there is nothing for the loc to point at.
+        let tree = parse_tree_from_json(&json, String::new()).unwrap();
+        assert!(tree[0].get_loc().is_none());
+        assert_eq!(
+            parse_tree_from_json(&json, "x".to_string()).unwrap_err().raw_message(),
+            "Invalid parse tree: [0]: mathord.loc: -1..5 is not a range of the source"
+        );
+        // Offsets are UTF-16 code units, so they never split a surrogate pair.
+        for (start, end) in [(0, 3), (2, 0), (1, 2)] {
+            let json = serde_json::json!([{"type": "mathord", "mode": "math", "text": "x", "loc": {"start": start, "end": end}}]);
+            assert!(parse_tree_from_json(&json, "𝑥".to_string()).is_err());
+        }
+        let json = serde_json::json!([{"type": "mathord", "mode": "math", "text": "x", "loc": {"start": 1, "end": 2}}]);
+        let loc = parse_tree_from_json(&json, "éx".to_string()).unwrap()[0].get_loc().cloned().unwrap();
+        assert_eq!((loc.start, loc.end), (2, 3));
+        // And are written as such, whichever input was written last.
+        for (expression, starts) in [("{😀}x", [0, 4]), ("ab", [0, 1]), ("{😀}x", [0, 4])] {
+            let json = crate::try_parse_to_json(expression.to_string(), Settings::new()).unwrap();
+            let actual: Vec<_> = (0..2).map(|i| json[i]["loc"]["start"].as_i64().unwrap()).collect();
+            assert_eq!(actual, starts);
+        }
+    }
+}
@@ -1,6 +1,9 @@
 use crate::parse_node::types::AnyParseNode;
 
+pub mod json;
 pub mod types;
+pub mod unparse;
+pub mod visit;
 
 
 // Some of these have a "-token" suffix since these are also used as `ParseNode`
@@ -14,11 +17,11 @@ const NON_ATOMS: [&str; 5] = ["accent-token", "mathord", "op-token", "spacing",
  * Returns the node more strictly typed iff it is of the given type. Otherwise,
  * returns null.
  */
-pub fn check_symbol_node_type(node: &Box<dyn AnyParseNode>) -> bool {
+pub fn check_symbol_node_type(node: &dyn AnyParseNode) -> bool {
     node.get_type() == "atom" || NON_ATOMS.contains(&node.get_type())
 }
 
-pub fn check_symbol_node_type_text(node: &Box<dyn AnyParseNode>)->String{
+pub fn check_symbol_node_type_text(node: &dyn AnyParseNode)->String{
     return if let Some(a) = node.as_any().downcast_ref::<types::atom>(){
         a.text.clone()
     } else if let Some(a) = node.as_any().downcast_ref::<types::accent_token>(){
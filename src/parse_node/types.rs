@@ -38,8 +38,25 @@ impl Clone for Box<dyn AnyParseNode> {
     }
 }
 
-pub trait AnyParseNode: ParseNodeToAny + NodeClone + Debug {
+pub trait AnyParseNode: ParseNodeToAny + NodeClone + Debug + Send + Sync {
     fn get_type(&self) -> &str;
+
+    /** The node in the JSON shape of `katex.__parse`, see `parse_node::json`. */
+    fn to_json(&self) -> serde_json::Value;
+
+    /** Where the node came from in the input, if the parser recorded it. */
+    fn get_loc(&self) -> Option<&SourceLocation>;
+
+    /** Calls `f` on every direct child node, in field order. See `parse_node::visit`. */
+    fn for_each_child(&self, f: &mut dyn FnMut(&Box<dyn AnyParseNode>));
+
+    fn for_each_child_mut(&mut self, f: &mut dyn FnMut(&mut Box<dyn AnyParseNode>));
+
+    /** Rebuilds the node with every direct child replaced by `f(child)`. */
+    fn map_children(
+        self: Box<Self>,
+        f: &mut dyn FnMut(Box<dyn AnyParseNode>) -> Box<dyn AnyParseNode>,
+    ) -> Box<dyn AnyParseNode>;
 }
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 // Type to indicate column separation in MathML
@@ -57,6 +74,7 @@ pub enum ArrayTag{
     B(Vec<Box<dyn AnyParseNode>>)
 }
 #[derive(parse_node_type, Clone, Debug)]
+#[json(check = "check_array")]
 pub struct array {
     pub mode: Mode,
     pub loc: Option<SourceLocation>,
@@ -64,6 +82,7 @@ pub struct array {
     pub hskip_before_and_after: bool,
     pub add_jot: bool,
     pub cols: Vec<crate::define::environments::array::AlignSpec>,
+    #[json(rename = "arraystretch")]
     pub array_stretch: f64,
     pub body: Vec<Vec<Box<dyn AnyParseNode>>>,
     // List of rows in the (2D) array.
@@ -72,6 +91,7 @@ pub struct array {
     // Whether each row should be automatically numbered, or an explicit tag
     pub tags: Option<Vec<ArrayTag>>,
     pub leqno: bool,
+    #[json(rename = "isCD")]
     pub is_cd: bool,
 }
 
@@ -110,6 +130,7 @@ pub struct color_token {
 // - `body` and `value` are NEVER set simultanouesly.
 // - When `symbol` is true, `body` is set.
 #[derive(parse_node_type, Clone, Debug)]
+#[json(check = "check_op")]
 pub struct op {
     pub mode: Mode,
     pub loc: Option<SourceLocation>,
@@ -127,6 +148,8 @@ pub struct ordgroup {
     pub mode: Mode,
     pub loc: Option<SourceLocation>,
     pub(crate) body: Vec<Box<dyn AnyParseNode>>,
+    /// Only set by `\begingroup`; KaTeX leaves the field out otherwise.
+    #[json(skip_false)]
     pub semisimple: bool,
 }
 
@@ -154,6 +177,7 @@ pub struct styling {
 }
 
 #[derive(parse_node_type, Clone, Debug)]
+#[json(check = "check_supsub")]
 pub struct supsub {
     pub mode: Mode,
     pub loc: Option<SourceLocation>,
@@ -238,6 +262,7 @@ impl Atom {
 // (Some of these have "-token" suffix to distinguish them from existing
 // `ParseNode` types.)
 #[derive(parse_node_type, Clone, Debug)]
+#[json(check = "check_atom")]
 pub struct atom {
     pub family: Atom,
     pub mode: Mode,
@@ -246,6 +271,7 @@ pub struct atom {
 }
 
 #[derive(parse_node_type, Clone, Debug)]
+#[json(check = "check_mathord")]
 pub struct mathord {
     pub mode: Mode,
     pub loc: Option<SourceLocation>,
@@ -253,6 +279,7 @@ pub struct mathord {
 }
 
 #[derive(parse_node_type, Clone, Debug)]
+#[json(check = "check_spacing")]
 pub struct spacing {
     pub mode: Mode,
     pub loc: Option<SourceLocation>,
@@ -260,6 +287,7 @@ pub struct spacing {
 }
 
 #[derive(parse_node_type, Clone, Debug)]
+#[json(check = "check_textord")]
 pub struct textord {
     pub mode: Mode,
     pub loc: Option<SourceLocation>,
@@ -284,6 +312,7 @@ pub struct op_token {
 // From functions.js and functions/*.js. See also "color", "op", "styling",
 // and "text" above.
 #[derive(parse_node_type, Clone, Debug)]
+#[json(check = "check_accent")]
 pub struct accent {
     pub mode: Mode,
     pub(crate) loc: Option<SourceLocation>,
@@ -294,6 +323,7 @@ pub struct accent {
 }
 
 #[derive(parse_node_type, Clone, Debug)]
+#[json(check = "check_accent_under")]
 pub struct accentUnder {
     pub mode: Mode,
     pub loc: Option<SourceLocation>,
@@ -312,6 +342,7 @@ pub struct cr {
 }
 
 #[derive(parse_node_type, Clone, Debug)]
+#[json(check = "check_delimsizing")]
 pub struct delimsizing {
     pub mode: Mode,
     pub loc: Option<SourceLocation>,
@@ -323,6 +354,7 @@ pub struct delimsizing {
 }
 
 #[derive(parse_node_type, Clone, Debug)]
+#[json(check = "check_enclose")]
 pub struct enclose {
     pub mode: Mode,
     pub loc: Option<SourceLocation>,
@@ -341,6 +373,7 @@ pub struct environment {
 }
 
 #[derive(parse_node_type, Clone, Debug)]
+#[json(check = "check_font")]
 pub struct font {
     pub mode: Mode,
     pub loc: Option<SourceLocation>,
@@ -349,6 +382,7 @@ pub struct font {
 }
 
 #[derive(parse_node_type, Clone, Debug)]
+#[json(check = "check_genfrac")]
 pub struct genfrac {
     pub mode: Mode,
     pub loc: Option<SourceLocation>,
@@ -371,6 +405,7 @@ pub struct hbox {
 }
 
 #[derive(parse_node_type, Clone, Debug)]
+#[json(check = "check_horiz_brace")]
 pub struct horizBrace {
     pub mode: Mode,
     pub loc: Option<SourceLocation>,
@@ -451,6 +486,7 @@ pub struct lap {
 }
 
 #[derive(parse_node_type, Clone, Debug)]
+#[json(check = "check_leftright")]
 pub struct leftright {
     pub mode: Mode,
     pub loc: Option<SourceLocation>,
@@ -479,6 +515,7 @@ pub struct mathchoice {
 }
 
 #[derive(parse_node_type, Clone, Debug)]
+#[json(check = "check_middle")]
 pub struct middle {
     pub mode: Mode,
     pub loc: Option<SourceLocation>,
@@ -486,6 +523,7 @@ pub struct middle {
 }
 
 #[derive(parse_node_type, Clone, Debug)]
+#[json(check = "check_mclass")]
 pub struct mclass {
     pub(crate) mode: Mode,
     pub(crate) loc: Option<SourceLocation>,
@@ -558,6 +596,7 @@ pub struct rule {
 }
 
 #[derive(parse_node_type, Clone, Debug)]
+#[json(check = "check_sizing")]
 pub struct sizing {
     pub mode: Mode,
     pub loc: Option<SourceLocation>,
@@ -597,6 +636,7 @@ pub struct vcenter {
 }
 
 #[derive(parse_node_type, Clone, Debug)]
+#[json(check = "check_x_arrow")]
 pub struct xArrow {
     pub mode: Mode,
     pub loc: Option<SourceLocation>,
@@ -0,0 +1,1142 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use crate::define::environments::array::AlignSpec;
+use crate::parse::try_parse_tree;
+use crate::parse_node::types::{self, AnyParseNode, ArrayTag, ColSeparationType};
+use crate::settings::Settings;
+use crate::symbols::get_symbol;
+use crate::symbols::public::Group;
+use crate::types::{Mode, StyleStr};
+use crate::units::Measurement;
+
+/**
+ * Turn a parse tree back into LaTeX source.
+ *
+ * The output is canonical rather than a copy of the input: whitespace is
+ * dropped wherever it carries no meaning, every argument is written in
+ * braces (`\frac12` becomes `\frac{1}{2}`), and the implicit groups of
+ * `\color`, `\large`, `\displaystyle`, `\over` and friends are written in
+ * one fixed form. Explicit groups are kept, even `{{x}}`, as each one adds a
+ * box. Shorthands that leave a node of their own are written back: `'`,
+ * `\bf`, `\not`, `\,` and the like, and unsupported or untrusted commands
+ * as they were written. Parsing the result again gives the same tree up to
+ * source locations and single-element groups, and printing that tree again
+ * gives the same string.
+ */
+pub fn parse_tree_to_latex(tree: &[Box<dyn AnyParseNode>]) -> String {
+    let mut writer = LatexWriter::default();
+    writer.list(tree);
+    writer.out
+}
+
+#[derive(Default)]
+struct LatexWriter {
+    out: String,
+    // The list being written may be followed by more of its group, as the
+    // numerator of `\over` is, so `\bf` must not take the rest of it.
+    open_end: bool,
+    // Write the expansion of a shorthand instead of the shorthand.
+    expanded: bool,
+}
+
+fn is_letter(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '@'
+}
+
+fn style_name(style: &StyleStr) -> &'static str {
+    match style {
+        StyleStr::display => "display",
+        StyleStr::text => "text",
+        StyleStr::script => "script",
+        StyleStr::scriptscript => "scriptscript",
+    }
+}
+
+fn measurement(m: &Measurement) -> String {
+    format!("{}{}", m.number, m.unit)
+}
+
+/**
+ * Escape the characters `\href` and `\url` strip a backslash from.
+ */
+fn url_escape(url: &str) -> String {
+    let mut res = String::with_capacity(url.len());
+    for c in url.chars() {
+        if "#$%&~_^{}".contains(c) {
+            res.push('\\');
+        }
+        res.push(c);
+    }
+    res
+}
+
+/** Whether the parser reads `s` as one token that stands for itself. */
+fn is_single_token(s: &str) -> bool {
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (Some('\\'), Some(c)) if is_letter(c) => chars.all(is_letter),
+        (Some('\\'), Some(_)) => chars.next().is_none(),
+        (Some(c), None) => !"{}^_&#%~$'\\ ".contains(c),
+        _ => false,
+    }
+}
+
+fn as_ordgroup(node: &dyn AnyParseNode) -> Option<&types::ordgroup> {
+    node.as_any()
+        .downcast_ref::<types::ordgroup>()
+        .filter(|group| !group.semisimple)
+}
+
+/**
+ * Fractions built by the infix `\atop`, `\brace` and `\brack` have no
+ * prefix form, so they are written back in infix form.
+ */
+fn infix_command(frac: &types::genfrac) -> Option<&'static str> {
+    if frac.continued || frac.hasBarLine || frac.barSize.is_some() || frac.size != "auto" {
+        return None;
+    }
+    match (frac.leftDelim.as_deref(), frac.rightDelim.as_deref()) {
+        (None, None) => Some("\\atop"),
+        (Some("\\{"), Some("\\}")) => Some("\\brace"),
+        (Some("["), Some("]")) => Some("\\brack"),
+        _ => None,
+    }
+}
+
+fn is_infix_frac(node: &dyn AnyParseNode) -> bool {
+    node.as_any()
+        .downcast_ref::<types::genfrac>()
+        .is_some_and(|frac| infix_command(frac).is_some())
+}
+
+fn is_prime(node: &dyn AnyParseNode) -> bool {
+    node.as_any()
+        .downcast_ref::<types::textord>()
+        .is_some_and(|n| n.mode == Mode::math && n.text == "\\prime")
+}
+
+/**
+ * The source text of an unsupported or untrusted command, which
+ * `Parser::format_unsupported_cmd` keeps in errorColor with the loc of the
+ * command and its arguments.
+ */
+fn unsupported_source(color: &types::color) -> Option<&str> {
+    let [text] = color.body.as_slice() else {
+        return None;
+    };
+    let text = text.as_any().downcast_ref::<types::text>().filter(|text| text.font.is_none())?;
+    let name = text
+        .body
+        .iter()
+        .map(|c| c.as_any().downcast_ref::<types::textord>().map(|c| c.text.as_str()))
+        .collect::<Option<String>>()?;
+    let loc = color.loc.as_ref()?;
+    let source = loc.lexer.get_input().get(loc.start as usize..loc.end as usize)?;
+    // A control word takes the spaces after it, unless it is `\ `.
+    let trimmed = source.trim_end();
+    let source = if trimmed.len() < source.len() && trimmed.ends_with('\\') {
+        &source[..trimmed.len() + 1]
+    } else {
+        trimmed
+    };
+    source.starts_with(&name).then_some(source)
+}
+
+/**
+ * The command that expands to `node` alone, for the nodes that `\not`,
+ * `\neq`, `\,`, `\quad` and their like leave. `expansion` is how `node`
+ * is written otherwise.
+ */
+fn shorthand(node: &dyn AnyParseNode, expansion: &str) -> Option<&'static str> {
+    const COMMANDS: [&str; 15] = [
+        "\\not", "\\neq", "\\notin", "\\textcopyright", "\\textregistered", "\\ulcorner",
+        "\\urcorner", "\\llcorner", "\\lrcorner", "\\,", "\\:", "\\;", "\\!", "\\quad",
+        "\\qquad",
+    ];
+    static SHORTHANDS: OnceLock<HashMap<(bool, String), &'static str>> = OnceLock::new();
+    let mode = if let Some(n) = node.as_any().downcast_ref::<types::htmlmathml>() {
+        n.mode
+    } else if let Some(n) = node.as_any().downcast_ref::<types::kern>() {
+        n.mode
+    } else {
+        return None;
+    };
+    let shorthands = SHORTHANDS.get_or_init(|| {
+        let mut shorthands = HashMap::new();
+        for command in COMMANDS {
+            for math in [true, false] {
+                let source = if math { command.to_string() } else { format!("\\text{{{}}}", command) };
+                let Ok(tree) = try_parse_tree(source, Settings::new()) else {
+                    continue;
+                };
+                let nodes = match tree.as_slice() {
+                    [text] if !math => match text.as_any().downcast_ref::<types::text>() {
+                        Some(text) => &text.body,
+                        None => continue,
+                    },
+                    _ => &tree,
+                };
+                if let [node] = nodes.as_slice() {
+                    let mut writer = LatexWriter { expanded: true, ..Default::default() };
+                    writer.node(node.as_ref());
+                    shorthands.entry((math, writer.out)).or_insert(command);
+                }
+            }
+        }
+        shorthands
+    });
+    shorthands.get(&(mode == Mode::math, expansion.to_string())).copied()
+}
+
+fn cell_style(array: &types::array) -> Option<StyleStr> {
+    array
+        .body
+        .iter()
+        .flatten()
+        .next()
+        .and_then(|cell| cell.as_any().downcast_ref::<types::styling>())
+        .map(|cell| cell.style.clone())
+}
+
+/** Whether no row of a tagged environment is numbered, as in {align*}. */
+fn is_starred(array: &types::array) -> bool {
+    array
+        .tags
+        .as_ref()
+        .is_some_and(|tags| tags.iter().all(|tag| matches!(tag, ArrayTag::A(false))))
+}
+
+fn is_matrix(array: &types::array) -> bool {
+    let mut aligns = array.cols.iter().map(|col| match col {
+        AlignSpec::Align(a) if a.pregap.is_none() && a.postgap.is_none() => Some(a.align.as_str()),
+        _ => None,
+    });
+    let first = aligns.next().flatten();
+    !array.hskip_before_and_after
+        && !array.add_jot
+        && array.col_separation_type.is_none()
+        && array.array_stretch == 1.0
+        && (array.cols.is_empty() || first.is_some())
+        && aligns.all(|a| a.is_some() && a == first)
+}
+
+fn is_cases(array: &types::array) -> bool {
+    let gaps = array
+        .cols
+        .iter()
+        .map(|col| match col {
+            AlignSpec::Align(a) => (
+                a.align.as_str(),
+                a.pregap.as_deref().unwrap_or(""),
+                a.postgap.as_deref().unwrap_or(""),
+            ),
+            AlignSpec::Separator(_) => ("", "", ""),
+        })
+        .collect::<Vec<_>>();
+    !array.hskip_before_and_after
+        && !array.add_jot
+        && array.col_separation_type.is_none()
+        && array.array_stretch == 1.2
+        && gaps == [("l", "0", "1"), ("l", "0", "0")]
+}
+
+fn matrix_name(left: &str, right: &str) -> Option<&'static str> {
+    match (left, right) {
+        ("(", ")") => Some("pmatrix"),
+        ("[", "]") => Some("bmatrix"),
+        ("\\{", "\\}") => Some("Bmatrix"),
+        ("|", "|") => Some("vmatrix"),
+        ("\\Vert", "\\Vert") => Some("Vmatrix"),
+        _ => None,
+    }
+}
+
+impl LatexWriter {
+    /**
+     * Append a token, separating a control word from a following letter.
+     */
+    fn push(&mut self, s: &str) {
+        if s.starts_with(is_letter) {
+            let word = self.out.trim_end_matches(is_letter);
+            let escapes = word.len() - word.trim_end_matches('\\').len();
+            if word.len() < self.out.len() && escapes % 2 == 1 {
+                self.out.push(' ');
+            }
+        }
+        self.out.push_str(s);
+    }
+
+    /** A writer for a part that is measured before it is written. */
+    fn inner(&self) -> LatexWriter {
+        LatexWriter { expanded: self.expanded, ..Default::default() }
+    }
+
+    fn list(&mut self, nodes: &[Box<dyn AnyParseNode>]) {
+        if nodes.len() == 1 && is_infix_frac(nodes[0].as_ref()) {
+            let frac = nodes[0].as_any().downcast_ref::<types::genfrac>().unwrap();
+            self.infix_frac(frac);
+            return;
+        }
+        let mut after_cr = false;
+        for (i, node) in nodes.iter().enumerate() {
+            if after_cr && (node.get_type() == "atom" || node.get_type() == "textord") {
+                // `\\[` would start the optional size argument.
+                if crate::parse_node::check_symbol_node_type_text(node.as_ref()).starts_with('[') {
+                    self.push("\\relax");
+                }
+            }
+            after_cr = node.get_type() == "cr";
+            if i + 1 == nodes.len() && !self.open_end && self.old_font(node.as_ref()) {
+                continue;
+            }
+            self.node(node.as_ref());
+        }
+    }
+
+    /**
+     * `\bf` and the other old font commands take the rest of the list, which
+     * `\mathbf` can only take as a group in a second pair of braces.
+     */
+    fn old_font(&mut self, node: &dyn AnyParseNode) -> bool {
+        let Some(font) = node.as_any().downcast_ref::<types::font>() else {
+            return false;
+        };
+        let command = match font.font.as_str() {
+            "mathrm" => "\\rm",
+            "mathsf" => "\\sf",
+            "mathtt" => "\\tt",
+            "mathbf" => "\\bf",
+            "mathit" => "\\it",
+            "mathcal" => "\\cal",
+            _ => return false,
+        };
+        match as_ordgroup(font.body.as_ref()) {
+            Some(group) if group.body.len() == 1 => {
+                self.push(command);
+                self.list(&group.body);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /** A list with more of its group after it, see `open_end`. */
+    fn open_list(&mut self, nodes: &[Box<dyn AnyParseNode>]) {
+        let open_end = std::mem::replace(&mut self.open_end, true);
+        self.list(nodes);
+        self.open_end = open_end;
+    }
+
+    /** A braced list, e.g. the body of `\text` or `\textcolor`. */
+    fn braced(&mut self, nodes: &[Box<dyn AnyParseNode>]) {
+        let open_end = std::mem::replace(&mut self.open_end, false);
+        self.push("{");
+        self.list(nodes);
+        self.push("}");
+        self.open_end = open_end;
+    }
+
+    /** A single argument, always written in braces. */
+    fn group(&mut self, node: &dyn AnyParseNode) {
+        match as_ordgroup(node) {
+            Some(group) => self.braced(&group.body),
+            None => {
+                self.push("{");
+                self.node(node);
+                self.push("}");
+            }
+        }
+    }
+
+    /**
+     * An argument the parser unwraps when it is a group of one node, as the
+     * body of `\mathbf`, so such a group is written in a second pair of braces.
+     */
+    fn unwrapped_group(&mut self, node: &dyn AnyParseNode) {
+        match as_ordgroup(node) {
+            Some(group) if group.body.len() == 1 => {
+                self.push("{");
+                self.node(node);
+                self.push("}");
+            }
+            _ => self.group(node),
+        }
+    }
+
+    /** The contents of an argument, without the braces. */
+    fn group_body(&mut self, node: &dyn AnyParseNode) {
+        match as_ordgroup(node) {
+            Some(group) => self.list(&group.body),
+            None => self.node(node),
+        }
+    }
+
+    /** An `hbox` argument, which the parser wraps in `\textstyle`. */
+    fn hbox_group(&mut self, node: &dyn AnyParseNode) {
+        if let Some(styling) = node.as_any().downcast_ref::<types::styling>() {
+            if styling.body.len() == 1 && as_ordgroup(styling.body[0].as_ref()).is_some() {
+                return self.group(styling.body[0].as_ref());
+            }
+        }
+        self.group(node)
+    }
+
+    /** An optional argument in brackets, braced when it contains a `]`. */
+    fn optional(&mut self, node: &dyn AnyParseNode) {
+        let mut inner = self.inner();
+        inner.group_body(node);
+        self.push("[");
+        if inner.out.contains(']') {
+            self.push("{");
+            self.push(&inner.out);
+            self.push("}");
+        } else {
+            self.push(&inner.out);
+        }
+        self.push("]");
+    }
+
+    fn command_with_group(&mut self, command: &str, node: &dyn AnyParseNode) {
+        self.push(command);
+        self.group(node);
+    }
+
+    fn command_with_list(&mut self, command: &str, nodes: &[Box<dyn AnyParseNode>]) {
+        self.push(command);
+        self.braced(nodes);
+    }
+
+    fn raw_group(&mut self, s: &str) {
+        self.push("{");
+        self.push(s);
+        self.push("}");
+    }
+
+    fn infix_frac(&mut self, frac: &types::genfrac) {
+        match as_ordgroup(frac.numer.as_ref()) {
+            Some(group) => self.open_list(&group.body),
+            None => self.node(frac.numer.as_ref()),
+        }
+        self.push(infix_command(frac).unwrap());
+        self.group_body(frac.denom.as_ref());
+    }
+
+    fn genfrac(&mut self, frac: &types::genfrac) {
+        if frac.continued {
+            self.push("\\cfrac");
+        } else if infix_command(frac).is_some() {
+            self.push("{");
+            self.infix_frac(frac);
+            self.push("}");
+            return;
+        } else {
+            let delims = (frac.leftDelim.as_deref(), frac.rightDelim.as_deref());
+            let named = match (frac.hasBarLine, frac.barSize.is_some(), delims, frac.size.as_str()) {
+                (true, false, (None, None), "auto") => Some("\\frac"),
+                (true, false, (None, None), "display") => Some("\\dfrac"),
+                (true, false, (None, None), "text") => Some("\\tfrac"),
+                (false, false, (Some("("), Some(")")), "auto") => Some("\\binom"),
+                (false, false, (Some("("), Some(")")), "display") => Some("\\dbinom"),
+                (false, false, (Some("("), Some(")")), "text") => Some("\\tbinom"),
+                _ => None,
+            };
+            match named {
+                Some(command) => self.push(command),
+                None => {
+                    self.push("\\genfrac");
+                    self.raw_group(delims.0.unwrap_or(""));
+                    self.raw_group(delims.1.unwrap_or(""));
+                    self.raw_group(&frac.barSize.as_ref().map(measurement).unwrap_or_default());
+                    let style = match frac.size.as_str() {
+                        "display" => "0",
+                        "text" => "1",
+                        "script" => "2",
+                        "scriptscript" => "3",
+                        _ => "",
+                    };
+                    self.raw_group(style);
+                }
+            }
+        }
+        self.group(frac.numer.as_ref());
+        self.group(frac.denom.as_ref());
+    }
+
+    fn supsub(&mut self, node: &types::supsub) {
+        if let Some(base) = &node.base {
+            self.node(base.as_ref());
+        }
+        if let Some(sub) = &node.sub {
+            self.push("_");
+            self.token_or_group(sub.as_ref());
+        }
+        if let Some(sup) = &node.sup {
+            self.superscript(sup.as_ref());
+        }
+    }
+
+    /**
+     * A superscript, where `x'` and `x'^2` leave a group that starts with
+     * `\prime`s.
+     */
+    fn superscript(&mut self, sup: &dyn AnyParseNode) {
+        if let Some(group) = as_ordgroup(sup) {
+            let primes = group.body.iter().take_while(|node| is_prime(node.as_ref())).count();
+            let rest = match &group.body[primes..] {
+                [] => Some(None),
+                [rest] if as_ordgroup(rest.as_ref()).is_some() => Some(Some(rest)),
+                [rest] if crate::parse_node::check_symbol_node_type(rest.as_ref()) => {
+                    let mut inner = self.inner();
+                    inner.node(rest.as_ref());
+                    is_single_token(&inner.out).then_some(Some(rest))
+                }
+                _ => None,
+            };
+            if let (true, Some(rest)) = (primes > 0, rest) {
+                self.push(&"'".repeat(primes));
+                if let Some(rest) = rest {
+                    self.push("^");
+                    self.token_or_group(rest.as_ref());
+                }
+                return;
+            }
+        }
+        self.push("^");
+        self.token_or_group(sup);
+    }
+
+    /**
+     * A sub- or superscript, or the base of a text accent. Braces would add
+     * an ordgroup, so an argument that had none, like the `n` of `x^n` or the
+     * `a` of `\"a`, is written bare when it is one token.
+     */
+    fn token_or_group(&mut self, node: &dyn AnyParseNode) {
+        if crate::parse_node::check_symbol_node_type(node) {
+            let mut inner = self.inner();
+            inner.node(node);
+            if is_single_token(&inner.out) {
+                return self.push(&inner.out);
+            }
+        }
+        self.group(node)
+    }
+
+    fn limits(&mut self, always_handle_sup_sub: bool, limits: bool) {
+        if always_handle_sup_sub {
+            self.push(if limits { "\\limits" } else { "\\nolimits" });
+        }
+    }
+
+    fn mclass(&mut self, node: &types::mclass) {
+        if let [inner] = node.body.as_slice() {
+            if let Some(font) = inner.as_any().downcast_ref::<types::font>() {
+                if font.font == "boldsymbol" {
+                    return self.command_with_group("\\boldsymbol", font.body.as_ref());
+                }
+            }
+            // \stackrel, \overset and \underset put their base in a \mathop
+            // with limits.
+            if let Some(supsub) = inner.as_any().downcast_ref::<types::supsub>() {
+                let op = supsub
+                    .base
+                    .as_ref()
+                    .and_then(|base| base.as_any().downcast_ref::<types::op>())
+                    .filter(|op| op.name.is_none() && op.alwaysHandleSupSub && op.limits);
+                if let (Some(op), Some(body)) = (op, op.and_then(|op| op.body.as_ref())) {
+                    match (&supsub.sup, &supsub.sub, op.suppressBaseShift) {
+                        (Some(sup), None, false) => {
+                            self.command_with_group("\\stackrel", sup.as_ref());
+                            return self.braced(body);
+                        }
+                        (Some(sup), None, true) => {
+                            self.command_with_group("\\overset", sup.as_ref());
+                            return self.braced(body);
+                        }
+                        (None, Some(sub), true) => {
+                            self.command_with_group("\\underset", sub.as_ref());
+                            return self.braced(body);
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+        let command = match node.mclass.as_str() {
+            "mbin" => "\\mathbin",
+            "mrel" => "\\mathrel",
+            "mopen" => "\\mathopen",
+            "mclose" => "\\mathclose",
+            "mpunct" => "\\mathpunct",
+            "minner" => "\\mathinner",
+            _ => "\\mathord",
+        };
+        self.command_with_list(command, &node.body)
+    }
+
+    fn leftright(&mut self, node: &types::leftright) {
+        if let [inner] = node.body.as_slice() {
+            if let Some(array) = inner.as_any().downcast_ref::<types::array>() {
+                let display = cell_style(array) == Some(StyleStr::display);
+                if is_cases(array) {
+                    let name = match (node.left.as_str(), node.right.as_str(), display) {
+                        ("\\{", ".", false) => Some("cases"),
+                        ("\\{", ".", true) => Some("dcases"),
+                        (".", "\\}", false) => Some("rcases"),
+                        (".", "\\}", true) => Some("drcases"),
+                        _ => None,
+                    };
+                    if let Some(name) = name {
+                        return self.environment(name, None, array);
+                    }
+                }
+                if let (Some(name), true, false) = (matrix_name(&node.left, &node.right), is_matrix(array), display) {
+                    return self.matrix(name, array);
+                }
+            }
+        }
+        self.push("\\left");
+        self.push(&node.left);
+        let mut body = node.body.as_slice();
+        let mut right_color = None;
+        if let (Some(color), Some(last)) = (&node.right_color, body.last()) {
+            // A \color that is still open at \right also colors the delimiter.
+            if let Some(last) = last.as_any().downcast_ref::<types::color>() {
+                if &last.color == color {
+                    right_color = Some(last);
+                    body = &body[..body.len() - 1];
+                }
+            }
+        }
+        self.list(body);
+        if let Some(color) = right_color {
+            self.push("\\color");
+            self.raw_group(&color.color);
+            self.list(&color.body);
+        }
+        self.push("\\right");
+        self.push(&node.right);
+    }
+
+    fn matrix(&mut self, name: &str, array: &types::array) {
+        let align = array.cols.first().and_then(|col| match col {
+            AlignSpec::Align(a) => Some(a.align.as_str()),
+            AlignSpec::Separator(_) => None,
+        });
+        match align {
+            Some(align) if align != "c" => {
+                let starred = format!("{}*", name);
+                self.environment(&starred, Some(&format!("[{}]", align)), array)
+            }
+            _ => self.environment(name, None, array),
+        }
+    }
+
+    fn array(&mut self, array: &types::array) {
+        if array.is_cd {
+            return self.cd(array);
+        }
+        // Starred environments have a tag slot on every row but no numbers.
+        let name = |numbered: &str, ed: &str| match &array.tags {
+            Some(_) if is_starred(array) => format!("{}*", numbered),
+            Some(_) => numbered.to_string(),
+            None => ed.to_string(),
+        };
+        let display = cell_style(array) == Some(StyleStr::display);
+        match &array.col_separation_type {
+            Some(ColSeparationType::Align) => self.environment(&name("align", "aligned"), None, array),
+            Some(ColSeparationType::AlignAt) => {
+                let arg = format!("{{{}}}", array.cols.len() / 2);
+                self.environment(&name("alignat", "alignedat"), Some(&arg), array)
+            }
+            Some(ColSeparationType::Gather) => self.environment(&name("gather", "gathered"), None, array),
+            Some(ColSeparationType::Small) => self.environment("smallmatrix", None, array),
+            Some(ColSeparationType::CD) => self.cd(array),
+            None if array.hskip_before_and_after => {
+                let mut spec = String::from("{");
+                for col in array.cols.iter() {
+                    match col {
+                        AlignSpec::Align(a) => spec.push_str(&a.align),
+                        AlignSpec::Separator(s) => spec.push_str(&s.separator),
+                    }
+                }
+                spec.push('}');
+                self.environment(if display { "darray" } else { "array" }, Some(&spec), array)
+            }
+            None if array.array_stretch == 0.5 => {
+                let align = match array.cols.first() {
+                    Some(AlignSpec::Align(a)) => a.align.clone(),
+                    _ => "c".to_string(),
+                };
+                self.environment("subarray", Some(&format!("{{{}}}", align)), array)
+            }
+            None if array.cols.is_empty() && !array.body.is_empty() => {
+                self.environment(&name("equation", "equation*"), None, array)
+            }
+            None => self.matrix("matrix", array),
+        }
+    }
+
+    fn environment(&mut self, name: &str, args: Option<&str>, array: &types::array) {
+        let aligned = matches!(
+            array.col_separation_type,
+            Some(ColSeparationType::Align) | Some(ColSeparationType::AlignAt)
+        );
+        self.push("\\begin");
+        self.raw_group(name);
+        if let Some(args) = args {
+            self.push(args);
+        }
+        for (i, row) in array.body.iter().enumerate() {
+            self.hlines(array, i);
+            for (j, cell) in row.iter().enumerate() {
+                if j > 0 {
+                    self.push("&");
+                }
+                let mut body = match cell.as_any().downcast_ref::<types::styling>() {
+                    Some(styling) => match styling.body.as_slice() {
+                        [group] => match as_ordgroup(group.as_ref()) {
+                            Some(group) => group.body.as_slice(),
+                            None => styling.body.as_slice(),
+                        },
+                        body => body,
+                    },
+                    None => std::slice::from_ref(cell),
+                };
+                // {aligned} puts an empty group in front of every second cell.
+                if aligned && j % 2 == 1 {
+                    if let Some(first) = body.first() {
+                        if as_ordgroup(first.as_ref()).is_some_and(|g| g.body.is_empty()) {
+                            body = &body[1..];
+                        }
+                    }
+                }
+                self.list(body);
+            }
+            match array.tags.as_ref().and_then(|tags| tags.get(i)) {
+                Some(ArrayTag::A(false)) if !is_starred(array) => self.push("\\nonumber"),
+                Some(ArrayTag::B(tag)) => self.tag(tag),
+                _ => {}
+            }
+            if i < array.row_gaps.len() {
+                self.push("\\\\");
+                if let Some(Some(gap)) = array.row_gaps.get(i) {
+                    self.push(&format!("[{}]", measurement(gap)));
+                }
+            }
+        }
+        self.hlines(array, array.body.len());
+        self.push("\\end");
+        self.raw_group(name);
+    }
+
+    fn hlines(&mut self, array: &types::array, row: usize) {
+        if let Some(lines) = array.h_lines_before_row.get(row) {
+            for dashed in lines {
+                self.push(if *dashed { "\\hdashline" } else { "\\hline" });
+            }
+        }
+    }
+
+    /** The `\tag*` that produced a tag, which is the content of `\text`. */
+    fn tag(&mut self, tag: &[Box<dyn AnyParseNode>]) {
+        self.push("\\tag*");
+        match tag {
+            [text] => match text.as_any().downcast_ref::<types::text>() {
+                Some(text) => self.braced(&text.body),
+                None => self.braced(tag),
+            },
+            _ => self.braced(tag),
+        }
+    }
+
+    fn cd(&mut self, array: &types::array) {
+        self.push("\\begin{CD}");
+        let rows = array.body.iter().take_while(|row| !row.is_empty());
+        for (i, row) in rows.enumerate() {
+            if i > 0 {
+                self.push("\\\\");
+            }
+            // Object rows alternate object and arrow cells; arrow rows start
+            // with an arrow.
+            let arrow_parity = if i % 2 == 0 { 1 } else { 0 };
+            for (j, cell) in row.iter().enumerate() {
+                let body = match cell.as_any().downcast_ref::<types::styling>() {
+                    Some(styling) => styling.body.as_slice(),
+                    None => std::slice::from_ref(cell),
+                };
+                if j % 2 == arrow_parity {
+                    match body {
+                        [arrow] => self.cd_arrow(arrow.as_ref()),
+                        _ => self.push("@."),
+                    }
+                } else {
+                    self.list(body);
+                }
+            }
+        }
+        self.push("\\end{CD}");
+    }
+
+    fn cd_arrow(&mut self, arrow: &dyn AnyParseNode) {
+        if let Some(x) = arrow.as_any().downcast_ref::<types::xArrow>() {
+            let c = match x.label.as_str() {
+                "\\\\cdrightarrow" => ">",
+                "\\\\cdleftarrow" => "<",
+                _ => return self.push("@="),
+            };
+            self.push(&format!("@{}", c));
+            self.group_body(x.body.as_ref());
+            self.push(c);
+            if let Some(below) = &x.below {
+                self.group_body(below.as_ref());
+            }
+            self.push(c);
+        } else if let Some(parent) = arrow.as_any().downcast_ref::<types::cdlabelparent>() {
+            let parts = parent
+                .fragment
+                .as_any()
+                .downcast_ref::<types::ordgroup>()
+                .map(|g| g.body.as_slice())
+                .unwrap_or_default();
+            let labels = parts
+                .iter()
+                .filter_map(|part| part.as_any().downcast_ref::<types::cdlabel>())
+                .collect::<Vec<_>>();
+            let up = parts.iter().any(|part| {
+                part.as_any()
+                    .downcast_ref::<types::delimsizing>()
+                    .is_some_and(|d| d.delim == "\\uparrow")
+            });
+            let c = if up { "A" } else { "V" };
+            self.push(&format!("@{}", c));
+            for label in labels {
+                self.group_body(label.label.as_ref());
+                self.push(c);
+            }
+        } else if arrow.get_type() == "delimsizing" {
+            self.push("@|");
+        } else {
+            self.push("@.");
+        }
+    }
+
+    fn node(&mut self, node: &dyn AnyParseNode) {
+        let any = node.as_any();
+        if !self.expanded && (node.get_type() == "htmlmathml" || node.get_type() == "kern") {
+            let mut expansion = LatexWriter { expanded: true, ..Default::default() };
+            expansion.node(node);
+            if let Some(command) = shorthand(node, &expansion.out) {
+                return self.push(command);
+            }
+        }
+        if let Some(n) = any.downcast_ref::<types::textord>() {
+            // A character the parser would read as some other kind of atom,
+            // or as markup in text, as produced by `\@char` and by
+            // `Parser::format_unsupported_cmd`.
+            let mut chars = n.text.chars();
+            if let (Some(c), None) = (chars.next(), chars.next()) {
+                let special = match n.mode {
+                    Mode::math => get_symbol(Mode::math, &n.text).is_none_or(|s| !matches!(s.group, Group::textord)),
+                    Mode::text => "\\{}$&#%^_~".contains(c),
+                };
+                if special {
+                    self.push("\\@char");
+                    return self.raw_group(&(c as u32).to_string());
+                }
+            }
+        }
+        if crate::parse_node::check_symbol_node_type(node) {
+            self.push(&crate::parse_node::check_symbol_node_type_text(node));
+        } else if let Some(n) = any.downcast_ref::<types::ordgroup>() {
+            if n.semisimple {
+                self.push("\\begingroup");
+                self.list(&n.body);
+                self.push("\\endgroup");
+            } else {
+                if let [inner] = n.body.as_slice() {
+                    if is_infix_frac(inner.as_ref()) {
+                        return self.node(inner.as_ref());
+                    }
+                }
+                self.braced(&n.body);
+            }
+        } else if let Some(n) = any.downcast_ref::<types::supsub>() {
+            self.supsub(n);
+        } else if let Some(n) = any.downcast_ref::<types::genfrac>() {
+            self.genfrac(n);
+        } else if let Some(n) = any.downcast_ref::<types::array>() {
+            self.array(n);
+        } else if let Some(n) = any.downcast_ref::<types::leftright>() {
+            self.leftright(n);
+        } else if let Some(n) = any.downcast_ref::<types::leftright_right>() {
+            self.push("\\right");
+            self.push(&n.delim);
+        } else if let Some(n) = any.downcast_ref::<types::middle>() {
+            self.push("\\middle");
+            self.push(&n.delim);
+        } else if let Some(n) = any.downcast_ref::<types::delimsizing>() {
+            let size = ["big", "Big", "bigg", "Bigg"][n.size.clamp(1, 4) - 1];
+            let suffix = match n.mclass.as_str() {
+                "mopen" => "l",
+                "mclose" => "r",
+                "mrel" => "m",
+                _ => "",
+            };
+            self.push(&format!("\\{}{}", size, suffix));
+            self.push(&n.delim);
+        } else if let Some(n) = any.downcast_ref::<types::op>() {
+            match (&n.name, &n.body) {
+                (Some(name), _) => self.push(name),
+                (None, Some(body)) => self.command_with_list("\\mathop", body),
+                (None, None) => self.push("\\mathop{}"),
+            }
+            self.limits(n.alwaysHandleSupSub, n.limits);
+        } else if let Some(n) = any.downcast_ref::<types::operatorname>() {
+            if n.always_handle_sup_sub && !n.limits {
+                self.command_with_list("\\operatornamewithlimits", &n.body);
+            } else {
+                self.command_with_list("\\operatorname", &n.body);
+                self.limits(n.always_handle_sup_sub, n.limits);
+            }
+        } else if let Some(n) = any.downcast_ref::<types::mclass>() {
+            self.mclass(n);
+        } else if let Some(n) = any.downcast_ref::<types::font>() {
+            self.push(&format!("\\{}", n.font));
+            self.unwrapped_group(n.body.as_ref());
+        } else if let Some(n) = any.downcast_ref::<types::color>() {
+            if let Some(source) = unsupported_source(n) {
+                return self.push(source);
+            }
+            // An unsupported command colored with an empty errorColor, which
+            // has no LaTeX form.
+            if n.color.is_empty() {
+                return self.list(&n.body);
+            }
+            self.push("\\textcolor");
+            self.raw_group(&n.color);
+            self.braced(&n.body);
+        } else if let Some(n) = any.downcast_ref::<types::color_token>() {
+            self.push(&n.color);
+        } else if let Some(n) = any.downcast_ref::<types::sizing>() {
+            const SIZES: [&str; 11] = [
+                "\\tiny", "\\sixptsize", "\\scriptsize", "\\footnotesize", "\\small", "\\normalsize",
+                "\\large", "\\Large", "\\LARGE", "\\huge", "\\Huge",
+            ];
+            if let Some(size) = n.size.checked_sub(1).and_then(|i| SIZES.get(i)) {
+                self.push(size);
+            }
+            self.list(&n.body);
+        } else if let Some(n) = any.downcast_ref::<types::styling>() {
+            // `\(...\)` in text mode is a \textstyle group whose body is math.
+            let math_body = n.body.first().is_none_or(|first| {
+                first.to_json().get("mode").and_then(|mode| mode.as_str()) == Some("math")
+            });
+            if n.mode == Mode::text && n.style == StyleStr::text && math_body {
+                self.push("\\(");
+                self.list(&n.body);
+                self.push("\\)");
+            } else {
+                self.push(&format!("\\{}style", style_name(&n.style)));
+                self.list(&n.body);
+            }
+        } else if let Some(n) = any.downcast_ref::<types::text>() {
+            self.command_with_list(n.font.as_deref().unwrap_or("\\text"), &n.body);
+        } else if let Some(n) = any.downcast_ref::<types::hbox>() {
+            self.command_with_list("\\hbox", &n.body);
+        } else if let Some(n) = any.downcast_ref::<types::accent>() {
+            self.push(&n.label);
+            match &n.base {
+                // Only math accents unwrap their argument.
+                Some(base) if n.mode == Mode::math => self.unwrapped_group(base.as_ref()),
+                Some(base) => self.token_or_group(base.as_ref()),
+                None => self.push("{}"),
+            }
+        } else if let Some(n) = any.downcast_ref::<types::accentUnder>() {
+            self.command_with_group(&n.label, n.base.as_ref());
+        } else if let Some(n) = any.downcast_ref::<types::horizBrace>() {
+            self.command_with_group(&n.label, n.base.as_ref());
+        } else if let Some(n) = any.downcast_ref::<types::xArrow>() {
+            self.push(&n.label);
+            if let Some(below) = &n.below {
+                self.optional(below.as_ref());
+            }
+            self.group(n.body.as_ref());
+        } else if let Some(n) = any.downcast_ref::<types::sqrt>() {
+            self.push("\\sqrt");
+            if let Some(index) = &n.index {
+                self.optional(index.as_ref());
+            }
+            self.group(n.body.as_ref());
+        } else if let Some(n) = any.downcast_ref::<types::enclose>() {
+            self.push(&n.label);
+            match n.label.as_str() {
+                "\\colorbox" => {
+                    self.raw_group(n.backgroundColor.as_deref().unwrap_or_default());
+                    self.group(n.body.as_ref());
+                }
+                "\\fcolorbox" => {
+                    self.raw_group(n.borderColor.as_deref().unwrap_or_default());
+                    self.raw_group(n.backgroundColor.as_deref().unwrap_or_default());
+                    self.group(n.body.as_ref());
+                }
+                "\\fbox" | "\\angl" => self.hbox_group(n.body.as_ref()),
+                _ => self.group(n.body.as_ref()),
+            }
+        } else if let Some(n) = any.downcast_ref::<types::overline>() {
+            self.command_with_group("\\overline", n.body.as_ref());
+        } else if let Some(n) = any.downcast_ref::<types::underline>() {
+            self.command_with_group("\\underline", n.body.as_ref());
+        } else if let Some(n) = any.downcast_ref::<types::vcenter>() {
+            self.command_with_group("\\vcenter", n.body.as_ref());
+        } else if let Some(n) = any.downcast_ref::<types::lap>() {
+            self.command_with_group(&format!("\\math{}", n.alignment), n.body.as_ref());
+        } else if let Some(n) = any.downcast_ref::<types::phantom>() {
+            self.command_with_list("\\phantom", &n.body);
+        } else if let Some(n) = any.downcast_ref::<types::hphantom>() {
+            self.command_with_group("\\hphantom", n.body.as_ref());
+        } else if let Some(n) = any.downcast_ref::<types::vphantom>() {
+            self.command_with_group("\\vphantom", n.body.as_ref());
+        } else if let Some(n) = any.downcast_ref::<types::pmb>() {
+            self.command_with_list("\\pmb", &n.body);
+        } else if let Some(n) = any.downcast_ref::<types::smash>() {
+            self.push("\\smash");
+            match (n.smash_height, n.smash_depth) {
+                (true, true) => {}
+                (true, false) => self.push("[t]"),
+                (false, true) => self.push("[b]"),
+                (false, false) => self.push("[]"),
+            }
+            self.group(n.body.as_ref());
+        } else if let Some(n) = any.downcast_ref::<types::raisebox>() {
+            self.push("\\raisebox");
+            self.raw_group(&measurement(&n.dy));
+            self.hbox_group(n.body.as_ref());
+        } else if let Some(n) = any.downcast_ref::<types::rule>() {
+            self.push("\\rule");
+            if let Some(shift) = &n.shift {
+                self.push(&format!("[{}]", measurement(shift)));
+            }
+            self.raw_group(&measurement(&n.width));
+            self.raw_group(&measurement(&n.height));
+        } else if let Some(n) = any.downcast_ref::<types::kern>() {
+            self.push(if n.dimension.unit == "mu" { "\\mkern" } else { "\\kern" });
+            self.raw_group(&measurement(&n.dimension));
+        } else if let Some(n) = any.downcast_ref::<types::size>() {
+            self.push(&measurement(&n.value));
+        } else if let Some(n) = any.downcast_ref::<types::cr>() {
+            self.push("\\\\");
+            if let Some(size) = &n.size {
+                self.push(&format!("[{}]", measurement(size)));
+            }
+        } else if let Some(n) = any.downcast_ref::<types::mathchoice>() {
+            self.command_with_list("\\mathchoice", &n.display);
+            self.braced(&n.text);
+            self.braced(&n.script);
+            self.braced(&n.scriptscript);
+        } else if let Some(n) = any.downcast_ref::<types::href>() {
+            let url_text = match n.body.as_slice() {
+                [text] => text
+                    .as_any()
+                    .downcast_ref::<types::text>()
+                    .filter(|text| text.font.as_deref() == Some("\\texttt"))
+                    .map(|text| {
+                        text.body
+                            .iter()
+                            .map(|c| match c.as_any().downcast_ref::<types::textord>() {
+                                Some(c) if c.text == "\\textasciitilde" => "~".to_string(),
+                                Some(c) => c.text.clone(),
+                                None => String::new(),
+                            })
+                            .collect::<String>()
+                    }),
+                _ => None,
+            };
+            if url_text.as_deref() == Some(n.href.as_str()) {
+                self.push("\\url");
+                self.raw_group(&url_escape(&n.href));
+            } else {
+                self.push("\\href");
+                self.raw_group(&url_escape(&n.href));
+                self.braced(&n.body);
+            }
+        } else if let Some(n) = any.downcast_ref::<types::url>() {
+            self.push(&url_escape(&n.url));
+        } else if let Some(n) = any.downcast_ref::<types::html>() {
+            let single = if n.attributes.len() == 1 {
+                n.attributes.iter().next().and_then(|(key, value)| {
+                    let command = match key.as_str() {
+                        "class" => "\\htmlClass",
+                        "id" => "\\htmlId",
+                        "style" => "\\htmlStyle",
+                        _ => return None,
+                    };
+                    Some((command, value))
+                })
+            } else {
+                None
+            };
+            match single {
+                Some((command, value)) => {
+                    self.push(command);
+                    self.raw_group(value);
+                }
+                None => {
+                    let data = n
+                        .attributes
+                        .iter()
+                        .map(|(key, value)| format!("{}={}", key.trim_start_matches("data-"), value))
+                        .collect::<Vec<_>>()
+                        .join(",");
+                    self.push("\\htmlData");
+                    self.raw_group(&data);
+                }
+            }
+            self.braced(&n.body);
+        } else if let Some(n) = any.downcast_ref::<types::htmlmathml>() {
+            self.command_with_list("\\html@mathml", &n.html);
+            self.braced(&n.mathml);
+        } else if let Some(n) = any.downcast_ref::<types::includegraphics>() {
+            let mut options = vec![];
+            let default_alt = n.src.rsplit(['/', '\\']).next().unwrap_or_default();
+            let default_alt = &default_alt[..default_alt.rfind('.').unwrap_or(default_alt.len())];
+            if n.alt != default_alt {
+                options.push(format!("alt={}", n.alt));
+            }
+            for (key, value, default) in [
+                ("width", &n.width, 0.0),
+                ("height", &n.height, 0.9),
+                ("totalheight", &n.totalheight, 0.0),
+            ] {
+                if value.number != default || value.unit != "em" {
+                    options.push(format!("{}={}", key, measurement(value)));
+                }
+            }
+            self.push("\\includegraphics");
+            if !options.is_empty() {
+                self.push(&format!("[{}]", options.join(",")));
+            }
+            self.raw_group(&url_escape(&n.src));
+        } else if let Some(n) = any.downcast_ref::<types::verb>() {
+            let delim = ['|', '!', '+', '/', '"', '=', '#', '@']
+                .into_iter()
+                .find(|c| !n.body.contains(*c))
+                .unwrap_or('|');
+            self.push(if n.star { "\\verb*" } else { "\\verb" });
+            self.out.push(delim);
+            self.out.push_str(&n.body);
+            self.out.push(delim);
+        } else if let Some(n) = any.downcast_ref::<types::tag>() {
+            self.open_list(&n.body);
+            self.tag(&n.tag);
+        } else if let Some(n) = any.downcast_ref::<types::raw>() {
+            self.push(&n.string);
+        } else if let Some(n) = any.downcast_ref::<types::cdlabel>() {
+            self.command_with_group(&format!("\\\\cd{}", n.side), n.label.as_ref());
+        } else if let Some(n) = any.downcast_ref::<types::cdlabelparent>() {
+            self.command_with_group("\\\\cdparent", n.fragment.as_ref());
+        }
+        // `internal`, `environment` and `infix` nodes leave no trace in the
+        // source: they are consumed by the parser before a tree is returned.
+    }
+}
@@ -0,0 +1,205 @@
+use crate::define::environments::array::AlignSpec;
+use crate::parse_node::types::{AnyParseNode, ArrayTag, Atom, ColSeparationType};
+use crate::sourceLocation::SourceLocation;
+use crate::token::Token;
+use crate::types::{Mode, StyleStr};
+use crate::units::Measurement;
+use indexmap::IndexMap;
+
+/**
+ * Traversal of parse trees.
+ *
+ * `#[derive(parse_node_type)]` implements `AnyParseNode::for_each_child`,
+ * `for_each_child_mut` and `map_children` by calling `Children` on every
+ * field, so each node type walks all of its children without a hand-written
+ * downcast chain. A field of a type with no `Children` impl is a compile
+ * error rather than a silently skipped subtree.
+ *
+ * `Visit`, `VisitMut` and `Fold` are built on top of these, in the style of
+ * `syn::visit`: override the method you care about and call the matching
+ * `walk_*`/`fold_children` function to keep descending.
+ */
+pub trait Children {
+    fn walk(&self, _f: &mut dyn FnMut(&Box<dyn AnyParseNode>)) {}
+
+    fn walk_mut(&mut self, _f: &mut dyn FnMut(&mut Box<dyn AnyParseNode>)) {}
+
+    fn map(self, _f: &mut dyn FnMut(Box<dyn AnyParseNode>) -> Box<dyn AnyParseNode>) -> Self
+    where
+        Self: Sized,
+    {
+        self
+    }
+}
+
+impl Children for Box<dyn AnyParseNode> {
+    fn walk(&self, f: &mut dyn FnMut(&Box<dyn AnyParseNode>)) {
+        f(self)
+    }
+
+    fn walk_mut(&mut self, f: &mut dyn FnMut(&mut Box<dyn AnyParseNode>)) {
+        f(self)
+    }
+
+    fn map(self, f: &mut dyn FnMut(Box<dyn AnyParseNode>) -> Box<dyn AnyParseNode>) -> Self {
+        f(self)
+    }
+}
+
+impl<T: Children> Children for Option<T> {
+    fn walk(&self, f: &mut dyn FnMut(&Box<dyn AnyParseNode>)) {
+        if let Some(v) = self {
+            v.walk(f);
+        }
+    }
+
+    fn walk_mut(&mut self, f: &mut dyn FnMut(&mut Box<dyn AnyParseNode>)) {
+        if let Some(v) = self {
+            v.walk_mut(f);
+        }
+    }
+
+    fn map(self, f: &mut dyn FnMut(Box<dyn AnyParseNode>) -> Box<dyn AnyParseNode>) -> Self {
+        self.map(|v| Children::map(v, f))
+    }
+}
+
+impl<T: Children> Children for Vec<T> {
+    fn walk(&self, f: &mut dyn FnMut(&Box<dyn AnyParseNode>)) {
+        for v in self.iter() {
+            v.walk(f);
+        }
+    }
+
+    fn walk_mut(&mut self, f: &mut dyn FnMut(&mut Box<dyn AnyParseNode>)) {
+        for v in self.iter_mut() {
+            v.walk_mut(f);
+        }
+    }
+
+    fn map(self, f: &mut dyn FnMut(Box<dyn AnyParseNode>) -> Box<dyn AnyParseNode>) -> Self {
+        self.into_iter().map(|v| Children::map(v, f)).collect()
+    }
+}
+
+impl Children for ArrayTag {
+    fn walk(&self, f: &mut dyn FnMut(&Box<dyn AnyParseNode>)) {
+        if let ArrayTag::B(nodes) = self {
+            nodes.walk(f);
+        }
+    }
+
+    fn walk_mut(&mut self, f: &mut dyn FnMut(&mut Box<dyn AnyParseNode>)) {
+        if let ArrayTag::B(nodes) = self {
+            nodes.walk_mut(f);
+        }
+    }
+
+    fn map(self, f: &mut dyn FnMut(Box<dyn AnyParseNode>) -> Box<dyn AnyParseNode>) -> Self {
+        match self {
+            ArrayTag::A(b) => ArrayTag::A(b),
+            ArrayTag::B(nodes) => ArrayTag::B(Children::map(nodes, f)),
+        }
+    }
+}
+
+// Fields that never hold parse nodes.
+impl Children for bool {}
+impl Children for usize {}
+impl Children for f64 {}
+impl Children for String {}
+impl Children for IndexMap<String, String> {}
+impl Children for Mode {}
+impl Children for StyleStr {}
+impl Children for Atom {}
+impl Children for ColSeparationType {}
+impl Children for AlignSpec {}
+impl Children for Measurement {}
+impl Children for SourceLocation {}
+impl Children for Token {}
+
+/**
+ * Read-only traversal. The default `visit_node` visits every descendant in
+ * field order; override it and call `walk_node` to continue into children.
+ */
+pub trait Visit {
+    fn visit_node(&mut self, node: &Box<dyn AnyParseNode>) {
+        walk_node(self, node);
+    }
+
+    fn visit_nodes(&mut self, nodes: &[Box<dyn AnyParseNode>]) {
+        for node in nodes.iter() {
+            self.visit_node(node);
+        }
+    }
+}
+
+pub fn walk_node<V: Visit + ?Sized>(visitor: &mut V, node: &Box<dyn AnyParseNode>) {
+    node.for_each_child(&mut |child| visitor.visit_node(child));
+}
+
+/** In-place traversal, see `Visit`. */
+pub trait VisitMut {
+    fn visit_node_mut(&mut self, node: &mut Box<dyn AnyParseNode>) {
+        walk_node_mut(self, node);
+    }
+
+    fn visit_nodes_mut(&mut self, nodes: &mut [Box<dyn AnyParseNode>]) {
+        for node in nodes.iter_mut() {
+            self.visit_node_mut(node);
+        }
+    }
+}
+
+pub fn walk_node_mut<V: VisitMut + ?Sized>(visitor: &mut V, node: &mut Box<dyn AnyParseNode>) {
+    node.for_each_child_mut(&mut |child| visitor.visit_node_mut(child));
+}
+
+/**
+ * Owning transformation: `fold_node` may return a node of a different type.
+ * The default rebuilds the node with its children folded.
+ */
+pub trait Fold {
+    fn fold_node(&mut self, node: Box<dyn AnyParseNode>) -> Box<dyn AnyParseNode> {
+        fold_children(self, node)
+    }
+
+    fn fold_nodes(&mut self, nodes: Vec<Box<dyn AnyParseNode>>) -> Vec<Box<dyn AnyParseNode>> {
+        nodes.into_iter().map(|node| self.fold_node(node)).collect()
+    }
+}
+
+pub fn fold_children<F: Fold + ?Sized>(folder: &mut F, node: Box<dyn AnyParseNode>) -> Box<dyn AnyParseNode> {
+    node.map_children(&mut |child| folder.fold_node(child))
+}
+
+struct AnyNode<P> {
+    predicate: P,
+    found: bool,
+}
+
+impl<P: FnMut(&Box<dyn AnyParseNode>) -> bool> Visit for AnyNode<P> {
+    fn visit_node(&mut self, node: &Box<dyn AnyParseNode>) {
+        if self.found {
+            return;
+        }
+        if (self.predicate)(node) {
+            self.found = true;
+        } else {
+            walk_node(self, node);
+        }
+    }
+}
+
+/** Whether `predicate` holds for any node in the trees, roots included. */
+pub fn any_node<P>(nodes: &[Box<dyn AnyParseNode>], predicate: P) -> bool
+where
+    P: FnMut(&Box<dyn AnyParseNode>) -> bool,
+{
+    let mut visitor = AnyNode {
+        predicate,
+        found: false,
+    };
+    visitor.visit_nodes(nodes);
+    visitor.found
+}
@@ -0,0 +1,139 @@
+use crate::define::macros::public::MacroDefinition;
+use crate::parse_error::ParseError;
+use crate::settings::Settings;
+use crate::Namespace::Mapping;
+use crate::Parser::Parser;
+use std::collections::HashMap;
+use std::fmt;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::{Arc, RwLock};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+// Commands a preamble may use besides macros that expand to nothing, such
+// as \newcommand, \newenvironment and \DeclareMathOperator.
+const DEFINITIONS: [&str; 9] = [
+    "\\def", "\\gdef", "\\edef", "\\xdef", "\\let", "\\futurelet", "\\global", "\\long", "\\relax",
+];
+
+/**
+ * Macros defined by a LaTeX preamble, parsed once and shared by every
+ * formula rendered with `Settings::set_preamble`. The preamble may use
+ * `\newcommand`, `\renewcommand`, `\def`, `\let`, `\DeclareMathOperator`,
+ * `\newenvironment` and the like; anything else is an error.
+ *
+ * Its definitions act as built-in macros: they cannot be changed, only
+ * shadowed by the `macros` of the settings or by definitions made while
+ * rendering, and `\newcommand` refuses to redefine them as in LaTeX.
+ */
+#[derive(Clone)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub struct Preamble {
+    source: String,
+    macros: Arc<Mapping<MacroDefinition>>,
+}
+
+impl fmt::Debug for Preamble {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Preamble").field("source", &self.source).finish_non_exhaustive()
+    }
+}
+
+impl Preamble {
+    /**
+     * Parses `source` with `settings` (e.g. for `maxExpand`). The definitions
+     * are added to the built-in macros of `settings`, so a preamble can build
+     * on another one. Errors point into `source`.
+     */
+    pub fn parse(source: &str, settings: &Settings) -> Result<Preamble, ParseError> {
+        match catch_unwind(AssertUnwindSafe(|| Preamble::parse_with_error(source, settings))) {
+            Ok(result) => result,
+            Err(payload) => Err(ParseError::from_panic(payload)),
+        }
+    }
+
+    fn parse_with_error(source: &str, settings: &Settings) -> Result<Preamble, ParseError> {
+        let mut settings = settings.clone();
+        settings.set_ref_macros(Arc::new(RwLock::new(HashMap::new())));
+        settings.set_keep_definitions(true);
+        // Every definition is made at the top level, where it is global.
+        settings.set_global_group(true);
+        // A trailing comment or a non-LaTeX character is no concern here.
+        settings.set_strict("ignore".to_string());
+
+        let mut parser = Parser::new(source.to_string(), &settings);
+        loop {
+            parser.consume_spaces();
+            let token = parser.fetch();
+            if let Some(error) = parser.take_error() {
+                return Err(error);
+            }
+            if token.text == "EOF" {
+                break;
+            }
+            if !DEFINITIONS.contains(&token.text.as_str()) {
+                return Err(ParseError::new(
+                    format!("Only definitions are allowed in a preamble, got '{}'", token.text),
+                    token.loc,
+                ));
+            }
+            parser.parse_atom(None);
+            if let Some(error) = parser.take_error() {
+                return Err(error);
+            }
+        }
+
+        let mut macros = (*settings.get_builtin_macros()).clone();
+        macros.extend(settings.get_ref_macros().read().unwrap().clone());
+        Ok(Preamble {
+            source: source.to_string(),
+            macros: Arc::new(macros),
+        })
+    }
+
+    pub(crate) fn get_macros(&self) -> Arc<Mapping<MacroDefinition>> {
+        self.macros.clone()
+    }
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl Preamble {
+    /** The LaTeX source the preamble was parsed from. */
+    pub fn source(&self) -> String {
+        self.source.clone()
+    }
+
+    /** Whether `name` (e.g. `\RR`) is defined, by the preamble or built in. */
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = hasMacro))]
+    pub fn has_macro(&self, name: &str) -> bool {
+        self.macros.contains_key(name)
+    }
+}
+
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+impl Preamble {
+    /** Throws a `ParseError` pointing into `source`. */
+    #[wasm_bindgen(constructor)]
+    pub fn new_from_js(source: &str, options: &JsValue) -> Result<Preamble, JsValue> {
+        Preamble::parse(source, &Settings::new_from_js(options)?).map_err(|error| error.to_js_error())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::preamble::Preamble;
+    use crate::settings::Settings;
+
+    #[test]
+    fn test_parse_errors() {
+        let settings = Settings::new();
+        let error = Preamble::parse("\\def\\a{a}\n\\newcommand\\b{b} x", &settings).unwrap_err();
+        assert!(error.raw_message().contains("Only definitions are allowed in a preamble"));
+        assert_eq!(error.position(), Some(27));
+        let error = Preamble::parse("\\def\\a{a}\n\\renewcommand\\b{b}", &settings).unwrap_err();
+        assert!(error.raw_message().contains("\\b"));
+        assert_eq!(error.position(), Some(23));
+        assert!(Preamble::parse("\\def", &settings).is_err());
+    }
+}
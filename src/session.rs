@@ -0,0 +1,170 @@
+use crate::define::macros::public::MacroDefinition;
+use crate::dom_tree::span::Span;
+use crate::parse_error::ParseError;
+use crate::parse_node::types::AnyParseNode;
+use crate::preamble::Preamble;
+use crate::settings::{CrossReferences, Settings};
+use crate::Namespace::Mapping;
+use std::sync::{Arc, Mutex, RwLock};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+/**
+ * A series of renders that share macro definitions, like the formulas of one
+ * document. This is KaTeX's behavior of passing the same `macros` object to
+ * every `render` call: `\gdef`, `\global\def` (and, with `globalGroup` set,
+ * top-level `\def` and `\newcommand`) made by one render are defined in the
+ * following ones. Definitions local to a formula are not kept.
+ *
+ * The session copies the macros of the settings it is created with, so the
+ * settings can still be used elsewhere without seeing its definitions.
+ *
+ * With `enable_cross_references`, the session also numbers equations across
+ * renders and resolves `\ref` and `\eqref` to labels of earlier formulas.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub struct RenderSession {
+    settings: Settings,
+    initial_macros: Mapping<MacroDefinition>,
+}
+
+/**
+ * The macros defined in a `RenderSession` at some point, see
+ * `RenderSession::snapshot`.
+ */
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub struct MacroSnapshot {
+    macros: Mapping<MacroDefinition>,
+}
+
+impl MacroSnapshot {
+    /** Names of the macros in the snapshot, sorted. */
+    pub fn macro_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.macros.keys().cloned().collect();
+        names.sort();
+        names
+    }
+}
+
+impl RenderSession {
+    pub fn new(settings: Settings) -> RenderSession {
+        let initial_macros = settings.get_ref_macros().read().unwrap().clone();
+        let mut settings = settings;
+        settings.set_ref_macros(Arc::new(RwLock::new(initial_macros.clone())));
+        settings.set_keep_definitions(true);
+        RenderSession {
+            settings,
+            initial_macros,
+        }
+    }
+
+    pub fn get_settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    /** The equation numbers and labels so far, if enabled. */
+    pub fn cross_references(&self) -> Option<CrossReferences> {
+        self.settings
+            .get_cross_references()
+            .map(|cross_references| cross_references.lock().unwrap().clone())
+    }
+
+    /** See `try_render_to_string`. */
+    pub fn try_render_to_string(&self, expression: String) -> Result<String, ParseError> {
+        crate::katex::try_render_to_string(expression, self.settings.clone())
+    }
+
+    /** See `try_render_to_dom_tree`. */
+    pub fn try_render_to_dom_tree(&self, expression: String) -> Result<Span, ParseError> {
+        crate::katex::try_render_to_dom_tree(expression, self.settings.clone())
+    }
+
+    /** See `try_parse_tree`. */
+    pub fn try_parse_tree(&self, expression: String) -> Result<Vec<Box<dyn AnyParseNode>>, ParseError> {
+        crate::katex::with_cross_references(&self.settings, |settings| {
+            crate::parse::try_parse_tree(expression, settings.clone())
+        })
+    }
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl RenderSession {
+    /** Whether `name` (e.g. `\RR`) is currently defined by the session. */
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = hasMacro))]
+    pub fn has_macro(&self, name: &str) -> bool {
+        self.settings.get_ref_macros().read().unwrap().contains_key(name)
+    }
+
+    pub fn snapshot(&self) -> MacroSnapshot {
+        MacroSnapshot {
+            macros: self.settings.get_ref_macros().read().unwrap().clone(),
+        }
+    }
+
+    /** Replaces the session's macros by those of `snapshot`. */
+    pub fn restore(&mut self, snapshot: &MacroSnapshot) {
+        *self.settings.get_ref_macros().write().unwrap() = snapshot.macros.clone();
+    }
+
+    /**
+     * Forgets every definition made since the session was created, and
+     * restarts equation numbering.
+     */
+    pub fn reset(&mut self) {
+        *self.settings.get_ref_macros().write().unwrap() = self.initial_macros.clone();
+        if self.settings.get_cross_references().is_some() {
+            self.enable_cross_references();
+        }
+    }
+
+    /**
+     * Numbers the rows of `equation`, `align`, `gather` and the like from 1
+     * across renders, instead of with CSS counters, and enables `\label`,
+     * `\ref` and `\eqref`. A `\label` names the tag of its row, whether
+     * numbered or given by `\tag`; `\eqref{name}` then renders as a link to
+     * that tag, whose id is `katex-eq-name`.
+     */
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = enableCrossReferences))]
+    pub fn enable_cross_references(&mut self) {
+        self.settings
+            .set_cross_references(Some(Arc::new(Mutex::new(CrossReferences::new()))));
+    }
+
+    /**
+     * Renders with the definitions of `preamble` as built-in macros, see
+     * `Settings::set_preamble`. Definitions made by earlier renders are kept.
+     */
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = setPreamble))]
+    pub fn set_preamble(&mut self, preamble: &Preamble) {
+        self.settings.set_preamble(Some(preamble.clone()));
+    }
+
+    /** What `\ref{label}` shows, if `label` is defined. */
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = labelTag))]
+    pub fn label_tag(&self, label: &str) -> Option<String> {
+        let cross_references = self.settings.get_cross_references()?;
+        let tag = cross_references.lock().unwrap().get(label).map(|tag| tag.to_string());
+        tag
+    }
+}
+
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+impl RenderSession {
+    #[wasm_bindgen(constructor)]
+    pub fn new_from_js(options: &JsValue) -> Result<RenderSession, JsValue> {
+        Ok(RenderSession::new(Settings::new_from_js(options)?))
+    }
+
+    #[wasm_bindgen(js_name = renderToString)]
+    pub fn render_to_string_for_js(&self, expression: String) -> Result<String, JsValue> {
+        self.try_render_to_string(expression)
+            .map_err(|error| error.to_js_error())
+    }
+
+    #[wasm_bindgen(js_name = render)]
+    pub fn render_for_js(&self, expression: String, base_node: &web_sys::Node) -> Result<(), JsValue> {
+        crate::katex::render_into(expression, base_node, self.settings.clone())
+    }
+}
@@ -0,0 +1,78 @@
+use indexmap::IndexMap;
+
+/**
+ * Equation numbers and labels shared by the formulas of one document, see
+ * `RenderSession::enable_cross_references`. Numbered rows of `equation`,
+ * `align`, `gather` and the like take the next number, `\label` names the
+ * tag of its row, and `\ref`/`\eqref` look up labels of earlier formulas.
+ */
+#[derive(Clone, Debug, Default)]
+pub struct CrossReferences {
+    counter: usize,
+    labels: IndexMap<String, String>,
+}
+
+impl CrossReferences {
+    pub fn new() -> CrossReferences {
+        CrossReferences::default()
+    }
+
+    /** Takes the next automatic equation number. */
+    pub(crate) fn next_number(&mut self) -> usize {
+        self.counter += 1;
+        self.counter
+    }
+
+    /**
+     * Makes `label` refer to the equation tagged `tag`, the LaTeX source of
+     * the tag without parentheses, e.g. `3` for (3). A label defined again
+     * refers to its latest equation.
+     */
+    pub(crate) fn define(&mut self, label: &str, tag: &str) {
+        self.labels.insert(label.to_string(), tag.to_string());
+    }
+
+    /** What `\ref{label}` shows, if `label` is defined. */
+    pub fn get(&self, label: &str) -> Option<&str> {
+        self.labels.get(label).map(|tag| tag.as_str())
+    }
+
+    /** The defined labels and their tags, in order of definition. */
+    pub fn labels(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.labels.iter().map(|(label, tag)| (label.as_str(), tag.as_str()))
+    }
+
+    /** How many equations have been numbered automatically. */
+    pub fn equation_count(&self) -> usize {
+        self.counter
+    }
+
+    /** The `id` given to the tag of the equation labelled `label`. */
+    pub fn anchor(label: &str) -> String {
+        let label: String = label
+            .trim()
+            .chars()
+            .map(|c| if c.is_whitespace() { '-' } else { c })
+            .collect();
+        format!("katex-eq-{}", label)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::settings::CrossReferences;
+
+    #[test]
+    fn test_cross_references() {
+        let mut refs = CrossReferences::new();
+        assert_eq!((refs.next_number(), refs.next_number()), (1, 2));
+        refs.define("a", "1");
+        refs.define("b", "$\\ast$");
+        refs.define("a", "2");
+        assert_eq!(refs.get("a"), Some("2"));
+        assert_eq!(refs.get("c"), None);
+        assert_eq!(refs.labels().collect::<Vec<_>>(), vec![("a", "2"), ("b", "$\\ast$")]);
+        assert_eq!(refs.equation_count(), 2);
+        assert_eq!(CrossReferences::anchor(" eq: one\ttwo "), "katex-eq-eq:-one-two");
+    }
+}
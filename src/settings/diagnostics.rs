@@ -0,0 +1,130 @@
+use crate::sourceLocation::SourceLocation;
+use crate::token::tokens_to_source;
+use std::fmt;
+
+/// Which primitive wrote a `Diagnostic`, or what the renderer noticed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DiagnosticKind {
+    /// `\message{text}`
+    Message,
+    /// `\errmessage{text}`; unlike TeX, rendering goes on.
+    ErrMessage,
+    /// `\show\name`, with the meaning of `\name`.
+    Show,
+    /// A glyph `output: "svg"` has no outline for, written as `<text>` in
+    /// the KaTeX web fonts instead; the text is e.g. `x in font Main-Regular`.
+    MissingCharacter,
+}
+
+impl DiagnosticKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DiagnosticKind::Message => "message",
+            DiagnosticKind::ErrMessage => "errmessage",
+            DiagnosticKind::Show => "show",
+            DiagnosticKind::MissingCharacter => "missingCharacter",
+        }
+    }
+}
+
+/**
+ * What TeX would print on the terminal for `\message`, `\errmessage` or
+ * `\show`, or a character the SVG output could not draw.
+ */
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub kind: DiagnosticKind,
+    pub text: String,
+    pub loc: Option<SourceLocation>,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            DiagnosticKind::Message => write!(f, "{}", self.text),
+            DiagnosticKind::ErrMessage => write!(f, "! {}", self.text),
+            DiagnosticKind::Show => write!(f, "> {}.", self.text),
+            // As TeX logs it with \tracinglostchars.
+            DiagnosticKind::MissingCharacter => write!(f, "Missing character: There is no {}!", self.text),
+        }
+    }
+}
+
+/**
+ * One macro expansion, recorded when the `traceMacros` setting is on: the
+ * macro, the LaTeX source of its arguments and the texts of the tokens it
+ * expanded to, in reading order.
+ */
+#[derive(Clone, Debug)]
+pub struct ExpansionStep {
+    pub name: String,
+    pub args: Vec<String>,
+    pub tokens: Vec<String>,
+    pub loc: Option<SourceLocation>,
+}
+
+impl fmt::Display for ExpansionStep {
+    /** Like TeX's `\tracingmacros`, e.g. `\foo #1<a> ->[a]`. */
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        for (i, arg) in self.args.iter().enumerate() {
+            write!(f, " #{}<{}>", i + 1, arg)?;
+        }
+        write!(f, " ->{}", tokens_to_source(self.tokens.iter().map(String::as_str)))
+    }
+}
+
+/** Where diagnostics and the expansion trace of one render go. */
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Diagnostics {
+    pub(crate) messages: Vec<Diagnostic>,
+    pub(crate) trace: Vec<ExpansionStep>,
+}
+
+/**
+ * Sets `position` and `length` of `obj` to `loc` in UTF-16 code units, as
+ * JS counts them.
+ */
+#[cfg(feature = "wasm")]
+pub(crate) fn set_js_position(obj: &js_sys::Object, loc: &Option<SourceLocation>) {
+    use js_sys::Reflect;
+    use wasm_bindgen::JsValue;
+    if let Some(loc) = loc.as_ref().filter(|loc| loc.start <= loc.end) {
+        let input = loc.lexer.get_input();
+        let (start, end) = (loc.start as usize, loc.end as usize);
+        let position = input[..start].encode_utf16().count() as u32;
+        let length = input[start..end].encode_utf16().count() as u32;
+        Reflect::set(obj, &"position".into(), &JsValue::from(position)).unwrap();
+        Reflect::set(obj, &"length".into(), &JsValue::from(length)).unwrap();
+    }
+}
+
+#[cfg(feature = "wasm")]
+impl Diagnostic {
+    /** `{kind, text, position, length}`. */
+    pub fn to_js_value(&self) -> wasm_bindgen::JsValue {
+        use js_sys::{Object, Reflect};
+        use wasm_bindgen::JsValue;
+        let obj = Object::new();
+        Reflect::set(&obj, &"kind".into(), &JsValue::from_str(self.kind.as_str())).unwrap();
+        Reflect::set(&obj, &"text".into(), &JsValue::from_str(&self.text)).unwrap();
+        set_js_position(&obj, &self.loc);
+        obj.into()
+    }
+}
+
+#[cfg(feature = "wasm")]
+impl ExpansionStep {
+    /** `{name, args, tokens, position, length}`. */
+    pub fn to_js_value(&self) -> wasm_bindgen::JsValue {
+        use js_sys::{Array, Object, Reflect};
+        use wasm_bindgen::JsValue;
+        let strings = |list: &[String]| list.iter().map(|s| JsValue::from_str(s)).collect::<Array>();
+        let obj = Object::new();
+        Reflect::set(&obj, &"name".into(), &JsValue::from_str(&self.name)).unwrap();
+        Reflect::set(&obj, &"args".into(), &strings(&self.args)).unwrap();
+        Reflect::set(&obj, &"tokens".into(), &strings(&self.tokens)).unwrap();
+        set_js_position(&obj, &self.loc);
+        obj.into()
+    }
+}
@@ -0,0 +1,193 @@
+use crate::dom_tree::css_style::CssStyle;
+use indexmap::IndexMap;
+use regex::Regex;
+
+lazy_static! {
+    static ref ID: Regex = Regex::new(r"^[A-Za-z0-9_:.-]+$").unwrap();
+    // Lengths, colors, keywords and functions such as rgb(); no quotes,
+    // escapes or at-rules.
+    static ref CSS_VALUE: Regex = Regex::new(r"^[#A-Za-z0-9.,%\s()+-]*$").unwrap();
+}
+
+/**
+ * What `\htmlClass`, `\htmlId`, `\htmlStyle` and `\htmlData` may set once
+ * trusted, for authors who are trusted with some HTML but not all of it.
+ * A command whose value the policy rejects is rendered as unsupported, like
+ * an untrusted one. An empty policy allows nothing.
+ */
+#[derive(Clone, Debug, Default)]
+pub struct HtmlPolicy {
+    class_patterns: Vec<Regex>,
+    id_prefix: Option<String>,
+    style_properties: Vec<String>,
+    data_keys: Vec<String>,
+}
+
+impl HtmlPolicy {
+    pub fn new() -> HtmlPolicy {
+        HtmlPolicy::default()
+    }
+
+    /**
+     * Allows class names matching `pattern` as a whole, e.g. `note-[a-z]+`.
+     * Every class given to `\htmlClass` has to match some pattern.
+     */
+    pub fn allow_class_pattern(&mut self, pattern: &str) -> Result<(), regex::Error> {
+        self.class_patterns.push(Regex::new(&format!("^(?:{})$", pattern))?);
+        Ok(())
+    }
+
+    /**
+     * Allows `\htmlId`, prefixing ids that do not already start with
+     * `prefix`, so that formulas cannot take over ids used by the page.
+     */
+    pub fn set_id_prefix(&mut self, prefix: &str) {
+        self.id_prefix = Some(prefix.to_string());
+    }
+
+    /** Allows a CSS property (e.g. `color`) in `\htmlStyle`. */
+    pub fn allow_style_property(&mut self, name: &str) {
+        self.style_properties.push(name.to_ascii_lowercase());
+    }
+
+    /** Allows `data-<key>` in `\htmlData`. */
+    pub fn allow_data_key(&mut self, key: &str) {
+        self.data_keys.push(key.to_string());
+    }
+
+    /** The patterns given to `allow_class_pattern`. */
+    pub fn class_pattern_sources(&self) -> Vec<String> {
+        self.class_patterns
+            .iter()
+            .map(|pattern| {
+                let source = pattern.as_str();
+                source[4..source.len() - 2].to_string()
+            })
+            .collect()
+    }
+
+    pub fn id_prefix(&self) -> Option<&str> {
+        self.id_prefix.as_deref()
+    }
+
+    pub fn style_properties(&self) -> &[String] {
+        &self.style_properties
+    }
+
+    pub fn data_keys(&self) -> &[String] {
+        &self.data_keys
+    }
+
+    /**
+     * Checks the attributes requested by one of the `\html*` commands,
+     * returning them as they should be rendered, or `None` if any of them
+     * is not allowed.
+     */
+    pub fn sanitize(&self, attributes: IndexMap<String, String>) -> Option<IndexMap<String, String>> {
+        let mut res = IndexMap::new();
+        for (name, value) in attributes {
+            let value = match name.as_str() {
+                "class" => self.sanitize_class(&value)?,
+                "id" => self.sanitize_id(&value)?,
+                "style" => self.sanitize_style(&value)?,
+                _ => {
+                    let key = name.strip_prefix("data-")?;
+                    if !self.data_keys.iter().any(|k| k == key) {
+                        return None;
+                    }
+                    value
+                }
+            };
+            res.insert(name, value);
+        }
+        Some(res)
+    }
+
+    fn sanitize_class(&self, value: &str) -> Option<String> {
+        let classes: Vec<&str> = value.split_whitespace().collect();
+        let allowed = classes
+            .iter()
+            .all(|class| self.class_patterns.iter().any(|pattern| pattern.is_match(class)));
+        if classes.is_empty() || !allowed {
+            return None;
+        }
+        Some(classes.join(" "))
+    }
+
+    fn sanitize_id(&self, value: &str) -> Option<String> {
+        let prefix = self.id_prefix.as_ref()?;
+        if !ID.is_match(value) {
+            return None;
+        }
+        if value.starts_with(prefix.as_str()) {
+            Some(value.to_string())
+        } else {
+            Some(format!("{}{}", prefix, value))
+        }
+    }
+
+    fn sanitize_style(&self, value: &str) -> Option<String> {
+        for declaration in value.split(';') {
+            if let Some((name, value)) = declaration.split_once(':') {
+                let name = name.trim().to_ascii_lowercase();
+                let lower = value.to_ascii_lowercase();
+                if !self.style_properties.contains(&name)
+                    || !CSS_VALUE.is_match(value)
+                    || lower.contains("url(")
+                    || lower.contains("expression(")
+                {
+                    return None;
+                }
+            }
+        }
+        let style = CssStyle::from_css_str(value).ok()?;
+        Some(style.to_css_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::settings::HtmlPolicy;
+    use indexmap::IndexMap;
+
+    fn sanitize(policy: &HtmlPolicy, name: &str, value: &str) -> Option<String> {
+        let attributes = IndexMap::from([(name.to_string(), value.to_string())]);
+        policy.sanitize(attributes).map(|attributes| attributes[name].clone())
+    }
+
+    #[test]
+    fn test_sanitize() {
+        let mut policy = HtmlPolicy::new();
+        policy.allow_class_pattern("note-[a-z]+").unwrap();
+        policy.set_id_prefix("eq-");
+        policy.allow_style_property("color");
+        policy.allow_data_key("foo");
+
+        assert_eq!(sanitize(&policy, "class", " note-a  note-b "), Some("note-a note-b".to_string()));
+        assert_eq!(sanitize(&policy, "class", "note-a katex"), None);
+        assert_eq!(sanitize(&policy, "class", "note-a1"), None);
+        assert_eq!(sanitize(&policy, "class", ""), None);
+        assert_eq!(sanitize(&policy, "id", "one"), Some("eq-one".to_string()));
+        assert_eq!(sanitize(&policy, "id", "eq-two"), Some("eq-two".to_string()));
+        assert_eq!(sanitize(&policy, "id", "a b"), None);
+        assert_eq!(sanitize(&policy, "style", "Color: red"), Some("color:red;".to_string()));
+        assert_eq!(sanitize(&policy, "style", "position: fixed"), None);
+        assert_eq!(sanitize(&policy, "style", "color: url(x)"), None);
+        assert_eq!(sanitize(&policy, "style", "color: \"red\""), None);
+        assert_eq!(sanitize(&policy, "data-foo", "1"), Some("1".to_string()));
+        assert_eq!(sanitize(&policy, "data-bar", "2"), None);
+        assert_eq!(sanitize(&policy, "title", "x"), None);
+        let attributes = IndexMap::from([
+            ("data-foo".to_string(), "1".to_string()),
+            ("data-bar".to_string(), "2".to_string()),
+        ]);
+        assert_eq!(policy.sanitize(attributes), None);
+
+        // An empty policy allows nothing.
+        let policy = HtmlPolicy::new();
+        for (name, value) in [("class", "a"), ("id", "a"), ("style", "color: red"), ("data-foo", "1")] {
+            assert_eq!(sanitize(&policy, name, value), None);
+        }
+        assert_eq!(HtmlPolicy::new().class_pattern_sources(), Vec::<String>::new());
+    }
+}
@@ -0,0 +1,49 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use js_sys::Function;
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    static FUNCTIONS: RefCell<HashMap<usize, Function>> = RefCell::new(HashMap::new());
+}
+
+/**
+ * A JS function given in the options (`trust`, `strict` or a function
+ * macro). A `JsValue` only means something on the thread that made it, so
+ * the function is kept in a table of that thread and the settings hold this
+ * handle to it, which is `Send + Sync`. Settings carrying JS functions may
+ * still be moved to another thread (e.g. a worker with shared memory), but
+ * there `get` finds nothing, and the function counts as failing.
+ */
+pub(crate) struct JsFunction {
+    id: usize,
+}
+
+impl JsFunction {
+    pub(crate) fn new(function: Function) -> JsFunction {
+        // Ids are unique across threads, so a handle dropped on another
+        // thread cannot remove that thread's functions.
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        FUNCTIONS.with(|functions| functions.borrow_mut().insert(id, function));
+        JsFunction { id }
+    }
+
+    /**
+     * The function, if this is the thread it came from. It is cloned out of
+     * the table so that calling it may register functions of its own, e.g.
+     * by rendering again.
+     */
+    pub(crate) fn get(&self) -> Option<Function> {
+        FUNCTIONS.with(|functions| functions.borrow().get(&self.id).cloned())
+    }
+}
+
+impl Drop for JsFunction {
+    fn drop(&mut self) {
+        // Dropped on another thread, the entry stays until its thread exits.
+        let _ = FUNCTIONS.try_with(|functions| functions.borrow_mut().remove(&self.id));
+    }
+}
@@ -0,0 +1,352 @@
+use super::settings_types::{OutputType, StrictType, TrustType};
+use super::{HtmlPolicy, Settings};
+use crate::preamble::Preamble;
+use crate::define::macros::public::MacroDefinition;
+use serde_json::{Map, Value};
+use std::str::FromStr;
+
+/**
+ * Settings as a JSON object with KaTeX's option names, e.g.
+ * `{"displayMode": true, "macros": {"\\RR": "\\mathbb{R}"}}`. Keys may also
+ * be written in snake_case or kebab-case, which reads better in TOML files.
+ *
+ * Options that hold functions (a `trust` or `strict` function, function
+ * macros) cannot be written out and are left out; so are macros defined by
+ * `\gdef` while rendering. A `preamble` is given as LaTeX source and parsed
+ * right away. A `null` limit (or `inf` in TOML) means no limit.
+ */
+impl Settings {
+    pub fn to_json(&self) -> Value {
+        let mut obj = Map::new();
+        obj.insert("displayMode".to_string(), Value::Bool(self.display_mode));
+        obj.insert("output".to_string(), Value::from(self.output.as_str()));
+        obj.insert("leqno".to_string(), Value::Bool(self.leqno));
+        obj.insert("fleqn".to_string(), Value::Bool(self.fleqn));
+        obj.insert("throwOnError".to_string(), Value::Bool(self.throw_on_error));
+        obj.insert("errorColor".to_string(), Value::from(self.error_color.clone()));
+        let macros: Map<String, Value> = self
+            .macros
+            .read()
+            .unwrap()
+            .iter()
+            .filter_map(|(name, definition)| match definition {
+                MacroDefinition::Str(expansion) => Some((name.clone(), Value::from(expansion.clone()))),
+                _ => None,
+            })
+            .collect();
+        obj.insert("macros".to_string(), Value::Object(macros));
+        obj.insert("minRuleThickness".to_string(), Value::from(self.min_rule_thickness));
+        obj.insert("colorIsTextColor".to_string(), Value::Bool(self.color_is_text_color));
+        if !matches!(self.strict, StrictType::Function(_)) {
+            obj.insert("strict".to_string(), Value::from(self.strict.as_str()));
+        }
+        if let TrustType::Bool(trust) = self.trust {
+            obj.insert("trust".to_string(), Value::Bool(trust));
+        }
+        if let Some(policy) = &self.html_policy {
+            obj.insert("htmlPolicy".to_string(), policy.to_json());
+        }
+        obj.insert("maxSize".to_string(), self.max_size.map_or(Value::Null, Value::from));
+        obj.insert("maxExpand".to_string(), self.max_expand.map_or(Value::Null, Value::from));
+        obj.insert("globalGroup".to_string(), Value::Bool(self.global_group));
+        obj.insert("maxNestingDepth".to_string(), self.max_nesting_depth.map_or(Value::Null, Value::from));
+        obj.insert("maxParseNodes".to_string(), self.max_parse_nodes.map_or(Value::Null, Value::from));
+        obj.insert("maxOutputBytes".to_string(), self.max_output_bytes.map_or(Value::Null, Value::from));
+        obj.insert("timeBudget".to_string(), self.time_budget.map_or(Value::Null, Value::from));
+        obj.insert("traceMacros".to_string(), Value::Bool(self.trace_macros));
+        obj.insert("inlineStyles".to_string(), Value::Bool(self.inline_styles));
+        if let Some(preamble) = &self.preamble {
+            obj.insert("preamble".to_string(), Value::from(preamble.source()));
+        }
+        Value::Object(obj)
+    }
+
+    /** Default settings with the options in `json` applied, see `update_from_json`. */
+    pub fn from_json(json: &Value) -> Result<Settings, String> {
+        let mut res = Settings::new();
+        res.update_from_json(json)?;
+        Ok(res)
+    }
+
+    /**
+     * Sets the options present in `json`, leaving the others alone. Given
+     * macros are added to the current ones. Unknown options are an error,
+     * so that typos in configuration files do not go unnoticed. The
+     * preamble is parsed last, with all the other options in effect.
+     */
+    pub fn update_from_json(&mut self, json: &Value) -> Result<(), String> {
+        let obj = json.as_object().ok_or("settings must be an object")?;
+        let mut preamble = None;
+        for (key, value) in obj.iter() {
+            let name = camel_case(key);
+            match name.as_str() {
+                "displayMode" => self.display_mode = boolean(&name, value)?,
+                "output" => {
+                    self.output = OutputType::from_str(string(&name, value)?)
+                        .map_err(|_| format!("output: unknown output type {}", value))?
+                }
+                "leqno" => self.leqno = boolean(&name, value)?,
+                "fleqn" => self.fleqn = boolean(&name, value)?,
+                "throwOnError" => self.throw_on_error = boolean(&name, value)?,
+                "errorColor" => self.error_color = string(&name, value)?.to_string(),
+                "macros" => {
+                    let macros = value.as_object().ok_or("macros: expected an object")?;
+                    for (macro_name, expansion) in macros.iter() {
+                        self.add_macro(macro_name, string(macro_name, expansion)?);
+                    }
+                }
+                "minRuleThickness" => self.min_rule_thickness = number(&name, value)?,
+                "colorIsTextColor" => self.color_is_text_color = boolean(&name, value)?,
+                "strict" => {
+                    self.strict = match value {
+                        Value::Bool(true) => StrictType::Error,
+                        Value::Bool(false) => StrictType::Ignore,
+                        _ => StrictType::from_str(string(&name, value)?)
+                            .map_err(|_| format!("strict: unknown strict mode {}", value))?,
+                    }
+                }
+                "trust" => self.trust = TrustType::Bool(boolean(&name, value)?),
+                "htmlPolicy" => self.html_policy = Some(HtmlPolicy::from_json(value)?),
+                "maxSize" => self.max_size = limit(&name, value, number)?,
+                "maxExpand" => {
+                    self.max_expand = limit(&name, value, |n, v| {
+                        i32::try_from(count(n, v)?).map_err(|_| format!("{}: {} is too large", n, v))
+                    })?
+                }
+                "globalGroup" => self.global_group = boolean(&name, value)?,
+                "maxNestingDepth" => self.max_nesting_depth = limit(&name, value, count)?,
+                "maxParseNodes" => self.max_parse_nodes = limit(&name, value, count)?,
+                "maxOutputBytes" => self.max_output_bytes = limit(&name, value, count)?,
+                "timeBudget" => self.time_budget = limit(&name, value, duration)?,
+                "traceMacros" => self.trace_macros = boolean(&name, value)?,
+                "inlineStyles" => self.inline_styles = boolean(&name, value)?,
+                "preamble" => preamble = Some(string(&name, value)?),
+                _ => return Err(format!("unknown option '{}'", key)),
+            }
+        }
+        if let Some(source) = preamble {
+            let preamble = Preamble::parse(source, self).map_err(|error| format!("preamble: {}", error))?;
+            self.preamble = Some(preamble);
+        }
+        Ok(())
+    }
+
+    /** See `from_json`. */
+    #[cfg(feature = "toml")]
+    pub fn from_toml(toml: &str) -> Result<Settings, String> {
+        let value: toml::Value = toml::from_str(toml).map_err(|error| error.to_string())?;
+        Settings::from_json(&serde_json::to_value(value).map_err(|error| error.to_string())?)
+    }
+
+    /** See `update_from_json`. */
+    #[cfg(feature = "toml")]
+    pub fn update_from_toml(&mut self, toml: &str) -> Result<(), String> {
+        let value: toml::Value = toml::from_str(toml).map_err(|error| error.to_string())?;
+        self.update_from_json(&serde_json::to_value(value).map_err(|error| error.to_string())?)
+    }
+}
+
+impl HtmlPolicy {
+    pub fn to_json(&self) -> Value {
+        let mut obj = Map::new();
+        obj.insert("classPatterns".to_string(), Value::from(self.class_pattern_sources()));
+        if let Some(prefix) = self.id_prefix() {
+            obj.insert("idPrefix".to_string(), Value::from(prefix));
+        }
+        obj.insert("styleProperties".to_string(), Value::from(self.style_properties().to_vec()));
+        obj.insert("dataKeys".to_string(), Value::from(self.data_keys().to_vec()));
+        Value::Object(obj)
+    }
+
+    pub fn from_json(json: &Value) -> Result<HtmlPolicy, String> {
+        let obj = json.as_object().ok_or("htmlPolicy: expected an object")?;
+        let mut policy = HtmlPolicy::new();
+        for (key, value) in obj.iter() {
+            let name = camel_case(key);
+            match name.as_str() {
+                "classPatterns" => {
+                    for pattern in strings(&name, value)? {
+                        policy
+                            .allow_class_pattern(pattern)
+                            .map_err(|error| format!("classPatterns: {}", error))?;
+                    }
+                }
+                "idPrefix" => policy.set_id_prefix(string(&name, value)?),
+                "styleProperties" => strings(&name, value)?
+                    .into_iter()
+                    .for_each(|property| policy.allow_style_property(property)),
+                "dataKeys" => strings(&name, value)?
+                    .into_iter()
+                    .for_each(|key| policy.allow_data_key(key)),
+                _ => return Err(format!("htmlPolicy: unknown option '{}'", key)),
+            }
+        }
+        Ok(policy)
+    }
+}
+
+/** `max_size`, `max-size` and `maxSize` all become `maxSize`. */
+fn camel_case(key: &str) -> String {
+    let mut res = String::with_capacity(key.len());
+    let mut upper = false;
+    for c in key.chars() {
+        if c == '_' || c == '-' {
+            upper = true;
+        } else if upper {
+            res.extend(c.to_uppercase());
+            upper = false;
+        } else {
+            res.push(c);
+        }
+    }
+    res
+}
+
+fn boolean(name: &str, value: &Value) -> Result<bool, String> {
+    value
+        .as_bool()
+        .ok_or_else(|| format!("{}: expected a boolean, got {}", name, value))
+}
+
+fn string<'a>(name: &str, value: &'a Value) -> Result<&'a str, String> {
+    value
+        .as_str()
+        .ok_or_else(|| format!("{}: expected a string, got {}", name, value))
+}
+
+fn strings<'a>(name: &str, value: &'a Value) -> Result<Vec<&'a str>, String> {
+    value
+        .as_array()
+        .ok_or_else(|| format!("{}: expected an array, got {}", name, value))?
+        .iter()
+        .map(|v| string(name, v))
+        .collect()
+}
+
+fn number(name: &str, value: &Value) -> Result<f64, String> {
+    value
+        .as_f64()
+        .ok_or_else(|| format!("{}: expected a number, got {}", name, value))
+}
+
+fn duration(name: &str, value: &Value) -> Result<f64, String> {
+    number(name, value)
+        .ok()
+        .filter(|n| *n >= 0.0)
+        .ok_or_else(|| format!("{}: expected a non-negative number, got {}", name, value))
+}
+
+fn count(name: &str, value: &Value) -> Result<usize, String> {
+    value
+        .as_u64()
+        .and_then(|n| usize::try_from(n).ok())
+        .ok_or_else(|| format!("{}: expected a non-negative integer, got {}", name, value))
+}
+
+fn limit<T>(
+    name: &str,
+    value: &Value,
+    parse: impl Fn(&str, &Value) -> Result<T, String>,
+) -> Result<Option<T>, String> {
+    match value {
+        Value::Null => Ok(None),
+        _ => parse(name, value).map(Some),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{camel_case, count, limit};
+    use crate::settings::Settings;
+    use serde_json::{json, Value};
+
+    #[test]
+    fn test_camel_case() {
+        for key in ["maxSize", "max_size", "max-size"] {
+            assert_eq!(camel_case(key), "maxSize");
+        }
+        assert_eq!(camel_case("html-policy"), "htmlPolicy");
+        assert_eq!(camel_case("color_is_text_color"), "colorIsTextColor");
+        assert_eq!(camel_case("leqno"), "leqno");
+    }
+
+    #[test]
+    fn test_limit() {
+        assert_eq!(limit("maxParseNodes", &Value::Null, count), Ok(None));
+        assert_eq!(limit("maxParseNodes", &json!(5), count), Ok(Some(5)));
+        assert_eq!(
+            limit("maxParseNodes", &json!(-5), count),
+            Err("maxParseNodes: expected a non-negative integer, got -5".to_string())
+        );
+        assert_eq!(
+            limit("maxParseNodes", &json!(1.5), count),
+            Err("maxParseNodes: expected a non-negative integer, got 1.5".to_string())
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let settings = Settings::from_json(&json!({
+            "displayMode": true,
+            "output": "mathml",
+            "leqno": true,
+            "strict": false,
+            "macros": {"\\RR": "\\mathbb{R}"},
+            "max_size": 10,
+            "max-expand": null,
+            "htmlPolicy": {"classPatterns": ["note-[a-z]+"], "idPrefix": "eq-"},
+        }))
+        .unwrap();
+        assert!(settings.get_display_mode() && settings.get_leqno());
+        assert_eq!(settings.get_output(), "mathml");
+        assert_eq!(settings.get_strict(), "ignore");
+        assert_eq!(settings.get_max_size(), Some(10.0));
+        assert_eq!(settings.get_max_expand(), None);
+
+        let json = settings.to_json();
+        assert_eq!(json["macros"]["\\RR"], "\\mathbb{R}");
+        assert_eq!(json["htmlPolicy"]["classPatterns"][0], "note-[a-z]+");
+        assert_eq!(Settings::from_json(&json).unwrap().to_json(), json);
+
+        assert!(Settings::from_json(&json!({"displaymode": true})).is_err());
+        assert!(Settings::from_json(&json!({"output": "latex"})).is_err());
+
+        #[cfg(feature = "toml")]
+        {
+            let settings = Settings::from_toml("display-mode = true\nmax_size = inf\n[macros]\n'\\RR' = '\\mathbb{R}'\n").unwrap();
+            assert!(settings.get_display_mode());
+            assert_eq!(settings.get_max_size(), None);
+            assert!(settings.get_ref_macros().read().unwrap().contains_key("\\RR"));
+        }
+    }
+
+    #[test]
+    fn test_limits_out_of_range() {
+        let error = Settings::from_json(&json!({"maxExpand": 1u64 << 32})).unwrap_err();
+        assert_eq!(error, "maxExpand: 4294967296 is too large");
+        assert!(Settings::from_json(&json!({"maxExpand": -1})).is_err());
+        let settings = Settings::from_json(&json!({"maxExpand": i32::MAX})).unwrap();
+        assert_eq!(settings.get_max_expand(), Some(i32::MAX));
+        let error = Settings::from_json(&json!({"timeBudget": -1})).unwrap_err();
+        assert_eq!(error, "timeBudget: expected a non-negative number, got -1");
+        let settings = Settings::from_json(&json!({"timeBudget": 2.5})).unwrap();
+        assert_eq!(settings.get_time_budget(), Some(2.5));
+    }
+
+    #[test]
+    fn test_preamble() {
+        let settings = Settings::from_json(&json!({"preamble": "\\def\\N{\\mathbb{N}}"})).unwrap();
+        assert!(settings.get_preamble().unwrap().has_macro("\\N"));
+        assert_eq!(settings.to_json()["preamble"], "\\def\\N{\\mathbb{N}}");
+        assert!(Settings::from_json(&json!({"preamble": "\\def"})).is_err());
+    }
+
+    #[test]
+    fn test_preamble_parsed_last() {
+        // Object keys keep their order, and the preamble comes first here.
+        let settings = json!({"preamble": "\\def\\a{x}\\edef\\b{\\a}", "maxExpand": 0});
+        assert!(Settings::from_json(&settings).unwrap_err().contains("Too many expansions"));
+        let settings = json!({"preamble": "\\def\\a{x}\\edef\\b{\\a}", "maxExpand": 1});
+        assert!(Settings::from_json(&settings).unwrap().get_preamble().unwrap().has_macro("\\b"));
+    }
+}
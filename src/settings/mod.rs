@@ -3,19 +3,36 @@
  * default settings.
  */
 
+mod cross_refs;
+mod diagnostics;
+mod html_policy;
+#[cfg(feature = "wasm")]
+mod js_function;
+mod json;
 mod settings_types;
 
 use crate::define::macros::public::MacroDefinition;
+use crate::parse_error::ParseError;
+use crate::preamble::Preamble;
+use crate::sourceLocation::SourceLocation;
 use crate::token::Token;
 use crate::utils;
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 
-use settings_types::{OutputType, StrictType};
+use settings_types::{OutputType, StrictType, TrustType};
+pub use settings_types::{StrictFunction, StrictLevel, StrictWarning};
 use std::collections::HashMap;
 use std::rc::Rc;
 use std::str::FromStr;
-use std::sync::Arc;
-pub use settings_types::TrustContext;
+use std::sync::{Arc, Mutex};
+pub use cross_refs::CrossReferences;
+pub(crate) use diagnostics::Diagnostics;
+pub use diagnostics::{Diagnostic, DiagnosticKind, ExpansionStep};
+pub use html_policy::HtmlPolicy;
+#[cfg(feature = "wasm")]
+pub(crate) use js_function::JsFunction;
+pub use settings_types::{TrustContext, TrustFunction};
 use crate::Namespace::Namespace;
 
 /// Options to be passed to KaTeX.
@@ -24,7 +41,7 @@ use crate::Namespace::Namespace;
 ///
 #[non_exhaustive]
 #[derive(Clone, Debug)]
-#[wasm_bindgen(getter_with_clone)]
+#[cfg_attr(feature = "wasm", wasm_bindgen(getter_with_clone))]
 // #[wasm_bindgen]
 pub struct Settings {
     /// Whether to render the math in the display mode.
@@ -41,12 +58,17 @@ pub struct Settings {
     error_color: String,
     /// Collection of custom macros.
     macros: crate::Namespace::MapRef<MacroDefinition>,
+    /// Whether global and top-level definitions are written back to
+    /// `macros`; only for a `RenderSession` or `Preamble` owning its copy.
+    keep_definitions: bool,
     /// Specifies a minimum thickness, in ems.
     min_rule_thickness: f64,
     color_is_text_color: bool,
     strict: StrictType,
-    /// Whether to trust users' input.
-    trust: bool,
+    /// Whether to trust users' input, or a function deciding per command.
+    trust: TrustType,
+    /// What the trusted `\html*` commands may set; anything when `None`.
+    html_policy: Option<HtmlPolicy>,
     /// Max size for user-specified sizes.
     /// If set to `None`, users can make elements and spaces arbitrarily large.
     #[allow(clippy::option_option)]
@@ -57,146 +79,374 @@ pub struct Settings {
     max_expand: Option<i32>,
 
     global_group: bool,
+
+    /// Limit the nesting of groups, arguments, environments and `\left`, and
+    /// of the nodes of the parse tree, so deeply nested input fails with a
+    /// ParseError instead of overflowing the stack. The default of 64 fits the
+    /// 1 MB stack of a wasm module.
+    max_nesting_depth: Option<usize>,
+    /// Limit the number of groups, arguments and symbols parsed, and of the
+    /// nodes of the parse tree.
+    max_parse_nodes: Option<usize>,
+    /// Limit the length in bytes of rendered markup.
+    max_output_bytes: Option<usize>,
+    /// Limit the time spent parsing and building, in milliseconds.
+    time_budget: Option<f64>,
+    /// Definitions used as built-in macros, see `Preamble`.
+    preamble: Option<Preamble>,
+    /// Record every macro expansion in the diagnostics being collected.
+    trace_macros: bool,
+    /// Write the rules of katex.css into the HTML as `style` attributes.
+    inline_styles: bool,
+
+    /// Where "warn"-level strict violations go; dropped when `None`.
+    warnings: Option<Arc<Mutex<Vec<StrictWarning>>>>,
+
+    /// Equation numbers and labels shared between renders; `\label`, `\ref`
+    /// and `\eqref` are unsupported when `None`.
+    cross_references: Option<Arc<Mutex<CrossReferences>>>,
+
+    /// Where `\message`, `\errmessage`, `\show` and the expansion trace go;
+    /// dropped when `None`.
+    diagnostics: Option<Arc<Mutex<Diagnostics>>>,
 }
 
 impl Settings {
     pub fn get_ref_macros(&self) -> crate::Namespace::MapRef<MacroDefinition> {
         return self.macros.clone();
     }
+
+    pub(crate) fn set_ref_macros(&mut self, macros: crate::Namespace::MapRef<MacroDefinition>) {
+        self.macros = macros;
+    }
+
+    pub(crate) fn keeps_definitions(&self) -> bool {
+        self.keep_definitions
+    }
+
+    /**
+     * Makes `\gdef` and other global definitions of a render outlive it in
+     * the macro map. Only for settings with a macro map of their own, as
+     * clones share it.
+     */
+    pub(crate) fn set_keep_definitions(&mut self, keep_definitions: bool) {
+        self.keep_definitions = keep_definitions;
+    }
+
+    /**
+     * Replaces the custom macros, as the `macros` option does: each name
+     * (e.g. `\RR`) expands to a LaTeX string in which `#1`..`#9` stand for
+     * the macro's arguments.
+     *
+     * Clones of these settings share one macro map, like the `macros` object
+     * in KaTeX.js, so they see the change too.
+     */
+    pub fn set_macros(&mut self, macros: HashMap<String, String>) {
+        let mut map = self.macros.write().unwrap();
+        map.clear();
+        for (name, expansion) in macros {
+            map.insert(name, MacroDefinition::Str(expansion));
+        }
+    }
+
+    /**
+     * Decides which potentially dangerous commands are allowed, like a
+     * function given as the `trust` option. For example, to allow links
+     * only over https:
+     *
+     * `settings.set_trust_function(|c| c.command == "\\href" && c.protocol.as_deref() == Some("https"))`
+     */
+    pub fn set_trust_function(&mut self, trust: impl Fn(&TrustContext) -> bool + Send + Sync + 'static) {
+        self.trust = TrustType::Function(Arc::new(trust));
+    }
+
+    /**
+     * Decides per violation how LaTeX-incompatible input is treated, like a
+     * function given as the `strict` option.
+     */
+    pub fn set_strict_function(
+        &mut self,
+        strict: impl Fn(&str, &str, Option<&Token>) -> StrictLevel + Send + Sync + 'static,
+    ) {
+        self.strict = StrictType::Function(Arc::new(strict));
+    }
+
+    /**
+     * Starts collecting strict warnings in a list of their own, so these
+     * settings no longer share one with the settings they were cloned from.
+     */
+    pub(crate) fn collect_warnings(&mut self) -> Arc<Mutex<Vec<StrictWarning>>> {
+        let warnings = Arc::new(Mutex::new(vec![]));
+        self.warnings = Some(warnings.clone());
+        warnings
+    }
+
+    /**
+     * Starts collecting diagnostics in a sink of their own, like
+     * `collect_warnings`.
+     */
+    pub(crate) fn collect_diagnostics(&mut self) -> Arc<Mutex<Diagnostics>> {
+        let diagnostics = Arc::new(Mutex::new(Diagnostics::default()));
+        self.diagnostics = Some(diagnostics.clone());
+        diagnostics
+    }
+
+    pub(crate) fn report_diagnostic(&self, kind: DiagnosticKind, text: String, loc: Option<SourceLocation>) {
+        if let Some(diagnostics) = &self.diagnostics {
+            diagnostics.lock().unwrap().messages.push(Diagnostic { kind, text, loc });
+        }
+    }
+
+    /** Whether `record_expansion` would keep anything. */
+    pub(crate) fn is_tracing_macros(&self) -> bool {
+        self.trace_macros && self.diagnostics.is_some()
+    }
+
+    pub(crate) fn record_expansion(&self, step: ExpansionStep) {
+        if let (true, Some(diagnostics)) = (self.trace_macros, &self.diagnostics) {
+            diagnostics.lock().unwrap().trace.push(step);
+        }
+    }
+
+    pub(crate) fn get_cross_references(&self) -> Option<Arc<Mutex<CrossReferences>>> {
+        self.cross_references.clone()
+    }
+
+    pub(crate) fn set_cross_references(&mut self, cross_references: Option<Arc<Mutex<CrossReferences>>>) {
+        self.cross_references = cross_references;
+    }
+
+    pub fn get_preamble(&self) -> Option<&Preamble> {
+        self.preamble.as_ref()
+    }
+
+    /**
+     * Renders with the definitions of `preamble` as built-in macros, instead
+     * of parsing them again for every formula.
+     */
+    pub fn set_preamble(&mut self, preamble: Option<Preamble>) {
+        self.preamble = preamble;
+    }
+
+    /** The built-in macros, and those of the preamble if any. */
+    pub(crate) fn get_builtin_macros(&self) -> Arc<crate::Namespace::Mapping<MacroDefinition>> {
+        match &self.preamble {
+            Some(preamble) => preamble.get_macros(),
+            None => crate::define::macros::macro_map::BUILTIN_MACROS.clone(),
+        }
+    }
+
+    pub fn get_html_policy(&self) -> Option<&HtmlPolicy> {
+        self.html_policy.as_ref()
+    }
+
+    /** Restricts the attributes set by trusted `\html*` commands. */
+    pub fn set_html_policy(&mut self, html_policy: Option<HtmlPolicy>) {
+        self.html_policy = html_policy;
+    }
+
+    /** Defines one custom macro, see `set_macros`. */
+    pub fn add_macro(&mut self, name: &str, expansion: &str) {
+        self.macros
+            .write()
+            .unwrap()
+            .insert(name.to_string(), MacroDefinition::Str(expansion.to_string()));
+    }
 }
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 impl Settings {
-    #[wasm_bindgen(getter = displayMode)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter = displayMode))]
     pub fn get_display_mode(&self) -> bool {
         self.display_mode
     }
 
-    #[wasm_bindgen(setter = displayMode)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(setter = displayMode))]
     pub fn set_display_mode(&mut self, display_mode: bool) {
         self.display_mode = display_mode
     }
 
-    #[wasm_bindgen(getter = output)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter = output))]
     pub fn get_output(&self) -> String {
         self.output.as_str().to_string()
     }
 
-    #[wasm_bindgen(setter = output)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(setter = output))]
     pub fn set_output(&mut self, output: String) {
         self.output = OutputType::from_str(output.as_str()).unwrap();
     }
 
-    #[wasm_bindgen(getter = leqno)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter = leqno))]
     pub fn get_leqno(&self)->bool{
         self.leqno
     }
 
-    #[wasm_bindgen(setter = leqno)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(setter = leqno))]
     pub fn set_leqno(&mut self, leqno:bool){
         self.leqno = leqno;
     }
 
-    #[wasm_bindgen(getter = fleqn)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter = fleqn))]
     pub fn get_fleqn(&self)->bool{
         self.fleqn
     }
 
-    #[wasm_bindgen(setter = fleqn)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(setter = fleqn))]
     pub fn set_fleqn(&mut self, fleqn:bool){
         self.fleqn = fleqn;
     }
 
-    #[wasm_bindgen(getter = throwOnError)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter = throwOnError))]
     pub fn get_throw_on_error(&self) -> bool {
         self.throw_on_error
     }
 
-    #[wasm_bindgen(setter = throwOnError)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(setter = throwOnError))]
     pub fn set_throw_on_error(&mut self, throw_on_error: bool) {
         self.throw_on_error = throw_on_error;
     }
 
-    #[wasm_bindgen(getter = errorColor)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter = errorColor))]
     pub fn get_error_color(&self) -> String {
         self.error_color.clone()
     }
 
-    #[wasm_bindgen(setter = errorColor)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(setter = errorColor))]
     pub fn set_error_color(&mut self, error_color: String) {
         self.error_color = error_color;
     }
 
-    #[wasm_bindgen(getter = minRuleThickness)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter = minRuleThickness))]
     pub fn get_min_rule_thickness(&self) -> f64 {
         self.min_rule_thickness
     }
 
-    #[wasm_bindgen(setter = minRuleThickness)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(setter = minRuleThickness))]
     pub fn set_min_rule_thickness(&mut self, min_rule_thickness: f64) {
         self.min_rule_thickness = min_rule_thickness;
     }
 
-    #[wasm_bindgen(getter = colorIsTextColor)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter = colorIsTextColor))]
     pub fn get_color_is_text_color(&self) -> bool {
         self.color_is_text_color
     }
 
-    #[wasm_bindgen(setter = colorIsTextColor)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(setter = colorIsTextColor))]
     pub fn set_color_is_text_color(&mut self, color_is_text_color: bool) {
         self.color_is_text_color = color_is_text_color;
     }
 
-    #[wasm_bindgen(getter = strict)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter = strict))]
     pub fn get_strict(&self) -> String {
         self.strict.as_str().to_string()
     }
 
-    #[wasm_bindgen(setter = strict)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(setter = strict))]
     pub fn set_strict(&mut self, strict: String) {
         //TODO 为了兼容katex，这里得传入 boolean|"ignore" | warn | "error" | StrictFunction 类型
         self.strict = StrictType::from_str(strict.as_str()).unwrap();
     }
 
-    #[wasm_bindgen(getter = trust)]
+    /** `false` when trust is decided by a function, see `is_trusted`. */
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter = trust))]
     pub fn get_trust(&self) -> bool {
-        self.trust
+        matches!(self.trust, TrustType::Bool(true))
     }
 
-    #[wasm_bindgen(setter = trust)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(setter = trust))]
     pub fn set_trust(&mut self, trust: bool) {
-        self.trust = trust;
+        self.trust = TrustType::Bool(trust);
     }
 
-    #[wasm_bindgen(getter = maxSize)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter = maxSize))]
     pub fn get_max_size(&self) -> Option<f64> {
         self.max_size
     }
 
-    #[wasm_bindgen(setter = maxSize)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(setter = maxSize))]
     pub fn set_max_size(&mut self, max_size: Option<f64>) {
         self.max_size = max_size;
     }
 
-    #[wasm_bindgen(getter = maxExpand)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter = maxExpand))]
     pub fn get_max_expand(&self) -> Option<i32> {
         self.max_expand
     }
 
-    #[wasm_bindgen(setter = maxExpand)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(setter = maxExpand))]
     pub fn set_max_expand(&mut self, max_expand: Option<i32>) {
         self.max_expand = max_expand;
     }
 
-    #[wasm_bindgen(getter = globalGroup)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter = globalGroup))]
     pub fn get_global_group(&self) -> bool {
-        self.color_is_text_color
+        self.global_group
     }
 
-    #[wasm_bindgen(setter = globalGroup)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(setter = globalGroup))]
     pub fn set_global_group(&mut self, global_group: bool) {
         self.global_group = global_group;
     }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter = maxNestingDepth))]
+    pub fn get_max_nesting_depth(&self) -> Option<usize> {
+        self.max_nesting_depth
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(setter = maxNestingDepth))]
+    pub fn set_max_nesting_depth(&mut self, max_nesting_depth: Option<usize>) {
+        self.max_nesting_depth = max_nesting_depth;
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter = maxParseNodes))]
+    pub fn get_max_parse_nodes(&self) -> Option<usize> {
+        self.max_parse_nodes
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(setter = maxParseNodes))]
+    pub fn set_max_parse_nodes(&mut self, max_parse_nodes: Option<usize>) {
+        self.max_parse_nodes = max_parse_nodes;
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter = maxOutputBytes))]
+    pub fn get_max_output_bytes(&self) -> Option<usize> {
+        self.max_output_bytes
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(setter = maxOutputBytes))]
+    pub fn set_max_output_bytes(&mut self, max_output_bytes: Option<usize>) {
+        self.max_output_bytes = max_output_bytes;
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter = timeBudget))]
+    pub fn get_time_budget(&self) -> Option<f64> {
+        self.time_budget
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(setter = timeBudget))]
+    pub fn set_time_budget(&mut self, time_budget: Option<f64>) {
+        self.time_budget = time_budget;
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter = traceMacros))]
+    pub fn get_trace_macros(&self) -> bool {
+        self.trace_macros
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(setter = traceMacros))]
+    pub fn set_trace_macros(&mut self, trace_macros: bool) {
+        self.trace_macros = trace_macros;
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter = inlineStyles))]
+    pub fn get_inline_styles(&self) -> bool {
+        self.inline_styles
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(setter = inlineStyles))]
+    pub fn set_inline_styles(&mut self, inline_styles: bool) {
+        self.inline_styles = inline_styles;
+    }
 }
 
 impl AsRef<Settings> for Settings {
@@ -205,10 +455,11 @@ impl AsRef<Settings> for Settings {
     }
 }
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 impl Settings {
+    #[cfg(feature = "wasm")]
     #[wasm_bindgen(constructor)]
-    pub fn new_from_js(js_v: &JsValue) -> Settings {
+    pub fn new_from_js(js_v: &JsValue) -> Result<Settings, JsValue> {
         let mut res = Settings::new();
         use js_sys::{Boolean, JsString, Reflect};
         if let Ok(opt_display_mode) = Reflect::get(&js_v, &JsString::from("displayMode")) {
@@ -240,7 +491,11 @@ impl Settings {
             res.error_color = String::from("#cc0000");
         }
 
-        // if let Ok(opt_macros) = Reflect::get(&js_v, &JsString::from("macros")){}
+        if let Ok(opt_macros) = Reflect::get(&js_v, &JsString::from("macros")) {
+            if opt_macros.is_object() {
+                *res.macros.write().unwrap() = crate::define::macros::js_macro::macros_from_js(&opt_macros);
+            }
+        }
 
         if let Ok(opt_min_rule_thickness) = Reflect::get(&js_v, &JsString::from("minRuleThickness"))
         {
@@ -252,25 +507,65 @@ impl Settings {
             res.color_is_text_color = opt_color_is_text_color.as_bool().unwrap_or_default();
         }
         if let Ok(opt_strict) = Reflect::get(&js_v, &JsString::from("strict")) {
-            res.strict = StrictType::from_str(opt_strict.as_string().unwrap_or_default().as_str())
-                .unwrap_or_default();
+            res.strict = if opt_strict.is_function() {
+                StrictType::Function(strict_function_from_js(js_sys::Function::from(opt_strict)))
+            } else if let Some(strict) = opt_strict.as_bool() {
+                if strict { StrictType::Error } else { StrictType::Ignore }
+            } else {
+                StrictType::from_str(opt_strict.as_string().unwrap_or_default().as_str())
+                    .unwrap_or_default()
+            };
         }
         if let Ok(opt_trust) = Reflect::get(&js_v, &JsString::from("trust")) {
-            res.trust = opt_trust.as_bool().unwrap_or_default();
+            res.trust = if opt_trust.is_function() {
+                TrustType::Function(trust_function_from_js(js_sys::Function::from(opt_trust)))
+            } else {
+                TrustType::Bool(opt_trust.as_bool().unwrap_or_default())
+            };
+        }
+        if let Ok(opt_html_policy) = Reflect::get(&js_v, &JsString::from("htmlPolicy")) {
+            if opt_html_policy.is_object() {
+                res.html_policy = Some(html_policy_from_js(&opt_html_policy));
+            }
         }
         if let Ok(opt_max_size) = Reflect::get(&js_v, &JsString::from("maxSize")) {
             res.max_size = opt_max_size.as_f64();
         }
         if let Ok(opt_max_expand) = Reflect::get(&js_v, &JsString::from("maxExpand")) {
-            res.max_expand = match opt_max_expand.as_f64() {
-                Some(d) => Some(d as i32),
-                None => None,
+            // As in KaTeX, `Infinity` lifts the limit and `undefined` keeps the default.
+            if opt_max_expand.as_f64() == Some(f64::INFINITY) {
+                res.max_expand = None;
+            } else if !opt_max_expand.is_undefined() {
+                res.max_expand = count_from_js("maxExpand", &opt_max_expand)?
+                    .map(|n| i32::try_from(n).map_err(|_| type_error(format!("maxExpand: {} is too large", n))))
+                    .transpose()?;
             }
         }
         if let Ok(opt_global_group) = Reflect::get(&js_v, &JsString::from("globalGroup")) {
             res.global_group = opt_global_group.as_bool().unwrap_or_default();
         }
-        return res;
+        if let Ok(opt_max_nesting_depth) = Reflect::get(&js_v, &JsString::from("maxNestingDepth")) {
+            // Only an explicit `null` lifts the default limit.
+            if !opt_max_nesting_depth.is_undefined() {
+                res.max_nesting_depth = count_from_js("maxNestingDepth", &opt_max_nesting_depth)?;
+            }
+        }
+        if let Ok(opt_max_parse_nodes) = Reflect::get(&js_v, &JsString::from("maxParseNodes")) {
+            res.max_parse_nodes = count_from_js("maxParseNodes", &opt_max_parse_nodes)?;
+        }
+        if let Ok(opt_max_output_bytes) = Reflect::get(&js_v, &JsString::from("maxOutputBytes")) {
+            res.max_output_bytes = count_from_js("maxOutputBytes", &opt_max_output_bytes)?;
+        }
+        if let Ok(opt_time_budget) = Reflect::get(&js_v, &JsString::from("timeBudget")) {
+            res.time_budget = duration_from_js("timeBudget", &opt_time_budget)?;
+        }
+        if let Ok(opt_trace_macros) = Reflect::get(&js_v, &JsString::from("traceMacros")) {
+            res.trace_macros = opt_trace_macros.as_bool().unwrap_or_default();
+        }
+        if let Ok(opt_inline_styles) = Reflect::get(&js_v, &JsString::from("inlineStyles")) {
+            res.inline_styles = opt_inline_styles.as_bool().unwrap_or_default();
+        }
+        return Ok(res);
     }
 
     pub fn new() -> Settings {
@@ -283,68 +578,32 @@ impl Settings {
             // Whether to let KaTeX throw a ParseError for invalid LaTeX.
             throw_on_error: false,
             // Color used for invalid LaTeX.
-            error_color: String::new(),
+            error_color: String::from("#cc0000"),
             // Collection of custom macros.
             macros: Arc::new(HashMap::<String, MacroDefinition>::new().into()),
+            keep_definitions: false,
             min_rule_thickness: 0.0,
             color_is_text_color: false,
             strict: StrictType::Warn,
-            trust: false,
+            trust: TrustType::Bool(false),
+            html_policy: None,
             max_size: None,
             max_expand: Some(1000),
             global_group: false,
+            max_nesting_depth: Some(64),
+            max_parse_nodes: None,
+            max_output_bytes: None,
+            time_budget: None,
+            preamble: None,
+            trace_macros: false,
+            inline_styles: false,
+            warnings: None,
+            cross_references: None,
+            diagnostics: None,
         };
         res
     }
-    /**
-     * Report nonstrict (non-LaTeX-compatible) input.
-     * Can safely not be called if `this.strict` is false in JavaScript.
-     */
-    pub fn report_nonstrict(&self, error_code: &str, error_msg: &str, token: Option<Token>) {
-        match self.strict {
-            StrictType::Ignore => {}
-            StrictType::Warn => {
-                println!(
-                    "LaTeX-incompatible input and strict mode is set to 'warn': {} [{}]",
-                    error_code,
-                    error_msg
-                );
-            }
-            StrictType::Error => {
-                panic!("error lllll");
-            }
-        }
-    }
-
-    /**
-     * Check whether to apply strict (LaTeX-adhering) behavior for unusual
-     * input (like `\\`).  Unlike `nonstrict`, will not throw an error;
-     * instead, "error" translates to a return value of `true`, while "ignore"
-     * translates to a return value of `false`.  May still print a warning:
-     * "warn" prints a warning and returns `false`.
-     * This is for the second category of `errorCode`s listed in the README.
-     */
-    //TODO
-    pub fn use_strict_behavior(&self, error_code: String, error_msg: String) -> bool {
-        match self.strict {
-            StrictType::Ignore => {
-                return false;
-            }
-            StrictType::Warn => {
-                println!(
-                    "LaTeX-incompatible input and strict mode is set to 'warn': {} [{}]",
-                    error_code,
-                    error_msg
-                );
-                return false;
-            }
-            StrictType::Error => {
-                panic!("error lllll");
-                return true;
-            }
-        }
-    }
-
+    #[cfg(feature = "wasm")]
     #[wasm_bindgen(js_name = "toJsValue")]
     pub fn to_js_value(&self) -> JsValue {
         use js_sys::{Object, Reflect, JsString};
@@ -360,7 +619,7 @@ impl Settings {
         Reflect::set(&obj, &JsString::from("minRuleThickness"), &JsValue::from_f64(self.min_rule_thickness)).unwrap();
         Reflect::set(&obj, &JsString::from("colorIsTextColor"), &JsValue::from_bool(self.color_is_text_color)).unwrap();
         Reflect::set(&obj, &JsString::from("strict"), &JsValue::from_str(self.strict.as_str())).unwrap();
-        Reflect::set(&obj, &JsString::from("trust"), &JsValue::from_bool(self.trust)).unwrap();
+        Reflect::set(&obj, &JsString::from("trust"), &JsValue::from_bool(self.get_trust())).unwrap();
 
         match self.max_size {
             Some(v) => { Reflect::set(&obj, &JsString::from("maxSize"), &JsValue::from_f64(v)).unwrap(); }
@@ -374,6 +633,19 @@ impl Settings {
 
         Reflect::set(&obj, &JsString::from("globalGroup"), &JsValue::from_bool(self.global_group)).unwrap();
 
+        let limits = [
+            ("maxNestingDepth", self.max_nesting_depth.map(|v| v as f64)),
+            ("maxParseNodes", self.max_parse_nodes.map(|v| v as f64)),
+            ("maxOutputBytes", self.max_output_bytes.map(|v| v as f64)),
+            ("timeBudget", self.time_budget),
+        ];
+        for (key, value) in limits {
+            let value = value.map(JsValue::from_f64).unwrap_or(JsValue::NULL);
+            Reflect::set(&obj, &JsString::from(key), &value).unwrap();
+        }
+        Reflect::set(&obj, &JsString::from("traceMacros"), &JsValue::from_bool(self.trace_macros)).unwrap();
+        Reflect::set(&obj, &JsString::from("inlineStyles"), &JsValue::from_bool(self.inline_styles)).unwrap();
+
         obj.into()
     }
 }
@@ -383,18 +655,230 @@ impl Settings{
      * `true` (trusted) or `false` (untrusted).  The sole argument `context`
      * should be an object with `command` field specifying the relevant LaTeX
      * command (as a string starting with `\`), and any other arguments, etc.
-     * If `context` has a `url` field, a `protocol` field will automatically
-     * get added by this function (changing the specified object).
+     * If `context` has a `url` field, the `trust` function sees it with a
+     * `protocol` field filled in by `utils::protocol_from_url`; a URL with no
+     * valid protocol is untrusted whatever `trust` says.
      */
-    // #[wasm_bindgen(js_name = isTrusted)]
     pub fn is_trusted(&self, context: &TrustContext) -> bool {
-        // if (context.url && !context.protocol) {
-        //     context.protocol = utils.protocolFromUrl(context.url);
-        // }
-        // const trust = typeof this.trust === "function"
-        //     ? this.trust(context)
-        //     : this.trust;
-        // return Boolean(trust);
-        return self.trust;
+        let mut context = context.clone();
+        if let (Some(url), None) = (&context.url, &context.protocol) {
+            // A URL whose protocol cannot be read is never trusted.
+            match utils::protocol_from_url(url) {
+                Some(protocol) => context.protocol = Some(protocol),
+                None => return false,
+            }
+        }
+        match &self.trust {
+            TrustType::Bool(trust) => *trust,
+            TrustType::Function(trust) => trust(&context),
+        }
+    }
+
+    /**
+     * Report nonstrict (non-LaTeX-compatible) input.
+     * Returns the ParseError to raise when strict mode is "error"; a "warn"
+     * is added to the warnings being collected, if any.
+     */
+    pub fn report_nonstrict(
+        &self,
+        error_code: &str,
+        error_msg: &str,
+        token: Option<Token>,
+    ) -> Result<(), ParseError> {
+        match self.strict.level(error_code, error_msg, token.as_ref()) {
+            StrictLevel::Ignore => Ok(()),
+            StrictLevel::Warn => {
+                self.warn(error_code, error_msg, token);
+                Ok(())
+            }
+            StrictLevel::Error => Err(ParseError::new(
+                format!(
+                    "LaTeX-incompatible input and strict mode is set to 'error': {} [{}]",
+                    error_msg, error_code
+                ),
+                token.and_then(|t| t.loc),
+            )),
+        }
+    }
+
+    /**
+     * Check whether to apply strict (LaTeX-adhering) behavior for unusual
+     * input (like `\\`).  Unlike `nonstrict`, will not throw an error;
+     * instead, "error" translates to a return value of `true`, while "ignore"
+     * translates to a return value of `false`.  May still print a warning:
+     * "warn" prints a warning and returns `false`.
+     * This is for the second category of `errorCode`s listed in the README.
+     */
+    pub fn use_strict_behavior(&self, error_code: &str, error_msg: &str, token: Option<Token>) -> bool {
+        match self.strict.level(error_code, error_msg, token.as_ref()) {
+            StrictLevel::Ignore => false,
+            StrictLevel::Warn => {
+                self.warn(error_code, error_msg, token);
+                false
+            }
+            StrictLevel::Error => true,
+        }
     }
+
+    fn warn(&self, error_code: &str, error_msg: &str, token: Option<Token>) {
+        if let Some(warnings) = &self.warnings {
+            warnings.lock().unwrap().push(StrictWarning {
+                code: error_code.to_string(),
+                msg: error_msg.to_string(),
+                loc: token.and_then(|t| t.loc),
+            });
+        }
+    }
+}
+
+/**
+ * Reads a limit given as a count; `null` and `undefined` mean no limit.
+ * Other values than non-negative integers are an error, as in
+ * `Settings::from_json`, rather than being cast to a count.
+ */
+#[cfg(feature = "wasm")]
+fn count_from_js(name: &str, value: &JsValue) -> Result<Option<usize>, JsValue> {
+    if value.is_null() || value.is_undefined() {
+        return Ok(None);
+    }
+    match value.as_f64() {
+        Some(n) if n >= 0.0 && n.fract() == 0.0 && n <= usize::MAX as f64 => Ok(Some(n as usize)),
+        n => Err(type_error(format!("{}: expected a non-negative integer, got {}", name, js_number(n, value)))),
+    }
+}
+
+/** Reads a limit given in milliseconds, which must be finite and non-negative. */
+#[cfg(feature = "wasm")]
+fn duration_from_js(name: &str, value: &JsValue) -> Result<Option<f64>, JsValue> {
+    if value.is_null() || value.is_undefined() {
+        return Ok(None);
+    }
+    match value.as_f64() {
+        Some(n) if n >= 0.0 && n.is_finite() => Ok(Some(n)),
+        n => Err(type_error(format!("{}: expected a non-negative number, got {}", name, js_number(n, value)))),
+    }
+}
+
+#[cfg(feature = "wasm")]
+fn js_number(n: Option<f64>, value: &JsValue) -> String {
+    n.map_or_else(|| format!("{:?}", value), |n| n.to_string())
+}
+
+#[cfg(feature = "wasm")]
+fn type_error(msg: String) -> JsValue {
+    js_sys::TypeError::new(&msg).into()
+}
+
+/**
+ * Wraps a JS `trust` function, which gets the context as a plain object.
+ * Called on another thread than the one it came from, it trusts nothing.
+ */
+#[cfg(feature = "wasm")]
+fn trust_function_from_js(function: js_sys::Function) -> TrustFunction {
+    use js_sys::{JsString, Object, Reflect};
+    let function = JsFunction::new(function);
+    Arc::new(move |context: &TrustContext| {
+        let function = match function.get() {
+            Some(function) => function,
+            None => return false,
+        };
+        let obj = Object::new();
+        let set = |key: &str, value: &Option<String>| {
+            if let Some(v) = value {
+                Reflect::set(&obj, &JsString::from(key), &JsValue::from_str(v)).unwrap();
+            }
+        };
+        set("command", &Some(context.command.clone()));
+        set("url", &context.url);
+        set("protocol", &context.protocol);
+        set("class", &context.class);
+        set("id", &context.id);
+        set("style", &context.style);
+        if let Some(attributes) = &context.attributes {
+            let attrs = Object::new();
+            for (k, v) in attributes.iter() {
+                Reflect::set(&attrs, &JsString::from(k.as_str()), &JsValue::from_str(v)).unwrap();
+            }
+            Reflect::set(&obj, &JsString::from("attributes"), &attrs).unwrap();
+        }
+        function
+            .call1(&JsValue::NULL, &obj)
+            .map(|v| v.is_truthy())
+            .unwrap_or(false)
+    })
+}
+
+/**
+ * Wraps a JS `strict` function. As in KaTeX.js, it may return a boolean or
+ * one of "ignore", "warn" and "error"; throwing, or being called on another
+ * thread than the one it came from, counts as "error".
+ */
+#[cfg(feature = "wasm")]
+fn strict_function_from_js(function: js_sys::Function) -> StrictFunction {
+    use js_sys::{JsString, Object, Reflect};
+    let function = JsFunction::new(function);
+    Arc::new(move |error_code: &str, error_msg: &str, token: Option<&Token>| {
+        let function = match function.get() {
+            Some(function) => function,
+            None => return StrictLevel::Error,
+        };
+        let js_token = match token {
+            Some(token) => {
+                let obj = Object::new();
+                Reflect::set(&obj, &JsString::from("text"), &JsValue::from_str(&token.text)).unwrap();
+                if let Some(loc) = &token.loc {
+                    let js_loc = Object::new();
+                    Reflect::set(&js_loc, &JsString::from("start"), &JsValue::from(loc.start)).unwrap();
+                    Reflect::set(&js_loc, &JsString::from("end"), &JsValue::from(loc.end)).unwrap();
+                    Reflect::set(&obj, &JsString::from("loc"), &js_loc).unwrap();
+                }
+                obj.into()
+            }
+            None => JsValue::UNDEFINED,
+        };
+        let result = function.call3(
+            &JsValue::NULL,
+            &JsValue::from_str(error_code),
+            &JsValue::from_str(error_msg),
+            &js_token,
+        );
+        match result {
+            Err(_) => StrictLevel::Error,
+            Ok(value) => match (value.as_bool(), value.as_string().as_deref()) {
+                (Some(true), _) | (_, Some("error")) => StrictLevel::Error,
+                (_, Some("warn")) => StrictLevel::Warn,
+                _ => StrictLevel::Ignore,
+            },
+        }
+    })
+}
+
+/**
+ * Reads `{classPatterns, idPrefix, styleProperties, dataKeys}`; invalid
+ * class patterns are skipped.
+ */
+#[cfg(feature = "wasm")]
+fn html_policy_from_js(value: &JsValue) -> HtmlPolicy {
+    use js_sys::{Array, JsString, Reflect};
+    let strings = |key: &str| -> Vec<String> {
+        Reflect::get(value, &JsString::from(key))
+            .ok()
+            .filter(|v| Array::is_array(v))
+            .map(|v| Array::from(&v).iter().filter_map(|s| s.as_string()).collect())
+            .unwrap_or_default()
+    };
+    let mut policy = HtmlPolicy::new();
+    for pattern in strings("classPatterns") {
+        let _ = policy.allow_class_pattern(&pattern);
+    }
+    if let Some(prefix) = Reflect::get(value, &JsString::from("idPrefix")).ok().and_then(|v| v.as_string()) {
+        policy.set_id_prefix(&prefix);
+    }
+    for name in strings("styleProperties") {
+        policy.allow_style_property(&name);
+    }
+    for key in strings("dataKeys") {
+        policy.allow_data_key(&key);
+    }
+    policy
 }
@@ -1,12 +1,37 @@
-use std::collections::HashMap;
+use crate::sourceLocation::SourceLocation;
+use crate::token::Token;
+use indexmap::IndexMap;
+use std::fmt;
 use std::str::FromStr;
+use std::sync::Arc;
 
-#[derive(Clone, Debug, Default)]
+/// What to do with one piece of LaTeX-incompatible input.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StrictLevel {
+    Ignore,
+    Warn,
+    Error,
+}
+
+/**
+ * The function form of the `strict` option: given the error code, the error
+ * message and the offending token (if any), decides how to treat the input.
+ */
+pub type StrictFunction = Arc<dyn Fn(&str, &str, Option<&Token>) -> StrictLevel + Send + Sync>;
+
+#[derive(Clone, Default)]
 pub(crate) enum StrictType {
     Ignore,
     #[default]
     Warn,
     Error,
+    Function(StrictFunction),
+}
+
+impl fmt::Debug for StrictType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
 }
 
 impl FromStr for StrictType {
@@ -27,8 +52,69 @@ impl StrictType {
             StrictType::Ignore => "ignore",
             StrictType::Warn => "warn",
             StrictType::Error => "error",
+            StrictType::Function(_) => "function",
         }
     }
+
+    pub fn level(&self, error_code: &str, error_msg: &str, token: Option<&Token>) -> StrictLevel {
+        match self {
+            StrictType::Ignore => StrictLevel::Ignore,
+            StrictType::Warn => StrictLevel::Warn,
+            StrictType::Error => StrictLevel::Error,
+            StrictType::Function(strict) => strict(error_code, error_msg, token),
+        }
+    }
+}
+
+/**
+ * LaTeX-incompatible input that was accepted because strict mode is set to
+ * "warn", see `try_render_to_string_with_warnings`.
+ */
+#[derive(Clone, Debug)]
+pub struct StrictWarning {
+    pub code: String,
+    pub msg: String,
+    pub loc: Option<SourceLocation>,
+}
+
+impl fmt::Display for StrictWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "LaTeX-incompatible input and strict mode is set to 'warn': {} [{}]",
+            self.msg, self.code
+        )
+    }
+}
+
+impl StrictWarning {
+    /** Byte offset of the offending token in the input, if known. */
+    pub fn position(&self) -> Option<usize> {
+        self.loc
+            .as_ref()
+            .filter(|loc| loc.start <= loc.end)
+            .map(|loc| loc.start as usize)
+    }
+
+    /** Length in bytes of the offending token in the input, if known. */
+    pub fn length(&self) -> Option<usize> {
+        self.loc
+            .as_ref()
+            .filter(|loc| loc.start <= loc.end)
+            .map(|loc| (loc.end - loc.start) as usize)
+    }
+
+    /** `{code, message, position, length}`, positions in UTF-16 as in JS. */
+    #[cfg(feature = "wasm")]
+    pub fn to_js_value(&self) -> wasm_bindgen::JsValue {
+        use js_sys::{Object, Reflect};
+        use wasm_bindgen::JsValue;
+        let obj = Object::new();
+        Reflect::set(&obj, &"code".into(), &JsValue::from_str(&self.code)).unwrap();
+        Reflect::set(&obj, &"message".into(), &JsValue::from_str(&self.msg)).unwrap();
+        super::diagnostics::set_js_position(&obj, &self.loc);
+        obj.into()
+    }
 }
 
 /// Output type from KaTeX.
@@ -41,6 +127,10 @@ pub enum OutputType {
     Mathml,
     /// Outputs HTML for visual rendering and includes MathML for accessibility.
     HtmlAndMathml,
+    /// Outputs a standalone SVG document, see `register_svg_font`.
+    Svg,
+    /// Outputs monospace Unicode text for terminals, see `crate::text`.
+    Text,
 }
 
 impl FromStr for OutputType {
@@ -51,6 +141,8 @@ impl FromStr for OutputType {
             "html" => Ok(OutputType::Html),
             "mathml" => Ok(OutputType::Mathml),
             "htmlAndMathml" => Ok(OutputType::HtmlAndMathml),
+            "svg" => Ok(OutputType::Svg),
+            "text" => Ok(OutputType::Text),
             _ => Err(()),
         }
     }
@@ -61,12 +153,51 @@ impl OutputType {
             OutputType::Html => "html",
             OutputType::Mathml => "mathml",
             OutputType::HtmlAndMathml => "htmlAndMathml",
+            OutputType::Svg => "svg",
+            OutputType::Text => "text",
         }
     }
 }
 
+/**
+ * What a potentially dangerous command asks for, passed to the `trust`
+ * function. Only the fields the command uses are set: `url` for `\href`,
+ * `\url` and `\includegraphics`, `class`, `id` and `style` for
+ * `\htmlClass`, `\htmlId` and `\htmlStyle`, and `attributes` (with the
+ * `data-` prefix) for `\htmlData`.
+ */
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TrustContext {
+    pub command: String,
+    pub url: Option<String>,
+    /// Filled in from `url` by `Settings::is_trusted`, see `protocol_from_url`.
+    pub protocol: Option<String>,
+    pub class: Option<String>,
+    pub id: Option<String>,
+    pub style: Option<String>,
+    pub attributes: Option<IndexMap<String, String>>,
+}
+
+pub type TrustFunction = Arc<dyn Fn(&TrustContext) -> bool + Send + Sync>;
 
-pub struct TrustContext{
-    pub command :String,
-    pub context :HashMap<String,String>
+/// The `trust` option: a fixed answer, or a function deciding per command.
+#[derive(Clone)]
+pub enum TrustType {
+    Bool(bool),
+    Function(TrustFunction),
+}
+
+impl Default for TrustType {
+    fn default() -> Self {
+        TrustType::Bool(false)
+    }
+}
+
+impl std::fmt::Debug for TrustType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TrustType::Bool(b) => write!(f, "Bool({})", b),
+            TrustType::Function(_) => write!(f, "Function"),
+        }
+    }
 }
\ No newline at end of file
@@ -1,15 +1,18 @@
 use crate::utils::{console_log, log};
 use regex::Regex;
+use std::sync::Arc;
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 
 /**
  * Interface required to break circular dependency between Token, Lexer, and
  * ParseError.
  */
-#[wasm_bindgen(getter_with_clone)]
+#[cfg_attr(feature = "wasm", wasm_bindgen(getter_with_clone))]
 #[derive( Clone)]
 pub struct LexerInterface {
-    input: String,
+    // Shared by every token and `SourceLocation` of the input.
+    input: Arc<str>,
     // pub tokenRegx: regex::Regex,
     token_regex: &'static Regex,
     last_index: usize,
@@ -29,13 +32,18 @@ impl LexerInterface {
     pub fn new(input: String, token_regex: &'static Regex) -> LexerInterface {
         // console_log!("input {}",input);
         return LexerInterface {
-            input,
+            input: input.into(),
             token_regex,
             last_index: 0,
         };
     }
 
-    pub fn get_input(&self)->&String{
+    pub fn get_input(&self)->&str{
+        &self.input
+    }
+
+    /** The input itself, to tell whether two lexers hold the same one. */
+    pub(crate) fn shared_input(&self) -> &Arc<str> {
         &self.input
     }
 
@@ -66,7 +74,7 @@ impl LexerInterface {
  * This object is immutable.
  */
 #[derive(Debug, Clone)]
-#[wasm_bindgen(getter_with_clone)]
+#[cfg_attr(feature = "wasm", wasm_bindgen(getter_with_clone))]
 pub struct SourceLocation {
     // The + prefix indicates that these fields aren't writeable
     pub lexer: LexerInterface,
@@ -76,9 +84,9 @@ pub struct SourceLocation {
     pub end: i32, // End offset, zero-based exclusive.
 }
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 impl SourceLocation {
-    #[wasm_bindgen(constructor)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
     pub fn new(lexer: &LexerInterface, start: f64, end: f32) -> SourceLocation {
         SourceLocation {
             lexer: lexer.clone(),
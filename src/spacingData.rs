@@ -3,7 +3,7 @@
  */
 use super::units::Measurement;
 use std::collections::HashMap;
-use std::sync::Mutex;
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 
 // Making the type below exact with all optional fields doesn't work due to
@@ -15,27 +15,27 @@ use wasm_bindgen::prelude::*;
 pub type __spacings = HashMap<String, HashMap<String, Measurement>>;
 
 lazy_static! {
-    pub static ref thinspace: Mutex<Measurement> = Mutex::new(Measurement{
+    pub static ref thinspace: Measurement = Measurement{
         number: 3.0,
         unit: String::from("mu")
-    });
-    static ref mediumspace: Mutex<Measurement> = Mutex::new(Measurement{
+    };
+    static ref mediumspace: Measurement = Measurement{
         number: 4.0,
         unit:  String::from("mu")
-    });
-    static ref thickspace: Mutex<Measurement> = Mutex::new(Measurement{
+    };
+    static ref thickspace: Measurement = Measurement{
         number: 5.0,
         unit:  String::from("mu")
-    });
+    };
 
 // Spacing relationships for display and text styles
 
-    static ref spacings:Mutex<__spacings>= Mutex::new({
+    static ref spacings: __spacings = {
         let mut m = HashMap::new();
         let mut mord = HashMap::new();
-        let thinspace_c = thinspace.lock().unwrap();
-        let mediumspace_c = mediumspace.lock().unwrap();
-        let thickspace_c = thickspace.lock().unwrap();
+        let thinspace_c = &*thinspace;
+        let mediumspace_c = &*mediumspace;
+        let thickspace_c = &*thickspace;
         mord.insert(String::from("mop"), thinspace_c.clone());
         mord.insert(String::from("mbin"),mediumspace_c.clone());
         mord.insert(String::from("mrel"),thickspace_c.clone());
@@ -91,9 +91,9 @@ lazy_static! {
         minner.insert(String::from("minner"), thinspace_c.clone());
         m.insert(String::from("minner"),minner);
         m
-    });
-    static ref tightSpacings:Mutex<__spacings>= Mutex::new({
-        let thinspace_c = thinspace.lock().unwrap();
+    };
+    static ref tightSpacings: __spacings = {
+        let thinspace_c = &*thinspace;
         let mut m = HashMap::new();
         let   mut mord = HashMap::new();
         mord.insert(String::from("mop"), thinspace_c.clone());
@@ -112,16 +112,17 @@ lazy_static! {
         minner.insert(String::from("mop"),thinspace_c.clone());
         m.insert(String::from("minner"),minner);
         m
-    });
+    };
 // Spacing relationships for script and scriptscript styles
 
 }
 
 pub fn get_spacings(k1: &str, k2: &str) -> Option<Measurement> {
-    let _spacings = spacings.lock().unwrap();
+    let _spacings = &*spacings;
     _spacings.get(k1).and_then(|p| p.get(k2)).cloned()
 }
 
+#[cfg(feature = "wasm")]
 #[wasm_bindgen]
 extern "C" {
     // Use `js_namespace` here to bind `console.log(..)` instead of just
@@ -149,6 +150,6 @@ macro_rules!  console_log {
 use std::panic;
 
 pub fn get_tightSpacings(k1: &str, k2: &str) -> Option<Measurement> {
-    let _tight_spacings = tightSpacings.lock().unwrap();
+    let _tight_spacings = &*tightSpacings;
     _tight_spacings.get(k1).and_then(|p| p.get(k2)).cloned()
 }
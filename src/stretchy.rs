@@ -5,7 +5,7 @@ use crate::dom_tree::span::Span;
 use crate::dom_tree::svg_node::SvgNode;
 use crate::{make_em, parse_node, AnyParseNode, HtmlDomNode, VirtualNode};
 use std::collections::HashMap;
-use std::sync::RwLock;
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 
 use crate::mathML_tree::{math_node::MathNode, public::MathNodeType, text_node::TextNode};
@@ -15,7 +15,7 @@ use crate::Options::Options;
 lazy_static! {
     static ref stretchy_codepoint: HashMap<&'static str, &'static str> = {
         HashMap::from([
-            ("\\widechar", "^"),
+            ("\\widehat", "^"),
             ("\\widecheck", "ˇ"),
             ("\\widetilde", "~"),
             ("\\utilde", "~"),
@@ -107,7 +107,7 @@ lazy_static! {
 // That is, inside the font, that arrowhead is 522 units tall, which
 // corresponds to 0.522 em inside the document.
 lazy_static! {
-    static ref KATEX_IMAGES_DATA :RwLock<HashMap<&'static str,(Vec<&'static str>,f64,i32,Option<&'static str>)>> = RwLock::new({
+    static ref KATEX_IMAGES_DATA: HashMap<&'static str,(Vec<&'static str>,f64,i32,Option<&'static str>)> = {
         let res = HashMap::< &str,(Vec<& str>,f64,i32,Option<&str>)>::from([
                               //   path(s), minWidth, height, align
             ("overrightarrow", (vec!["rightarrow"], 0.888, 522, Some("xMaxYMin"))),
@@ -158,14 +158,31 @@ lazy_static! {
 ("xleftequilibrium", (vec!["shortbaraboveleftharpoon","shortrightharpoonabovebar"], 1.75, 716, None))
        ]);
         res
-    });
+    };
+}
+
+/**
+ * Whether `svg_span` can draw `label`, e.g. `\overrightarrow`. The wide
+ * accents (`\widehat` and the like) are drawn for `accent` and
+ * `accentUnder` nodes only.
+ */
+pub(crate) fn is_stretchy_label(label: &str, wide_accents: bool) -> bool {
+    match label.strip_prefix('\\') {
+        Some(name) => {
+            KATEX_IMAGES_DATA.contains_key(name)
+                || (wide_accents && ["widehat", "widecheck", "widetilde", "utilde"].contains(&name))
+        }
+        None => false,
+    }
 }
 
 pub(crate) fn math_ml_node(label: &String) -> MathNode {
     let mut node = MathNode::new(
         MathNodeType::Mo,
         vec![Box::new(TextNode::new(
-            stretchy_codepoint.get(label.as_str()).unwrap().to_string(),
+            stretchy_codepoint
+                .get(label.as_str())
+                .map_or(String::new(), |c| c.to_string()),
         ))],
         vec![],
     );
@@ -270,7 +287,7 @@ fn build_svg_span(group: Box<dyn AnyParseNode>, options: Options) -> (Span, f64,
         );
     } else {
         let mut spans = vec![];
-        let katex_image_data = KATEX_IMAGES_DATA.read().unwrap();
+        let katex_image_data = &*KATEX_IMAGES_DATA;
         let data = katex_image_data.get(label).unwrap();
         let (paths, minWidth, viewBoxHeight, _) = data;
         let height = *viewBoxHeight as f64 / 1000.0;
@@ -404,10 +421,9 @@ pub fn enclose_span(
             lines.push(Box::new(tmp) as Box<dyn VirtualNode>);
         }
         lazy_static! {
-            static ref X_CANCEL: std::sync::Mutex<regex::Regex> =
-                std::sync::Mutex::new({ regex::Regex::new("^x?cancel$").unwrap() });
+            static ref X_CANCEL: regex::Regex = regex::Regex::new("^x?cancel$").unwrap();
         }
-        let x_cancel = X_CANCEL.lock().unwrap();
+        let x_cancel = &*X_CANCEL;
 
         if x_cancel.is_match(&label) {
             let attr = HashMap::from([
@@ -0,0 +1,457 @@
+//! A port of KaTeX's `katex.less`, so the stylesheet always matches the
+//! classes of the markup this crate renders.
+
+use crate::Options::consts::SIZE_MULTIPLIERS;
+use std::fmt::Write;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+/// The KaTeX fonts as family, variant, `font-weight` and `font-style`.
+const FONT_FACES: [(&str, &str, &str, &str); 20] = [
+    ("AMS", "Regular", "normal", "normal"),
+    ("Caligraphic", "Bold", "bold", "normal"),
+    ("Caligraphic", "Regular", "normal", "normal"),
+    ("Fraktur", "Bold", "bold", "normal"),
+    ("Fraktur", "Regular", "normal", "normal"),
+    ("Main", "Bold", "bold", "normal"),
+    ("Main", "BoldItalic", "bold", "italic"),
+    ("Main", "Italic", "normal", "italic"),
+    ("Main", "Regular", "normal", "normal"),
+    ("Math", "BoldItalic", "bold", "italic"),
+    ("Math", "Italic", "normal", "italic"),
+    ("SansSerif", "Bold", "bold", "normal"),
+    ("SansSerif", "Italic", "normal", "italic"),
+    ("SansSerif", "Regular", "normal", "normal"),
+    ("Script", "Regular", "normal", "normal"),
+    ("Size1", "Regular", "normal", "normal"),
+    ("Size2", "Regular", "normal", "normal"),
+    ("Size3", "Regular", "normal", "normal"),
+    ("Size4", "Regular", "normal", "normal"),
+    ("Typewriter", "Regular", "normal", "normal"),
+];
+
+/// The width of `\nulldelimiterspace`.
+pub(crate) const NULL_DELIMITER_SPACE: &str = "0.12em";
+
+type Rule = (&'static str, &'static [(&'static str, &'static str)]);
+
+/// Rules of `.katex` before the sizing tables.
+const RULES: &[Rule] = &[
+    (
+        ".katex",
+        &[
+            ("font", "normal 1.21em KaTeX_Main, Times New Roman, serif"),
+            ("line-height", "1.2"),
+            ("text-indent", "0"),
+            ("text-rendering", "auto"),
+        ],
+    ),
+    (
+        ".katex *",
+        &[("-ms-high-contrast-adjust", "none !important"), ("border-color", "currentColor")],
+    ),
+    (
+        ".katex .katex-mathml",
+        &[
+            ("position", "absolute"),
+            ("clip", "rect(1px, 1px, 1px, 1px)"),
+            ("padding", "0"),
+            ("border", "0"),
+            ("height", "1px"),
+            ("width", "1px"),
+            ("overflow", "hidden"),
+        ],
+    ),
+    (".katex .katex-html > .newline", &[("display", "block")]),
+    (
+        ".katex .base",
+        &[
+            ("position", "relative"),
+            ("display", "inline-block"),
+            ("white-space", "nowrap"),
+            ("width", "min-content"),
+        ],
+    ),
+    (".katex .strut", &[("display", "inline-block")]),
+    (".katex .textbf", &[("font-weight", "bold")]),
+    (".katex .textit", &[("font-style", "italic")]),
+    (".katex .textrm", &[("font-family", "KaTeX_Main")]),
+    (".katex .textsf", &[("font-family", "KaTeX_SansSerif")]),
+    (".katex .texttt", &[("font-family", "KaTeX_Typewriter")]),
+    (".katex .mathnormal", &[("font-family", "KaTeX_Math"), ("font-style", "italic")]),
+    (".katex .mathit", &[("font-family", "KaTeX_Main"), ("font-style", "italic")]),
+    (".katex .mathrm", &[("font-style", "normal")]),
+    (".katex .mathbf", &[("font-family", "KaTeX_Main"), ("font-weight", "bold")]),
+    (
+        ".katex .boldsymbol",
+        &[("font-family", "KaTeX_Math"), ("font-weight", "bold"), ("font-style", "italic")],
+    ),
+    (".katex .amsrm", &[("font-family", "KaTeX_AMS")]),
+    (".katex .mathbb, .katex .textbb", &[("font-family", "KaTeX_AMS")]),
+    (".katex .mathcal", &[("font-family", "KaTeX_Caligraphic")]),
+    (".katex .mathfrak, .katex .textfrak", &[("font-family", "KaTeX_Fraktur")]),
+    (
+        ".katex .mathboldfrak, .katex .textboldfrak",
+        &[("font-family", "KaTeX_Fraktur"), ("font-weight", "bold")],
+    ),
+    (".katex .mathtt", &[("font-family", "KaTeX_Typewriter")]),
+    (".katex .mathscr, .katex .textscr", &[("font-family", "KaTeX_Script")]),
+    (".katex .mathsf, .katex .textsf", &[("font-family", "KaTeX_SansSerif")]),
+    (
+        ".katex .mathboldsf, .katex .textboldsf",
+        &[("font-family", "KaTeX_SansSerif"), ("font-weight", "bold")],
+    ),
+    (
+        ".katex .mathitsf, .katex .mathsfit, .katex .textitsf",
+        &[("font-family", "KaTeX_SansSerif"), ("font-style", "italic")],
+    ),
+    (".katex .mainrm", &[("font-family", "KaTeX_Main"), ("font-style", "normal")]),
+    (
+        ".katex .vlist-t",
+        &[("display", "inline-table"), ("table-layout", "fixed"), ("border-collapse", "collapse")],
+    ),
+    (".katex .vlist-r", &[("display", "table-row")]),
+    (
+        ".katex .vlist",
+        &[("display", "table-cell"), ("vertical-align", "bottom"), ("position", "relative")],
+    ),
+    (
+        ".katex .vlist > span",
+        &[("display", "block"), ("height", "0"), ("position", "relative")],
+    ),
+    (".katex .vlist > span > span", &[("display", "inline-block")]),
+    (".katex .vlist > span > .pstrut", &[("overflow", "hidden"), ("width", "0")]),
+    (".katex .vlist-t2", &[("margin-right", "-2px")]),
+    (
+        ".katex .vlist-s",
+        &[
+            ("display", "table-cell"),
+            ("vertical-align", "bottom"),
+            ("font-size", "1px"),
+            ("width", "2px"),
+            ("min-width", "2px"),
+        ],
+    ),
+    (
+        ".katex .vbox",
+        &[("display", "inline-flex"), ("flex-direction", "column"), ("align-items", "baseline")],
+    ),
+    (
+        ".katex .hbox",
+        &[("display", "inline-flex"), ("flex-direction", "row"), ("width", "100%")],
+    ),
+    (
+        ".katex .thinbox",
+        &[("display", "inline-flex"), ("flex-direction", "row"), ("width", "0"), ("max-width", "0")],
+    ),
+    (".katex .msupsub", &[("text-align", "left")]),
+    (".katex .mfrac > span > span", &[("text-align", "center")]),
+    (
+        ".katex .mfrac .frac-line",
+        &[("display", "inline-block"), ("width", "100%"), ("border-bottom-style", "solid")],
+    ),
+    (
+        ".katex .mfrac .frac-line, .katex .overline .overline-line, .katex .underline .underline-line, \
+         .katex .hline, .katex .hdashline, .katex .rule",
+        &[("min-height", "1px")],
+    ),
+    (".katex .mspace", &[("display", "inline-block")]),
+    (".katex .llap, .katex .rlap, .katex .clap", &[("width", "0"), ("position", "relative")]),
+    (
+        ".katex .llap > .inner, .katex .rlap > .inner, .katex .clap > .inner",
+        &[("position", "absolute")],
+    ),
+    (
+        ".katex .llap > .fix, .katex .rlap > .fix, .katex .clap > .fix",
+        &[("display", "inline-block")],
+    ),
+    (".katex .llap > .inner", &[("right", "0")]),
+    (".katex .rlap > .inner, .katex .clap > .inner", &[("left", "0")]),
+    (
+        ".katex .clap > .inner > span",
+        &[("margin-left", "-50%"), ("margin-right", "50%")],
+    ),
+    (
+        ".katex .rule",
+        &[("display", "inline-block"), ("border", "solid 0"), ("position", "relative")],
+    ),
+    (
+        ".katex .overline .overline-line, .katex .underline .underline-line, .katex .hline",
+        &[("display", "inline-block"), ("width", "100%"), ("border-bottom-style", "solid")],
+    ),
+    (
+        ".katex .hdashline",
+        &[("display", "inline-block"), ("width", "100%"), ("border-bottom-style", "dashed")],
+    ),
+    (
+        ".katex .sqrt > .root",
+        &[("margin-left", "0.2777777778em"), ("margin-right", "-0.5555555556em")],
+    ),
+];
+
+/// Rules of `.katex` after the sizing tables and delimiter fonts.
+const RULES_AFTER_SIZING: &[Rule] = &[
+    (".katex .nulldelimiter", &[("display", "inline-block"), ("width", NULL_DELIMITER_SPACE)]),
+    (".katex .delimcenter", &[("position", "relative")]),
+    (".katex .op-symbol", &[("position", "relative")]),
+    (".katex .op-symbol.small-op", &[("font-family", "KaTeX_Size1")]),
+    (".katex .op-symbol.large-op", &[("font-family", "KaTeX_Size2")]),
+    (
+        ".katex .op-limits > .vlist-t, .katex .accent > .vlist-t",
+        &[("text-align", "center")],
+    ),
+    (".katex .accent .accent-body", &[("position", "relative")]),
+    (".katex .accent .accent-body:not(.accent-full)", &[("width", "0")]),
+    (".katex .overlay", &[("display", "block")]),
+    (
+        ".katex .mtable .vertical-separator",
+        &[("display", "inline-block"), ("min-width", "1px")],
+    ),
+    (".katex .mtable .arraycolsep", &[("display", "inline-block")]),
+    (".katex .mtable .col-align-c > .vlist-t", &[("text-align", "center")]),
+    (".katex .mtable .col-align-l > .vlist-t", &[("text-align", "left")]),
+    (".katex .mtable .col-align-r > .vlist-t", &[("text-align", "right")]),
+    (".katex .svg-align", &[("text-align", "left")]),
+    (
+        ".katex svg",
+        &[
+            ("display", "block"),
+            ("position", "absolute"),
+            ("width", "100%"),
+            ("height", "inherit"),
+            ("fill", "currentColor"),
+            ("stroke", "currentColor"),
+            ("fill-rule", "nonzero"),
+            ("fill-opacity", "1"),
+            ("stroke-width", "1"),
+            ("stroke-linecap", "butt"),
+            ("stroke-linejoin", "miter"),
+            ("stroke-miterlimit", "4"),
+            ("stroke-dasharray", "none"),
+            ("stroke-dashoffset", "0"),
+            ("stroke-opacity", "1"),
+        ],
+    ),
+    (".katex svg path", &[("stroke", "none")]),
+    (
+        ".katex img",
+        &[
+            ("border-style", "none"),
+            ("min-width", "0"),
+            ("min-height", "0"),
+            ("max-width", "none"),
+            ("max-height", "none"),
+        ],
+    ),
+    (
+        ".katex .stretchy",
+        &[("width", "100%"), ("display", "block"), ("position", "relative"), ("overflow", "hidden")],
+    ),
+    (".katex .stretchy::before, .katex .stretchy::after", &[("content", "\"\"")]),
+    (
+        ".katex .hide-tail",
+        &[("width", "100%"), ("position", "relative"), ("overflow", "hidden")],
+    ),
+    (
+        ".katex .halfarrow-left",
+        &[("position", "absolute"), ("left", "0"), ("width", "50.2%"), ("overflow", "hidden")],
+    ),
+    (
+        ".katex .halfarrow-right",
+        &[("position", "absolute"), ("right", "0"), ("width", "50.2%"), ("overflow", "hidden")],
+    ),
+    (
+        ".katex .brace-left",
+        &[("position", "absolute"), ("left", "0"), ("width", "25.1%"), ("overflow", "hidden")],
+    ),
+    (
+        ".katex .brace-center",
+        &[("position", "absolute"), ("left", "25%"), ("width", "50%"), ("overflow", "hidden")],
+    ),
+    (
+        ".katex .brace-right",
+        &[("position", "absolute"), ("right", "0"), ("width", "25.1%"), ("overflow", "hidden")],
+    ),
+    (".katex .x-arrow-pad", &[("padding", "0 0.5em")]),
+    (".katex .cd-arrow-pad", &[("padding", "0 0.55556em 0 0.27778em")]),
+    (".katex .x-arrow, .katex .mover, .katex .munder", &[("text-align", "center")]),
+    (".katex .boxpad", &[("padding", "0 0.3em 0 0.3em")]),
+    (
+        ".katex .fbox, .katex .fcolorbox",
+        &[("box-sizing", "border-box"), ("border", "0.04em solid")],
+    ),
+    (".katex .cancel-pad", &[("padding", "0 0.2em 0 0.2em")]),
+    (".katex .cancel-lap", &[("margin-left", "-0.2em"), ("margin-right", "-0.2em")]),
+    (
+        ".katex .sout",
+        &[("border-bottom-style", "solid"), ("border-bottom-width", "0.08em")],
+    ),
+    (
+        ".katex .angl",
+        &[
+            ("box-sizing", "border-box"),
+            ("border-top", "0.049em solid"),
+            ("border-right", "0.049em solid"),
+            ("margin-right", "0.03889em"),
+        ],
+    ),
+    (".katex .anglpad", &[("padding", "0 0.03889em 0 0.03889em")]),
+    (
+        ".katex .eqn-num::before",
+        &[("counter-increment", "katexEqnNo"), ("content", "\"(\" counter(katexEqnNo) \")\"")],
+    ),
+    (
+        ".katex .mml-eqn-num::before",
+        &[("counter-increment", "mmlEqnNo"), ("content", "\"(\" counter(mmlEqnNo) \")\"")],
+    ),
+    (".katex .mtr-glue", &[("width", "50%")]),
+    (".katex .cd-vert-arrow", &[("display", "inline-block"), ("position", "relative")]),
+    (
+        ".katex .cd-label-left",
+        &[
+            ("display", "inline-block"),
+            ("position", "absolute"),
+            ("right", "calc(50% + 0.3em)"),
+            ("text-align", "left"),
+        ],
+    ),
+    (
+        ".katex .cd-label-right",
+        &[
+            ("display", "inline-block"),
+            ("position", "absolute"),
+            ("left", "calc(50% + 0.3em)"),
+            ("text-align", "right"),
+        ],
+    ),
+    (
+        ".katex-display",
+        &[("display", "block"), ("margin", "1em 0"), ("text-align", "center")],
+    ),
+    (
+        ".katex-display > .katex",
+        &[("display", "block"), ("text-align", "center"), ("white-space", "nowrap")],
+    ),
+    (
+        ".katex-display > .katex > .katex-html",
+        &[("display", "block"), ("position", "relative")],
+    ),
+    (
+        ".katex-display > .katex > .katex-html > .tag",
+        &[("position", "absolute"), ("right", "0")],
+    ),
+    (
+        ".katex-display.leqno > .katex > .katex-html > .tag",
+        &[("left", "0"), ("right", "auto")],
+    ),
+    (
+        ".katex-display.fleqn > .katex",
+        &[("text-align", "left"), ("padding-left", "2em")],
+    ),
+    ("body", &[("counter-reset", "katexEqnNo mmlEqnNo")]),
+];
+
+fn push_rule(out: &mut String, selector: &str, declarations: &[(&str, &str)]) {
+    out.push_str(selector);
+    out.push_str(" {\n");
+    for (name, value) in declarations {
+        let _ = writeln!(out, "  {}: {};", name, value);
+    }
+    out.push_str("}\n");
+}
+
+/**
+ * The `font-size` of `.sizing.reset-size{reset}.size{size}`, sizes counting
+ * from 1 as in the class names.
+ */
+pub(crate) fn sizing_font_size(reset: usize, size: usize) -> String {
+    let ratio = SIZE_MULTIPLIERS[size - 1] / SIZE_MULTIPLIERS[reset - 1];
+    let rounded = format!("{:.8}", ratio);
+    format!("{}em", rounded.trim_end_matches('0').trim_end_matches('.'))
+}
+
+/**
+ * The rules of `katex.css` after the `@font-face`s, in order, as a selector
+ * and its declarations. `inlineStyles` applies them to the markup itself.
+ */
+pub(crate) fn style_rules() -> Vec<(String, Vec<(String, String)>)> {
+    fn owned(selector: &str, declarations: &[(&str, &str)]) -> (String, Vec<(String, String)>) {
+        let declarations = declarations.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect();
+        (selector.to_string(), declarations)
+    }
+
+    let mut rules: Vec<_> = RULES.iter().map(|(selector, declarations)| owned(selector, declarations)).collect();
+    // The sizing tables: `\scriptsize` inside `\Large` is `size3` inside `size7`.
+    for reset in 1..=SIZE_MULTIPLIERS.len() {
+        for size in 1..=SIZE_MULTIPLIERS.len() {
+            let selector = format!(
+                ".katex .sizing.reset-size{reset}.size{size}, .katex .fontsize-ensurer.reset-size{reset}.size{size}",
+                reset = reset,
+                size = size
+            );
+            rules.push(owned(&selector, &[("font-size", &sizing_font_size(reset, size))]));
+        }
+    }
+    for size in 1..=4 {
+        let family = format!("KaTeX_Size{}", size);
+        rules.push(owned(&format!(".katex .delimsizing.size{}", size), &[("font-family", &family)]));
+    }
+    rules.push(owned(".katex .delimsizing.mult .delim-size1 > span", &[("font-family", "KaTeX_Size1")]));
+    rules.push(owned(".katex .delimsizing.mult .delim-size4 > span", &[("font-family", "KaTeX_Size4")]));
+    rules.extend(RULES_AFTER_SIZING.iter().map(|(selector, declarations)| owned(selector, declarations)));
+    rules
+}
+
+/**
+ * Generates `katex.css` for the markup of this version. The fonts are
+ * loaded from `font_url_prefix` followed by the file name, e.g.
+ * `fonts/KaTeX_Main-Regular.woff2` for `fonts/` (what the KaTeX
+ * distribution uses); a `/` is added to a non-empty prefix without one.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen(js_name = katexCss))]
+pub fn katex_css(font_url_prefix: &str) -> String {
+    let mut prefix = font_url_prefix.to_string();
+    if !prefix.is_empty() && !prefix.ends_with('/') {
+        prefix.push('/');
+    }
+
+    let mut out = String::with_capacity(32 * 1024);
+    for (family, variant, weight, style) in FONT_FACES.iter() {
+        let file = format!("{}KaTeX_{}-{}", prefix, family, variant);
+        let src = format!(
+            "url({file}.woff2) format(\"woff2\"), url({file}.woff) format(\"woff\"), url({file}.ttf) format(\"truetype\")",
+            file = file
+        );
+        let family = format!("KaTeX_{}", family);
+        push_rule(
+            &mut out,
+            "@font-face",
+            &[("font-family", &family), ("src", &src), ("font-weight", weight), ("font-style", style)],
+        );
+    }
+    for (selector, declarations) in style_rules() {
+        let declarations: Vec<(&str, &str)> =
+            declarations.iter().map(|(name, value)| (name.as_str(), value.as_str())).collect();
+        push_rule(&mut out, &selector, &declarations);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stylesheet::katex_css;
+
+    #[test]
+    fn test_katex_css() {
+        let css = katex_css("fonts");
+        assert_eq!(css.matches("@font-face").count(), 20);
+        assert!(css.contains("src: url(fonts/KaTeX_Main-Regular.woff2) format(\"woff2\")"));
+        assert_eq!(css.matches(".katex .sizing.reset-size").count(), 121);
+        assert!(css.contains(
+            ".katex .sizing.reset-size2.size1, .katex .fontsize-ensurer.reset-size2.size1 {\n  font-size: 0.83333333em;\n}"
+        ));
+        assert!(css.contains(".katex .delimsizing.size4 {\n  font-family: KaTeX_Size4;\n}"));
+        assert!(css.contains(".katex .vlist > span > .pstrut {\n  overflow: hidden;\n  width: 0;\n}"));
+        assert!(katex_css("").contains("src: url(KaTeX_AMS-Regular.woff2)"));
+    }
+}
@@ -0,0 +1,169 @@
+use super::num;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use ttf_parser::{Face, OutlineBuilder};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+lazy_static! {
+    static ref FONTS: RwLock<HashMap<String, Arc<Vec<u8>>>> = RwLock::new(HashMap::new());
+    // Outlines already converted, by font and character; `None` if the
+    // font has no glyph for it.
+    static ref OUTLINES: RwLock<HashMap<(String, char), Option<Outline>>> = RwLock::new(HashMap::new());
+}
+
+/// Fonts tried, in order, for characters missing from the font asked for.
+const FALLBACK_FONTS: [&str; 2] = ["Main-Regular", "AMS-Regular"];
+
+/**
+ * A glyph outline in thousandths of an em with y pointing down, as in the
+ * SVG output, and its advance width in ems.
+ */
+#[derive(Clone, Debug)]
+pub(crate) struct Outline {
+    pub(crate) font: String,
+    pub(crate) path: String,
+    pub(crate) advance: f64,
+}
+
+struct PathBuilder {
+    path: String,
+    scale: f64,
+}
+
+impl PathBuilder {
+    fn point(&mut self, x: f32, y: f32) {
+        self.path.push_str(&num(x as f64 * self.scale));
+        self.path.push(' ');
+        self.path.push_str(&num(-y as f64 * self.scale));
+    }
+}
+
+impl OutlineBuilder for PathBuilder {
+    fn move_to(&mut self, x: f32, y: f32) {
+        self.path.push('M');
+        self.point(x, y);
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        self.path.push('L');
+        self.point(x, y);
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        self.path.push('Q');
+        self.point(x1, y1);
+        self.path.push(' ');
+        self.point(x, y);
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        self.path.push('C');
+        self.point(x1, y1);
+        self.path.push(' ');
+        self.point(x2, y2);
+        self.path.push(' ');
+        self.point(x, y);
+    }
+
+    fn close(&mut self) {
+        self.path.push('Z');
+    }
+}
+
+/** `KaTeX_Main-Regular.ttf` and `KaTeX_Main-Regular` are both `Main-Regular`. */
+fn font_name(name: &str) -> &str {
+    let name = name.strip_prefix("KaTeX_").unwrap_or(name);
+    name.strip_suffix(".ttf").unwrap_or(name)
+}
+
+/**
+ * Makes the glyphs of a KaTeX font available to `output: "svg"`. `name` is
+ * the font as in the metrics, e.g. `Main-Regular` or `KaTeX_Math-Italic`,
+ * and `data` the contents of its TrueType file. Without registered fonts the
+ * SVG output falls back to `<text>` elements naming the KaTeX fonts, which
+ * only render correctly where those fonts are installed, and reports each
+ * such glyph as a `MissingCharacter` diagnostic.
+ */
+pub fn register_svg_font(name: &str, data: Vec<u8>) -> Result<(), String> {
+    Face::parse(&data, 0).map_err(|error| format!("Invalid font {}: {}", name, error))?;
+    let name = font_name(name).to_string();
+    OUTLINES.write().unwrap().retain(|(font, _), _| *font != name);
+    FONTS.write().unwrap().insert(name, Arc::new(data));
+    Ok(())
+}
+
+/** `data` is the font file as a `Uint8Array`. */
+#[cfg(feature = "wasm")]
+#[wasm_bindgen(js_name = registerSvgFont)]
+pub fn register_svg_font_for_js(name: &str, data: &[u8]) -> Result<(), JsValue> {
+    register_svg_font(name, data.to_vec()).map_err(|error| js_sys::Error::new(&error).into())
+}
+
+/**
+ * Registers every `KaTeX_*.ttf` file in `dir`, such as the `fonts` directory
+ * of a KaTeX release, and returns how many were found.
+ */
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load_svg_font_dir(dir: &std::path::Path) -> Result<usize, String> {
+    let entries = std::fs::read_dir(dir).map_err(|error| format!("{}: {}", dir.display(), error))?;
+    let mut count = 0;
+    for entry in entries {
+        let path = entry.map_err(|error| format!("{}: {}", dir.display(), error))?.path();
+        let file_name = path.file_name().and_then(|name| name.to_str()).unwrap_or("");
+        if file_name.starts_with("KaTeX_") && file_name.ends_with(".ttf") {
+            let data = std::fs::read(&path).map_err(|error| format!("{}: {}", path.display(), error))?;
+            register_svg_font(file_name, data)?;
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+/** The names of the fonts registered with `register_svg_font`, sorted. */
+pub fn registered_svg_fonts() -> Vec<String> {
+    let mut names: Vec<String> = FONTS.read().unwrap().keys().cloned().collect();
+    names.sort();
+    names
+}
+
+fn load_outline(font: &str, ch: char) -> Option<Outline> {
+    let data = FONTS.read().unwrap().get(font)?.clone();
+    let face = Face::parse(&data, 0).ok()?;
+    let glyph = face.glyph_index(ch)?;
+    let units_per_em = face.units_per_em() as f64;
+    let mut builder = PathBuilder {
+        path: String::new(),
+        scale: 1000.0 / units_per_em,
+    };
+    // Spaces have an advance but no outline.
+    face.outline_glyph(glyph, &mut builder);
+    Some(Outline {
+        font: font.to_string(),
+        path: builder.path,
+        advance: face.glyph_hor_advance(glyph).unwrap_or(0) as f64 / units_per_em,
+    })
+}
+
+/**
+ * The outline of `ch` in `font`, or in the first fallback font that has
+ * it, if any of them is registered.
+ */
+pub(crate) fn outline(font: &str, ch: char) -> Option<Outline> {
+    let fonts = std::iter::once(font).chain(FALLBACK_FONTS.iter().copied().filter(|name| *name != font));
+    for font in fonts {
+        let key = (font.to_string(), ch);
+        if let Some(cached) = OUTLINES.read().unwrap().get(&key) {
+            match cached {
+                Some(outline) => return Some(outline.clone()),
+                None => continue,
+            }
+        }
+        let outline = load_outline(font, ch);
+        OUTLINES.write().unwrap().insert(key, outline.clone());
+        if outline.is_some() {
+            return outline;
+        }
+    }
+    None
+}
@@ -0,0 +1,759 @@
+use super::fonts;
+use crate::dom_tree::css_style::CssStyle;
+use crate::dom_tree::img::Img;
+use crate::dom_tree::svg_node::SvgNode;
+use crate::dom_tree::symbol_node::SymbolNode;
+use crate::metrics::get_character_metrics;
+use crate::tree::HtmlDomNode;
+use crate::types::Mode;
+
+// The font sizes of `.sizing.reset-sizeN.sizeM` in katex.css, relative to
+// the normal size 6.
+const SIZES: [f64; 11] = [0.5, 0.6, 0.7, 0.8, 0.9, 1.0, 1.2, 1.44, 1.728, 2.074, 2.488];
+
+// Elements whose katex.css width is 100%: they stretch to the width of the
+// vlist they are in.
+const FILL_CLASSES: [&str; 7] = [
+    "frac-line",
+    "overline-line",
+    "underline-line",
+    "hline",
+    "hdashline",
+    "hide-tail",
+    "stretchy",
+];
+
+/// Space between a formula and its `\tag` in display mode, in ems.
+const TAG_GAP: f64 = 1.0;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+/** The inherited CSS properties the layout depends on. */
+#[derive(Clone, Debug)]
+struct Context {
+    /// Font size in ems of the formula.
+    size: f64,
+    family: &'static str,
+    bold: bool,
+    italic: bool,
+    color: Option<String>,
+    align: Align,
+    /// The width an element with width:100% takes, once known.
+    fill_width: Option<f64>,
+}
+
+impl Context {
+    /** The font of the metrics and of the registered fonts, e.g. `Main-Bold`. */
+    fn font(&self) -> String {
+        let variant = match (self.family, self.bold, self.italic) {
+            ("Math", true, _) | ("Main", true, true) => "BoldItalic",
+            ("Math", false, _) => "Italic",
+            ("Main" | "SansSerif" | "Caligraphic" | "Fraktur", true, _) => "Bold",
+            ("Main" | "SansSerif", false, true) => "Italic",
+            _ => "Regular",
+        };
+        format!("{}-{}", self.family, variant)
+    }
+
+    /** The context inside an element with `classes` and `style`, as katex.css has it. */
+    fn enter(&self, classes: &[String], style: &CssStyle) -> Context {
+        let mut ctx = self.clone();
+        let has = |name: &str| classes.iter().any(|class| class == name);
+        let size_class = |prefix: &str| {
+            classes
+                .iter()
+                .filter_map(|class| class.strip_prefix(prefix)?.parse::<usize>().ok())
+                .find(|size| (1..=SIZES.len()).contains(size))
+        };
+        if has("sizing") || has("fontsize-ensurer") {
+            if let (Some(reset), Some(size)) = (size_class("reset-size"), size_class("size")) {
+                ctx.size *= SIZES[size - 1] / SIZES[reset - 1];
+            }
+        }
+        for class in classes {
+            match class.as_str() {
+                "textbf" => ctx.bold = true,
+                "textit" => ctx.italic = true,
+                "textrm" => ctx.family = "Main",
+                "textsf" | "mathsf" => ctx.family = "SansSerif",
+                "texttt" | "mathtt" => ctx.family = "Typewriter",
+                "mathnormal" => (ctx.family, ctx.italic) = ("Math", true),
+                "mathit" => (ctx.family, ctx.italic) = ("Main", true),
+                "mathrm" => ctx.italic = false,
+                "mathbf" => (ctx.family, ctx.bold) = ("Main", true),
+                "boldsymbol" => (ctx.family, ctx.bold, ctx.italic) = ("Math", true, true),
+                "amsrm" | "mathbb" | "textbb" => ctx.family = "AMS",
+                "mathcal" => ctx.family = "Caligraphic",
+                "mathfrak" | "textfrak" => ctx.family = "Fraktur",
+                "mathboldfrak" | "textboldfrak" => (ctx.family, ctx.bold) = ("Fraktur", true),
+                "mathscr" | "textscr" => ctx.family = "Script",
+                "mathboldsf" | "textboldsf" => (ctx.family, ctx.bold) = ("SansSerif", true),
+                "mathitsf" | "mathsfit" | "textitsf" => (ctx.family, ctx.italic) = ("SansSerif", true),
+                "mainrm" => (ctx.family, ctx.italic) = ("Main", false),
+                "delim-size1" => ctx.family = "Size1",
+                "delim-size4" => ctx.family = "Size4",
+                "small-op" if has("op-symbol") => ctx.family = "Size1",
+                "large-op" if has("op-symbol") => ctx.family = "Size2",
+                "mfrac" | "op-limits" | "accent" | "mover" | "munder" | "x-arrow" | "col-align-c" => {
+                    ctx.align = Align::Center
+                }
+                "col-align-l" | "msupsub" | "svg-align" | "cd-label-left" | "cd-label-right" => {
+                    ctx.align = Align::Left
+                }
+                "col-align-r" => ctx.align = Align::Right,
+                _ => {}
+            }
+        }
+        if has("delimsizing") {
+            if let Some(size) = size_class("size").filter(|size| *size <= 4) {
+                ctx.family = ["Size1", "Size2", "Size3", "Size4"][size - 1];
+            }
+        }
+        if let Some(color) = &style.color {
+            ctx.color = Some(color.clone());
+        }
+        ctx
+    }
+}
+
+/** Something drawn, positioned in ems of the formula with y pointing down. */
+#[derive(Clone, Debug)]
+pub(crate) enum Item {
+    Glyph {
+        x: f64,
+        y: f64,
+        size: f64,
+        font: String,
+        ch: char,
+        width: f64,
+        height: f64,
+        depth: f64,
+        color: Option<String>,
+    },
+    /// A filled rectangle, from a border or a background.
+    Rule {
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+        dashed: bool,
+        color: Option<String>,
+    },
+    /// An `SvgNode` scaled to its box, like the `<svg>` elements of the HTML output.
+    Svg {
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+        size: f64,
+        node: SvgNode,
+        color: Option<String>,
+    },
+    Image {
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+        src: String,
+    },
+}
+
+impl Item {
+    fn translate(&mut self, dx: f64, dy: f64) {
+        match self {
+            Item::Glyph { x, y, .. } | Item::Rule { x, y, .. } | Item::Svg { x, y, .. } | Item::Image { x, y, .. } => {
+                *x += dx;
+                *y += dy;
+            }
+        }
+    }
+
+    /** Left, top, right and bottom edges. */
+    pub(crate) fn bounds(&self) -> (f64, f64, f64, f64) {
+        match self {
+            Item::Glyph {
+                x,
+                y,
+                width,
+                height,
+                depth,
+                ..
+            } => (*x, y - height, x + width, y + depth),
+            Item::Rule {
+                x, y, width, height, ..
+            }
+            | Item::Svg {
+                x, y, width, height, ..
+            }
+            | Item::Image {
+                x, y, width, height, ..
+            } => (*x, *y, x + width, y + height),
+        }
+    }
+}
+
+/**
+ * A laid out element: its advance width including margins, its extent above
+ * and below the baseline, and what it draws relative to its left end on the
+ * baseline.
+ */
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Frag {
+    pub(crate) width: f64,
+    pub(crate) height: f64,
+    pub(crate) depth: f64,
+    /// Whether the width comes from the enclosing vlist (width:100%).
+    fills: bool,
+    pub(crate) items: Vec<Item>,
+}
+
+impl Frag {
+    fn translate(&mut self, dx: f64, dy: f64) {
+        for item in self.items.iter_mut() {
+            item.translate(dx, dy);
+        }
+        self.height -= dy;
+        self.depth += dy;
+    }
+
+    /** Draws `other` with its origin at `(x, y)`. */
+    fn place(&mut self, mut other: Frag, x: f64, y: f64) {
+        other.translate(x, y);
+        self.height = self.height.max(other.height);
+        self.depth = self.depth.max(other.depth);
+        self.items.append(&mut other.items);
+    }
+
+    /** Extends the frag to cover the box from `top` to `bottom`. */
+    fn cover(&mut self, top: f64, bottom: f64) {
+        self.height = self.height.max(-top);
+        self.depth = self.depth.max(bottom);
+    }
+}
+
+/** Parses a length in ems; KaTeX writes every length it computes that way. */
+fn em(value: &Option<String>) -> Option<f64> {
+    value.as_ref()?.trim().strip_suffix("em")?.parse().ok()
+}
+
+fn has_class(node: &dyn HtmlDomNode, name: &str) -> bool {
+    node.get_classes().iter().any(|class| class == name)
+}
+
+fn aligned(align: Align, available: f64, width: f64) -> f64 {
+    match align {
+        Align::Left => 0.0,
+        Align::Center => (available - width) / 2.0,
+        Align::Right => available - width,
+    }
+}
+
+/** Width, height and depth of `ch` in `font`, in ems at size 1. */
+fn char_box(font: &str, ch: char, symbol: &SymbolNode) -> (f64, f64, f64) {
+    let family_regular = format!("{}-Regular", font.split('-').next().unwrap_or("Main"));
+    let text = ch.to_string();
+    for font in [font, family_regular.as_str(), "Main-Regular"] {
+        if let Some(metrics) = get_character_metrics(&text, font, Mode::math) {
+            return (metrics.width, metrics.height, metrics.depth);
+        }
+    }
+    let width = match fonts::outline(font, ch) {
+        Some(outline) => outline.advance,
+        // Wide characters of scripts KaTeX has no font for.
+        None if ch >= '\u{2e80}' => 1.0,
+        None => 0.5,
+    };
+    (width, symbol.height, symbol.depth)
+}
+
+/**
+ * Lays out the box tree of the HTML output the way katex.css does, with the
+ * positions of vlist children from their `top` and the widths of glyphs
+ * from the font metrics.
+ */
+#[derive(Default)]
+pub(crate) struct Layout {
+    equation_number: usize,
+}
+
+impl Layout {
+    /** Lays out the tree built for one formula, normally a `katex` span. */
+    pub(crate) fn layout(&mut self, tree: &dyn HtmlDomNode) -> Frag {
+        let ctx = Context {
+            size: 1.0,
+            family: "Main",
+            bold: false,
+            italic: false,
+            color: None,
+            align: Align::Left,
+            fill_width: None,
+        };
+        self.root(tree, &ctx, false, false)
+    }
+
+    fn root(&mut self, node: &dyn HtmlDomNode, ctx: &Context, leqno: bool, fleqn: bool) -> Frag {
+        if node.as_any().is::<SymbolNode>() || node.as_any().is::<SvgNode>() {
+            return self.node(node, ctx);
+        }
+        let ctx = ctx.enter(node.get_classes(), node.get_style());
+        if has_class(node, "katex-html") {
+            return self.lines(node, &ctx, leqno, fleqn);
+        }
+        if has_class(node, "katex-display") {
+            let mut display_ctx = ctx.clone();
+            let fleqn = has_class(node, "fleqn");
+            display_ctx.align = if fleqn { Align::Left } else { Align::Center };
+            if let Some(child) = node.get_children().and_then(|children| children.first()) {
+                return self.root(&**child, &display_ctx, has_class(node, "leqno"), fleqn);
+            }
+        }
+        if has_class(node, "katex") {
+            for child in node.get_children().into_iter().flatten() {
+                if has_class(&**child, "katex-html") {
+                    return self.root(&**child, &ctx, leqno, fleqn);
+                }
+            }
+        }
+        self.node(node, &ctx)
+    }
+
+    /** The `katex-html` span: lines broken by `\\` and `\newline`, then a tag. */
+    fn lines(&mut self, node: &dyn HtmlDomNode, ctx: &Context, leqno: bool, fleqn: bool) -> Frag {
+        let mut lines = vec![(Frag::default(), 0.0)];
+        let mut tags = vec![];
+        for child in node.get_children().into_iter().flatten() {
+            if !child.as_any().is::<SvgNode>() && has_class(&**child, "newline") {
+                let margin = em(&child.get_style().margin_top).unwrap_or(0.0) * ctx.size;
+                lines.push((Frag::default(), margin));
+            } else if !child.as_any().is::<SvgNode>() && has_class(&**child, "tag") {
+                tags.push(self.node(&**child, ctx));
+            } else {
+                let frag = self.node(&**child, ctx);
+                let line = &mut lines.last_mut().unwrap().0;
+                let x = line.width;
+                line.width += frag.width;
+                line.place(frag, x, 0.0);
+            }
+        }
+
+        let width = lines.iter().map(|(line, _)| line.width).fold(0.0, f64::max);
+        let mut res = Frag::default();
+        let mut y = 0.0;
+        let mut previous_depth = None;
+        for (line, margin) in lines {
+            if let Some(depth) = previous_depth {
+                y += depth + margin + line.height;
+            }
+            previous_depth = Some(line.depth);
+            let x = aligned(if fleqn { Align::Left } else { ctx.align }, width, line.width);
+            res.place(line, x, y);
+        }
+        res.width = width;
+
+        for tag in tags {
+            let tag_width = tag.width;
+            if leqno {
+                res.translate(tag_width + TAG_GAP, 0.0);
+                res.place(tag, 0.0, 0.0);
+            } else {
+                res.place(tag, res.width + TAG_GAP, 0.0);
+            }
+            res.width += TAG_GAP + tag_width;
+        }
+        res
+    }
+
+    fn node(&mut self, node: &dyn HtmlDomNode, ctx: &Context) -> Frag {
+        let any = node.as_any();
+        if let Some(symbol) = any.downcast_ref::<SymbolNode>() {
+            return self.symbol(symbol, ctx);
+        }
+        if let Some(svg) = any.downcast_ref::<SvgNode>() {
+            return self.bare_svg(svg, ctx);
+        }
+        if let Some(img) = any.downcast_ref::<Img>() {
+            return self.image(img, ctx);
+        }
+        if has_class(node, "vlist-t") {
+            return self.vlist(node, ctx);
+        }
+        if has_class(node, "llap") || has_class(node, "rlap") || has_class(node, "clap") {
+            return self.lap(node, ctx);
+        }
+        if has_class(node, "eqn-num") {
+            // Numbered by a CSS counter in the HTML output.
+            self.equation_number += 1;
+            let number = SymbolNode::new(format!("({})", self.equation_number));
+            return self.symbol(&number, &ctx.enter(node.get_classes(), node.get_style()));
+        }
+        self.span(node, ctx)
+    }
+
+    fn symbol(&mut self, symbol: &SymbolNode, ctx: &Context) -> Frag {
+        let ctx = ctx.enter(symbol.get_classes(), symbol.get_style());
+        let style = symbol.get_style();
+        let font = ctx.font();
+        let mut frag = Frag::default();
+        let mut x = em(&style.margin_left).unwrap_or(0.0) * ctx.size;
+        for ch in symbol.text.chars().filter(|ch| *ch != '\u{200b}') {
+            let (width, height, depth) = char_box(&font, ch, symbol);
+            let glyph = Item::Glyph {
+                x,
+                y: 0.0,
+                size: ctx.size,
+                font: font.clone(),
+                ch,
+                width: width * ctx.size,
+                height: height * ctx.size,
+                depth: depth * ctx.size,
+                color: ctx.color.clone(),
+            };
+            frag.cover(-height * ctx.size, depth * ctx.size);
+            frag.items.push(glyph);
+            x += width * ctx.size;
+        }
+        // Italic correction is a right margin, unless the style sets one.
+        let italic = if symbol.italic > 0.0 { symbol.italic } else { 0.0 };
+        frag.width = x + em(&style.margin_right).unwrap_or(italic) * ctx.size;
+        self.shadow(&mut frag, style, &ctx);
+        let (dx, dy) = self.offset(style, &ctx);
+        frag.translate(dx, dy);
+        frag
+    }
+
+    /** `\pmb` draws everything a second time, offset like its text-shadow. */
+    fn shadow(&self, frag: &mut Frag, style: &CssStyle, ctx: &Context) {
+        if let Some(shadow) = &style.text_shadow {
+            let mut lengths = shadow.split_whitespace().map(|length| em(&Some(length.to_string())));
+            if let (Some(Some(dx)), Some(Some(dy))) = (lengths.next(), lengths.next()) {
+                let mut copies = frag.items.clone();
+                for item in copies.iter_mut() {
+                    item.translate(dx * ctx.size, dy * ctx.size);
+                }
+                frag.items.append(&mut copies);
+            }
+        }
+    }
+
+    /** Shifts of a relatively positioned element. */
+    fn offset(&self, style: &CssStyle, ctx: &Context) -> (f64, f64) {
+        let dx = em(&style.left).unwrap_or(0.0);
+        let dy = em(&style.top).unwrap_or(0.0) - em(&style.bottom).unwrap_or(0.0);
+        (dx * ctx.size, dy * ctx.size)
+    }
+
+    fn image(&mut self, img: &Img, ctx: &Context) -> Frag {
+        let style = img.get_style();
+        let width = em(&style.width).unwrap_or(0.0) * ctx.size;
+        let height = em(&style.height).unwrap_or(0.0) * ctx.size;
+        let bottom = -em(&style.vertical_align).unwrap_or(0.0) * ctx.size;
+        let mut frag = Frag {
+            width,
+            ..Frag::default()
+        };
+        frag.cover(bottom - height, bottom);
+        frag.items.push(Item::Image {
+            x: 0.0,
+            y: bottom - height,
+            width,
+            height,
+            src: img.get_src().clone(),
+        });
+        frag
+    }
+
+    /** An `<svg>` outside of a sized span, at the size of its attributes. */
+    fn bare_svg(&mut self, svg: &SvgNode, ctx: &Context) -> Frag {
+        let width = em(&svg.get_attribute("width").cloned()).unwrap_or(0.0) * ctx.size;
+        let height = em(&svg.get_attribute("height").cloned()).unwrap_or(0.0) * ctx.size;
+        let mut frag = Frag {
+            width,
+            ..Frag::default()
+        };
+        frag.cover(-height, 0.0);
+        frag.items.push(self.svg_item(svg, 0.0, -height, width, height, ctx));
+        frag
+    }
+
+    fn svg_item(&self, svg: &SvgNode, x: f64, y: f64, width: f64, height: f64, ctx: &Context) -> Item {
+        Item::Svg {
+            x,
+            y,
+            width,
+            height,
+            size: ctx.size,
+            node: svg.clone(),
+            color: ctx.color.clone(),
+        }
+    }
+
+    /**
+     * A vlist-t table: each child of its first row sits with its baseline
+     * `top` plus the height of its pstrut below the baseline of the list.
+     */
+    fn vlist(&mut self, node: &dyn HtmlDomNode, ctx: &Context) -> Frag {
+        let mut ctx = ctx.enter(node.get_classes(), node.get_style());
+        ctx.fill_width = None;
+        let wrappers = node
+            .get_children()
+            .and_then(|rows| rows.first())
+            .and_then(|row| row.get_children())
+            .and_then(|cells| cells.first())
+            .and_then(|cell| cell.get_children());
+
+        let mut entries = vec![];
+        for wrapper in wrappers.into_iter().flatten() {
+            let style = wrapper.get_style();
+            let wrapper_ctx = ctx.enter(wrapper.get_classes(), style);
+            let size = wrapper_ctx.size;
+            let margin_left = em(&style.margin_left).unwrap_or(0.0) * size;
+            let margin_right = em(&style.margin_right).unwrap_or(0.0) * size;
+            let mut y = em(&style.top).unwrap_or(0.0) * size;
+            let mut elems = vec![];
+            for child in wrapper.get_children().into_iter().flatten() {
+                if !child.as_any().is::<SvgNode>() && has_class(&**child, "pstrut") {
+                    y += em(&child.get_style().height).unwrap_or(0.0) * size;
+                } else {
+                    elems.push(child);
+                }
+            }
+            for elem in elems {
+                let frag = self.node(&**elem, &wrapper_ctx);
+                entries.push((elem, wrapper_ctx.clone(), margin_left, margin_right, y, frag));
+            }
+        }
+
+        let width = entries
+            .iter()
+            .map(|(_, _, margin_left, margin_right, _, frag)| margin_left + frag.width + margin_right)
+            .fold(0.0, f64::max);
+        let mut res = Frag {
+            width,
+            ..Frag::default()
+        };
+        for (elem, mut elem_ctx, margin_left, margin_right, y, mut frag) in entries {
+            let available = width - margin_left - margin_right;
+            let x = if frag.fills {
+                elem_ctx.fill_width = Some(available);
+                frag = self.node(&**elem, &elem_ctx);
+                margin_left
+            } else {
+                margin_left + aligned(elem_ctx.align, available, frag.width)
+            };
+            res.place(frag, x, y);
+        }
+        res
+    }
+
+    /** `\mathllap`, `\mathrlap` and `\mathclap` take no width. */
+    fn lap(&mut self, node: &dyn HtmlDomNode, ctx: &Context) -> Frag {
+        let ctx = ctx.enter(node.get_classes(), node.get_style());
+        let mut res = Frag::default();
+        for child in node.get_children().into_iter().flatten() {
+            let frag = self.node(&**child, &ctx);
+            let x = if !has_class(&**child, "inner") || has_class(node, "rlap") {
+                0.0
+            } else if has_class(node, "llap") {
+                -frag.width
+            } else {
+                -frag.width / 2.0
+            };
+            res.place(frag, x, 0.0);
+        }
+        res
+    }
+
+    fn span(&mut self, node: &dyn HtmlDomNode, ctx: &Context) -> Frag {
+        let classes = node.get_classes();
+        let has = |name: &str| classes.iter().any(|class| class == name);
+        let style = node.get_style();
+        let mut ctx = ctx.enter(classes, style);
+        let size = ctx.size;
+
+        let (mut margin_left, mut margin_right, mut padding_left, mut padding_right) = (0.0, 0.0, 0.0, 0.0);
+        let mut fixed_width = None;
+        for class in classes {
+            match class.as_str() {
+                "root" => (margin_left, margin_right) = (0.27777778, -0.55555556),
+                "cancel-lap" => (margin_left, margin_right) = (-0.2, -0.2),
+                "angl" => margin_right = 0.03889,
+                "x-arrow-pad" => (padding_left, padding_right) = (0.5, 0.5),
+                "cd-arrow-pad" => (padding_left, padding_right) = (0.27778, 0.55556),
+                "boxpad" => (padding_left, padding_right) = (0.3, 0.3),
+                "cancel-pad" => (padding_left, padding_right) = (0.2, 0.2),
+                "anglpad" => (padding_left, padding_right) = (0.03889, 0.03889),
+                "nulldelimiter" => fixed_width = Some(0.12),
+                "thinbox" | "pstrut" | "vlist-s" => fixed_width = Some(0.0),
+                "accent-body" if !has("accent-full") => fixed_width = Some(0.0),
+                _ => {}
+            }
+        }
+        let margin_left = em(&style.margin_left).unwrap_or(margin_left) * size;
+        let margin_right = em(&style.margin_right).unwrap_or(margin_right) * size;
+        let padding_left = em(&style.padding_left).unwrap_or(padding_left) * size;
+        let padding_right = padding_right * size;
+        let fixed_width = em(&style.width).or(fixed_width).map(|width| width * size);
+        let min_width = em(&style.min_width).unwrap_or(0.0) * size;
+        let style_height = em(&style.height).map(|height| height * size);
+        let vertical_align = em(&style.vertical_align).unwrap_or(0.0) * size;
+
+        // Borders, top, right, bottom and left.
+        let border = |value: &Option<String>| em(value).unwrap_or(0.0) * size;
+        let mut borders = (0.0, 0.0, 0.0, 0.0);
+        let mut dashed = false;
+        if has("frac-line") || has("overline-line") || has("underline-line") || has("hline") || has("hdashline") {
+            borders.2 = border(&style.border_bottom_width);
+            dashed = has("hdashline");
+        } else if has("rule") {
+            borders = (border(&style.border_top_width), border(&style.border_right_width), 0.0, 0.0);
+        } else if has("fbox") || has("fcolorbox") {
+            let width = em(&style.border_width).unwrap_or(0.04) * size;
+            borders = (width, width, width, width);
+        } else if has("angl") {
+            let width = |value: &Option<String>| em(value).unwrap_or(0.049) * size;
+            borders = (width(&style.border_top_width), width(&style.border_right_width), 0.0, 0.0);
+        } else if has("sout") {
+            borders.2 = 0.08 * size;
+        } else if style.border_right_width.is_some() {
+            // The vertical separators of arrays.
+            borders.1 = border(&style.border_right_width);
+            dashed = style.border_right_style.as_deref() == Some("dashed");
+        }
+
+        let may_fill = has_fill_class(classes) || fixed_width.is_none();
+        if !may_fill {
+            ctx.fill_width = None;
+        }
+
+        // Lay out the children in a row; <svg> elements and the parts of
+        // stretchy arrows and braces are drawn over the box afterwards.
+        let mut content = Frag::default();
+        let mut overlays = vec![];
+        let mut all_fill = true;
+        for child in node.get_children().into_iter().flatten() {
+            let is_overlay = child.as_any().is::<SvgNode>()
+                || ["halfarrow-left", "halfarrow-right", "brace-left", "brace-center", "brace-right"]
+                    .iter()
+                    .any(|class| has_class(&**child, class));
+            if is_overlay {
+                overlays.push(child);
+                continue;
+            }
+            let frag = self.node(&**child, &ctx);
+            all_fill &= frag.fills;
+            let x = content.width;
+            content.width += frag.width;
+            content.place(frag, x, 0.0);
+        }
+        let in_flow = !content.items.is_empty() || content.width != 0.0;
+        let svg_fills = overlays.iter().any(|child| match child.as_any().downcast_ref::<SvgNode>() {
+            Some(svg) => !svg.get_attribute("style").map_or(false, |style| style.starts_with("width")),
+            None => true,
+        });
+        let fills = has_fill_class(classes)
+            || (fixed_width.is_none() && if in_flow { all_fill } else { svg_fills });
+
+        let natural_width = content.width + padding_left + padding_right + borders.1 + borders.3;
+        let width = match (fixed_width, ctx.fill_width) {
+            (Some(width), _) => width,
+            (None, Some(fill_width)) if fills => fill_width,
+            _ => natural_width,
+        }
+        .max(min_width);
+
+        // The border box; an inline-block without content sits on the baseline.
+        let (top, bottom) = if in_flow {
+            (-content.height, content.depth)
+        } else {
+            let height = style_height.unwrap_or(borders.0 + borders.2);
+            (-height - vertical_align, -vertical_align)
+        };
+
+        let mut res = Frag {
+            width: margin_left + width + margin_right,
+            fills,
+            ..Frag::default()
+        };
+        res.cover(top, bottom);
+        if let Some(background) = &style.background_color {
+            res.items.push(Item::Rule {
+                x: margin_left,
+                y: top,
+                width,
+                height: bottom - top,
+                dashed: false,
+                color: Some(background.clone()),
+            });
+        }
+        let border_color = style.border_color.clone().or(ctx.color.clone());
+        let (border_top, border_right, border_bottom, border_left) = borders;
+        for (x, y, rule_width, rule_height) in [
+            (margin_left, top, width, border_top),
+            (margin_left + width - border_right, top, border_right, bottom - top),
+            (margin_left, bottom - border_bottom, width, border_bottom),
+            (margin_left, top, border_left, bottom - top),
+        ] {
+            if rule_width > 0.0 && rule_height > 0.0 {
+                res.items.push(Item::Rule {
+                    x,
+                    y,
+                    width: rule_width,
+                    height: rule_height,
+                    dashed,
+                    color: border_color.clone(),
+                });
+            }
+        }
+
+        res.place(content, margin_left + border_left + padding_left, 0.0);
+
+        for overlay in overlays {
+            if let Some(svg) = overlay.as_any().downcast_ref::<SvgNode>() {
+                // width:100% and height:inherit, unless set on the <svg>.
+                let svg_width = svg
+                    .get_attribute("style")
+                    .and_then(|style| em(&Some(style.trim_start_matches("width:").to_string())))
+                    .map_or(width, |svg_width| svg_width * size);
+                let svg_height = style_height
+                    .or(em(&svg.get_attribute("height").cloned()).map(|height| height * size))
+                    .unwrap_or(bottom - top);
+                res.items.push(self.svg_item(svg, margin_left, top, svg_width, svg_height, &ctx));
+                res.cover(top, top + svg_height);
+            } else {
+                let part_ctx = ctx.enter(overlay.get_classes(), overlay.get_style());
+                let (x, part_width) = if has_class(&**overlay, "halfarrow-left") {
+                    (0.0, 0.502 * width)
+                } else if has_class(&**overlay, "halfarrow-right") {
+                    (0.498 * width, 0.502 * width)
+                } else if has_class(&**overlay, "brace-left") {
+                    (0.0, 0.251 * width)
+                } else if has_class(&**overlay, "brace-center") {
+                    (0.25 * width, 0.5 * width)
+                } else {
+                    (0.749 * width, 0.251 * width)
+                };
+                let part_height = em(&overlay.get_style().height).map_or(bottom - top, |height| height * size);
+                for svg in overlay.get_children().into_iter().flatten() {
+                    if let Some(svg) = svg.as_any().downcast_ref::<SvgNode>() {
+                        res.items.push(self.svg_item(svg, margin_left + x, top, part_width, part_height, &part_ctx));
+                    }
+                }
+                res.cover(top, top + part_height);
+            }
+        }
+
+        self.shadow(&mut res, style, &ctx);
+        let (dx, dy) = self.offset(style, &ctx);
+        res.translate(dx, dy - if in_flow { vertical_align } else { 0.0 });
+        res
+    }
+}
+
+fn has_fill_class(classes: &[String]) -> bool {
+    classes.iter().any(|class| FILL_CLASSES.contains(&class.as_str()))
+}
@@ -0,0 +1,269 @@
+//! The `output: "svg"` backend: a standalone SVG document for places that
+//! cannot load `katex.css` or the KaTeX web fonts.
+
+mod fonts;
+mod layout;
+#[cfg(feature = "png")]
+mod raster;
+
+use crate::tree::HtmlDomNode;
+use crate::units::make_em;
+use crate::utils::escape_to;
+use crate::settings::{DiagnosticKind, Settings};
+use indexmap::{IndexMap, IndexSet};
+use layout::{Frag, Item, Layout};
+use std::fmt::Write;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use fonts::load_svg_font_dir;
+pub use fonts::{register_svg_font, registered_svg_fonts};
+#[cfg(feature = "png")]
+pub(crate) use raster::to_png;
+#[cfg(feature = "png")]
+pub use raster::PngOptions;
+
+/** Formats a coordinate in thousandths of an em, the unit of the document. */
+pub(crate) fn num(value: f64) -> String {
+    let rounded = (value * 10.0).round() / 10.0;
+    if rounded == 0.0 {
+        "0".to_string()
+    } else {
+        rounded.to_string()
+    }
+}
+
+fn push_attribute(out: &mut String, name: &str, value: &str) {
+    out.push(' ');
+    out.push_str(name);
+    out.push_str("=\"");
+    escape_to(out, value);
+    out.push('"');
+}
+
+fn push_color(out: &mut String, name: &str, color: &Option<String>) {
+    if let Some(color) = color {
+        push_attribute(out, name, color);
+    }
+}
+
+/**
+ * Left, top, right and bottom edges of a laid out formula: its box and
+ * whatever is drawn outside of it.
+ */
+pub(crate) fn bounds(frag: &Frag) -> (f64, f64, f64, f64) {
+    let (mut left, mut top, mut right, mut bottom) = (0.0f64, -frag.height, frag.width, frag.depth);
+    for item in frag.items.iter() {
+        let (item_left, item_top, item_right, item_bottom) = item.bounds();
+        left = left.min(item_left);
+        top = top.min(item_top);
+        right = right.max(item_right);
+        bottom = bottom.max(item_bottom);
+    }
+    (left, top, right, bottom)
+}
+
+/**
+ * Renders the HTML box tree of a formula as one SVG document, in ems so
+ * that it scales with the surrounding text: glyphs are outlines of the
+ * fonts given to `register_svg_font`, and the `<svg>` images of the HTML
+ * output are inlined. A glyph with no registered font is written as
+ * `<text>` in the KaTeX web fonts and reported to `settings` as a
+ * `MissingCharacter` diagnostic, once per character and font. `title`
+ * becomes the accessible name, usually the LaTeX source.
+ */
+pub(crate) fn to_svg(tree: &dyn HtmlDomNode, title: Option<&str>, settings: &Settings) -> String {
+    let frag = Layout::default().layout(tree);
+    let (left, top, right, bottom) = bounds(&frag);
+
+    let mut defs: IndexMap<String, String> = IndexMap::new();
+    let mut missing: IndexSet<(String, char)> = IndexSet::new();
+    let mut body = String::new();
+    for item in frag.items.iter() {
+        push_item(&mut body, &mut defs, &mut missing, item);
+    }
+    for (font, ch) in missing {
+        settings.report_diagnostic(DiagnosticKind::MissingCharacter, format!("{} in font {}", ch, font), None);
+    }
+
+    let mut markup = String::with_capacity(body.len() + 256);
+    markup.push_str("<svg xmlns=\"http://www.w3.org/2000/svg\" xmlns:xlink=\"http://www.w3.org/1999/xlink\"");
+    push_attribute(&mut markup, "width", &make_em(right - left));
+    push_attribute(&mut markup, "height", &make_em(bottom - top));
+    push_attribute(
+        &mut markup,
+        "viewBox",
+        &format!(
+            "{} {} {} {}",
+            num(left * 1000.0),
+            num(top * 1000.0),
+            num((right - left) * 1000.0),
+            num((bottom - top) * 1000.0)
+        ),
+    );
+    // Puts the baseline on the baseline of the surrounding text.
+    push_attribute(&mut markup, "style", &format!("vertical-align:{}", make_em(-bottom)));
+    markup.push_str(" role=\"img\">");
+    if let Some(title) = title {
+        markup.push_str("<title>");
+        escape_to(&mut markup, title);
+        markup.push_str("</title>");
+    }
+    if !defs.is_empty() {
+        markup.push_str("<defs>");
+        for (id, path) in defs.iter() {
+            let _ = write!(markup, "<path id=\"{}\" d=\"{}\"/>", id, path);
+        }
+        markup.push_str("</defs>");
+    }
+    markup.push_str("<g fill=\"currentColor\">");
+    markup.push_str(&body);
+    markup.push_str("</g></svg>");
+    markup
+}
+
+fn push_item(out: &mut String, defs: &mut IndexMap<String, String>, missing: &mut IndexSet<(String, char)>, item: &Item) {
+    match item {
+        Item::Glyph {
+            x,
+            y,
+            size,
+            font,
+            ch,
+            color,
+            ..
+        } => {
+            let scale = if *size == 1.0 {
+                String::new()
+            } else {
+                format!(" scale({})", (size * 10000.0).round() / 10000.0)
+            };
+            match fonts::outline(font, *ch) {
+                Some(outline) if outline.path.is_empty() => {}
+                Some(outline) => {
+                    let id = format!("KaTeX_{}-{:x}", outline.font, *ch as u32);
+                    defs.entry(id.clone()).or_insert(outline.path);
+                    let _ = write!(
+                        out,
+                        "<use xlink:href=\"#{}\" transform=\"translate({} {}){}\"",
+                        id,
+                        num(x * 1000.0),
+                        num(y * 1000.0),
+                        scale
+                    );
+                    push_color(out, "fill", color);
+                    out.push_str("/>");
+                }
+                None if ch.is_whitespace() => {}
+                None => {
+                    // Without outlines, name the font as katex.css does.
+                    missing.insert((font.to_string(), *ch));
+                    let (family, variant) = font.split_once('-').unwrap_or((font, "Regular"));
+                    out.push_str("<text");
+                    push_attribute(out, "x", &num(x * 1000.0));
+                    push_attribute(out, "y", &num(y * 1000.0));
+                    push_attribute(out, "font-family", &format!("KaTeX_{}, serif", family));
+                    push_attribute(out, "font-size", &num(size * 1000.0));
+                    if variant.contains("Bold") {
+                        push_attribute(out, "font-weight", "bold");
+                    }
+                    if variant.contains("Italic") {
+                        push_attribute(out, "font-style", "italic");
+                    }
+                    push_color(out, "fill", color);
+                    out.push('>');
+                    escape_to(out, &ch.to_string());
+                    out.push_str("</text>");
+                }
+            }
+        }
+        Item::Rule {
+            x,
+            y,
+            width,
+            height,
+            dashed,
+            color,
+        } => {
+            if *dashed {
+                // Dashes three times as long as the border is thick, as browsers draw them.
+                let (x1, y1, x2, y2, thickness) = if width >= height {
+                    (*x, y + height / 2.0, x + width, y + height / 2.0, *height)
+                } else {
+                    (x + width / 2.0, *y, x + width / 2.0, y + height, *width)
+                };
+                let _ = write!(
+                    out,
+                    "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke-width=\"{}\" stroke-dasharray=\"{}\"",
+                    num(x1 * 1000.0),
+                    num(y1 * 1000.0),
+                    num(x2 * 1000.0),
+                    num(y2 * 1000.0),
+                    num(thickness * 1000.0),
+                    num(thickness * 3000.0)
+                );
+                out.push_str(" stroke=\"");
+                escape_to(out, color.as_deref().unwrap_or("currentColor"));
+                out.push_str("\"/>");
+            } else {
+                let _ = write!(
+                    out,
+                    "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"",
+                    num(x * 1000.0),
+                    num(y * 1000.0),
+                    num(width * 1000.0),
+                    num(height * 1000.0)
+                );
+                push_color(out, "fill", color);
+                out.push_str("/>");
+            }
+        }
+        Item::Svg {
+            x,
+            y,
+            width,
+            height,
+            size,
+            node,
+            color,
+        } => {
+            out.push_str("<svg");
+            push_attribute(out, "x", &num(x * 1000.0));
+            push_attribute(out, "y", &num(y * 1000.0));
+            push_attribute(out, "width", &num(width * 1000.0));
+            push_attribute(out, "height", &num(height * 1000.0));
+            for name in ["viewBox", "preserveAspectRatio"] {
+                if let Some(value) = node.get_attribute(name) {
+                    push_attribute(out, name, value);
+                }
+            }
+            // Lengths such as the stroke-width of \cancel are in ems.
+            push_attribute(out, "font-size", &num(size * 1000.0));
+            push_attribute(out, "stroke", color.as_deref().unwrap_or("currentColor"));
+            push_attribute(out, "stroke-width", "0");
+            push_color(out, "fill", color);
+            out.push('>');
+            for child in node.svg_children() {
+                out.push_str(&child.to_markup());
+            }
+            out.push_str("</svg>");
+        }
+        Item::Image {
+            x,
+            y,
+            width,
+            height,
+            src,
+        } => {
+            let _ = write!(
+                out,
+                "<image x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" preserveAspectRatio=\"none\"",
+                num(x * 1000.0),
+                num(y * 1000.0),
+                num(width * 1000.0),
+                num(height * 1000.0)
+            );
+            push_attribute(out, "xlink:href", src);
+            out.push_str("/>");
+        }
+    }
+}
@@ -0,0 +1,323 @@
+use super::fonts;
+use super::layout::{Item, Layout};
+use crate::dom_tree::line_node::LineNode;
+use crate::dom_tree::path_node::PathNode;
+use crate::dom_tree::svg_node::SvgNode;
+use crate::tree::HtmlDomNode;
+use svgtypes::{Align, AspectRatio, SimplePathSegment, SimplifyingPathParser, ViewBox};
+use tiny_skia::{Color, FillRule, Mask, Paint, Path, PathBuilder, Pixmap, Rect, Stroke, StrokeDash, Transform};
+
+/// `.katex { font-size: 1.21em }` in katex.css.
+const KATEX_SCALE: f64 = 1.21;
+/// Formulas needing more pixels than this are refused rather than allocated.
+const MAX_PIXELS: f64 = 100_000_000.0;
+
+/** How `try_render_to_png` rasterizes a formula. */
+#[derive(Clone, Debug)]
+pub struct PngOptions {
+    /// Resolution in dots per inch, also written to the `pHYs` chunk.
+    pub dpi: f64,
+    /// The size of the surrounding text in points; as in the HTML output,
+    /// formulas are drawn 1.21 times as large.
+    pub font_size: f64,
+    /// CSS color of everything `\color` and the like leave uncolored. The
+    /// background is transparent.
+    pub color: String,
+}
+
+impl Default for PngOptions {
+    /** 12pt text at 96 dpi, the 16px default of browsers, in black. */
+    fn default() -> PngOptions {
+        PngOptions {
+            dpi: 96.0,
+            font_size: 12.0,
+            color: "black".to_string(),
+        }
+    }
+}
+
+fn parse_color(color: &str) -> Option<Color> {
+    let color = color.parse::<svgtypes::Color>().ok()?;
+    Some(Color::from_rgba8(color.red, color.green, color.blue, color.alpha))
+}
+
+fn paint(color: Color) -> Paint<'static> {
+    let mut paint = Paint::default();
+    paint.set_color(color);
+    paint.anti_alias = true;
+    paint
+}
+
+fn parse_path(data: &str) -> Option<Path> {
+    let mut builder = PathBuilder::new();
+    for segment in SimplifyingPathParser::from(data) {
+        match segment.ok()? {
+            SimplePathSegment::MoveTo { x, y } => builder.move_to(x as f32, y as f32),
+            SimplePathSegment::LineTo { x, y } => builder.line_to(x as f32, y as f32),
+            SimplePathSegment::Quadratic { x1, y1, x, y } => {
+                builder.quad_to(x1 as f32, y1 as f32, x as f32, y as f32)
+            }
+            SimplePathSegment::CurveTo {
+                x1,
+                y1,
+                x2,
+                y2,
+                x,
+                y,
+            } => builder.cubic_to(x1 as f32, y1 as f32, x2 as f32, y2 as f32, x as f32, y as f32),
+            SimplePathSegment::ClosePath => builder.close(),
+        }
+    }
+    builder.finish()
+}
+
+/** A length of an `<svg>` child: a percentage of `full`, ems, or user units. */
+fn length(value: &str, full: f64, size: f64) -> f64 {
+    if let Some(percent) = value.strip_suffix('%') {
+        percent.trim().parse::<f64>().unwrap_or(0.0) / 100.0 * full
+    } else if let Some(ems) = value.strip_suffix("em") {
+        ems.trim().parse::<f64>().unwrap_or(0.0) * size
+    } else {
+        value.trim().parse::<f64>().unwrap_or(0.0)
+    }
+}
+
+/**
+ * Maps the `viewBox` of an `<svg>` onto its box as `preserveAspectRatio`
+ * says, or leaves the user units in ems if it has none.
+ */
+fn viewport_transform(node: &SvgNode, x: f64, y: f64, width: f64, height: f64) -> Transform {
+    let view_box = node.get_attribute("viewBox").and_then(|value| value.parse::<ViewBox>().ok());
+    let view_box = match view_box {
+        Some(view_box) if view_box.w > 0.0 && view_box.h > 0.0 => view_box,
+        _ => return Transform::from_translate(x as f32, y as f32),
+    };
+    let ratio = node
+        .get_attribute("preserveAspectRatio")
+        .and_then(|value| value.parse::<AspectRatio>().ok())
+        .unwrap_or_default();
+    let (mut scale_x, mut scale_y) = (width / view_box.w, height / view_box.h);
+    if ratio.align != Align::None {
+        let scale = if ratio.slice {
+            scale_x.max(scale_y)
+        } else {
+            scale_x.min(scale_y)
+        };
+        scale_x = scale;
+        scale_y = scale;
+    }
+    let (free_x, free_y) = (width - view_box.w * scale_x, height - view_box.h * scale_y);
+    let (align_x, align_y) = match ratio.align {
+        Align::None | Align::XMinYMin => (0.0, 0.0),
+        Align::XMidYMin => (0.5, 0.0),
+        Align::XMaxYMin => (1.0, 0.0),
+        Align::XMinYMid => (0.0, 0.5),
+        Align::XMidYMid => (0.5, 0.5),
+        Align::XMaxYMid => (1.0, 0.5),
+        Align::XMinYMax => (0.0, 1.0),
+        Align::XMidYMax => (0.5, 1.0),
+        Align::XMaxYMax => (1.0, 1.0),
+    };
+    Transform::from_row(
+        scale_x as f32,
+        0.0,
+        0.0,
+        scale_y as f32,
+        (x + free_x * align_x - view_box.x * scale_x) as f32,
+        (y + free_y * align_y - view_box.y * scale_y) as f32,
+    )
+}
+
+struct Canvas {
+    pixmap: Pixmap,
+    /// From ems, y pointing down, to pixels.
+    base: Transform,
+    foreground: Color,
+}
+
+impl Canvas {
+    fn color(&self, color: &Option<String>) -> Color {
+        color.as_deref().and_then(parse_color).unwrap_or(self.foreground)
+    }
+
+    fn draw(&mut self, item: &Item) {
+        match item {
+            Item::Glyph {
+                x,
+                y,
+                size,
+                font,
+                ch,
+                width,
+                height,
+                depth,
+                color,
+            } => {
+                let paint = paint(self.color(color));
+                match fonts::outline(font, *ch) {
+                    Some(outline) => {
+                        if let Some(path) = parse_path(&outline.path) {
+                            let scale = (size / 1000.0) as f32;
+                            let transform = self.base.pre_translate(*x as f32, *y as f32).pre_scale(scale, scale);
+                            self.pixmap.fill_path(&path, &paint, FillRule::Winding, transform, None);
+                        }
+                    }
+                    None if ch.is_whitespace() => {}
+                    None => {
+                        // A hollow box, as font renderers draw missing glyphs.
+                        let inset = 0.05 * size;
+                        let rect = Rect::from_xywh(
+                            (x + inset) as f32,
+                            (y - height + inset) as f32,
+                            (width - 2.0 * inset) as f32,
+                            (height + depth - 2.0 * inset) as f32,
+                        );
+                        if let Some(rect) = rect {
+                            let stroke = Stroke {
+                                width: (0.04 * size) as f32,
+                                ..Stroke::default()
+                            };
+                            let path = PathBuilder::from_rect(rect);
+                            self.pixmap.stroke_path(&path, &paint, &stroke, self.base, None);
+                        }
+                    }
+                }
+            }
+            Item::Rule {
+                x,
+                y,
+                width,
+                height,
+                dashed,
+                color,
+            } => {
+                let paint = paint(self.color(color));
+                if *dashed {
+                    let (x1, y1, x2, y2, thickness) = if width >= height {
+                        (*x, y + height / 2.0, x + width, y + height / 2.0, *height)
+                    } else {
+                        (x + width / 2.0, *y, x + width / 2.0, y + height, *width)
+                    };
+                    let mut builder = PathBuilder::new();
+                    builder.move_to(x1 as f32, y1 as f32);
+                    builder.line_to(x2 as f32, y2 as f32);
+                    let stroke = Stroke {
+                        width: thickness as f32,
+                        dash: StrokeDash::new(vec![3.0 * thickness as f32, 3.0 * thickness as f32], 0.0),
+                        ..Stroke::default()
+                    };
+                    if let Some(path) = builder.finish() {
+                        self.pixmap.stroke_path(&path, &paint, &stroke, self.base, None);
+                    }
+                } else if let Some(rect) = Rect::from_xywh(*x as f32, *y as f32, *width as f32, *height as f32) {
+                    self.pixmap.fill_rect(rect, &paint, self.base, None);
+                }
+            }
+            Item::Svg {
+                x,
+                y,
+                width,
+                height,
+                size,
+                node,
+                color,
+            } => {
+                let paint = paint(self.color(color));
+                let rect = match Rect::from_xywh(*x as f32, *y as f32, *width as f32, *height as f32) {
+                    Some(rect) => rect,
+                    None => return,
+                };
+                // Inner `<svg>` elements clip what overflows them.
+                let mut clip = match Mask::new(self.pixmap.width(), self.pixmap.height()) {
+                    Some(clip) => clip,
+                    None => return,
+                };
+                clip.fill_path(&PathBuilder::from_rect(rect), FillRule::Winding, true, self.base);
+                let transform = self.base.pre_concat(viewport_transform(node, *x, *y, *width, *height));
+                for child in node.svg_children() {
+                    if let Some(path) = child.as_any().downcast_ref::<PathNode>() {
+                        if let Some(path) = parse_path(&path.path_data()) {
+                            self.pixmap.fill_path(&path, &paint, FillRule::Winding, transform, Some(&clip));
+                        }
+                    } else if let Some(line) = child.as_any().downcast_ref::<LineNode>() {
+                        let coordinate = |name: &str, full: f64| {
+                            line.get_attribute(name).map_or(0.0, |value| length(value, full, *size)) as f32
+                        };
+                        let mut builder = PathBuilder::new();
+                        builder.move_to(coordinate("x1", *width), coordinate("y1", *height));
+                        builder.line_to(coordinate("x2", *width), coordinate("y2", *height));
+                        let stroke = Stroke {
+                            width: coordinate("stroke-width", 0.0),
+                            ..Stroke::default()
+                        };
+                        if let Some(path) = builder.finish() {
+                            self.pixmap.stroke_path(&path, &paint, &stroke, transform, Some(&clip));
+                        }
+                    }
+                }
+            }
+            // \includegraphics sources are not fetched.
+            Item::Image { .. } => {}
+        }
+    }
+}
+
+fn encode(pixmap: &Pixmap, dpi: f64) -> Result<Vec<u8>, String> {
+    let mut data = Vec::with_capacity(pixmap.data().len());
+    for pixel in pixmap.pixels() {
+        let color = pixel.demultiply();
+        data.extend_from_slice(&[color.red(), color.green(), color.blue(), color.alpha()]);
+    }
+    let mut png_data = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png_data, pixmap.width(), pixmap.height());
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    // Lets documents that honour the resolution size the image as rendered.
+    let pixels_per_meter = (dpi / 0.0254).round() as u32;
+    encoder.set_pixel_dims(Some(png::PixelDimensions {
+        xppu: pixels_per_meter,
+        yppu: pixels_per_meter,
+        unit: png::Unit::Meter,
+    }));
+    let mut writer = encoder.write_header().map_err(|error| error.to_string())?;
+    writer.write_image_data(&data).map_err(|error| error.to_string())?;
+    writer.finish().map_err(|error| error.to_string())?;
+    Ok(png_data)
+}
+
+/**
+ * Rasterizes the layout of the SVG output to a PNG image with a transparent
+ * background. Glyphs come from the fonts given to `register_svg_font`; one
+ * without a registered font is drawn as a hollow box.
+ */
+pub(crate) fn to_png(tree: &dyn HtmlDomNode, options: &PngOptions) -> Result<Vec<u8>, String> {
+    let foreground = parse_color(&options.color).ok_or_else(|| format!("Invalid color '{}'", options.color))?;
+    if !(options.dpi > 0.0 && options.font_size > 0.0) {
+        return Err("The resolution and font size must be positive".to_string());
+    }
+    let frag = Layout::default().layout(tree);
+    let (left, top, right, bottom) = super::bounds(&frag);
+    let scale = options.font_size * KATEX_SCALE / 72.0 * options.dpi;
+    let (width, height) = (((right - left) * scale).ceil().max(1.0), ((bottom - top) * scale).ceil().max(1.0));
+    if width * height > MAX_PIXELS {
+        return Err(format!("Image too large: {}x{} pixels", width, height));
+    }
+    let pixmap = Pixmap::new(width as u32, height as u32)
+        .ok_or_else(|| format!("Image too large: {}x{} pixels", width, height))?;
+    let mut canvas = Canvas {
+        pixmap,
+        base: Transform::from_row(
+            scale as f32,
+            0.0,
+            0.0,
+            scale as f32,
+            (-left * scale) as f32,
+            (-top * scale) as f32,
+        ),
+        foreground,
+    };
+    for item in frag.items.iter() {
+        canvas.draw(item);
+    }
+    encode(&canvas.pixmap, options.dpi)
+}
@@ -3,6 +3,7 @@
  * It's a storehouse of path geometry for SVG images.
  */
 // In all paths below, the viewBox-to-em scale is 1000:1.
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 
 // The viniculum of a \sqrt can be made thicker by a KaTeX rendering option.
@@ -138,7 +139,7 @@ s76,-153,76,-153s77,-151,77,-151c0.7,0.7,35.7,202,105,604c67.3,400.7,102,602.7,1
     );
 }
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 pub fn phase_path(y: f64) -> String {
     let x = y / 2.0; // x coordinate at top of angle
     return format!("M400000 {y} H0 L{x} 0 l65 45 L145 {} H400000z", y - 80.0);
@@ -180,7 +181,7 @@ pub fn sqrt_path(size: &str, mut extra_viniculum: f64, view_box_height: f64) ->
     }
 }
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 pub fn inner_path(name: &str, height: f64) -> String {
     // The inner part of stretchy tall delimiters
     match name {
@@ -204,10 +205,9 @@ pub fn inner_path(name: &str, height: f64) -> String {
 }
 
 use std::collections::HashMap;
-use std::sync::Mutex;
 
 lazy_static! {
-    static ref svg_path:Mutex<HashMap<&'static str,&'static str> > = Mutex::new({
+    static ref svg_path: HashMap<&'static str,&'static str> = {
         let mut res = HashMap::new();
 
         // The doubleleftarrow geometry is from glyph U+21D0 in the font KaTeX Main
@@ -683,15 +683,13 @@ lazy_static! {
         );
 
         res
-    });
+    };
 
 }
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 pub fn path_get(key: String) -> String {
     let res = svg_path
-        .lock()
-        .unwrap()
         .get(key.as_str())
         .unwrap()
         .to_string();
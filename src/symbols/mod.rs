@@ -1,3 +1,4 @@
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 mod mathS;
 pub(crate) mod public;
@@ -26,6 +27,7 @@ pub fn get_symbol(mode: Mode, name: &str) -> Option<Symbol> {
         Mode::text => TEXT_SYMBOLS.get(name).cloned(),
     }
 }
+#[cfg(feature = "wasm")]
 #[wasm_bindgen]
 pub fn _get_symbol(mode: String, name: String) -> Option<js_sys::Object> {
     let res = get_symbol(Mode::from_str(mode.as_str()).unwrap(), &name);
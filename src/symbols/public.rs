@@ -101,6 +101,7 @@ pub struct Symbol {
 }
 
 impl Symbol {
+    #[cfg(feature = "wasm")]
     pub fn to_js_object(&self) -> js_sys::Object {
         let mut m = js_sys::Object::new();
         js_sys::Reflect::set(
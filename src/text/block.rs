@@ -0,0 +1,176 @@
+use unicode_normalization::char::is_combining_mark;
+
+/** Columns `c` takes in a terminal: none for combining marks, two for wide characters. */
+fn char_width(c: char) -> usize {
+    if is_combining_mark(c) || c == '\u{200b}' {
+        return 0;
+    }
+    match c as u32 {
+        0x1100..=0x115f
+        | 0x2e80..=0x303e
+        | 0x3041..=0x33ff
+        | 0x3400..=0x4dbf
+        | 0x4e00..=0x9fff
+        | 0xa000..=0xa4cf
+        | 0xac00..=0xd7a3
+        | 0xf900..=0xfaff
+        | 0xfe30..=0xfe4f
+        | 0xff00..=0xff60
+        | 0xffe0..=0xffe6
+        | 0x1f300..=0x1f64f
+        | 0x1f900..=0x1f9ff
+        | 0x20000..=0x3fffd => 2,
+        _ => 1,
+    }
+}
+
+/**
+ * A rectangle of monospace text with a baseline. Each cell holds one
+ * character with its combining marks, or nothing to the right of a
+ * double-width character.
+ */
+#[derive(Clone, Debug)]
+pub(crate) struct Block {
+    pub(crate) rows: Vec<Vec<String>>,
+    pub(crate) width: usize,
+    /// The row the baseline runs through.
+    pub(crate) baseline: usize,
+}
+
+impl Block {
+    /** `width` blank columns, `above` rows above the baseline and `below` under it. */
+    pub(crate) fn blank(width: usize, above: usize, below: usize) -> Block {
+        Block {
+            rows: vec![vec![" ".to_string(); width]; above + below + 1],
+            width,
+            baseline: above,
+        }
+    }
+
+    pub(crate) fn empty() -> Block {
+        Block::blank(0, 0, 0)
+    }
+
+    /** One row of text. */
+    pub(crate) fn text(text: &str) -> Block {
+        let mut row: Vec<String> = Vec::new();
+        for c in text.chars() {
+            match char_width(c) {
+                0 => match row.iter_mut().rev().find(|cell| !cell.is_empty()) {
+                    Some(cell) => cell.push(c),
+                    None => row.push(format!(" {}", c)),
+                },
+                width => {
+                    row.push(c.to_string());
+                    row.extend(std::iter::repeat_n(String::new(), width - 1));
+                }
+            }
+        }
+        Block {
+            width: row.len(),
+            rows: vec![row],
+            baseline: 0,
+        }
+    }
+
+    /** `c` repeated `count` times on one row. */
+    pub(crate) fn repeat(c: &str, count: usize) -> Block {
+        Block::text(&c.repeat(count))
+    }
+
+    pub(crate) fn height(&self) -> usize {
+        self.rows.len()
+    }
+
+    /** Rows above the baseline. */
+    pub(crate) fn above(&self) -> usize {
+        self.baseline
+    }
+
+    /** Rows below the baseline. */
+    pub(crate) fn below(&self) -> usize {
+        self.rows.len() - self.baseline - 1
+    }
+
+    /** The only cell of a one-character block. */
+    pub(crate) fn single_cell(&mut self) -> Option<&mut String> {
+        match self.rows.as_mut_slice() {
+            [row] if row.len() == 1 => row.first_mut(),
+            _ => None,
+        }
+    }
+
+    /** Adds the combining mark `mark` to every non-blank cell. */
+    pub(crate) fn combine(mut self, mark: char) -> Block {
+        for cell in self.rows.iter_mut().flatten() {
+            if !cell.is_empty() && cell != " " {
+                cell.push(mark);
+            }
+        }
+        self
+    }
+
+    /**
+     * Places `parts` on one canvas, each given by its left column and the
+     * row of its baseline relative to that of the result. Blank cells of a
+     * part leave what is under them visible.
+     */
+    pub(crate) fn compose(parts: &[(usize, isize, &Block)]) -> Block {
+        let top = parts
+            .iter()
+            .map(|(_, dy, block)| dy - block.above() as isize)
+            .min()
+            .unwrap_or(0)
+            .min(0);
+        let bottom = parts
+            .iter()
+            .map(|(_, dy, block)| dy + block.below() as isize)
+            .max()
+            .unwrap_or(0)
+            .max(0);
+        let width = parts.iter().map(|(x, _, block)| x + block.width).max().unwrap_or(0);
+        let mut result = Block::blank(width, (-top) as usize, bottom as usize);
+        for (x, dy, block) in parts {
+            let first_row = (dy - block.above() as isize - top) as usize;
+            for (i, row) in block.rows.iter().enumerate() {
+                for (j, cell) in row.iter().enumerate() {
+                    if cell != " " {
+                        result.rows[first_row + i][x + j] = cell.clone();
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    /** `blocks` side by side on a common baseline. */
+    pub(crate) fn row(blocks: &[Block]) -> Block {
+        let mut x = 0;
+        let mut parts = Vec::with_capacity(blocks.len());
+        for block in blocks {
+            parts.push((x, 0, block));
+            x += block.width;
+        }
+        let mut result = Block::compose(&parts);
+        result.width = x;
+        for row in result.rows.iter_mut() {
+            row.resize(x, " ".to_string());
+        }
+        result
+    }
+
+    /** The block centered in `width` columns. */
+    pub(crate) fn centered(&self, width: usize) -> Block {
+        let left = width.saturating_sub(self.width) / 2;
+        let right = width.saturating_sub(self.width + left);
+        Block::row(&[Block::blank(left, 0, 0), self.clone(), Block::blank(right, 0, 0)])
+    }
+
+    /** The lines of the block, without trailing blanks. */
+    pub(crate) fn lines(&self) -> Vec<String> {
+        self.rows
+            .iter()
+            .map(|row| row.concat().trim_end().to_string())
+            .collect()
+    }
+}
@@ -0,0 +1,999 @@
+use super::block::Block;
+use crate::define::environments::array::AlignSpec;
+use crate::parse_node::types::{self, AnyParseNode, ArrayTag, Atom, ColSeparationType};
+use crate::symbols::get_symbol;
+use crate::types::{Mode, StyleStr};
+use crate::units::Measurement;
+
+/// Columns between the columns of an array.
+const COLUMN_GAP: usize = 2;
+/// The widest a `\rule` or a space is drawn, about a wide terminal.
+const MAX_COLUMNS: usize = 200;
+
+/** The TeX atom class of a laid out node, which decides the spaces around it. */
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Class {
+    Ord,
+    Op,
+    Bin,
+    Rel,
+    Open,
+    Close,
+    Punct,
+    Inner,
+    /// Explicit space, which TeX spacing ignores.
+    Space,
+    /// The start or end of a list.
+    Edge,
+}
+
+fn class_of_atom(family: &Atom) -> Class {
+    match family {
+        Atom::bin => Class::Bin,
+        Atom::close => Class::Close,
+        Atom::inner => Class::Inner,
+        Atom::open => Class::Open,
+        Atom::punct => Class::Punct,
+        Atom::rel => Class::Rel,
+    }
+}
+
+fn class_of_mclass(mclass: &str) -> Class {
+    match mclass {
+        "mbin" => Class::Bin,
+        "mrel" => Class::Rel,
+        "mopen" => Class::Open,
+        "mclose" => Class::Close,
+        "mpunct" => Class::Punct,
+        "minner" => Class::Inner,
+        "mop" => Class::Op,
+        _ => Class::Ord,
+    }
+}
+
+/** Spaces between adjacent atoms, after TeX's table of inter-atom spacing. */
+fn space_between(left: Class, right: Class, script: bool) -> usize {
+    use Class::*;
+    match (left, right) {
+        (Space, _) | (_, Space) | (Edge, _) | (_, Edge) => 0,
+        (Op, Ord | Op | Inner) | (Ord | Close | Inner, Op) => 1,
+        _ if script => 0,
+        (Bin, _) | (_, Bin) => 1,
+        (Rel, Rel) | (Open, Rel) | (Rel, Close | Punct) => 0,
+        (Rel, _) | (_, Rel) | (Punct, _) => 1,
+        _ => 0,
+    }
+}
+
+/** The style a node is laid out in. */
+#[derive(Clone, Default)]
+struct Ctx {
+    display: bool,
+    script: bool,
+    /// A font command such as `mathbb`, or a text one such as `\textbf`.
+    font: Option<String>,
+}
+
+impl Ctx {
+    fn script(&self) -> Ctx {
+        Ctx {
+            display: false,
+            script: true,
+            font: self.font.clone(),
+        }
+    }
+
+    fn inline(&self) -> Ctx {
+        Ctx {
+            display: false,
+            ..self.clone()
+        }
+    }
+
+    fn with_font(&self, font: &str) -> Ctx {
+        Ctx {
+            font: Some(font.to_string()),
+            ..self.clone()
+        }
+    }
+}
+
+/**
+ * A length in columns, counting two per em as monospace fonts are about half
+ * an em wide, and at most `MAX_COLUMNS`.
+ */
+fn columns(measurement: &Measurement) -> usize {
+    let em = match measurement.unit.as_str() {
+        "em" => 1.0,
+        "mu" => 1.0 / 18.0,
+        "ex" => 0.431,
+        "pt" | "bp" | "dd" | "px" => 0.1,
+        "pc" | "cc" => 1.2,
+        "mm" => 0.285,
+        "cm" => 2.85,
+        "in" => 7.23,
+        _ => 0.0,
+    };
+    (measurement.number * em * 2.0).round().clamp(0.0, MAX_COLUMNS as f64) as usize
+}
+
+/**
+ * The letters and digits of a font as Unicode mathematical alphanumerics,
+ * with the exceptions that were encoded before that block.
+ */
+fn styled_char(c: char, font: &str) -> char {
+    let (upper, lower, digit, exceptions): (u32, u32, Option<u32>, &[(char, char)]) = match font {
+        "mathbf" | "boldsymbol" | "\\textbf" => (0x1d400, 0x1d41a, Some(0x1d7ce), &[]),
+        "mathbb" | "textbb" => (
+            0x1d538,
+            0x1d552,
+            Some(0x1d7d8),
+            &[
+                ('C', 'ℂ'),
+                ('H', 'ℍ'),
+                ('N', 'ℕ'),
+                ('P', 'ℙ'),
+                ('Q', 'ℚ'),
+                ('R', 'ℝ'),
+                ('Z', 'ℤ'),
+            ],
+        ),
+        "mathcal" | "mathscr" | "textscr" => (
+            0x1d49c,
+            0x1d4b6,
+            None,
+            &[
+                ('B', 'ℬ'),
+                ('E', 'ℰ'),
+                ('F', 'ℱ'),
+                ('H', 'ℋ'),
+                ('I', 'ℐ'),
+                ('L', 'ℒ'),
+                ('M', 'ℳ'),
+                ('R', 'ℛ'),
+                ('e', 'ℯ'),
+                ('g', 'ℊ'),
+                ('o', 'ℴ'),
+            ],
+        ),
+        "mathfrak" | "textfrak" => (
+            0x1d504,
+            0x1d51e,
+            None,
+            &[('C', 'ℭ'), ('H', 'ℌ'), ('I', 'ℑ'), ('R', 'ℜ'), ('Z', 'ℨ')],
+        ),
+        "mathsf" | "\\textsf" => (0x1d5a0, 0x1d5ba, Some(0x1d7e2), &[]),
+        "mathtt" | "\\texttt" => (0x1d670, 0x1d68a, Some(0x1d7f6), &[]),
+        _ => return c,
+    };
+    if let Some((_, styled)) = exceptions.iter().find(|(plain, _)| *plain == c) {
+        return *styled;
+    }
+    let code = match c {
+        'A'..='Z' => upper + (c as u32 - 'A' as u32),
+        'a'..='z' => lower + (c as u32 - 'a' as u32),
+        '0'..='9' => match digit {
+            Some(digit) => digit + (c as u32 - '0' as u32),
+            None => return c,
+        },
+        _ => return c,
+    };
+    char::from_u32(code).unwrap_or(c)
+}
+
+/** What a symbol command such as `\alpha` stands for. */
+fn symbol_text(mode: Mode, text: &str) -> String {
+    match get_symbol(mode, text).and_then(|symbol| symbol.replace) {
+        Some(replace) => replace,
+        None => text.to_string(),
+    }
+}
+
+/** The combining mark and the spacing character drawing an accent. */
+fn accent_marks(label: &str) -> Option<(char, &'static str)> {
+    Some(match label {
+        "\\hat" | "\\widehat" | "\\^" => ('\u{302}', "^"),
+        "\\check" | "\\widecheck" | "\\v" => ('\u{30c}', "ˇ"),
+        "\\tilde" | "\\widetilde" | "\\~" => ('\u{303}', "~"),
+        "\\acute" | "\\'" => ('\u{301}', "´"),
+        "\\grave" | "\\`" => ('\u{300}', "`"),
+        "\\dot" | "\\." => ('\u{307}', "˙"),
+        "\\ddot" | "\\\"" => ('\u{308}', "¨"),
+        "\\bar" | "\\=" => ('\u{304}', "_"),
+        "\\vec" => ('\u{20d7}', "→"),
+        "\\breve" | "\\u" => ('\u{306}', "˘"),
+        "\\mathring" | "\\r" => ('\u{30a}', "˚"),
+        "\\H" => ('\u{30b}', "˝"),
+        _ => return None,
+    })
+}
+
+/** A horizontal arrow or line `width` columns wide, as drawn by `label`. */
+fn stretchy(label: &str, width: usize) -> Block {
+    let width = width.max(2);
+    let (left, fill, right) = match label.trim_start_matches('\\') {
+        "overleftarrow" | "underleftarrow" | "xleftarrow" | "cdleftarrow" => ("←", "─", "─"),
+        "overleftrightarrow" | "underleftrightarrow" | "xleftrightarrow" => ("←", "─", "→"),
+        "Overrightarrow" | "xRightarrow" => ("═", "═", "⇒"),
+        "xLeftarrow" => ("⇐", "═", "═"),
+        "xLeftrightarrow" => ("⇐", "═", "⇒"),
+        "xmapsto" => ("├", "─", "→"),
+        "xhookrightarrow" => ("↩", "─", "→"),
+        "xhookleftarrow" => ("←", "─", "↪"),
+        "xtwoheadrightarrow" => ("─", "─", "↠"),
+        "xtwoheadleftarrow" => ("↞", "─", "─"),
+        "xrightharpoonup" | "overrightharpoon" => ("─", "─", "⇀"),
+        "xrightharpoondown" => ("─", "─", "⇁"),
+        "xleftharpoonup" | "overleftharpoon" => ("↼", "─", "─"),
+        "xleftharpoondown" => ("↽", "─", "─"),
+        "xrightleftharpoons" | "xrightequilibrium" => ("─", "─", "⇌"),
+        "xleftrightharpoons" | "xleftequilibrium" => ("⇋", "─", "─"),
+        "xrightleftarrows" => ("─", "─", "⇄"),
+        "xtofrom" => ("⇄", "─", "─"),
+        "xlongequal" | "cdlongequal" => ("═", "═", "═"),
+        "overlinesegment" | "underlinesegment" => ("├", "─", "┤"),
+        "overgroup" => ("╭", "─", "╮"),
+        "undergroup" => ("╰", "─", "╯"),
+        "utilde" => ("~", "~", "~"),
+        _ => ("─", "─", "→"),
+    };
+    Block::text(&format!("{}{}{}", left, fill.repeat(width - 2), right))
+}
+
+/**
+ * A delimiter reaching `above` rows over the baseline and `below` under it,
+ * built from the pieces of U+239B to U+23AD where there are some.
+ */
+fn delimiter(delim: &str, above: usize, below: usize) -> Block {
+    let text = symbol_text(Mode::math, delim);
+    if text == "." {
+        return Block::blank(0, above, below);
+    }
+    let height = above + below + 1;
+    if height == 1 {
+        return Block::text(&text);
+    }
+    let mut block = Block::blank(1, above, below);
+    if text == "⟨" || text == "⟩" {
+        // Slopes meeting at the baseline.
+        let (upper, lower) = if text == "⟨" { ("╱", "╲") } else { ("╲", "╱") };
+        for (i, row) in block.rows.iter_mut().enumerate() {
+            row[0] = match i.cmp(&above) {
+                std::cmp::Ordering::Less => upper,
+                std::cmp::Ordering::Equal => text.as_str(),
+                std::cmp::Ordering::Greater => lower,
+            }
+            .to_string();
+        }
+        return block;
+    }
+    // Top, extension, bottom and an optional middle piece.
+    let (top, fill, bottom, middle) = match text.as_str() {
+        "(" => ("⎛", "⎜", "⎝", None),
+        ")" => ("⎞", "⎟", "⎠", None),
+        "[" => ("⎡", "⎢", "⎣", None),
+        "]" => ("⎤", "⎥", "⎦", None),
+        "{" => ("⎧", "⎪", "⎩", Some("⎨")),
+        "}" => ("⎫", "⎪", "⎭", Some("⎬")),
+        "⌊" => ("⎢", "⎢", "⎣", None),
+        "⌋" => ("⎥", "⎥", "⎦", None),
+        "⌈" => ("⎡", "⎢", "⎢", None),
+        "⌉" => ("⎤", "⎥", "⎥", None),
+        "|" | "∣" => ("│", "│", "│", None),
+        "‖" | "∥" => ("‖", "‖", "‖", None),
+        "/" => ("╱", "╱", "╱", None),
+        "\\" => ("╲", "╲", "╲", None),
+        // Anything else stays one row high, on the baseline.
+        _ => return Block::compose(&[(0, 0, &block), (0, 0, &Block::text(&text))]),
+    };
+    for (i, row) in block.rows.iter_mut().enumerate() {
+        let piece = if i == 0 {
+            top
+        } else if i == height - 1 {
+            bottom
+        } else {
+            fill
+        };
+        row[0] = piece.to_string();
+    }
+    if let (Some(middle), true) = (middle, height >= 3) {
+        block.rows[(height - 1) / 2][0] = middle.to_string();
+    }
+    block
+}
+
+/** Rows of `\big`, `\Big`, `\bigg` and `\Bigg` over and under the baseline. */
+fn delimsizing_extent(size: usize) -> (usize, usize) {
+    match size {
+        1 => (0, 0),
+        2 => (1, 0),
+        3 => (1, 1),
+        _ => (2, 1),
+    }
+}
+
+/**
+ * A box drawn around `body`, with a column of padding on each side.
+ */
+fn framed(body: &Block) -> Block {
+    let width = body.width + 2;
+    let mut frame = Block::blank(width + 2, body.above() + 1, body.below() + 1);
+    let last_row = frame.height() - 1;
+    for (i, row) in frame.rows.iter_mut().enumerate() {
+        let (left, fill, right) = if i == 0 {
+            ("┌", "─", "┐")
+        } else if i == last_row {
+            ("└", "─", "┘")
+        } else {
+            ("│", " ", "│")
+        };
+        row[0] = left.to_string();
+        for cell in row[1..=width].iter_mut() {
+            *cell = fill.to_string();
+        }
+        row[width + 1] = right.to_string();
+    }
+    Block::compose(&[(0, 0, &frame), (2, 0, body)])
+}
+
+/** Lays out a parse tree as text, see `crate::text`. */
+pub(crate) struct Layout {
+    /// The number of the last equation numbered by an environment.
+    equation: usize,
+    ctx: Ctx,
+}
+
+impl Layout {
+    pub(crate) fn new(display: bool) -> Layout {
+        Layout {
+            equation: 0,
+            ctx: Ctx {
+                display,
+                ..Ctx::default()
+            },
+        }
+    }
+
+    /** The top level, where `\\` and `\newline` start a new line. */
+    pub(crate) fn layout(&mut self, tree: &[Box<dyn AnyParseNode>]) -> Block {
+        let ctx = self.ctx.clone();
+        let lines: Vec<&[Box<dyn AnyParseNode>]> = tree
+            .split(|node| node.as_any().downcast_ref::<types::cr>().is_some_and(|cr| cr.newLine))
+            .collect();
+        if lines.len() == 1 {
+            return self.list(tree, &ctx);
+        }
+        let lines: Vec<Block> = lines.iter().map(|line| self.list(line, &ctx)).collect();
+        let mut parts = Vec::with_capacity(lines.len());
+        let mut dy = 0;
+        for (i, line) in lines.iter().enumerate() {
+            if i > 0 {
+                dy += lines[i - 1].below() as isize + line.above() as isize + 1;
+            }
+            parts.push((0, dy, line));
+        }
+        Block::compose(&parts)
+    }
+
+    fn list(&mut self, nodes: &[Box<dyn AnyParseNode>], ctx: &Ctx) -> Block {
+        self.list_after(nodes, ctx, Class::Edge)
+    }
+
+    /** Lays out `nodes` side by side, spaced as if they followed an atom of class `before`. */
+    fn list_after(&mut self, nodes: &[Box<dyn AnyParseNode>], ctx: &Ctx, before: Class) -> Block {
+        let mut items: Vec<(Class, Block)> = nodes.iter().map(|node| self.node(node.as_ref(), ctx)).collect();
+        // A binary operator with nothing to operate on is an ordinary symbol.
+        for i in 0..items.len() {
+            if items[i].0 != Class::Bin {
+                continue;
+            }
+            let previous = items[..i]
+                .iter()
+                .rev()
+                .map(|(class, _)| *class)
+                .find(|class| *class != Class::Space)
+                .unwrap_or(before);
+            let next = items[i + 1..]
+                .iter()
+                .map(|(class, _)| *class)
+                .find(|class| *class != Class::Space)
+                .unwrap_or(Class::Edge);
+            if matches!(previous, Class::Edge | Class::Bin | Class::Op | Class::Rel | Class::Open | Class::Punct)
+                || matches!(next, Class::Rel | Class::Close | Class::Punct | Class::Edge)
+            {
+                items[i].0 = Class::Ord;
+            }
+        }
+
+        let mut blocks = Vec::with_capacity(items.len() * 2);
+        let mut previous = before;
+        for (class, block) in items {
+            let spaces = space_between(previous, class, ctx.script);
+            if spaces > 0 && (!blocks.is_empty() || before != Class::Edge) {
+                blocks.push(Block::blank(spaces, 0, 0));
+            }
+            blocks.push(block);
+            previous = class;
+        }
+        Block::row(&blocks)
+    }
+
+    fn group(&mut self, node: &dyn AnyParseNode, ctx: &Ctx) -> Block {
+        self.node(node, ctx).1
+    }
+
+    fn node(&mut self, node: &dyn AnyParseNode, ctx: &Ctx) -> (Class, Block) {
+        let any = node.as_any();
+        if let Some(n) = any.downcast_ref::<types::mathord>() {
+            (Class::Ord, self.symbol(n.mode, &n.text, ctx))
+        } else if let Some(n) = any.downcast_ref::<types::textord>() {
+            (Class::Ord, self.symbol(n.mode, &n.text, ctx))
+        } else if let Some(n) = any.downcast_ref::<types::atom>() {
+            (class_of_atom(&n.family), self.symbol(n.mode, &n.text, ctx))
+        } else if let Some(n) = any.downcast_ref::<types::spacing>() {
+            let text = symbol_text(n.mode, &n.text).replace('\u{a0}', " ");
+            let text = if text.trim().is_empty() { " ".repeat(text.chars().count().max(1)) } else { text };
+            (Class::Space, Block::text(&text))
+        } else if let Some(n) = any.downcast_ref::<types::kern>() {
+            (Class::Space, Block::blank(columns(&n.dimension), 0, 0))
+        } else if let Some(n) = any.downcast_ref::<types::ordgroup>() {
+            (Class::Ord, self.list(&n.body, ctx))
+        } else if let Some(n) = any.downcast_ref::<types::supsub>() {
+            self.supsub(n, ctx)
+        } else if let Some(n) = any.downcast_ref::<types::genfrac>() {
+            (Class::Inner, self.genfrac(n, ctx))
+        } else if let Some(n) = any.downcast_ref::<types::sqrt>() {
+            (Class::Ord, self.sqrt(n, ctx))
+        } else if let Some(n) = any.downcast_ref::<types::array>() {
+            (Class::Ord, self.array(n, ctx))
+        } else if let Some(n) = any.downcast_ref::<types::leftright>() {
+            (Class::Inner, self.leftright(n, ctx))
+        } else if let Some(n) = any.downcast_ref::<types::middle>() {
+            (Class::Ord, delimiter(&n.delim, 0, 0))
+        } else if let Some(n) = any.downcast_ref::<types::delimsizing>() {
+            let (above, below) = delimsizing_extent(n.size);
+            (class_of_mclass(&n.mclass), delimiter(&n.delim, above, below))
+        } else if let Some(n) = any.downcast_ref::<types::op>() {
+            (Class::Op, self.op(n, ctx))
+        } else if let Some(n) = any.downcast_ref::<types::operatorname>() {
+            (Class::Op, self.list(&n.body, &ctx.with_font("mathrm")))
+        } else if let Some(n) = any.downcast_ref::<types::styling>() {
+            let ctx = Ctx {
+                display: matches!(n.style, StyleStr::display),
+                script: matches!(n.style, StyleStr::script | StyleStr::scriptscript),
+                font: ctx.font.clone(),
+            };
+            self.body_class(&n.body, &ctx)
+        } else if let Some(n) = any.downcast_ref::<types::font>() {
+            let class = self.node(n.body.as_ref(), ctx).0;
+            (class, self.group(n.body.as_ref(), &ctx.with_font(&n.font)))
+        } else if let Some(n) = any.downcast_ref::<types::text>() {
+            let ctx = match &n.font {
+                Some(font) => ctx.with_font(font),
+                None => ctx.clone(),
+            };
+            (Class::Ord, self.list(&n.body, &ctx))
+        } else if let Some(n) = any.downcast_ref::<types::color>() {
+            self.body_class(&n.body, ctx)
+        } else if let Some(n) = any.downcast_ref::<types::sizing>() {
+            self.body_class(&n.body, ctx)
+        } else if let Some(n) = any.downcast_ref::<types::mclass>() {
+            (class_of_mclass(&n.mclass), self.list(&n.body, ctx))
+        } else if let Some(n) = any.downcast_ref::<types::pmb>() {
+            (class_of_mclass(&n.mclass), self.list(&n.body, ctx))
+        } else if let Some(n) = any.downcast_ref::<types::accent>() {
+            (Class::Ord, self.accent(&n.label, n.base.as_deref(), ctx))
+        } else if let Some(n) = any.downcast_ref::<types::accentUnder>() {
+            let base = self.group(n.base.as_ref(), ctx);
+            let mark = match n.label.as_str() {
+                "\\underbar" => Block::repeat("‾", base.width),
+                "\\utilde" => stretchy(&n.label, base.width),
+                label => stretchy(label, base.width),
+            };
+            (Class::Ord, self.under(&base, &mark))
+        } else if let Some(n) = any.downcast_ref::<types::overline>() {
+            let base = self.group(n.body.as_ref(), ctx);
+            (Class::Ord, self.over(&base, &Block::repeat("_", base.width)))
+        } else if let Some(n) = any.downcast_ref::<types::underline>() {
+            let base = self.group(n.body.as_ref(), ctx);
+            (Class::Ord, self.under(&base, &Block::repeat("‾", base.width)))
+        } else if let Some(n) = any.downcast_ref::<types::horizBrace>() {
+            (Class::Ord, self.horiz_brace(n, ctx))
+        } else if let Some(n) = any.downcast_ref::<types::xArrow>() {
+            (Class::Rel, self.x_arrow(n, ctx))
+        } else if let Some(n) = any.downcast_ref::<types::enclose>() {
+            (Class::Ord, self.enclose(n, ctx))
+        } else if let Some(n) = any.downcast_ref::<types::tag>() {
+            let body = self.list(&n.body, ctx);
+            let tag = self.list(&n.tag, ctx);
+            (Class::Ord, Block::row(&[body, Block::blank(4, 0, 0), tag]))
+        } else if let Some(n) = any.downcast_ref::<types::phantom>() {
+            let body = self.list(&n.body, ctx);
+            (Class::Ord, Block::blank(body.width, body.above(), body.below()))
+        } else if let Some(n) = any.downcast_ref::<types::hphantom>() {
+            (Class::Ord, Block::blank(self.group(n.body.as_ref(), ctx).width, 0, 0))
+        } else if let Some(n) = any.downcast_ref::<types::vphantom>() {
+            let body = self.group(n.body.as_ref(), ctx);
+            (Class::Ord, Block::blank(0, body.above(), body.below()))
+        } else if let Some(n) = any.downcast_ref::<types::mathchoice>() {
+            let body = if ctx.display {
+                &n.display
+            } else if ctx.script {
+                &n.script
+            } else {
+                &n.text
+            };
+            (Class::Ord, self.list(body, ctx))
+        } else if let Some(n) = any.downcast_ref::<types::rule>() {
+            (Class::Ord, Block::repeat("█", columns(&n.width)))
+        } else if let Some(n) = any.downcast_ref::<types::includegraphics>() {
+            (Class::Ord, Block::text(&format!("[{}]", n.alt)))
+        } else if let Some(n) = any.downcast_ref::<types::url>() {
+            (Class::Ord, Block::text(&n.url))
+        } else if let Some(n) = any.downcast_ref::<types::verb>() {
+            let text = if n.star { n.body.replace(' ', "␣") } else { n.body.clone() };
+            (Class::Ord, Block::text(&text))
+        } else if let Some(n) = any.downcast_ref::<types::raw>() {
+            (Class::Ord, Block::text(&n.string))
+        } else if let Some(n) = any.downcast_ref::<types::htmlmathml>() {
+            (Class::Ord, self.list(&n.html, ctx))
+        } else {
+            // \href, \html*, \hbox, \raisebox, \smash, \vcenter, the laps
+            // and whatever else only wraps its children.
+            let mut children = Vec::new();
+            node.for_each_child(&mut |child| children.push(child.clone()));
+            (Class::Ord, self.list(&children, ctx))
+        }
+    }
+
+    /** A list taking the class of its only element, as `\color{red}{+}` is still a binary operator. */
+    fn body_class(&mut self, body: &[Box<dyn AnyParseNode>], ctx: &Ctx) -> (Class, Block) {
+        match body {
+            [only] => self.node(only.as_ref(), ctx),
+            _ => (Class::Ord, self.list(body, ctx)),
+        }
+    }
+
+    fn symbol(&mut self, mode: Mode, text: &str, ctx: &Ctx) -> Block {
+        let text = symbol_text(mode, text);
+        match &ctx.font {
+            Some(font) => Block::text(&text.chars().map(|c| styled_char(c, font)).collect::<String>()),
+            None => Block::text(&text),
+        }
+    }
+
+    fn op(&mut self, op: &types::op, ctx: &Ctx) -> Block {
+        match (&op.name, &op.body) {
+            (Some(name), _) if op.symbol => Block::text(&symbol_text(op.mode, name)),
+            (Some(name), _) => Block::text(name.trim_start_matches('\\')),
+            (None, Some(body)) => self.list(body, ctx),
+            (None, None) => Block::empty(),
+        }
+    }
+
+    /** Whether the scripts of `base` go over and under it. */
+    fn has_limits(base: &dyn AnyParseNode, ctx: &Ctx) -> bool {
+        let any = base.as_any();
+        if let Some(op) = any.downcast_ref::<types::op>() {
+            op.limits && (ctx.display || op.alwaysHandleSupSub)
+        } else if let Some(op) = any.downcast_ref::<types::operatorname>() {
+            op.limits && (ctx.display || op.always_handle_sup_sub)
+        } else {
+            any.is::<types::horizBrace>()
+        }
+    }
+
+    fn supsub(&mut self, node: &types::supsub, ctx: &Ctx) -> (Class, Block) {
+        let (class, mut base) = match node.base.as_deref() {
+            Some(base) => self.node(base, ctx),
+            None => (Class::Ord, Block::empty()),
+        };
+        let script = ctx.script();
+        let mut sup = node.sup.as_deref().map(|sup| self.group(sup, &script));
+        let sub = node.sub.as_deref().map(|sub| self.group(sub, &script));
+
+        if node.base.as_deref().is_some_and(|base| Layout::has_limits(base, ctx)) {
+            let width = [Some(&base), sup.as_ref(), sub.as_ref()]
+                .iter()
+                .flatten()
+                .map(|block| block.width)
+                .max()
+                .unwrap_or(0);
+            let base = base.centered(width);
+            let mut parts = vec![(0, 0, &base)];
+            let sup = sup.map(|sup| sup.centered(width));
+            let sub = sub.map(|sub| sub.centered(width));
+            if let Some(sup) = &sup {
+                parts.push((0, -(base.above() as isize) - 1 - sup.below() as isize, sup));
+            }
+            if let Some(sub) = &sub {
+                parts.push((0, base.below() as isize + 1 + sub.above() as isize, sub));
+            }
+            return (class, Block::compose(&parts));
+        }
+
+        // Primes stay on the line, as in f′(x).
+        if let Some(group) = node.sup.as_deref().and_then(|sup| sup.as_any().downcast_ref::<types::ordgroup>()) {
+            let primes = group
+                .body
+                .iter()
+                .take_while(|node| node.as_any().downcast_ref::<types::textord>().is_some_and(|n| n.text == "\\prime"))
+                .count();
+            if primes > 0 {
+                base = Block::row(&[base, Block::repeat("′", primes)]);
+                sup = Some(self.list(&group.body[primes..], &script)).filter(|sup| sup.width > 0);
+            }
+        }
+
+        let x = base.width;
+        let mut parts = vec![(0, 0, &base)];
+        if let Some(sup) = &sup {
+            parts.push((x, -(base.above() as isize) - 1 - sup.below() as isize, sup));
+        }
+        if let Some(sub) = &sub {
+            parts.push((x, base.below() as isize + 1 + sub.above() as isize, sub));
+        }
+        (class, Block::compose(&parts))
+    }
+
+    fn genfrac(&mut self, frac: &types::genfrac, ctx: &Ctx) -> Block {
+        let inner = ctx.inline();
+        let numer = self.group(frac.numer.as_ref(), &inner);
+        let denom = self.group(frac.denom.as_ref(), &inner);
+        let width = numer.width.max(denom.width) + 2;
+        let bar = if frac.hasBarLine {
+            Block::repeat("─", width)
+        } else {
+            Block::blank(width, 0, 0)
+        };
+        let numer = numer.centered(width);
+        let denom = denom.centered(width);
+        let stack = Block::compose(&[
+            (0, -1 - numer.below() as isize, &numer),
+            (0, 0, &bar),
+            (0, 1 + denom.above() as isize, &denom),
+        ]);
+        let (above, below) = (stack.above(), stack.below());
+        let left = frac.leftDelim.as_deref().map(|delim| delimiter(delim, above, below));
+        let right = frac.rightDelim.as_deref().map(|delim| delimiter(delim, above, below));
+        let blocks: Vec<Block> = [left, Some(stack), right].into_iter().flatten().collect();
+        Block::row(&blocks)
+    }
+
+    /**
+     * `√x` under a bar for one row, and a slanted sign reaching the bar
+     * for more.
+     */
+    fn sqrt(&mut self, node: &types::sqrt, ctx: &Ctx) -> Block {
+        let body = self.group(node.body.as_ref(), ctx);
+        let height = body.height();
+        let sign_width = if height == 1 { 1 } else { height + 1 };
+        let mut sign = Block::blank(sign_width, body.above(), body.below());
+        if height == 1 {
+            sign.rows[0][0] = "√".to_string();
+        } else {
+            for level in 0..height {
+                let row = height - 1 - level;
+                if level == 0 {
+                    sign.rows[row][0] = "╲".to_string();
+                }
+                sign.rows[row][level + 1] = "╱".to_string();
+            }
+        }
+        let bar = Block::repeat("_", body.width);
+        let radical = Block::compose(&[
+            (0, 0, &sign),
+            (sign_width, 0, &body),
+            (sign_width, -(body.above() as isize) - 1, &bar),
+        ]);
+        match node.index.as_deref() {
+            Some(index) => {
+                let index = self.group(index, &ctx.script());
+                // Ending over the start of the sign, on the row above its foot.
+                let dy = body.below() as isize - 1 - index.below() as isize;
+                Block::compose(&[(0, dy, &index), (index.width, 0, &radical)])
+            }
+            None => radical,
+        }
+    }
+
+    fn over(&mut self, base: &Block, mark: &Block) -> Block {
+        let width = base.width.max(mark.width);
+        let base = base.centered(width);
+        let mark = mark.centered(width);
+        Block::compose(&[(0, 0, &base), (0, -(base.above() as isize) - 1 - mark.below() as isize, &mark)])
+    }
+
+    fn under(&mut self, base: &Block, mark: &Block) -> Block {
+        let width = base.width.max(mark.width);
+        let base = base.centered(width);
+        let mark = mark.centered(width);
+        Block::compose(&[(0, 0, &base), (0, base.below() as isize + 1 + mark.above() as isize, &mark)])
+    }
+
+    fn accent(&mut self, label: &str, base: Option<&dyn AnyParseNode>, ctx: &Ctx) -> Block {
+        let mut base = match base {
+            Some(base) => self.group(base, ctx),
+            None => Block::empty(),
+        };
+        match accent_marks(label) {
+            Some((mark, _)) if base.single_cell().is_some() => {
+                base.single_cell().unwrap().push(mark);
+                base
+            }
+            Some((_, spacing)) => {
+                let mark = Block::text(spacing);
+                self.over(&base, &mark)
+            }
+            None => {
+                let mark = stretchy(label, base.width);
+                self.over(&base, &mark)
+            }
+        }
+    }
+
+    fn horiz_brace(&mut self, node: &types::horizBrace, ctx: &Ctx) -> Block {
+        let base = self.group(node.base.as_ref(), ctx);
+        let width = base.width.max(3);
+        let (left, tip, right) = match (node.label.as_str(), node.isOver) {
+            ("\\overbracket", _) => ("┌", "─", "┐"),
+            ("\\underbracket", _) => ("└", "─", "┘"),
+            (_, true) => ("╭", "┴", "╮"),
+            (_, false) => ("╰", "┬", "╯"),
+        };
+        let half = (width - 3) / 2;
+        let brace = Block::text(&format!(
+            "{}{}{}{}{}",
+            left,
+            "─".repeat(half),
+            tip,
+            "─".repeat(width - 3 - half),
+            right
+        ));
+        if node.isOver {
+            self.over(&base, &brace)
+        } else {
+            self.under(&base, &brace)
+        }
+    }
+
+    fn x_arrow(&mut self, node: &types::xArrow, ctx: &Ctx) -> Block {
+        let script = ctx.script();
+        let above = self.group(node.body.as_ref(), &script);
+        let below = node.below.as_deref().map(|below| self.group(below, &script));
+        let width = above.width.max(below.as_ref().map_or(0, |below| below.width)) + 2;
+        let arrow = stretchy(&node.label, width.max(3));
+        let arrow = self.over(&arrow, &above);
+        match below {
+            Some(below) => self.under(&arrow, &below),
+            None => arrow,
+        }
+    }
+
+    fn enclose(&mut self, node: &types::enclose, ctx: &Ctx) -> Block {
+        let body = self.group(node.body.as_ref(), ctx);
+        match node.label.as_str() {
+            "\\fbox" | "\\fcolorbox" => framed(&body),
+            "\\cancel" | "\\bcancel" | "\\xcancel" => body.combine('\u{338}'),
+            "\\sout" => body.combine('\u{336}'),
+            _ => body,
+        }
+    }
+
+    /** The delimiters of `\left` and `\right`, and of `\middle` in between, sized to the body. */
+    fn leftright(&mut self, node: &types::leftright, ctx: &Ctx) -> Block {
+        let mut segments = Vec::new();
+        let mut middles = Vec::new();
+        for part in node.body.split(|node| node.as_any().is::<types::middle>()) {
+            segments.push(self.list(part, ctx));
+        }
+        for middle in node.body.iter().filter_map(|node| node.as_any().downcast_ref::<types::middle>()) {
+            middles.push(middle.delim.as_str());
+        }
+        let above = segments.iter().map(Block::above).max().unwrap_or(0);
+        let below = segments.iter().map(Block::below).max().unwrap_or(0);
+
+        let mut blocks = vec![delimiter(&node.left, above, below)];
+        for (i, segment) in segments.into_iter().enumerate() {
+            if i > 0 {
+                blocks.push(Block::blank(1, 0, 0));
+                blocks.push(delimiter(middles[i - 1], above, below));
+                blocks.push(Block::blank(1, 0, 0));
+            }
+            blocks.push(segment);
+        }
+        blocks.push(delimiter(&node.right, above, below));
+        Block::row(&blocks)
+    }
+
+    /**
+     * Rows and columns aligned as the column specification says, with
+     * `\hline`s, `|` separators and equation numbers.
+     */
+    fn array(&mut self, array: &types::array, ctx: &Ctx) -> Block {
+        let aligns: Vec<&str> = array
+            .cols
+            .iter()
+            .filter_map(|col| match col {
+                AlignSpec::Align(align) => Some(align.align.as_str()),
+                AlignSpec::Separator(_) => None,
+            })
+            .collect();
+        // Separators by the number of columns left of them.
+        let mut separators: Vec<(usize, &str)> = Vec::new();
+        let mut column = 0;
+        for col in array.cols.iter() {
+            match col {
+                AlignSpec::Align(_) => column += 1,
+                AlignSpec::Separator(separator) => {
+                    separators.push((column, if separator.separator == ":" { "┆" } else { "│" }))
+                }
+            }
+        }
+        let aligned = matches!(
+            array.col_separation_type,
+            Some(ColSeparationType::Align) | Some(ColSeparationType::AlignAt)
+        );
+
+        let rows: Vec<&Vec<Box<dyn AnyParseNode>>> =
+            array.body.iter().filter(|row| !(row.is_empty() || is_empty_last_row(row))).collect();
+        let column_count = rows.iter().map(|row| row.len()).max().unwrap_or(0).max(aligns.len());
+        let mut cells: Vec<Vec<Block>> = Vec::with_capacity(rows.len());
+        for row in rows.iter() {
+            let mut blocks = Vec::with_capacity(column_count);
+            for (j, cell) in row.iter().enumerate() {
+                // The right column of an aligned pair continues the left one.
+                let before = if aligned && j % 2 == 1 { Class::Ord } else { Class::Edge };
+                blocks.push(self.list_after(std::slice::from_ref(cell), ctx, before));
+            }
+            blocks.resize(column_count, Block::empty());
+            cells.push(blocks);
+        }
+
+        let widths: Vec<usize> = (0..column_count)
+            .map(|j| cells.iter().map(|row| row[j].width).max().unwrap_or(0))
+            .collect();
+        // Left columns of the cells, and of the separators.
+        let mut lefts = Vec::with_capacity(column_count);
+        let mut separator_columns = Vec::new();
+        let mut x = 0;
+        for (j, width) in widths.iter().enumerate() {
+            let mut separated = false;
+            for (_, separator) in separators.iter().filter(|(column, _)| *column == j) {
+                if j > 0 {
+                    x += 1;
+                }
+                separator_columns.push((x, *separator));
+                x += 2;
+                separated = true;
+            }
+            if j > 0 && !separated && !(aligned && j % 2 == 1) {
+                x += COLUMN_GAP;
+            }
+            lefts.push(x);
+            x += width;
+        }
+        for (_, separator) in separators.iter().filter(|(column, _)| *column == column_count) {
+            x += 1;
+            separator_columns.push((x, *separator));
+            x += 1;
+        }
+        let width = x;
+
+        // Rows top to bottom, with a blank row between rows taller than one.
+        let mut parts: Vec<(usize, isize, Block)> = Vec::new();
+        let mut tags: Vec<(isize, Block)> = Vec::new();
+        let mut rules: Vec<(isize, bool)> = Vec::new();
+        let mut y: isize = 0;
+        let mut previous_below: Option<usize> = None;
+        for (i, row) in cells.iter().enumerate() {
+            let above = row.iter().map(Block::above).max().unwrap_or(0);
+            let below = row.iter().map(Block::below).max().unwrap_or(0);
+            let lines = array.h_lines_before_row.get(i).map_or(&[][..], |lines| lines.as_slice());
+            if let Some(previous_below) = previous_below {
+                y += previous_below as isize + 1;
+                if (previous_below > 0 || above > 0) && lines.is_empty() {
+                    y += 1;
+                }
+            }
+            for dashed in lines {
+                rules.push((y, *dashed));
+                y += 1;
+            }
+            y += above as isize;
+            for (j, cell) in row.iter().enumerate() {
+                let x = lefts[j]
+                    + match aligns.get(j).copied().unwrap_or("c") {
+                        "l" => 0,
+                        "r" => widths[j] - cell.width,
+                        _ => (widths[j] - cell.width) / 2,
+                    };
+                parts.push((x, y, cell.clone()));
+            }
+            if let Some(tag) = self.tag(array, i, ctx) {
+                tags.push((y, tag));
+            }
+            previous_below = Some(below);
+        }
+        let mut bottom = y + previous_below.unwrap_or(0) as isize;
+        for dashed in array.h_lines_before_row.get(cells.len()).into_iter().flatten() {
+            bottom += 1;
+            rules.push((bottom, *dashed));
+        }
+
+        for (y, dashed) in rules.iter() {
+            parts.push((0, *y, Block::repeat(if *dashed { "┄" } else { "─" }, width)));
+        }
+        for (x, separator) in separator_columns.iter() {
+            let mut line = Block::blank(1, 0, bottom.max(0) as usize);
+            for (y, row) in line.rows.iter_mut().enumerate() {
+                let crossed = rules.iter().any(|(rule, _)| *rule == y as isize);
+                row[0] = if crossed {
+                    junction(y > 0, y < bottom as usize, *x > 0, x + 1 < width).to_string()
+                } else {
+                    separator.to_string()
+                };
+            }
+            parts.push((*x, 0, line));
+        }
+
+        // Equation numbers go four columns right of the array, or left of it with leqno.
+        let tag_width = tags.iter().map(|(_, tag)| tag.width).max().unwrap_or(0);
+        let shift = if array.leqno && tag_width > 0 { tag_width + 4 } else { 0 };
+        let mut refs: Vec<(usize, isize, &Block)> =
+            parts.iter().map(|(x, y, block)| (x + shift, *y, block)).collect();
+        for (y, tag) in tags.iter() {
+            let x = if array.leqno { 0 } else { width + 4 };
+            refs.push((x, *y, tag));
+        }
+        let mut block = Block::compose(&refs);
+        // Matrices are centered on the baseline of the surrounding text.
+        if cells.len() > 1 {
+            block.baseline = (block.height() - 1) / 2;
+        }
+        block
+    }
+
+    /** The equation number of row `i`, if the environment numbers it. */
+    fn tag(&mut self, array: &types::array, i: usize, ctx: &Ctx) -> Option<Block> {
+        match array.tags.as_ref()?.get(i)? {
+            ArrayTag::A(true) => {
+                self.equation += 1;
+                Some(Block::text(&format!("({})", self.equation)))
+            }
+            ArrayTag::A(false) => None,
+            ArrayTag::B(tag) => Some(self.list(tag, ctx)),
+        }
+    }
+}
+
+/** The box-drawing piece joining lines that go up, down, left and right. */
+fn junction(up: bool, down: bool, left: bool, right: bool) -> &'static str {
+    match (up, down, left, right) {
+        (false, true, false, true) => "┌",
+        (false, true, true, false) => "┐",
+        (true, false, false, true) => "└",
+        (true, false, true, false) => "┘",
+        (false, true, true, true) => "┬",
+        (true, false, true, true) => "┴",
+        (true, true, false, true) => "├",
+        (true, true, true, false) => "┤",
+        (false, false, _, _) => "─",
+        _ => "┼",
+    }
+}
+
+/** The empty row a trailing `\\` leaves at the end of an environment. */
+fn is_empty_last_row(row: &[Box<dyn AnyParseNode>]) -> bool {
+    fn is_empty(node: &dyn AnyParseNode) -> bool {
+        let any = node.as_any();
+        if let Some(n) = any.downcast_ref::<types::styling>() {
+            n.body.iter().all(|node| is_empty(node.as_ref()))
+        } else if let Some(n) = any.downcast_ref::<types::ordgroup>() {
+            n.body.iter().all(|node| is_empty(node.as_ref()))
+        } else {
+            false
+        }
+    }
+    row.len() == 1 && is_empty(row[0].as_ref())
+}
@@ -0,0 +1,18 @@
+//! The `output: "text"` backend: the parse tree laid out as monospace
+//! Unicode art, for terminals and other places without a browser.
+//!
+//! Fractions are stacked over a `─` bar, scripts raised or lowered onto
+//! their own rows, radicals drawn with `√` or slanted strokes, and tall
+//! delimiters built from the bracket pieces of U+239B to U+23AD.
+
+mod block;
+mod layout;
+
+use crate::parse_node::types::AnyParseNode;
+use crate::settings::Settings;
+use layout::Layout;
+
+/** The lines of `tree` laid out as text, joined with `\n`. */
+pub(crate) fn to_text(tree: &[Box<dyn AnyParseNode>], settings: &Settings) -> String {
+    Layout::new(settings.get_display_mode()).layout(tree).lines().join("\n")
+}
@@ -1,6 +1,7 @@
 use std::fmt::Display;
 
 use unicode_normalization::IsNormalized::No;
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 
 /**
@@ -18,7 +19,7 @@ use wasm_bindgen::prelude::*;
  */
 use super::sourceLocation::SourceLocation;
 
-#[wasm_bindgen(getter_with_clone)]
+#[cfg_attr(feature = "wasm", wasm_bindgen(getter_with_clone))]
 #[derive(Clone,Debug)]
 pub struct Token {
     pub text: String,
@@ -32,9 +33,9 @@ impl std::fmt::Display for Token {
         write!(f , "{} {:?} {:?} {:?}" , self.text, self.loc, self.noexpand,self.treatAsRelax)
     }
 }
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 impl Token {
-    #[wasm_bindgen(constructor)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
     pub fn new(
         text: String, // the text of this token
         loc: Option<SourceLocation>,
@@ -75,3 +76,22 @@ impl Token {
         }
     }
 }
+
+/**
+ * Joins token texts, in reading order, back into LaTeX source, with a space
+ * after each control word that is followed by a letter.
+ */
+pub(crate) fn tokens_to_source<'a>(texts: impl IntoIterator<Item = &'a str>) -> String {
+    let mut source = String::new();
+    let mut after_control_word = false;
+    for text in texts {
+        if after_control_word && text.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            source.push(' ');
+        }
+        after_control_word = text.len() > 1
+            && text.starts_with('\\')
+            && text[1..].starts_with(|c: char| c.is_ascii_alphabetic());
+        source.push_str(text);
+    }
+    source
+}
@@ -1,7 +1,9 @@
 use std::any::Any;
 use std::fmt::Debug;
 use struct_format::html_dom_node;
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
+#[cfg(feature = "wasm")]
 use web_sys::Node;
 
 use crate::{
@@ -9,9 +11,10 @@ use crate::{
 };
 
 ///////////////////////////////////////////////////////////////////////////////////////////////////
-pub trait VirtualNode: VirtualNodeClone + Debug {
+pub trait VirtualNode: VirtualNodeClone + Debug + Send + Sync {
     fn as_any(&self) -> &dyn Any;
     fn as_mut_any(&mut self)->&mut dyn Any;
+    #[cfg(feature = "wasm")]
     fn to_node(&self) -> web_sys::Node;
     fn to_markup(&self) -> String;
 }
@@ -62,7 +62,7 @@ pub enum ArgType {
 }
 
 // LaTeX display style.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum StyleStr {
     text,
     display,
@@ -87,19 +87,19 @@ impl StyleStr {
     pub fn as_style(&self) -> StyleInterface {
         match self {
             StyleStr::text => {
-                let res = crate::Style::TEXT.read().unwrap();
+                let res = &*crate::Style::TEXT;
                 res.clone()
             }
             StyleStr::display => {
-                let res = crate::Style::DISPLAY.read().unwrap();
+                let res = &*crate::Style::DISPLAY;
                 res.clone()
             }
             StyleStr::script => {
-                let res = crate::Style::SCRIPT.read().unwrap();
+                let res = &*crate::Style::SCRIPT;
                 res.clone()
             }
             StyleStr::scriptscript => {
-                let res = crate::Style::SCRIPTSCRIPT.read().unwrap();
+                let res = &*crate::Style::SCRIPTSCRIPT;
                 res.clone()
             }
         }
@@ -1,7 +1,8 @@
 
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 pub struct unicodeAccentsTextAndMath {
     text: &'static str,
     math: &'static str,
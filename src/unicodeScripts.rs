@@ -4,6 +4,7 @@
  * scriptData array below. Adding scripts to the scriptData array allows
  * characters from that script to appear in \text{} environments.
  */
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 
 type BlockType = [i64; 2];
@@ -84,7 +85,7 @@ const scriptData: [Script; 7] = [
  * Given a codepoint, return the name of the script or script family
  * it is from, or null if it is not part of a known block
  */
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 pub fn scriptFromCodepoint(codepoint: f64) -> Option<String> {
     for script in scriptData {
         for block in script.blocks {
@@ -106,7 +107,7 @@ pub fn scriptFromCodepoint(codepoint: f64) -> Option<String> {
  */
 
  
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 pub fn supportedCodepoint(codepoint: f64) -> bool {
     for script in scriptData {
         for block in script.blocks {
@@ -1,7 +1,7 @@
-use std::{collections::HashMap, sync::Mutex};
+use std::collections::HashMap;
 
 lazy_static! {
-    pub static ref U_SUBS_AND_SUPS: Mutex<HashMap<&'static str, &'static str>> = Mutex::new({
+    pub static ref U_SUBS_AND_SUPS: HashMap<&'static str, &'static str> = {
         let res = HashMap::from([
             ("₊", "+"),
             ("₋", "-"),
@@ -107,5 +107,5 @@ lazy_static! {
             ("\u{1DBF}", "θ"),
         ]);
         res
-    });
+    };
 }
@@ -5,14 +5,14 @@
 // Flow comment types syntax is used.
 use super::unicodeAccents::unicodeAccents;
 use std::collections::HashMap;
-use std::sync::Mutex;
 use unicode_normalization::UnicodeNormalization;
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 
 const LETTERS: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ\
     αβγδεϵζηθϑικλμνξοπϖρϱςστυφϕχψωΓΔΘΛΞΠΣΥΦΨΩ";
 lazy_static! {
-    pub static ref unicodeSysmbols: Mutex<HashMap<String, String>> = Mutex::new({
+    pub static ref unicodeSysmbols: HashMap<String, String> = {
         let mut m = HashMap::new();
         for letter in LETTERS.chars() {
             for accent in unicodeAccents.iter() {
@@ -34,12 +34,12 @@ lazy_static! {
             }
         }
         m
-    });
+    };
 }
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 pub fn unicode_sysmbols_result_get(key: String) -> Option<String> {
-    match unicodeSysmbols.lock().unwrap().get(&key) {
+    match unicodeSysmbols.get(&key) {
         Some(s) => Some(s.clone()),
         None => None,
     }
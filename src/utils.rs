@@ -1,5 +1,7 @@
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 
+#[cfg(feature = "wasm")]
 #[wasm_bindgen]
 extern "C" {
     // Use `js_namespace` here to bind `console.log(..)` instead of just
@@ -18,6 +20,14 @@ extern "C" {
     pub fn log_many(a: &str, b: &str);
 }
 
+/**
+ * Outside the browser there is no `console`; log to stderr instead.
+ */
+#[cfg(not(feature = "wasm"))]
+pub fn log(s: &str) {
+    eprintln!("{}", s);
+}
+
 macro_rules!  console_log {
     // Note that this is using the `log` function imported above during
     // `bare_bones`
@@ -26,7 +36,7 @@ macro_rules!  console_log {
 
 pub(crate) use console_log;
 
-#[wasm_bindgen(start)]
+#[cfg_attr(feature = "wasm", wasm_bindgen(start))]
 pub fn set_panic_hook() {
     // When the `console_error_panic_hook` feature is enabled, we can call the
     // `set_panic_hook` function at least once during initialization, and then
@@ -47,10 +57,16 @@ use std::collections::HashMap;
 lazy_static! {
     static ref UPPERCASE: Regex = Regex::new("([A-Z])").unwrap();
     static ref ESCAPE_REGEX: Regex = Regex::new("[&><\"']").unwrap();
+    // https://url.spec.whatwg.org/#url-parsing strips leading whitespace
+    // (U+20) or C0 control (U+00-U+1F) characters.
+    static ref PROTOCOL: Regex =
+        Regex::new(r"(?i)^[\x00-\x20]*([^\\/#?]*?)(:|&#0*58|&#x0*3a|&colon)").unwrap();
+    // https://datatracker.ietf.org/doc/html/rfc3986#section-3.1
+    static ref SCHEME: Regex = Regex::new(r"^[a-zA-Z][a-zA-Z\d+\-.]*$").unwrap();
 }
 
 // hyphenate and escape adapted from Facebook's React under Apache 2 license
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 pub fn hyphenate(s: String) -> String {
     return UPPERCASE
         .replace_all(s.as_str(), "-$1")
@@ -59,6 +75,45 @@ pub fn hyphenate(s: String) -> String {
 }
 
 
+/**
+ * Return the protocol of a URL, or "_relative" if the URL does not specify a
+ * protocol (and thus is relative), or `None` if URL has invalid protocol
+ * (so should be outright rejected).
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen(js_name = protocolFromUrl))]
+pub fn protocol_from_url(url: &str) -> Option<String> {
+    let protocol = match PROTOCOL.captures(url) {
+        Some(c) => c,
+        None => return Some("_relative".to_string()),
+    };
+    // Reject weird colons
+    if &protocol[2] != ":" {
+        return None;
+    }
+    // Reject invalid characters in scheme
+    if !SCHEME.is_match(&protocol[1]) {
+        return None;
+    }
+    // Lowercase the protocol
+    Some(protocol[1].to_lowercase())
+}
+
+/**
+ * Milliseconds since a fixed point in time, for `Settings::time_budget`.
+ * Natively this is a monotonic clock, which changes to the system clock do
+ * not move.
+ */
+pub(crate) fn now_ms() -> f64 {
+    #[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+    {
+        js_sys::Date::now()
+    }
+    #[cfg(not(all(target_arch = "wasm32", feature = "wasm")))]
+    {
+        static START: std::sync::OnceLock<std::time::Instant> = std::sync::OnceLock::new();
+        START.get_or_init(std::time::Instant::now).elapsed().as_secs_f64() * 1000.0
+    }
+}
 
 pub fn escape_to(out: &mut String, text: &str) {
     for c in text.chars() {
@@ -112,3 +167,18 @@ pub fn is_character_box(group: &Box<dyn AnyParseNode>) -> bool {
         || base_elem.get_type() == "textord"
         || base_elem.get_type() == "atom";
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::protocol_from_url;
+
+    #[test]
+    fn test_protocol_from_url() {
+        assert_eq!(protocol_from_url("https://katex.org"), Some("https".to_string()));
+        assert_eq!(protocol_from_url(" \tJavaScript:alert(1)"), Some("javascript".to_string()));
+        assert_eq!(protocol_from_url("images/a.png"), Some("_relative".to_string()));
+        assert_eq!(protocol_from_url("#katex-eq-1"), Some("_relative".to_string()));
+        assert_eq!(protocol_from_url("javascript&colon;alert(1)"), None);
+        assert_eq!(protocol_from_url("java script:alert(1)"), None);
+    }
+}
@@ -52,6 +52,18 @@ pub fn derive_format(input: TokenStream) -> TokenStream {
                 })
                 .collect();
 
+            let set_by_name: Vec<_> = fields_name
+                .named
+                .iter()
+                .map(|field| {
+                    let f = field.ident.as_ref().unwrap();
+                    let css_name = f.to_string().replace("_", "-");
+                    quote! {
+                        #css_name => self.#f = Some(value),
+                    }
+                })
+                .collect();
+
             let get_underline_fields: Vec<_> = fields_name
                 .named
                 .iter()
@@ -70,11 +82,11 @@ pub fn derive_format(input: TokenStream) -> TokenStream {
 
                     let s = Ident::new(format!("set_{}", f_str).as_str(), struct_name.span());
                     quote! {
-                        #[wasm_bindgen(getter = #g)]
+                        #[cfg_attr(feature = "wasm", wasm_bindgen(getter = #g))]
                         pub fn #f(&self)->Option<String>{
                             self.#f.clone()
                         }
-                         #[wasm_bindgen(setter = #g)]
+                         #[cfg_attr(feature = "wasm", wasm_bindgen(setter = #g))]
                         pub fn #s(&mut self,x:Option<String>){
                             self.#f = x;
                         }
@@ -94,8 +106,18 @@ pub fn derive_format(input: TokenStream) -> TokenStream {
                         #(#get_some)*
                         #struct_str
                     }
+
+                    /// Sets a property by its CSS name (e.g. `margin-left`);
+                    /// returns `false` for a property the struct does not have.
+                    pub fn set_css_property(&mut self, name: &str, value: String) -> bool {
+                        match name {
+                            #(#set_by_name)*
+                            _ => return false,
+                        }
+                        true
+                    }
                 }
-                #[wasm_bindgen]
+                #[cfg_attr(feature = "wasm", wasm_bindgen)]
                 impl #struct_name{
                     #(#get_underline_fields)*
                 }
@@ -109,12 +131,130 @@ pub fn derive_format(input: TokenStream) -> TokenStream {
     expended.into()
 }
 
-#[proc_macro_derive(parse_node_type)]
+/**
+KaTeX 中 ParseNode 的 JSON 字段名：默认把 snake_case 转成 camelCase，
+也可以用 `#[json(rename = "...")]` 指定。
+*/
+fn json_field_name(field: &syn::Field) -> String {
+    for attr in &field.attrs {
+        if !attr.path.is_ident("json") {
+            continue;
+        }
+        if let Ok(syn::Meta::List(list)) = attr.parse_meta() {
+            for nested in list.nested {
+                if let syn::NestedMeta::Meta(syn::Meta::NameValue(nv)) = nested {
+                    if nv.path.is_ident("rename") {
+                        if let syn::Lit::Str(s) = nv.lit {
+                            return s.value();
+                        }
+                    }
+                }
+            }
+        }
+    }
+    let f_str = field.ident.as_ref().unwrap().to_string();
+    RE.replace_all(f_str.as_str(), |captures: &regex::Captures| {
+        captures[1].to_uppercase()
+    })
+    .to_string()
+}
+
+/**
+`#[json(skip_false)]`：布尔字段为 `false` 时不输出，对应 KaTeX 中只在为真时才设置的字段。
+*/
+fn json_skip_false(field: &syn::Field) -> bool {
+    field.attrs.iter().filter(|attr| attr.path.is_ident("json")).any(|attr| {
+        matches!(attr.parse_meta(), Ok(syn::Meta::List(list)) if list.nested.iter().any(|nested| {
+            matches!(nested, syn::NestedMeta::Meta(syn::Meta::Path(path)) if path.is_ident("skip_false"))
+        }))
+    })
+}
+
+/**
+结构体上的 `#[json(check = "name")]`：从 JSON 读出节点后调用
+`crate::parse_node::json::name(&node)`，检查 builder 依赖、但 JSON 形状表达不了的取值
+（范围、标签、非空等）。错误信息以字段名开头，例如 `"size: ..."`。
+*/
+fn json_check(attrs: &[syn::Attribute]) -> Option<syn::Ident> {
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("json")) {
+        if let Ok(syn::Meta::List(list)) = attr.parse_meta() {
+            for nested in list.nested {
+                if let syn::NestedMeta::Meta(syn::Meta::NameValue(nv)) = nested {
+                    if let (true, syn::Lit::Str(s)) = (nv.path.is_ident("check"), &nv.lit) {
+                        return Some(syn::Ident::new(&s.value(), s.span()));
+                    }
+                }
+            }
+        }
+    }
+    None
+}
+
+#[proc_macro_derive(parse_node_type, attributes(json))]
 pub fn derive_node_type(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
     let struct_name = input.ident;
     let struct_name_str = struct_name.to_string();
     let sstr = struct_name_str.replace("_", "-");
+    let field_idents: Vec<_> = if let Data::Struct(r#struct) = &input.data {
+        if let Fields::Named(fields_name) = &r#struct.fields {
+            fields_name
+                .named
+                .iter()
+                .map(|field| field.ident.clone().unwrap())
+                .collect()
+        } else {
+            Vec::new()
+        }
+    } else {
+        Vec::new()
+    };
+    let (json_fields, from_json_fields): (Vec<_>, Vec<_>) = if let Data::Struct(r#struct) = &input.data {
+        if let Fields::Named(fields_name) = &r#struct.fields {
+            fields_name
+                .named
+                .iter()
+                .map(|field| {
+                    let f = field.ident.as_ref().unwrap();
+                    let key = json_field_name(field);
+                    let to_json = if json_skip_false(field) {
+                        quote! {
+                            if self.#f {
+                                obj.insert(#key.to_string(), serde_json::Value::Bool(true));
+                            }
+                        }
+                    } else {
+                        quote! {
+                            if let Some(v) = crate::parse_node::json::ToJson::to_json(&self.#f) {
+                                obj.insert(#key.to_string(), v);
+                            }
+                        }
+                    };
+                    let from_json = quote! {
+                        #f: crate::parse_node::json::FromJson::from_json(obj.get(#key), ctx)
+                            .map_err(|e| format!("{}.{}: {}", #sstr, #key, e))?
+                    };
+                    (to_json, from_json)
+                })
+                .unzip()
+        } else {
+            (Vec::new(), Vec::new())
+        }
+    } else {
+        panic!("sorry, parse_node_type is not implemented for union or enum type.")
+    };
+    let check = match json_check(&input.attrs) {
+        Some(check) => quote! {
+            crate::parse_node::json::#check(&node).map_err(|e| format!("{}.{}", #sstr, e))?;
+        },
+        None => quote! {},
+    };
+    // Every parse node but the column specs of `array` has a `loc`.
+    let get_loc = if field_idents.iter().any(|f| f == "loc") {
+        quote! { self.loc.as_ref() }
+    } else {
+        quote! { None }
+    };
     let expended = quote! {
         // #[wasm_bindgen]
         // impl #struct_name{
@@ -141,6 +281,50 @@ pub fn derive_node_type(input: TokenStream) -> TokenStream {
             fn get_type(&self)->&str{
                 return #sstr;
             }
+
+            fn to_json(&self) -> serde_json::Value {
+                let mut obj = serde_json::Map::new();
+                obj.insert("type".to_string(), serde_json::Value::from(#sstr));
+                #(#json_fields)*
+                serde_json::Value::Object(obj)
+            }
+
+            fn get_loc(&self) -> Option<&crate::sourceLocation::SourceLocation> {
+                #get_loc
+            }
+
+            fn for_each_child(&self, f: &mut dyn FnMut(&Box<dyn AnyParseNode>)) {
+                #(crate::parse_node::visit::Children::walk(&self.#field_idents, f);)*
+            }
+
+            fn for_each_child_mut(&mut self, f: &mut dyn FnMut(&mut Box<dyn AnyParseNode>)) {
+                #(crate::parse_node::visit::Children::walk_mut(&mut self.#field_idents, f);)*
+            }
+
+            fn map_children(
+                self: Box<Self>,
+                f: &mut dyn FnMut(Box<dyn AnyParseNode>) -> Box<dyn AnyParseNode>,
+            ) -> Box<dyn AnyParseNode> {
+                let this = *self;
+                Box::new(#struct_name {
+                    #(#field_idents: crate::parse_node::visit::Children::map(this.#field_idents, f)),*
+                })
+            }
+        }
+        impl crate::parse_node::json::FromJson for #struct_name {
+            fn from_json(
+                value: Option<&serde_json::Value>,
+                ctx: &crate::parse_node::json::JsonContext,
+            ) -> Result<Self, String> {
+                let obj = value
+                    .and_then(|v| v.as_object())
+                    .ok_or_else(|| format!("expected a '{}' node object", #sstr))?;
+                let node = #struct_name {
+                    #(#from_json_fields),*
+                };
+                #check
+                Ok(node)
+            }
         }
     };
     expended.into()
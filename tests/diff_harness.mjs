@@ -3,7 +3,7 @@
  * JS-based diff harness for comparing JS KaTeX and Rust WASM KaTeX output.
  *
  * Usage:
- *   node --experimental-wasm-modules tests/diff_harness.mjs <formulas.txt|formulas.yaml> [start_line] [end_line] [--log-level <summary|error|normal|debug>]
+ *   node --experimental-wasm-modules tests/diff_harness.mjs <formulas.txt|formulas.yaml> [start_line] [end_line] [--log-level <summary|error|normal|debug>] [--ast]
  *
  * Example:
  *   node --experimental-wasm-modules tests/diff_harness.mjs tests/fixtures/formulas.txt 1 5
@@ -26,6 +26,7 @@ const yaml = demoRequire('js-yaml');
 const rawArgs = process.argv.slice(2);
 const args = [];
 let logLevel;
+let compareAst = false;
 
 for (let i = 0; i < rawArgs.length; i++) {
     const arg = rawArgs[i];
@@ -46,6 +47,11 @@ for (let i = 0; i < rawArgs.length; i++) {
         continue;
     }
 
+    if (arg === '--ast') {
+        compareAst = true;
+        continue;
+    }
+
     args.push(arg);
 }
 
@@ -63,7 +69,7 @@ const showAllFormulaLogs = logLevel === 'normal' || logLevel === 'debug';
 const showSettings = logLevel === 'debug';
 
 if (args.length < 1) {
-    console.error('Usage: diff_harness.mjs <formulas.txt|formulas.yaml> [start_line] [end_line] [--log-level <summary|error|normal|debug>]');
+    console.error('Usage: diff_harness.mjs <formulas.txt|formulas.yaml> [start_line] [end_line] [--log-level <summary|error|normal|debug>] [--ast]');
     console.error();
     console.error('  formulas.txt   Path to a text file with one LaTeX formula per line');
     console.error('  formulas.yaml  Path to a YAML file; values are formulas or objects with a tex field');
@@ -74,6 +80,7 @@ if (args.length < 1) {
     console.error('                 normal: print per-formula render results and match status');
     console.error('                 debug: normal output plus processed JS/Rust settings');
     console.error('                 default: debug');
+    console.error('  --ast          compare parse trees (katex.__parse) instead of HTML');
     console.error();
     console.error('Example:');
     console.error('  node --experimental-wasm-modules diff_harness/scripts/diff_harness.mjs tests/fixtures/formulas.txt 1 5');
@@ -222,6 +229,39 @@ function htmlMatchesWithinTolerance(jsHtml, rustHtml) {
     return jsHtml.slice(jsIndex) === rustHtml.slice(rustIndex);
 }
 
+// Parse trees are compared as JSON. `loc` is reduced to start/end (the JS one
+// also carries the lexer), null/undefined fields are dropped on both sides, and
+// object keys are sorted, since the Rust fields follow struct order rather than
+// the order KaTeX's builders happen to write them in.
+function astToJson(tree) {
+    return JSON.stringify(tree, (key, value) => {
+        if (key === 'loc' && value) {
+            return { start: value.start, end: value.end };
+        }
+        if (value === null && key !== '') {
+            return undefined;
+        }
+        if (value && typeof value === 'object' && !Array.isArray(value)) {
+            return Object.fromEntries(Object.keys(value).sort().map((name) => [name, value[name]]));
+        }
+        return value;
+    });
+}
+
+function renderJs(formula) {
+    return compareAst
+        ? astToJson(katex.__parse(formula, renderSettings))
+        : katex.renderToString(formula, renderSettings);
+}
+
+function renderRust(formula) {
+    return compareAst
+        ? astToJson(katexWasm.__parse(formula, renderSettings))
+        : katexWasm.renderToString(formula, renderSettings);
+}
+
+const outputLabel = compareAst ? 'AST' : 'HTML';
+
 // ── Main loop ─────────────────────────────────────────────────────────────────
 
 if (showDetails) {
@@ -265,7 +305,7 @@ for (let lineNum = startLine; lineNum <= endLine; lineNum++) {
     let jsHadError = false;
     try {
         const jsStart = performance.now();
-        jsHtml = katex.renderToString(formula, renderSettings);
+        jsHtml = renderJs(formula);
         jsTime = performance.now() - jsStart;
     } catch (error) {
         jsHtml = `JS_ERROR: ${error.message || error}`;
@@ -279,7 +319,7 @@ for (let lineNum = startLine; lineNum <= endLine; lineNum++) {
     let rustHadError = false;
     try {
         const rustStart = performance.now();
-        rustHtml = katexWasm.renderToString(formula, renderSettings);
+        rustHtml = renderRust(formula);
         rustTime = performance.now() - rustStart;
     } catch (error) {
         rustHtml = `RUST_ERROR: ${error.message || error}`;
@@ -308,10 +348,10 @@ for (let lineNum = startLine; lineNum <= endLine; lineNum++) {
             console.log();
         }
 
-        console.log(`${DIM}--- JS HTML (${jsTime.toFixed(2)}ms) ---${RESET}`);
+        console.log(`${DIM}--- JS ${outputLabel} (${jsTime.toFixed(2)}ms) ---${RESET}`);
         console.log(jsHtml);
         console.log();
-        console.log(`${DIM}--- Rust HTML (${rustTime.toFixed(2)}ms) ---${RESET}`);
+        console.log(`${DIM}--- Rust ${outputLabel} (${rustTime.toFixed(2)}ms) ---${RESET}`);
         console.log(rustHtml);
         console.log();
     }